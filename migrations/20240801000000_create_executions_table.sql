@@ -0,0 +1,27 @@
+-- Fills imported from NinjaTrader. execution_id is NinjaTrader's own ID for the fill
+-- and is used to skip executions that were already imported.
+CREATE TABLE executions (
+    id uuid PRIMARY KEY NOT NULL,
+    user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    execution_id TEXT NOT NULL,
+    account TEXT NOT NULL,
+    instrument TEXT NOT NULL,
+    action TEXT NOT NULL CHECK (action IN ('buy', 'sell')),
+    quantity INTEGER NOT NULL CHECK (quantity > 0),
+    price DOUBLE PRECISION NOT NULL,
+    executed_at TIMESTAMPTZ NOT NULL,
+    order_id TEXT NOT NULL DEFAULT '',
+    name TEXT NOT NULL DEFAULT '',
+    commission DOUBLE PRECISION NOT NULL DEFAULT 0,
+    connection TEXT NOT NULL DEFAULT '',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_executions_user_id_execution_id ON executions (user_id, execution_id);
+CREATE INDEX idx_executions_user_id_account_instrument ON executions (user_id, account, instrument, executed_at);
+
+CREATE TRIGGER update_executions_updated_at
+BEFORE UPDATE ON executions
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
@@ -0,0 +1,31 @@
+-- Round trips rebuilt from executions. opening_execution_id is the NinjaTrader ID of the
+-- fill that opened the trade, so a trade keeps its id when trades are rebuilt.
+CREATE TABLE trades (
+    id uuid PRIMARY KEY NOT NULL,
+    user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    account TEXT NOT NULL,
+    instrument TEXT NOT NULL,
+    direction TEXT NOT NULL CHECK (direction IN ('long', 'short')),
+    opening_execution_id TEXT NOT NULL,
+    execution_ids TEXT[] NOT NULL,
+    entry_time TIMESTAMPTZ NOT NULL,
+    exit_time TIMESTAMPTZ NOT NULL,
+    quantity INTEGER NOT NULL,
+    max_position INTEGER NOT NULL,
+    entry_price DOUBLE PRECISION NOT NULL,
+    exit_price DOUBLE PRECISION NOT NULL,
+    gross_points DOUBLE PRECISION NOT NULL,
+    gross_pnl DOUBLE PRECISION NOT NULL,
+    commission DOUBLE PRECISION NOT NULL,
+    net_pnl DOUBLE PRECISION NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_trades_user_id_opening_execution_id ON trades (user_id, opening_execution_id);
+CREATE INDEX idx_trades_user_id_exit_time ON trades (user_id, exit_time);
+
+CREATE TRIGGER update_trades_updated_at
+BEFORE UPDATE ON trades
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
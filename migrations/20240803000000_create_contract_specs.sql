@@ -0,0 +1,52 @@
+-- Point value and tick size per instrument root, used to turn points into dollars.
+CREATE TABLE contract_specs (
+    root TEXT PRIMARY KEY NOT NULL,
+    description TEXT NOT NULL DEFAULT '',
+    exchange TEXT NOT NULL DEFAULT '',
+    point_value DOUBLE PRECISION NOT NULL CHECK (point_value > 0),
+    tick_size DOUBLE PRECISION NOT NULL CHECK (tick_size > 0),
+    currency TEXT NOT NULL DEFAULT 'USD',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER update_contract_specs_updated_at
+BEFORE UPDATE ON contract_specs
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
+
+INSERT INTO contract_specs (root, description, exchange, point_value, tick_size) VALUES
+    ('ES', 'E-mini S&P 500', 'CME', 50, 0.25),
+    ('MES', 'Micro E-mini S&P 500', 'CME', 5, 0.25),
+    ('NQ', 'E-mini Nasdaq-100', 'CME', 20, 0.25),
+    ('MNQ', 'Micro E-mini Nasdaq-100', 'CME', 2, 0.25),
+    ('YM', 'E-mini Dow', 'CBOT', 5, 1),
+    ('MYM', 'Micro E-mini Dow', 'CBOT', 0.5, 1),
+    ('RTY', 'E-mini Russell 2000', 'CME', 50, 0.1),
+    ('M2K', 'Micro E-mini Russell 2000', 'CME', 5, 0.1),
+    ('CL', 'Crude Oil', 'NYMEX', 1000, 0.01),
+    ('MCL', 'Micro Crude Oil', 'NYMEX', 100, 0.01),
+    ('QM', 'E-mini Crude Oil', 'NYMEX', 500, 0.025),
+    ('NG', 'Natural Gas', 'NYMEX', 10000, 0.001),
+    ('GC', 'Gold', 'COMEX', 100, 0.1),
+    ('MGC', 'Micro Gold', 'COMEX', 10, 0.1),
+    ('SI', 'Silver', 'COMEX', 5000, 0.005),
+    ('SIL', 'Micro Silver', 'COMEX', 1000, 0.005),
+    ('HG', 'Copper', 'COMEX', 25000, 0.0005),
+    ('ZB', '30-Year U.S. Treasury Bond', 'CBOT', 1000, 0.03125),
+    ('ZN', '10-Year U.S. Treasury Note', 'CBOT', 1000, 0.015625),
+    ('ZF', '5-Year U.S. Treasury Note', 'CBOT', 1000, 0.0078125),
+    ('ZT', '2-Year U.S. Treasury Note', 'CBOT', 2000, 0.00390625),
+    ('ZC', 'Corn', 'CBOT', 50, 0.25),
+    ('ZS', 'Soybeans', 'CBOT', 50, 0.25),
+    ('ZW', 'Wheat', 'CBOT', 50, 0.25),
+    ('6E', 'Euro FX', 'CME', 125000, 0.00005),
+    ('6B', 'British Pound', 'CME', 62500, 0.0001),
+    ('6J', 'Japanese Yen', 'CME', 12500000, 0.0000005),
+    ('6A', 'Australian Dollar', 'CME', 100000, 0.00005),
+    ('6C', 'Canadian Dollar', 'CME', 100000, 0.00005),
+    ('M6E', 'Micro Euro FX', 'CME', 12500, 0.0001);
+
+-- Ticks captured over every contract of the trade. NULL when the instrument
+-- has no contract spec.
+ALTER TABLE trades ADD COLUMN gross_ticks DOUBLE PRECISION;
@@ -0,0 +1,46 @@
+-- Journal entries written against a trade. Trades keep their id when they are
+-- rebuilt, so notes, tags and screenshots survive later imports.
+ALTER TABLE trades ADD COLUMN notes TEXT NOT NULL DEFAULT '';
+ALTER TABLE trades ADD COLUMN rating SMALLINT CHECK (rating BETWEEN 1 AND 5);
+
+CREATE TABLE tags (
+    id uuid PRIMARY KEY NOT NULL,
+    user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_tags_user_id_name ON tags (user_id, LOWER(name));
+
+CREATE TRIGGER update_tags_updated_at
+BEFORE UPDATE ON tags
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
+
+CREATE TABLE trade_tags (
+    trade_id uuid NOT NULL REFERENCES trades (id) ON DELETE CASCADE,
+    tag_id uuid NOT NULL REFERENCES tags (id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (trade_id, tag_id)
+);
+
+CREATE INDEX idx_trade_tags_tag_id ON trade_tags (tag_id);
+
+-- file_name is the name on disk under the screenshots directory
+CREATE TABLE trade_screenshots (
+    id uuid PRIMARY KEY NOT NULL,
+    trade_id uuid NOT NULL REFERENCES trades (id) ON DELETE CASCADE,
+    user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    file_name TEXT NOT NULL UNIQUE,
+    original_name TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_trade_screenshots_trade_id ON trade_screenshots (trade_id);
+
+CREATE TRIGGER update_trade_screenshots_updated_at
+BEFORE UPDATE ON trade_screenshots
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
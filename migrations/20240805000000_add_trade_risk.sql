@@ -0,0 +1,3 @@
+-- The dollar amount the trader planned to risk, recorded in their journal.
+-- A trade's R multiple is its net PnL over this risk.
+ALTER TABLE trades ADD COLUMN risk DOUBLE PRECISION CHECK (risk > 0);
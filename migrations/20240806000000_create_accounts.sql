@@ -0,0 +1,26 @@
+-- The NinjaTrader accounts a user trades in. name is the account name from the
+-- executions export, which trades and executions refer to.
+CREATE TABLE accounts (
+    id uuid PRIMARY KEY NOT NULL,
+    user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    display_name TEXT NOT NULL,
+    account_type TEXT NOT NULL CHECK (account_type IN ('sim', 'live', 'evaluation', 'funded')),
+    starting_balance DOUBLE PRECISION NOT NULL DEFAULT 0,
+    currency TEXT NOT NULL DEFAULT 'USD',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_accounts_user_id_name ON accounts (user_id, name);
+
+CREATE TRIGGER update_accounts_updated_at
+BEFORE UPDATE ON accounts
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
+
+-- Accounts for executions imported before this table existed
+INSERT INTO accounts (id, user_id, name, display_name, account_type)
+SELECT gen_random_uuid(), user_id, account, account,
+    CASE WHEN account ILIKE 'sim%' THEN 'sim' ELSE 'live' END
+FROM (SELECT DISTINCT user_id, account FROM executions) AS imported;
@@ -0,0 +1,31 @@
+-- Prop firm evaluation rules for an account. NULL rules don't apply.
+CREATE TABLE evaluation_rules (
+    account_id uuid PRIMARY KEY NOT NULL REFERENCES accounts (id) ON DELETE CASCADE,
+    profit_target DOUBLE PRECISION,
+    max_drawdown DOUBLE PRECISION,
+    drawdown_type TEXT NOT NULL DEFAULT 'intraday' CHECK (drawdown_type IN ('intraday', 'end_of_day')),
+    daily_loss_limit DOUBLE PRECISION,
+    min_trading_days INTEGER,
+    consistency_percent DOUBLE PRECISION,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE TRIGGER update_evaluation_rules_updated_at
+BEFORE UPDATE ON evaluation_rules
+FOR EACH ROW
+EXECUTE FUNCTION update_updated_at_column();
+
+-- Rules broken by a trade. A rule is broken at most once per trading day.
+CREATE TABLE rule_breaches (
+    id uuid PRIMARY KEY NOT NULL,
+    account_id uuid NOT NULL REFERENCES accounts (id) ON DELETE CASCADE,
+    rule TEXT NOT NULL,
+    trade_id uuid REFERENCES trades (id) ON DELETE SET NULL,
+    trading_day DATE NOT NULL,
+    value DOUBLE PRECISION NOT NULL,
+    breach_limit DOUBLE PRECISION NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX idx_rule_breaches_account_id_rule_trading_day ON rule_breaches (account_id, rule, trading_day);
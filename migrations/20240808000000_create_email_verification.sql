@@ -0,0 +1,19 @@
+-- NULL until the user follows the link in their confirmation email.
+ALTER TABLE users ADD COLUMN email_verified_at TIMESTAMPTZ;
+
+-- Users from before verification existed keep access to everything.
+UPDATE users SET email_verified_at = created_at;
+
+-- Single use email confirmation tokens. Only a SHA-256 hash of the token is
+-- stored, so a leaked table can't be used to confirm addresses. email is the
+-- address the token confirms.
+CREATE TABLE email_verification_tokens (
+    token_hash TEXT PRIMARY KEY NOT NULL,
+    user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    email TEXT NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_email_verification_tokens_user_id ON email_verification_tokens (user_id);
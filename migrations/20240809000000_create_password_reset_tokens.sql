@@ -0,0 +1,11 @@
+-- Single use password reset tokens, stored as SHA-256 hashes like
+-- email_verification_tokens.
+CREATE TABLE password_reset_tokens (
+    token_hash TEXT PRIMARY KEY NOT NULL,
+    user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_password_reset_tokens_user_id ON password_reset_tokens (user_id);
@@ -0,0 +1,11 @@
+-- Security sensitive changes users make to their own account, like a new
+-- password or email address. Rows are only ever inserted.
+CREATE TABLE audit_events (
+    id uuid PRIMARY KEY NOT NULL DEFAULT gen_random_uuid(),
+    user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    action TEXT NOT NULL,
+    detail TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_audit_events_user_id_created_at ON audit_events (user_id, created_at DESC);
@@ -0,0 +1,7 @@
+-- Columns for the admin console. Disabled users can't log in, and their
+-- sessions stop working.
+ALTER TABLE users ADD COLUMN last_login_at TIMESTAMPTZ;
+ALTER TABLE users ADD COLUMN disabled_at TIMESTAMPTZ;
+
+-- Who made a change, when it wasn't the user themselves
+ALTER TABLE audit_events ADD COLUMN actor_id uuid REFERENCES users (id) ON DELETE SET NULL;
@@ -0,0 +1,25 @@
+-- TOTP two factor authentication. A row is created when the user starts
+-- enrolling and is enabled once they verify their first code.
+CREATE TABLE two_factor (
+    user_id uuid PRIMARY KEY REFERENCES users (id) ON DELETE CASCADE,
+    -- Base32, as authenticator apps take it
+    secret TEXT NOT NULL,
+    enabled_at TIMESTAMPTZ,
+    -- The 30 second step of the last accepted code, so codes can't be replayed
+    last_used_step BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Single use codes for when the authenticator is lost. Only hashes are stored.
+CREATE TABLE two_factor_recovery_codes (
+    id SERIAL PRIMARY KEY,
+    user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    code_hash TEXT NOT NULL,
+    used_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX two_factor_recovery_codes_user_id_idx ON two_factor_recovery_codes (user_id);
+
+-- Users with a role that requires it have to enroll before using the app
+ALTER TABLE roles ADD COLUMN requires_two_factor BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,18 @@
+-- Emails waiting to be sent, queued in the same transaction as the change
+-- they're about and delivered by a background worker.
+CREATE TABLE email_outbox (
+    id uuid PRIMARY KEY,
+    recipient TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    html_body TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    sent_at TIMESTAMPTZ,
+    -- Set when the worker gives up after too many failures
+    failed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX email_outbox_pending_idx ON email_outbox (next_attempt_at)
+    WHERE sent_at IS NULL AND failed_at IS NULL;
@@ -0,0 +1,2 @@
+-- Plain text variant sent alongside the HTML. Empty for emails queued before it existed.
+ALTER TABLE email_outbox ADD COLUMN text_body TEXT NOT NULL DEFAULT '';
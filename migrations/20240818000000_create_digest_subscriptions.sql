@@ -0,0 +1,12 @@
+-- Users who opted in to daily or weekly digest emails. last_period_start is
+-- the first trading day of the last period handled, so each period's digest
+-- is queued once.
+CREATE TABLE digest_subscriptions (
+    user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    kind TEXT NOT NULL CHECK (kind IN ('daily', 'weekly')),
+    last_period_start DATE NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (user_id, kind)
+);
+
+CREATE INDEX idx_digest_subscriptions_kind_last_period_start ON digest_subscriptions (kind, last_period_start);
@@ -0,0 +1,17 @@
+-- Background work that shouldn't hold up a request, run by workers started
+-- with the app. Finished jobs are deleted.
+CREATE TABLE jobs (
+    id uuid PRIMARY KEY,
+    kind TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL,
+    last_error TEXT,
+    run_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    -- Set when the job runs out of attempts. Failed jobs are kept so they can
+    -- be looked into and retried by clearing it.
+    failed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX jobs_pending_idx ON jobs (run_at) WHERE failed_at IS NULL;
@@ -0,0 +1,6 @@
+-- Trades in instruments without a contract spec have no dollar PnL until a
+-- spec is added and their trades are rebuilt. gross_ticks is only missing for
+-- those trades, whose PnL used to be stored in points.
+ALTER TABLE trades ALTER COLUMN gross_pnl DROP NOT NULL;
+ALTER TABLE trades ALTER COLUMN net_pnl DROP NOT NULL;
+UPDATE trades SET gross_pnl = NULL, net_pnl = NULL WHERE gross_ticks IS NULL;
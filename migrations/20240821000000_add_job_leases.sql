@@ -0,0 +1,4 @@
+-- Workers claim a job by setting when their claim runs out, instead of
+-- keeping it locked in a transaction while it runs. A job whose worker stopped
+-- is claimed again once the time passes.
+ALTER TABLE jobs ADD COLUMN locked_until TIMESTAMPTZ;
@@ -0,0 +1,106 @@
+//! src/account_settings.rs
+//! Changes users make to their own login details. Every change is written to
+//! the audit log in the same transaction.
+use sqlx::{PgConnection, PgPool};
+use crate::audit;
+use crate::constants::{email_templates, strings};
+use crate::email_outbox;
+use crate::email_verification;
+use crate::emailer::{Email, EmailTemplate};
+use crate::startup::AppState;
+use crate::user::{self, User};
+
+#[derive(Debug, serde::Serialize)]
+pub struct EmailChangedEmail {
+    pub old_email: String,
+    pub new_email: String,
+}
+
+impl EmailTemplate for EmailChangedEmail {
+    const NAME: &'static str = email_templates::EMAIL_CHANGED;
+
+    fn sample() -> Self {
+        Self {
+            old_email: "trader@example.com".to_string(),
+            new_email: "new.trader@example.com".to_string(),
+        }
+    }
+}
+
+/// Sets the user's new password hash and returns the updated user, so the
+/// current session can log in again with it. Sessions are tied to the password
+/// hash, so every other session is logged out.
+pub async fn change_password(db: &PgPool, user_id: uuid::Uuid, password_hash: &str) -> Result<User, sqlx::Error> {
+    let mut transaction = db.begin().await?;
+
+    sqlx::query("UPDATE users SET password_hash = $1 WHERE id = $2")
+        .bind(password_hash)
+        .bind(user_id)
+        .execute(&mut *transaction)
+        .await?;
+    // A reset link sent before the change shouldn't be able to undo it
+    sqlx::query("DELETE FROM password_reset_tokens WHERE user_id = $1 AND used_at IS NULL")
+        .bind(user_id)
+        .execute(&mut *transaction)
+        .await?;
+    audit::record(&mut *transaction, user_id, audit::PASSWORD_CHANGED, None).await?;
+    let user = user::find_by_id(&mut *transaction, user_id)
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)?;
+
+    transaction.commit().await?;
+    Ok(user)
+}
+
+/// Moves the user to `new_email`, which needs confirming again, and queues the
+/// confirmation link and a notice to the old address. Returns false when
+/// another user already has that address.
+pub async fn change_email(state: &AppState, user: &User, new_email: &str) -> Result<bool, anyhow::Error> {
+    let mut transaction = state.db.begin().await?;
+
+    let updated = sqlx::query("UPDATE users SET email = $1, email_verified_at = NULL WHERE id = $2")
+        .bind(new_email)
+        .bind(user.id)
+        .execute(&mut *transaction)
+        .await;
+    match updated {
+        Ok(_) => {},
+        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => return Ok(false),
+        Err(err) => return Err(err.into()),
+    }
+    let detail = format!("{} to {}", user.email, new_email);
+    audit::record(&mut *transaction, user.id, audit::EMAIL_CHANGED, Some(&detail)).await?;
+    email_verification::queue_confirmation(
+        &mut transaction,
+        state,
+        user.id,
+        new_email,
+        strings::CONFIRM_EMAIL_SUBJECT,
+    ).await?;
+    notify_email_changed(&mut transaction, state, &user.email, new_email).await?;
+
+    transaction.commit().await?;
+    Ok(true)
+}
+
+/// Tells the old address about the change, in case it wasn't the owner who made it.
+async fn notify_email_changed(
+    connection: &mut PgConnection,
+    state: &AppState,
+    old_email: &str,
+    new_email: &str,
+) -> Result<(), anyhow::Error> {
+    let changed = EmailChangedEmail {
+        old_email: old_email.to_string(),
+        new_email: new_email.to_string(),
+    };
+    let message = Email::render(
+        &state.tera,
+        &state.email_settings.branding,
+        old_email,
+        strings::EMAIL_CHANGED_SUBJECT,
+        &changed,
+    )?;
+    email_outbox::enqueue(connection, &message).await?;
+    Ok(())
+}
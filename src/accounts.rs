@@ -0,0 +1,95 @@
+//! src/accounts.rs
+//! The brokerage, sim and prop firm accounts a user trades in. Accounts are
+//! created the first time an import mentions them and are matched to trades
+//! and executions by their NinjaTrader name.
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use utoipa::ToSchema;
+use crate::domain::{AccountDetails, AccountType};
+
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
+pub struct Account {
+    pub id: uuid::Uuid,
+    /// The NinjaTrader account name, e.g. Sim101
+    pub name: String,
+    pub display_name: String,
+    pub account_type: String,
+    pub starting_balance: f64,
+    pub currency: String,
+}
+
+pub async fn for_user(db: &PgPool, user_id: uuid::Uuid) -> Result<Vec<Account>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT id, name, display_name, account_type, starting_balance, currency
+        FROM accounts WHERE user_id = $1 ORDER BY LOWER(display_name), name"
+    )
+        .bind(user_id)
+        .fetch_all(db)
+        .await
+}
+
+/// One of the user's accounts. `None` when it doesn't exist or belongs to someone else.
+pub async fn find_for_user(
+    db: &PgPool,
+    user_id: uuid::Uuid,
+    account_id: uuid::Uuid,
+) -> Result<Option<Account>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT id, name, display_name, account_type, starting_balance, currency
+        FROM accounts WHERE id = $1 AND user_id = $2"
+    )
+        .bind(account_id)
+        .bind(user_id)
+        .fetch_optional(db)
+        .await
+}
+
+/// Creates any of `names` the user doesn't have an account for yet.
+pub async fn ensure_exist(db: &PgPool, user_id: uuid::Uuid, names: &[&str]) -> Result<(), sqlx::Error> {
+    for name in names {
+        sqlx::query(
+            "INSERT INTO accounts (id, user_id, name, display_name, account_type)
+            VALUES ($1, $2, $3, $3, $4)
+            ON CONFLICT (user_id, name) DO NOTHING"
+        )
+            .bind(uuid::Uuid::new_v4())
+            .bind(user_id)
+            .bind(name)
+            .bind(AccountType::guess(name).as_str())
+            .execute(db)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Returns false when the account doesn't belong to the user.
+pub async fn update(
+    db: &PgPool,
+    user_id: uuid::Uuid,
+    account_id: uuid::Uuid,
+    details: &AccountDetails,
+) -> Result<bool, sqlx::Error> {
+    let updated = sqlx::query(
+        "UPDATE accounts SET display_name = $1, account_type = $2, starting_balance = $3, currency = $4
+        WHERE id = $5 AND user_id = $6"
+    )
+        .bind(&details.display_name)
+        .bind(details.account_type.as_str())
+        .bind(details.starting_balance)
+        .bind(&details.currency)
+        .bind(account_id)
+        .bind(user_id)
+        .execute(db)
+        .await?;
+    Ok(updated.rows_affected() > 0)
+}
+
+/// The combined starting balance of the named accounts, or of every account
+/// when `names` is empty.
+pub fn starting_balance(accounts: &[Account], names: &[String]) -> f64 {
+    accounts
+        .iter()
+        .filter(|account| names.is_empty() || names.contains(&account.name))
+        .map(|account| account.starting_balance)
+        .sum()
+}
@@ -0,0 +1,193 @@
+//! src/admin.rs
+//! Queries and changes behind the admin console. Every change is written to
+//! the affected user's audit log with the admin as the actor.
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use time::OffsetDateTime;
+use crate::audit;
+use crate::two_factor;
+use crate::user;
+
+const SEARCH_LIMIT: i64 = 100;
+
+const SELECT_SUMMARY: &str = "SELECT users.id, users.email, users.created_at, users.last_login_at,
+    users.email_verified_at, users.disabled_at,
+    COALESCE(array_agg(roles.name::text ORDER BY roles.name) FILTER (WHERE roles.name IS NOT NULL), '{}') AS roles,
+    EXISTS (
+        SELECT 1 FROM two_factor WHERE two_factor.user_id = users.id AND two_factor.enabled_at IS NOT NULL
+    ) AS two_factor_enabled
+FROM users
+LEFT JOIN user_roles ON user_roles.user_id = users.id
+LEFT JOIN roles ON roles.id = user_roles.role_id";
+
+/// What the console shows about a user.
+#[derive(Debug, Serialize, FromRow)]
+pub struct UserSummary {
+    pub id: uuid::Uuid,
+    pub email: String,
+    pub roles: Vec<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_login_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub email_verified_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub disabled_at: Option<OffsetDateTime>,
+    pub two_factor_enabled: bool,
+}
+
+/// A role and whether its users have to use two factor authentication.
+#[derive(Debug, Serialize, FromRow)]
+pub struct RoleSummary {
+    pub name: String,
+    pub requires_two_factor: bool,
+    pub users: i64,
+}
+
+/// Users whose email contains `search`, newest first. An empty search lists everyone.
+pub async fn search_users(db: &PgPool, search: &str) -> Result<Vec<UserSummary>, sqlx::Error> {
+    let pattern = format!(
+        "%{}%",
+        search.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+    );
+    sqlx::query_as(&format!(
+        "{} WHERE users.email ILIKE $1 GROUP BY users.id ORDER BY users.created_at DESC LIMIT $2",
+        SELECT_SUMMARY
+    ))
+        .bind(pattern)
+        .bind(SEARCH_LIMIT)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn find_user(db: &PgPool, user_id: uuid::Uuid) -> Result<Option<UserSummary>, sqlx::Error> {
+    sqlx::query_as(&format!("{} WHERE users.id = $1 GROUP BY users.id", SELECT_SUMMARY))
+        .bind(user_id)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn role_names(db: &PgPool) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar("SELECT name::text FROM roles ORDER BY name")
+        .fetch_all(db)
+        .await
+}
+
+pub async fn roles(db: &PgPool) -> Result<Vec<RoleSummary>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT roles.name::text AS name, roles.requires_two_factor, COUNT(user_roles.user_id) AS users
+        FROM roles
+        LEFT JOIN user_roles ON user_roles.role_id = roles.id
+        GROUP BY roles.id
+        ORDER BY roles.name"
+    )
+        .fetch_all(db)
+        .await
+}
+
+/// Users with the role who haven't enrolled are sent to do so on their next
+/// request. Recorded in the admin's log, since it affects many users. Returns
+/// false when there's no role called `role`.
+pub async fn set_two_factor_required(db: &PgPool, admin_id: uuid::Uuid, role: &str, required: bool) -> Result<bool, sqlx::Error> {
+    let mut transaction = db.begin().await?;
+    let updated = sqlx::query("UPDATE roles SET requires_two_factor = $1 WHERE name = $2")
+        .bind(required)
+        .bind(role)
+        .execute(&mut *transaction)
+        .await?;
+    if updated.rows_affected() == 0 {
+        return Ok(false);
+    }
+    let detail = format!("{}: {}", role, if required { "required" } else { "optional" });
+    audit::record(&mut *transaction, admin_id, audit::TWO_FACTOR_REQUIREMENT_CHANGED, Some(&detail)).await?;
+    transaction.commit().await?;
+    Ok(true)
+}
+
+/// Returns false when there's no role called `role`.
+pub async fn grant_role(db: &PgPool, admin_id: uuid::Uuid, user_id: uuid::Uuid, role: &str) -> Result<bool, sqlx::Error> {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM roles WHERE name = $1)")
+        .bind(role)
+        .fetch_one(db)
+        .await?;
+    if !exists {
+        return Ok(false);
+    }
+
+    let mut transaction = db.begin().await?;
+    user::assign_role(&mut *transaction, user_id, role).await?;
+    audit::record_by(&mut *transaction, admin_id, user_id, audit::ROLE_GRANTED, Some(role)).await?;
+    transaction.commit().await?;
+    Ok(true)
+}
+
+pub async fn revoke_role(db: &PgPool, admin_id: uuid::Uuid, user_id: uuid::Uuid, role: &str) -> Result<(), sqlx::Error> {
+    let mut transaction = db.begin().await?;
+    user::revoke_role(&mut *transaction, user_id, role).await?;
+    audit::record_by(&mut *transaction, admin_id, user_id, audit::ROLE_REVOKED, Some(role)).await?;
+    transaction.commit().await
+}
+
+/// Disabled users can't log in, and `Backend::get_user` ends their sessions.
+pub async fn set_disabled(db: &PgPool, admin_id: uuid::Uuid, user_id: uuid::Uuid, disabled: bool) -> Result<(), sqlx::Error> {
+    let mut transaction = db.begin().await?;
+    sqlx::query(
+        "UPDATE users SET disabled_at = CASE WHEN $1 THEN COALESCE(disabled_at, NOW()) END
+        WHERE id = $2"
+    )
+        .bind(disabled)
+        .bind(user_id)
+        .execute(&mut *transaction)
+        .await?;
+    let action = if disabled { audit::ACCOUNT_DISABLED } else { audit::ACCOUNT_ENABLED };
+    audit::record_by(&mut *transaction, admin_id, user_id, action, None).await?;
+    transaction.commit().await
+}
+
+/// Deletes the user and everything they own. The user's audit log goes with
+/// them, so the deletion is recorded in the admin's. Returns the file names of
+/// their screenshots, for the caller to remove once the rows are gone.
+pub async fn delete_user(db: &PgPool, admin_id: uuid::Uuid, user: &UserSummary) -> Result<Vec<String>, sqlx::Error> {
+    let mut transaction = db.begin().await?;
+    let screenshots = sqlx::query_scalar("DELETE FROM trade_screenshots WHERE user_id = $1 RETURNING file_name")
+        .bind(user.id)
+        .fetch_all(&mut *transaction)
+        .await?;
+    sqlx::query("DELETE FROM users WHERE id = $1")
+        .bind(user.id)
+        .execute(&mut *transaction)
+        .await?;
+    audit::record(&mut *transaction, admin_id, audit::ACCOUNT_DELETED, Some(&user.email)).await?;
+    transaction.commit().await?;
+    Ok(screenshots)
+}
+
+/// Replaces the password hash with `password_hash`, the hash of a secret no
+/// one knows, which also logs the user out everywhere. They get back in with
+/// a reset link. A real hash keeps failed logins as slow as for anyone else,
+/// so timing doesn't show whose password was reset.
+pub async fn force_password_reset(
+    db: &PgPool,
+    admin_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    password_hash: &str,
+) -> Result<(), sqlx::Error> {
+    let mut transaction = db.begin().await?;
+    sqlx::query("UPDATE users SET password_hash = $1 WHERE id = $2")
+        .bind(password_hash)
+        .bind(user_id)
+        .execute(&mut *transaction)
+        .await?;
+    audit::record_by(&mut *transaction, admin_id, user_id, audit::PASSWORD_RESET_FORCED, None).await?;
+    transaction.commit().await
+}
+
+/// For users who lost their authenticator and their recovery codes. They log
+/// in with just their password until they enroll again.
+pub async fn reset_two_factor(db: &PgPool, admin_id: uuid::Uuid, user_id: uuid::Uuid) -> Result<(), sqlx::Error> {
+    let mut transaction = db.begin().await?;
+    two_factor::remove(&mut transaction, user_id).await?;
+    audit::record_by(&mut *transaction, admin_id, user_id, audit::TWO_FACTOR_RESET, None).await?;
+    transaction.commit().await
+}
@@ -0,0 +1,178 @@
+//! src/api_tokens.rs
+//! Personal API tokens, so scripts and add-ons can use the JSON routes without
+//! a browser session. Tokens are shown once when they're created and only a
+//! hash is stored, like reset links.
+//!
+//! `ApiUser` extracts the user from an `Authorization: Bearer` header, or from
+//! the cookie session when there isn't one, so routes work with either.
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts},
+    Extension,
+};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use time::OffsetDateTime;
+use crate::audit;
+use crate::domain::{ApiScope, NewApiToken};
+use crate::email_verification::{generate_token, hash_token};
+use crate::startup::AppState;
+use crate::user::{self, AuthSession, User};
+use crate::utils::ApiError;
+
+/// Makes tokens easy to spot, say in a leaked config file
+const TOKEN_PREFIX: &str = "tsk_";
+/// How much of the token is kept to tell tokens apart in lists
+const DISPLAYED_PREFIX_LENGTH: usize = 8;
+
+/// A token as listed on the settings page. The token itself isn't kept.
+#[derive(Debug, Serialize, FromRow)]
+pub struct ApiToken {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub token_prefix: String,
+    pub scope: String,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub expires_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_used_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+/// Creates a token for the user. Returns the token, which can't be shown again.
+pub async fn create(db: &PgPool, user_id: uuid::Uuid, new_token: &NewApiToken) -> Result<String, sqlx::Error> {
+    let token = format!("{}{}", TOKEN_PREFIX, generate_token());
+    let mut transaction = db.begin().await?;
+
+    sqlx::query(
+        "INSERT INTO api_tokens (id, user_id, name, token_prefix, token_hash, scope, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, NOW() + make_interval(secs => $7))"
+    )
+        .bind(uuid::Uuid::new_v4())
+        .bind(user_id)
+        .bind(&new_token.name)
+        .bind(&token[..DISPLAYED_PREFIX_LENGTH])
+        .bind(hash_token(&token))
+        .bind(new_token.scope.as_str())
+        .bind(new_token.expires_in.map(|expires_in| expires_in.as_seconds_f64()))
+        .execute(&mut *transaction)
+        .await?;
+    audit::record(&mut *transaction, user_id, audit::API_TOKEN_CREATED, Some(&new_token.name)).await?;
+
+    transaction.commit().await?;
+    Ok(token)
+}
+
+/// The user's tokens, newest first.
+pub async fn for_user(db: &PgPool, user_id: uuid::Uuid) -> Result<Vec<ApiToken>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT id, name, token_prefix, scope, expires_at, last_used_at, created_at
+        FROM api_tokens
+        WHERE user_id = $1
+        ORDER BY created_at DESC"
+    )
+        .bind(user_id)
+        .fetch_all(db)
+        .await
+}
+
+/// Deletes one of the user's tokens. Returns false when they have no such token.
+pub async fn revoke(db: &PgPool, user_id: uuid::Uuid, token_id: uuid::Uuid) -> Result<bool, sqlx::Error> {
+    let mut transaction = db.begin().await?;
+    let name: Option<String> = sqlx::query_scalar(
+        "DELETE FROM api_tokens WHERE id = $1 AND user_id = $2 RETURNING name"
+    )
+        .bind(token_id)
+        .bind(user_id)
+        .fetch_optional(&mut *transaction)
+        .await?;
+    let name = match name {
+        Some(name) => name,
+        None => return Ok(false),
+    };
+    audit::record(&mut *transaction, user_id, audit::API_TOKEN_REVOKED, Some(&name)).await?;
+    transaction.commit().await?;
+    Ok(true)
+}
+
+/// The user and scope of a token that exists and hasn't expired. Disabled
+/// users' tokens stop working, like their sessions.
+pub async fn authenticate(db: &PgPool, token: &str) -> Result<Option<(User, ApiScope)>, sqlx::Error> {
+    let found: Option<(uuid::Uuid, String)> = sqlx::query_as(
+        "UPDATE api_tokens SET last_used_at = NOW()
+        WHERE token_hash = $1 AND (expires_at IS NULL OR expires_at > NOW())
+        RETURNING user_id, scope"
+    )
+        .bind(hash_token(token))
+        .fetch_optional(db)
+        .await?;
+    let (user_id, scope) = match found {
+        Some(found) => found,
+        None => return Ok(None),
+    };
+    let scope = match ApiScope::parse(&scope) {
+        Ok(scope) => scope,
+        Err(_) => return Ok(None),
+    };
+
+    let user = user::find_by_id(db, user_id).await?;
+    Ok(user.filter(|user| !user.is_disabled()).map(|user| (user, scope)))
+}
+
+/// The user making a JSON request, from a bearer token or the cookie session.
+/// Sessions can do anything the user can.
+#[derive(Debug, Clone)]
+pub struct ApiUser {
+    pub user: User,
+    pub scope: ApiScope,
+}
+
+impl ApiUser {
+    /// Routes that change anything call this first.
+    pub fn require(&self, scope: ApiScope) -> Result<(), ApiError> {
+        if self.scope >= scope {
+            Ok(())
+        } else {
+            Err(ApiError::forbidden(format!("This token needs the {} scope.", scope.as_str())))
+        }
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ApiUser
+where
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let authorization = match parts.headers.get(header::AUTHORIZATION) {
+            Some(authorization) => authorization.to_str().unwrap_or_default().to_string(),
+            None => {
+                let auth_session = AuthSession::from_request_parts(parts, state)
+                    .await
+                    .map_err(|(_, message)| ApiError::internal(message))?;
+                return match auth_session.user {
+                    Some(user) => Ok(ApiUser { user, scope: ApiScope::Write }),
+                    None => Err(ApiError::unauthorized("Log in or send an API token.")),
+                };
+            }
+        };
+
+        let token = authorization
+            .strip_prefix("Bearer ")
+            .map(str::trim)
+            .ok_or_else(|| ApiError::unauthorized("Send the token as `Authorization: Bearer <token>`."))?;
+        let Extension(app_state) = Extension::<AppState>::from_request_parts(parts, state)
+            .await
+            .map_err(ApiError::internal)?;
+
+        match authenticate(&app_state.db, token).await {
+            Ok(Some((user, scope))) => Ok(ApiUser { user, scope }),
+            Ok(None) => Err(ApiError::unauthorized("The token is invalid, expired or revoked.")),
+            Err(err) => Err(ApiError::internal(err)),
+        }
+    }
+}
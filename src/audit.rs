@@ -0,0 +1,81 @@
+//! src/audit.rs
+//! An append only log of security sensitive changes to a user's account.
+use serde::Serialize;
+use sqlx::{FromRow, PgExecutor, PgPool};
+use time::OffsetDateTime;
+
+pub const PASSWORD_CHANGED: &str = "password_changed";
+pub const EMAIL_CHANGED: &str = "email_changed";
+pub const ROLE_GRANTED: &str = "role_granted";
+pub const ROLE_REVOKED: &str = "role_revoked";
+pub const ACCOUNT_DISABLED: &str = "account_disabled";
+pub const ACCOUNT_ENABLED: &str = "account_enabled";
+pub const ACCOUNT_DELETED: &str = "account_deleted";
+pub const PASSWORD_RESET_FORCED: &str = "password_reset_forced";
+pub const PASSWORD_RESET: &str = "password_reset";
+pub const IMPERSONATION_STARTED: &str = "impersonation_started";
+pub const IMPERSONATION_STOPPED: &str = "impersonation_stopped";
+pub const TWO_FACTOR_ENABLED: &str = "two_factor_enabled";
+pub const TWO_FACTOR_DISABLED: &str = "two_factor_disabled";
+pub const TWO_FACTOR_RESET: &str = "two_factor_reset";
+pub const RECOVERY_CODES_REGENERATED: &str = "recovery_codes_regenerated";
+pub const RECOVERY_CODE_USED: &str = "recovery_code_used";
+pub const TWO_FACTOR_REQUIREMENT_CHANGED: &str = "two_factor_requirement_changed";
+pub const API_TOKEN_CREATED: &str = "api_token_created";
+pub const API_TOKEN_REVOKED: &str = "api_token_revoked";
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct AuditEvent {
+    pub action: String,
+    pub detail: Option<String>,
+    /// Set when someone else, like an admin, made the change
+    pub actor_email: Option<String>,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+/// Records `action` for the user. Takes any executor so the event can be
+/// written in the same transaction as the change it describes.
+pub async fn record<'c>(
+    executor: impl PgExecutor<'c>,
+    user_id: uuid::Uuid,
+    action: &str,
+    detail: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    record_by(executor, user_id, user_id, action, detail).await
+}
+
+/// Records `action` for the user, made by `actor_id`.
+pub async fn record_by<'c>(
+    executor: impl PgExecutor<'c>,
+    actor_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    action: &str,
+    detail: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO audit_events (user_id, actor_id, action, detail) VALUES ($1, $2, $3, $4)")
+        .bind(user_id)
+        .bind(actor_id)
+        .bind(action)
+        .bind(detail)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+/// The user's latest events, newest first.
+pub async fn recent_for_user(db: &PgPool, user_id: uuid::Uuid, limit: i64) -> Result<Vec<AuditEvent>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT audit_events.action, audit_events.detail, audit_events.created_at,
+            CASE WHEN audit_events.actor_id <> audit_events.user_id THEN actors.email END AS actor_email
+        FROM audit_events
+        LEFT JOIN users AS actors ON actors.id = audit_events.actor_id
+        WHERE audit_events.user_id = $1
+        ORDER BY audit_events.created_at DESC
+        LIMIT $2"
+    )
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(db)
+        .await
+}
@@ -1,9 +1,11 @@
 //! src/configuration.rs
+use std::net::IpAddr;
 use secrecy::{Secret, ExposeSecret};
 use serde_aux::field_attributes::deserialize_number_from_string;
 use sqlx::postgres::{PgConnectOptions, PgSslMode};
 use sqlx::ConnectOptions;
 use std::convert::{TryFrom, TryInto};
+use crate::domain::TradingSession;
 
 /// The possible runtime environment for our application.
 pub enum Environment {
@@ -41,7 +43,13 @@ pub struct Settings {
     pub test: TestSettings,
     pub application: ApplicationSettings,
     pub email: EmailSettings,
+    pub trading_session: TradingSessionSettings,
     pub redis_uri: Secret<String>,
+    /// Where sessions are kept, `postgres` unless set
+    #[serde(default)]
+    pub session_store: SessionStoreKind,
+    #[serde(default)]
+    pub stats_cache: StatsCacheSettings,
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
@@ -51,6 +59,12 @@ pub struct ApplicationSettings {
     pub host: String,
     pub base_url: String,
     pub hmac_secret: Secret<String>,
+    /// Where uploaded files such as trade screenshots are stored
+    pub upload_directory: String,
+    /// Reverse proxies whose `X-Forwarded-For` is believed. Without them every
+    /// client behind a proxy shares its IP for login throttling.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
@@ -66,14 +80,148 @@ pub struct DatabaseSettings {
 
 #[derive(serde::Deserialize, Clone, Debug)]
 pub struct EmailSettings {
+    /// How emails are delivered, `smtp` unless set
+    #[serde(default)]
+    pub transport: EmailTransport,
     pub smtp_host: String,
     #[serde(deserialize_with = "deserialize_number_from_string")]
     pub smtp_port: u16,
+    #[serde(default)]
+    pub smtp_tls: SmtpTls,
+    /// Leave empty for servers that don't need a login, like MailHog
     pub smtp_username: String,
     pub smtp_password: Secret<String>,
+    /// Where the `file` transport writes emails
+    #[serde(default = "default_email_directory")]
+    pub file_directory: String,
     pub admin_email: String,
     pub support_email: String,
     pub welcome_email: String,
+    #[serde(default)]
+    pub branding: EmailBranding,
+    /// Serves sample emails at `/dev/emails`. Only turn this on in development.
+    #[serde(default)]
+    pub previews: bool,
+}
+
+/// How emails look in each environment
+#[derive(serde::Deserialize, serde::Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct EmailBranding {
+    #[serde(default = "default_product_name")]
+    pub product_name: String,
+    /// Any CSS color, used for the header
+    #[serde(default = "default_accent_color")]
+    pub accent_color: String,
+    /// Put in front of subjects and shown above the email, e.g. `Local`, so
+    /// emails from other environments aren't mistaken for real ones
+    #[serde(default)]
+    pub environment_label: Option<String>,
+}
+
+impl Default for EmailBranding {
+    fn default() -> Self {
+        Self {
+            product_name: default_product_name(),
+            accent_color: default_accent_color(),
+            environment_label: None,
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailTransport {
+    #[default]
+    Smtp,
+    /// Writes `.eml` files to `file_directory` instead of sending
+    File,
+    /// Keeps emails in memory, for tests
+    Memory,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Plain text, only for local servers like MailHog
+    None,
+    /// Upgrades the connection with STARTTLS, usually on port 587
+    #[default]
+    Starttls,
+    /// TLS from the start, usually on port 465
+    Tls,
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionStoreKind {
+    #[default]
+    Postgres,
+    /// The Redis server at `redis_uri`
+    Redis,
+    /// Kept in the app's memory and lost on restart, for tests
+    Memory,
+}
+
+/// Caching computed statistics, like the equity curve, per user and filter
+#[derive(serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct StatsCacheSettings {
+    #[serde(default)]
+    pub backend: StatsCacheBackend,
+    /// Entries are dropped when the user's trades change, and after this long
+    /// in case a change was missed
+    #[serde(default = "default_stats_cache_ttl")]
+    pub ttl_seconds: u64,
+}
+
+impl Default for StatsCacheSettings {
+    fn default() -> Self {
+        Self {
+            backend: StatsCacheBackend::default(),
+            ttl_seconds: default_stats_cache_ttl(),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsCacheBackend {
+    /// Statistics are computed on every request
+    #[default]
+    None,
+    /// Per app instance, so only for a single instance or tests
+    Memory,
+    /// The Redis server at `redis_uri`, shared by every instance
+    Redis,
+}
+
+fn default_stats_cache_ttl() -> u64 {
+    300
+}
+
+fn default_email_directory() -> String {
+    "emails".to_string()
+}
+
+fn default_product_name() -> String {
+    "TradeSalsa".to_string()
+}
+
+fn default_accent_color() -> String {
+    "#d94f30".to_string()
+}
+
+/// The session boundary trades are bucketed into trading days by, e.g.
+/// `America/Chicago` and `17:00` for the CME rollover.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct TradingSessionSettings {
+    pub timezone: String,
+    pub rollover: String,
+}
+
+impl TradingSessionSettings {
+    pub fn parse(&self) -> Result<TradingSession, String> {
+        TradingSession::parse(&self.timezone, &self.rollover)
+    }
 }
 
 #[derive(serde::Deserialize, Clone, Debug)]
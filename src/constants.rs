@@ -9,21 +9,120 @@ pub mod html_templates {
     pub const LOGIN: &str = "login.html";
     pub const HOMEPAGE: &str = "homepage.html";
     pub const E500: &str = "500.html";
+    pub const IMPORT_EXECUTIONS: &str = "import_executions.html";
+    pub const CONTRACT_SPECS: &str = "contract_specs.html";
+    pub const CALENDAR: &str = "calendar.html";
+    pub const TRADES: &str = "trades.html";
+    pub const TRADE: &str = "trade.html";
+    pub const REPORTS: &str = "reports.html";
+    pub const ACCOUNTS: &str = "accounts.html";
+    pub const EVALUATION: &str = "evaluation.html";
+    pub const FORGOT_PASSWORD: &str = "forgot_password.html";
+    pub const RESET_PASSWORD: &str = "reset_password.html";
+    pub const SETTINGS: &str = "settings.html";
+    pub const ADMIN_USERS: &str = "admin_users.html";
+    pub const ADMIN_USER: &str = "admin_user.html";
+    pub const ADMIN_ROLES: &str = "admin_roles.html";
+    pub const IMPERSONATION_BANNER: &str = "partials/_impersonation_banner.html";
+    pub const TWO_FACTOR_LOGIN: &str = "two_factor_login.html";
+    pub const TWO_FACTOR_SETTINGS: &str = "two_factor.html";
+    pub const RECOVERY_CODES: &str = "recovery_codes.html";
+    pub const API_TOKENS: &str = "api_tokens.html";
+    pub const EMAIL_PREVIEWS: &str = "email_previews.html";
+    pub const DIGEST_UNSUBSCRIBE: &str = "digest_unsubscribe.html";
 }
 
-/// email templates
+/// Email templates in `templates/emails`, each with an `.html` and a `.txt` variant
 pub mod email_templates {
-    pub const EMAIL_VERIFICATION: &str = "emails/email_verification.html";
+    pub const EMAIL_VERIFICATION: &str = "email_verification";
+    pub const PASSWORD_RESET: &str = "password_reset";
+    pub const EMAIL_CHANGED: &str = "email_changed";
+    pub const ACCOUNT_LOCKED: &str = "account_locked";
+    pub const DIGEST: &str = "digest";
+}
+
+/// Job kinds stored in the `jobs` table
+pub mod job_kinds {
+    pub const REBUILD_TRADES_FOR_ROOT: &str = "rebuild_trades_for_root";
+    pub const REBUILD_TRADES: &str = "rebuild_trades";
+    pub const QUEUE_DUE_DIGESTS: &str = "queue_due_digests";
+    pub const SEND_RESET_LINK: &str = "send_reset_link";
+    pub const NOTIFY_LOCKOUT: &str = "notify_lockout";
+}
+
+/// Role names from the `roles` table
+pub mod roles {
+    pub const ADMIN: &str = "admin";
+    pub const BASIC: &str = "basic";
+}
+
+/// Permission names from the `permissions` table
+pub mod permissions {
+    pub const MANAGE_CONTRACT_SPECS: &str = "contract_specs.manage";
 }
 
 /// Strings
 pub mod strings {
     pub const WELCOME_EMAIL_SUBJECT: &str = "Welcome to TradeSalsa!";
+    pub const CONFIRM_EMAIL_SUBJECT: &str = "Confirm your TradeSalsa email address";
+    pub const PASSWORD_RESET_EMAIL_SUBJECT: &str = "Reset your TradeSalsa password";
+    pub const EMAIL_CHANGED_SUBJECT: &str = "Your TradeSalsa email address was changed";
+    pub const ACCOUNT_LOCKED_EMAIL_SUBJECT: &str = "Your TradeSalsa account was locked";
+    pub const DAILY_DIGEST_SUBJECT: &str = "Your TradeSalsa daily recap";
+    pub const WEEKLY_DIGEST_SUBJECT: &str = "Your TradeSalsa weekly summary";
     pub const INTERNAL_SERVER_ERROR: &str = "Internal Server Error";
     pub const REGISTER_ACCOUNT_SUCCESS: &str = "Successfully registered account!";
     pub const INVALID_CREDENTIALS: &str = "Invalid Credentials";
+    pub const TOO_MANY_LOGIN_ATTEMPTS: &str = "Too many failed login attempts. Try again in";
     pub const FAILED_TO_COMPILE_SCSS: &str = "Failed to compile SCSS";
     pub const FAILED_TO_WRITE_SCSS: &str = "Failed to write SCSS";
+    pub const CONTRACT_SPEC_SAVED: &str = "Contract spec saved. Trades are being recalculated.";
+    pub const CONTRACT_SPEC_DELETED: &str = "Contract spec deleted. Trades are being recalculated.";
+    pub const IMPORT_FILE_REQUIRED: &str = "Choose a NinjaTrader executions export to import.";
+    pub const JOURNAL_SAVED: &str = "Journal saved.";
+    pub const SCREENSHOT_REQUIRED: &str = "Choose a screenshot to upload.";
+    pub const SCREENSHOT_NOT_AN_IMAGE: &str = "Screenshots must be PNG, JPEG, GIF or WebP images.";
+    pub const SCREENSHOT_UPLOADED: &str = "Screenshot uploaded.";
+    pub const SCREENSHOT_DELETED: &str = "Screenshot deleted.";
+    pub const ACCOUNT_SAVED: &str = "Account saved.";
+    pub const EVALUATION_RULES_SAVED: &str = "Evaluation rules saved.";
+    pub const EMAIL_CONFIRMED: &str = "Your email address is confirmed.";
+    pub const INVALID_CONFIRMATION_LINK: &str = "This confirmation link is invalid or has expired. Log in to send a new one.";
+    pub const CONFIRMATION_SENT: &str = "We sent a new confirmation link to your email address.";
+    pub const EMAIL_ALREADY_CONFIRMED: &str = "Your email address is already confirmed.";
+    pub const EMAIL_NOT_VERIFIED: &str = "Confirm your email address before importing trades.";
+    pub const PASSWORD_RESET_SENT: &str = "If that email address has an account, we sent it a link to reset the password.";
+    pub const INVALID_RESET_LINK: &str = "This password reset link is invalid or has expired. Request a new one below.";
+    pub const PASSWORDS_DO_NOT_MATCH: &str = "The passwords don't match.";
+    pub const PASSWORD_RESET: &str = "Your password was reset. Log in with your new password.";
+    pub const INCORRECT_PASSWORD: &str = "Your current password is incorrect.";
+    pub const PASSWORD_CHANGED: &str = "Your password was changed. Your other sessions were logged out.";
+    pub const EMAIL_UNCHANGED: &str = "That is already your email address.";
+    pub const EMAIL_TAKEN: &str = "That email address is already in use.";
+    pub const EMAIL_CHANGED: &str = "Your email address was changed. We sent a confirmation link to the new address.";
+    pub const ROLE_GRANTED: &str = "Role granted.";
+    pub const ROLE_REVOKED: &str = "Role revoked.";
+    pub const UNKNOWN_ROLE: &str = "That role doesn't exist.";
+    pub const USER_DISABLED: &str = "Account disabled. The user was logged out everywhere.";
+    pub const USER_ENABLED: &str = "Account enabled.";
+    pub const USER_DELETED: &str = "Account deleted.";
+    pub const PASSWORD_RESET_FORCED: &str = "The password was cleared and the user was emailed a reset link.";
+    pub const NOT_ON_YOUR_OWN_ACCOUNT: &str = "You can't do that to your own account.";
+    pub const CANNOT_IMPERSONATE_DISABLED: &str = "Disabled accounts can't be impersonated.";
+    pub const CANNOT_IMPERSONATE_ADMIN: &str = "Other admins can't be impersonated.";
+    pub const ALREADY_IMPERSONATING: &str = "Stop impersonating this user before impersonating another.";
+    pub const IMPERSONATION_STOPPED: &str = "You're back in your own account.";
+    pub const INVALID_TWO_FACTOR_CODE: &str = "That code is incorrect or has already been used.";
+    pub const TWO_FACTOR_LOGIN_EXPIRED: &str = "Your login expired. Enter your password again.";
+    pub const TWO_FACTOR_DISABLED: &str = "Two factor authentication is off.";
+    pub const TWO_FACTOR_REQUIRED: &str = "Your role requires two factor authentication. Set it up to continue.";
+    pub const TWO_FACTOR_REQUIRED_BY_ROLE: &str = "Your role requires two factor authentication, so it can't be turned off.";
+    pub const TWO_FACTOR_REQUIREMENT_SAVED: &str = "Two factor requirement saved.";
+    pub const TWO_FACTOR_RESET: &str = "Two factor authentication was turned off for the user.";
+    pub const API_TOKEN_REVOKED: &str = "Token revoked. It stops working right away.";
+    pub const NO_API_TOKENS_WHILE_IMPERSONATING: &str = "API tokens can't be created while impersonating a user.";
+    pub const DIGESTS_SAVED: &str = "Email digest settings saved.";
+    pub const INVALID_UNSUBSCRIBE_LINK: &str = "This unsubscribe link is invalid. You can turn digests off in your settings instead.";
 }
 
 /// paths
@@ -32,7 +131,72 @@ pub mod route_paths {
     pub const REGISTER: &str = "/register";
     pub const LOGIN: &str = "/login";
     pub const LOGOUT: &str = "/logout";
+    pub const LOGIN_TWO_FACTOR: &str = "/login/two-factor";
+    pub const CONFIRM: &str = "/confirm";
+    pub const RESEND_CONFIRMATION: &str = "/confirm/resend";
+    pub const FORGOT_PASSWORD: &str = "/forgot-password";
+    pub const RESET_PASSWORD: &str = "/reset-password";
+    pub const SETTINGS: &str = "/settings";
+    pub const SETTINGS_PASSWORD: &str = "/password";
+    pub const SETTINGS_EMAIL: &str = "/email";
+    pub const SETTINGS_TWO_FACTOR: &str = "/two-factor";
+    pub const SETTINGS_TWO_FACTOR_ENABLE: &str = "/two-factor/enable";
+    pub const SETTINGS_TWO_FACTOR_DISABLE: &str = "/two-factor/disable";
+    pub const SETTINGS_RECOVERY_CODES: &str = "/two-factor/recovery-codes";
+    /// `SETTINGS_TWO_FACTOR` under `SETTINGS`, for redirects
+    pub const TWO_FACTOR_SETUP: &str = "/settings/two-factor";
+    pub const SETTINGS_API_TOKENS: &str = "/api-tokens";
+    pub const SETTINGS_REVOKE_API_TOKEN: &str = "/api-tokens/:id/revoke";
+    pub const SETTINGS_DIGESTS: &str = "/digests";
+    /// `SETTINGS_API_TOKENS` under `SETTINGS`, for redirects
+    pub const API_TOKENS: &str = "/settings/api-tokens";
+    pub const ADMIN: &str = "/admin";
+    pub const ADMIN_USER: &str = "/users/:id";
+    pub const ADMIN_GRANT_ROLE: &str = "/users/:id/roles/grant";
+    pub const ADMIN_REVOKE_ROLE: &str = "/users/:id/roles/revoke";
+    pub const ADMIN_DISABLE_USER: &str = "/users/:id/disable";
+    pub const ADMIN_ENABLE_USER: &str = "/users/:id/enable";
+    pub const ADMIN_DELETE_USER: &str = "/users/:id/delete";
+    pub const ADMIN_RESET_PASSWORD: &str = "/users/:id/reset-password";
+    pub const ADMIN_IMPERSONATE: &str = "/users/:id/impersonate";
+    pub const ADMIN_RESET_TWO_FACTOR: &str = "/users/:id/two-factor/reset";
+    pub const ADMIN_ROLES: &str = "/roles";
+    pub const ADMIN_ROLE_TWO_FACTOR: &str = "/roles/:name/two-factor";
+    pub const STOP_IMPERSONATING: &str = "/impersonation/stop";
     pub const HEALTH: &str = "/health";
     pub const PROTECTED: &str = "/protected";
+    pub const EXECUTIONS: &str = "/executions";
+    pub const IMPORT: &str = "/import";
+    pub const CONTRACT_SPECS: &str = "/contract-specs";
+    pub const DELETE_CONTRACT_SPEC: &str = "/:root/delete";
+    pub const EQUITY: &str = "/equity";
+    pub const CALENDAR: &str = "/calendar";
+    pub const TRADES: &str = "/trades";
+    pub const TRADE: &str = "/:id";
+    pub const TRADE_JOURNAL: &str = "/:id/journal";
+    pub const TRADE_SCREENSHOTS: &str = "/:id/screenshots";
+    pub const DELETE_TRADE_SCREENSHOT: &str = "/:id/screenshots/:screenshot_id/delete";
+    pub const SCREENSHOTS: &str = "/screenshots";
+    pub const SCREENSHOT: &str = "/:file_name";
+    pub const REPORTS: &str = "/reports";
+    pub const ACCOUNTS: &str = "/accounts";
+    pub const ACCOUNT: &str = "/:id";
+    pub const ACCOUNT_EVALUATION: &str = "/:id/evaluation";
+    pub const API_V1: &str = "/api/v1";
+    pub const API_OPENAPI: &str = "/openapi.json";
+    pub const API_EXECUTIONS: &str = "/executions";
+    pub const API_TRADES: &str = "/trades";
+    pub const API_TRADE: &str = "/trades/:id";
+    pub const API_ACCOUNTS: &str = "/accounts";
+    pub const API_ACCOUNT: &str = "/accounts/:id";
+    pub const API_TAGS: &str = "/tags";
+    pub const API_STATS: &str = "/stats";
+    pub const API_STATS_EQUITY: &str = "/stats/equity";
+    pub const API_STATS_DAILY: &str = "/stats/daily";
+    pub const API_STATS_BREAKDOWNS: &str = "/stats/breakdowns";
+    pub const EMAIL_PREVIEWS: &str = "/dev/emails";
+    pub const EMAIL_PREVIEW: &str = "/:name";
+    pub const DIGESTS: &str = "/digests";
+    pub const DIGEST_UNSUBSCRIBE: &str = "/unsubscribe";
 }
 
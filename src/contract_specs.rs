@@ -0,0 +1,192 @@
+//! src/contract_specs.rs
+//! The registry of futures contract specifications. Point value and tick size
+//! are looked up by instrument root, so "ES 09-24" and "ES 12-24" share the
+//! "ES" spec.
+use std::collections::HashMap;
+use serde::Serialize;
+use sqlx::{FromRow, PgExecutor, PgPool};
+use crate::domain::{ContractMonth, Instrument, NewContractSpec};
+use crate::trades::ContractMultiplier;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ContractSpec {
+    pub root: String,
+    pub description: String,
+    pub exchange: String,
+    pub point_value: f64,
+    pub tick_size: f64,
+    pub currency: String,
+}
+
+impl ContractSpec {
+    /// Dollar value of a single tick
+    pub fn tick_value(&self) -> f64 {
+        self.point_value * self.tick_size
+    }
+
+    pub fn multiplier(&self) -> ContractMultiplier {
+        ContractMultiplier {
+            point_value: self.point_value,
+            tick_size: self.tick_size,
+        }
+    }
+}
+
+/// A NinjaTrader instrument matched to its contract spec.
+#[derive(Debug, Clone)]
+pub struct ResolvedInstrument<'a> {
+    pub root: String,
+    pub expiry: Option<ContractMonth>,
+    pub spec: &'a ContractSpec,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ContractSpecRegistry {
+    specs: HashMap<String, ContractSpec>,
+}
+
+impl ContractSpecRegistry {
+    pub fn new(specs: Vec<ContractSpec>) -> Self {
+        Self {
+            specs: specs.into_iter().map(|spec| (spec.root.clone(), spec)).collect(),
+        }
+    }
+
+    pub async fn load(db: &PgPool) -> Result<Self, sqlx::Error> {
+        Ok(Self::new(all(db).await?))
+    }
+
+    pub fn get(&self, root: &str) -> Option<&ContractSpec> {
+        self.specs.get(&root.to_uppercase())
+    }
+
+    /// Parses a NinjaTrader instrument like "ES 09-24" and finds the spec for its root.
+    pub fn resolve(&self, instrument: &str) -> Result<ResolvedInstrument<'_>, String> {
+        let parsed = Instrument::parse(instrument)?;
+        let spec = self
+            .get(&parsed.root)
+            .ok_or_else(|| format!("There is no contract spec for {}.", parsed.root))?;
+        Ok(ResolvedInstrument {
+            root: parsed.root,
+            expiry: parsed.expiry,
+            spec,
+        })
+    }
+
+    pub fn multiplier(&self, instrument: &str) -> Option<ContractMultiplier> {
+        match self.resolve(instrument) {
+            Ok(resolved) => Some(resolved.spec.multiplier()),
+            Err(err) => {
+                tracing::warn!(instrument, "{}", err);
+                None
+            }
+        }
+    }
+}
+
+pub async fn all(db: &PgPool) -> Result<Vec<ContractSpec>, sqlx::Error> {
+    sqlx::query_as("SELECT root, description, exchange, point_value, tick_size, currency FROM contract_specs ORDER BY root")
+        .fetch_all(db)
+        .await
+}
+
+pub async fn upsert<'e, E>(executor: E, spec: &NewContractSpec) -> Result<(), sqlx::Error>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query(
+        "INSERT INTO contract_specs (root, description, exchange, point_value, tick_size, currency)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (root) DO UPDATE SET
+            description = EXCLUDED.description,
+            exchange = EXCLUDED.exchange,
+            point_value = EXCLUDED.point_value,
+            tick_size = EXCLUDED.tick_size,
+            currency = EXCLUDED.currency"
+    )
+        .bind(&spec.root)
+        .bind(&spec.description)
+        .bind(&spec.exchange)
+        .bind(spec.point_value)
+        .bind(spec.tick_size)
+        .bind(&spec.currency)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+pub async fn delete<'e, E>(executor: E, root: &str) -> Result<(), sqlx::Error>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query("DELETE FROM contract_specs WHERE root = $1")
+        .bind(root.to_uppercase())
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+/// Users with executions in any contract of `root`. Their trades need
+/// rebuilding when the spec for `root` changes.
+pub async fn users_trading_root(db: &PgPool, root: &str) -> Result<Vec<uuid::Uuid>, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT DISTINCT user_id FROM executions
+        WHERE UPPER(SPLIT_PART(instrument, ' ', 1)) = $1"
+    )
+        .bind(root.to_uppercase())
+        .fetch_all(db)
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContractSpec, ContractSpecRegistry};
+    use claims::assert_err;
+    use time::Month;
+
+    fn registry() -> ContractSpecRegistry {
+        ContractSpecRegistry::new(vec![
+            ContractSpec {
+                root: "ES".to_string(),
+                description: "E-mini S&P 500".to_string(),
+                exchange: "CME".to_string(),
+                point_value: 50.0,
+                tick_size: 0.25,
+                currency: "USD".to_string(),
+            },
+            ContractSpec {
+                root: "MNQ".to_string(),
+                description: "Micro E-mini Nasdaq-100".to_string(),
+                exchange: "CME".to_string(),
+                point_value: 2.0,
+                tick_size: 0.25,
+                currency: "USD".to_string(),
+            },
+        ])
+    }
+
+    #[test]
+    fn instruments_resolve_to_their_root() {
+        let registry = registry();
+        let resolved = registry.resolve("ES 09-24").unwrap();
+        assert_eq!(resolved.root, "ES");
+        assert_eq!(resolved.expiry.unwrap().month, Month::September);
+        assert_eq!(resolved.spec.point_value, 50.0);
+        assert_eq!(resolved.spec.tick_value(), 12.5);
+
+        let resolved = registry.resolve("MNQ 12-24").unwrap();
+        assert_eq!(resolved.spec.point_value, 2.0);
+    }
+
+    #[test]
+    fn unknown_roots_are_rejected() {
+        assert_err!(registry().resolve("ZZ 09-24"));
+        assert!(registry().multiplier("ZZ 09-24").is_none());
+    }
+
+    #[test]
+    fn roots_are_not_matched_by_prefix() {
+        // MES must not pick up the ES spec
+        assert_err!(registry().resolve("MES 09-24"));
+    }
+}
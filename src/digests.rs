@@ -0,0 +1,399 @@
+//! src/digests.rs
+//! Daily and weekly digest emails users opt in to at `/settings`. A task
+//! started in `startup::run` queues a `QueueDueDigests` job every few minutes,
+//! which checks for periods that have closed and queues a digest for each
+//! subscriber who traded in them.
+//! Unsubscribe links are signed with `hmac_secret`, so they work without
+//! logging in.
+use std::collections::HashSet;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, Secret};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::{FromRow, PgPool};
+use time::{Date, Duration};
+use time::macros::{date, format_description};
+use crate::constants::{email_templates, job_kinds, route_paths, strings};
+use crate::domain::{DigestKind, DigestPeriod, TradingSession};
+use crate::email_outbox;
+use crate::emailer::{Email, EmailTemplate};
+use crate::jobs::{self, Job};
+use crate::routes::email_previews::sample_trade;
+use crate::startup::AppState;
+use crate::stats::DigestStats;
+use crate::trades::{self, TradeFilter};
+
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// Which digests a user gets.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Subscriptions {
+    pub daily: bool,
+    pub weekly: bool,
+}
+
+pub async fn subscriptions(db: &PgPool, user_id: uuid::Uuid) -> Result<Subscriptions, sqlx::Error> {
+    let kinds: Vec<String> = sqlx::query_scalar("SELECT kind FROM digest_subscriptions WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_all(db)
+        .await?;
+    Ok(Subscriptions {
+        daily: kinds.iter().any(|kind| kind == DigestKind::Daily.as_str()),
+        weekly: kinds.iter().any(|kind| kind == DigestKind::Weekly.as_str()),
+    })
+}
+
+/// Subscribes or unsubscribes the user from each digest. New subscriptions
+/// start with the next period to close rather than one that already has.
+pub async fn save_subscriptions(
+    db: &PgPool,
+    user_id: uuid::Uuid,
+    subscriptions: Subscriptions,
+    session: &TradingSession,
+) -> Result<(), sqlx::Error> {
+    let last_closed_day = last_closed_trading_day(db, session).await?;
+    for kind in DigestKind::ALL {
+        let subscribed = match kind {
+            DigestKind::Daily => subscriptions.daily,
+            DigestKind::Weekly => subscriptions.weekly,
+        };
+        if subscribed {
+            sqlx::query(
+                "INSERT INTO digest_subscriptions (user_id, kind, last_period_start) VALUES ($1, $2, $3)
+                ON CONFLICT (user_id, kind) DO NOTHING"
+            )
+                .bind(user_id)
+                .bind(kind.as_str())
+                .bind(kind.latest_period(last_closed_day).first_day)
+                .execute(db)
+                .await?;
+        } else {
+            unsubscribe(db, user_id, kind).await?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn unsubscribe(db: &PgPool, user_id: uuid::Uuid, kind: DigestKind) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM digest_subscriptions WHERE user_id = $1 AND kind = $2")
+        .bind(user_id)
+        .bind(kind.as_str())
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// The trading day before the one in progress.
+pub async fn last_closed_trading_day(db: &PgPool, session: &TradingSession) -> Result<Date, sqlx::Error> {
+    Ok(trades::current_trading_day(db, session).await? - Duration::days(1))
+}
+
+fn unsubscribe_mac(hmac_secret: &Secret<String>, user_id: uuid::Uuid, kind: DigestKind) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(hmac_secret.expose_secret().as_bytes())
+        .expect("HMAC can take a key of any size");
+    mac.update(format!("digest-unsubscribe:{}:{}", user_id, kind.as_str()).as_bytes());
+    mac
+}
+
+/// Hex HMAC-SHA256 of the user and digest, for unsubscribe links.
+pub fn unsubscribe_signature(hmac_secret: &Secret<String>, user_id: uuid::Uuid, kind: DigestKind) -> String {
+    hex::encode(unsubscribe_mac(hmac_secret, user_id, kind).finalize().into_bytes())
+}
+
+/// Checks an unsubscribe link's signature in constant time.
+pub fn verify_unsubscribe_signature(
+    hmac_secret: &Secret<String>,
+    user_id: uuid::Uuid,
+    kind: DigestKind,
+    signature: &str,
+) -> bool {
+    match hex::decode(signature) {
+        Ok(signature) => unsubscribe_mac(hmac_secret, user_id, kind).verify_slice(&signature).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// A rule an evaluation account broke during the period.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct DigestBreach {
+    pub account: String,
+    pub rule: String,
+    pub trading_day: Date,
+    pub value: f64,
+    pub breach_limit: f64,
+}
+
+/// What a digest email shows.
+#[derive(Debug, Clone, Serialize)]
+pub struct DigestEmail {
+    /// "Daily recap" or "Weekly summary"
+    pub title: String,
+    /// The trading days covered, e.g. "Monday, July 22, 2024"
+    pub period: String,
+    pub stats: DigestStats,
+    pub breaches: Vec<DigestBreach>,
+    pub trades_link: String,
+    pub settings_link: String,
+    pub unsubscribe_link: String,
+}
+
+impl EmailTemplate for DigestEmail {
+    const NAME: &'static str = email_templates::DIGEST;
+
+    fn sample() -> Self {
+        let period = DigestKind::Weekly.latest_period(date!(2024-07-26));
+        DigestEmail {
+            title: title(DigestKind::Weekly).to_string(),
+            period: describe_period(&period),
+            stats: DigestStats::new(
+                &[
+                    sample_trade("ES 09-24", 412.5, date!(2024-07-22)),
+                    sample_trade("NQ 09-24", -180.0, date!(2024-07-24)),
+                    sample_trade("ES 09-24", 95.0, date!(2024-07-26)),
+                ],
+                &HashSet::new(),
+            ),
+            breaches: vec![DigestBreach {
+                account: "APEX-12345-01".to_string(),
+                rule: rule_name("daily_loss_limit").to_string(),
+                trading_day: date!(2024-07-24),
+                value: -1150.0,
+                breach_limit: 1000.0,
+            }],
+            trades_link: format!("https://example.com{}?from=2024-07-22&to=2024-07-26", route_paths::TRADES),
+            settings_link: format!("https://example.com{}", route_paths::SETTINGS),
+            unsubscribe_link: format!("https://example.com{}{}?sample", route_paths::DIGESTS, route_paths::DIGEST_UNSUBSCRIBE),
+        }
+    }
+}
+
+fn title(kind: DigestKind) -> &'static str {
+    match kind {
+        DigestKind::Daily => "Daily recap",
+        DigestKind::Weekly => "Weekly summary",
+    }
+}
+
+fn subject(kind: DigestKind) -> &'static str {
+    match kind {
+        DigestKind::Daily => strings::DAILY_DIGEST_SUBJECT,
+        DigestKind::Weekly => strings::WEEKLY_DIGEST_SUBJECT,
+    }
+}
+
+fn describe_period(period: &DigestPeriod) -> String {
+    let day = format_description!("[weekday], [month repr:long] [day padding:none], [year]");
+    let month_day = format_description!("[month repr:long] [day padding:none]");
+    match period.kind {
+        DigestKind::Daily => period.first_day.format(day).unwrap_or_default(),
+        DigestKind::Weekly => format!(
+            "{} to {}, {}",
+            period.first_day.format(month_day).unwrap_or_default(),
+            period.last_day.format(month_day).unwrap_or_default(),
+            period.last_day.year(),
+        ),
+    }
+}
+
+/// Matches the names on the evaluation page.
+fn rule_name(rule: &str) -> &str {
+    match rule {
+        "profit_target" => "Profit Target",
+        "max_drawdown" => "Max Drawdown",
+        "daily_loss_limit" => "Daily Loss Limit",
+        "min_trading_days" => "Minimum Trading Days",
+        "consistency" => "Consistency",
+        other => other,
+    }
+}
+
+async fn breaches_between(db: &PgPool, user_id: uuid::Uuid, period: &DigestPeriod) -> Result<Vec<DigestBreach>, sqlx::Error> {
+    let mut breaches: Vec<DigestBreach> = sqlx::query_as(
+        "SELECT accounts.display_name AS account, rule_breaches.rule, rule_breaches.trading_day,
+            rule_breaches.value, rule_breaches.breach_limit
+        FROM rule_breaches JOIN accounts ON accounts.id = rule_breaches.account_id
+        WHERE accounts.user_id = $1 AND rule_breaches.trading_day BETWEEN $2 AND $3
+        ORDER BY rule_breaches.trading_day, accounts.display_name, rule_breaches.rule"
+    )
+        .bind(user_id)
+        .bind(period.first_day)
+        .bind(period.last_day)
+        .fetch_all(db)
+        .await?;
+    for breach in &mut breaches {
+        breach.rule = rule_name(&breach.rule).to_string();
+    }
+    Ok(breaches)
+}
+
+async fn noted_trade_ids(db: &PgPool, user_id: uuid::Uuid, trade_ids: &[uuid::Uuid]) -> Result<HashSet<uuid::Uuid>, sqlx::Error> {
+    let noted: Vec<uuid::Uuid> = sqlx::query_scalar(
+        "SELECT id FROM trades WHERE user_id = $1 AND id = ANY($2) AND notes <> ''"
+    )
+        .bind(user_id)
+        .bind(trade_ids)
+        .fetch_all(db)
+        .await?;
+    Ok(noted.into_iter().collect())
+}
+
+/// The user's digest for the period, or `None` when they didn't trade in it.
+pub async fn render_digest(
+    state: &AppState,
+    user_id: uuid::Uuid,
+    email: &str,
+    period: &DigestPeriod,
+) -> Result<Option<Email>, anyhow::Error> {
+    let filter = TradeFilter {
+        from: Some(period.first_day),
+        to: Some(period.last_day),
+        ..Default::default()
+    };
+    let period_trades = trades::for_user(&state.db, user_id, &filter, &state.trading_session).await?;
+    if period_trades.is_empty() {
+        return Ok(None);
+    }
+    let trade_ids: Vec<uuid::Uuid> = period_trades.iter().map(|trade| trade.id).collect();
+    let noted = noted_trade_ids(&state.db, user_id, &trade_ids).await?;
+
+    let signature = unsubscribe_signature(&state.hmac_secret, user_id, period.kind);
+    let digest = DigestEmail {
+        title: title(period.kind).to_string(),
+        period: describe_period(period),
+        stats: DigestStats::new(&period_trades, &noted),
+        breaches: breaches_between(&state.db, user_id, period).await?,
+        trades_link: format!(
+            "{}{}?from={}&to={}",
+            state.base_url, route_paths::TRADES, period.first_day, period.last_day,
+        ),
+        settings_link: format!("{}{}", state.base_url, route_paths::SETTINGS),
+        unsubscribe_link: format!(
+            "{}{}{}?{}",
+            state.base_url,
+            route_paths::DIGESTS,
+            route_paths::DIGEST_UNSUBSCRIBE,
+            serde_urlencoded::to_string([
+                ("user", user_id.to_string().as_str()),
+                ("kind", period.kind.as_str()),
+                ("signature", signature.as_str()),
+            ])?,
+        ),
+    };
+    let subject = format!("{} for {}", subject(period.kind), digest.period);
+    Ok(Some(Email::render(&state.tera, &state.email_settings.branding, email, &subject, &digest)?))
+}
+
+/// Queues the digests for periods that closed by `last_closed_day` to every
+/// confirmed subscriber who hasn't had them yet. Returns how many were queued.
+///
+/// Each digest is rendered before anything is locked, then queued in its own
+/// short transaction that also moves the subscription on to the period. The
+/// update only matches while the period is still due, so several app
+/// instances never queue the same digest twice. A digest that fails to render
+/// is logged and left due for the next check, without holding up the others.
+pub async fn queue_due(state: &AppState, last_closed_day: Date) -> Result<usize, anyhow::Error> {
+    let mut queued = 0;
+    for kind in DigestKind::ALL {
+        let period = kind.latest_period(last_closed_day);
+        let due: Vec<(uuid::Uuid, String)> = sqlx::query_as(
+            "SELECT users.id, users.email FROM digest_subscriptions
+            JOIN users ON users.id = digest_subscriptions.user_id
+            WHERE digest_subscriptions.kind = $1 AND digest_subscriptions.last_period_start < $2
+                AND users.email_verified_at IS NOT NULL AND users.disabled_at IS NULL"
+        )
+            .bind(kind.as_str())
+            .bind(period.first_day)
+            .fetch_all(&state.db)
+            .await?;
+
+        for (user_id, email) in due {
+            match queue_digest(state, user_id, &email, &period).await {
+                Ok(true) => queued += 1,
+                Ok(false) => {}
+                Err(err) => tracing::error!(error = %err, %user_id, kind = kind.as_str(), "Failed to queue a digest"),
+            }
+        }
+    }
+    Ok(queued)
+}
+
+/// Queues one subscriber's digest for `period`. Returns whether an email was
+/// queued, which it isn't when they didn't trade or it was already queued.
+async fn queue_digest(
+    state: &AppState,
+    user_id: uuid::Uuid,
+    email: &str,
+    period: &DigestPeriod,
+) -> Result<bool, anyhow::Error> {
+    let message = render_digest(state, user_id, email, period).await?;
+
+    let mut transaction = state.db.begin().await?;
+    let claimed = sqlx::query(
+        "UPDATE digest_subscriptions SET last_period_start = $1
+        WHERE user_id = $2 AND kind = $3 AND last_period_start < $1"
+    )
+        .bind(period.first_day)
+        .bind(user_id)
+        .bind(period.kind.as_str())
+        .execute(&mut *transaction)
+        .await?
+        .rows_affected() == 1;
+    if !claimed {
+        return Ok(false);
+    }
+    if let Some(message) = &message {
+        email_outbox::enqueue(&mut *transaction, message).await?;
+    }
+    transaction.commit().await?;
+    Ok(message.is_some())
+}
+
+/// Queues the digests for every period that has closed.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QueueDueDigests;
+
+#[async_trait]
+impl Job for QueueDueDigests {
+    const KIND: &'static str = job_kinds::QUEUE_DUE_DIGESTS;
+
+    async fn run(&self, state: &AppState) -> Result<(), anyhow::Error> {
+        let last_closed_day = last_closed_trading_day(&state.db, &state.trading_session).await?;
+        let queued = queue_due(state, last_closed_day).await?;
+        tracing::info!(queued, "Queued digest emails");
+        Ok(())
+    }
+}
+
+/// Queues a `QueueDueDigests` job every few minutes, until the task is
+/// aborted. Only the job does any work, so stopping this loses nothing.
+pub async fn run_scheduler(db: PgPool) {
+    loop {
+        if let Err(err) = jobs::enqueue_unless_queued(&db, &QueueDueDigests).await {
+            tracing::error!(error = %err, "Failed to queue the digest check");
+        }
+        tokio::time::sleep(CHECK_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{unsubscribe_signature, verify_unsubscribe_signature};
+    use crate::domain::DigestKind;
+    use secrecy::Secret;
+
+    #[test]
+    fn unsubscribe_signatures_are_tied_to_the_user_digest_and_secret() {
+        let secret = Secret::new("secret".to_string());
+        let user_id = uuid::Uuid::new_v4();
+        let signature = unsubscribe_signature(&secret, user_id, DigestKind::Daily);
+        assert_eq!(signature.len(), 64);
+
+        assert!(verify_unsubscribe_signature(&secret, user_id, DigestKind::Daily, &signature));
+        assert!(!verify_unsubscribe_signature(&secret, user_id, DigestKind::Weekly, &signature));
+        assert!(!verify_unsubscribe_signature(&secret, uuid::Uuid::new_v4(), DigestKind::Daily, &signature));
+        let other_secret = Secret::new("other".to_string());
+        assert!(!verify_unsubscribe_signature(&other_secret, user_id, DigestKind::Daily, &signature));
+        assert!(!verify_unsubscribe_signature(&secret, user_id, DigestKind::Daily, "not hex"));
+    }
+}
@@ -0,0 +1,109 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountType {
+    Sim,
+    Live,
+    /// A prop firm evaluation or challenge
+    Evaluation,
+    /// A funded prop firm account
+    Funded,
+}
+
+impl AccountType {
+    pub fn parse(s: &str) -> Result<AccountType, String> {
+        match s.trim().to_lowercase().as_str() {
+            "sim" => Ok(Self::Sim),
+            "live" => Ok(Self::Live),
+            "evaluation" => Ok(Self::Evaluation),
+            "funded" => Ok(Self::Funded),
+            other => Err(format!("{} is not an account type. Use sim, live, evaluation or funded.", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Sim => "sim",
+            Self::Live => "live",
+            Self::Evaluation => "evaluation",
+            Self::Funded => "funded",
+        }
+    }
+
+    /// NinjaTrader's simulation accounts are named Sim101, Sim102 and so on.
+    /// Everything else is assumed to be live until the user says otherwise.
+    pub fn guess(account_name: &str) -> AccountType {
+        if account_name.to_lowercase().starts_with("sim") {
+            Self::Sim
+        } else {
+            Self::Live
+        }
+    }
+}
+
+/// The user editable details of an account.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountDetails {
+    pub display_name: String,
+    pub account_type: AccountType,
+    pub starting_balance: f64,
+    pub currency: String,
+}
+
+impl AccountDetails {
+    pub fn parse(
+        display_name: &str,
+        account_type: &str,
+        starting_balance: &str,
+        currency: &str,
+    ) -> Result<AccountDetails, String> {
+        let display_name = display_name.trim();
+        if display_name.is_empty() || display_name.chars().count() > 100 {
+            return Err("Display names must be between 1 and 100 characters.".to_string());
+        }
+        let account_type = AccountType::parse(account_type)?;
+        let starting_balance = match starting_balance.trim().trim_start_matches('$').replace(',', "").parse::<f64>() {
+            Ok(balance) if balance.is_finite() && balance >= 0.0 => balance,
+            _ => return Err("Starting balance must be a dollar amount of zero or more.".to_string()),
+        };
+        let currency = currency.trim().to_uppercase();
+        if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(format!("{} is not a valid currency code.", currency));
+        }
+
+        Ok(AccountDetails {
+            display_name: display_name.to_string(),
+            account_type,
+            starting_balance,
+            currency,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AccountDetails, AccountType};
+    use claims::assert_err;
+
+    #[test]
+    fn valid_details_are_parsed_successfully() {
+        let details = AccountDetails::parse(" Apex 50K ", "Evaluation", "$50,000", "usd").unwrap();
+        assert_eq!(details.display_name, "Apex 50K");
+        assert_eq!(details.account_type, AccountType::Evaluation);
+        assert_eq!(details.starting_balance, 50_000.0);
+        assert_eq!(details.currency, "USD");
+    }
+
+    #[test]
+    fn invalid_details_are_rejected() {
+        assert_err!(AccountDetails::parse("", "sim", "0", "USD"));
+        assert_err!(AccountDetails::parse("Sim", "paper", "0", "USD"));
+        assert_err!(AccountDetails::parse("Sim", "sim", "-1", "USD"));
+        assert_err!(AccountDetails::parse("Sim", "sim", "lots", "USD"));
+        assert_err!(AccountDetails::parse("Sim", "sim", "0", "DOLLARS"));
+    }
+
+    #[test]
+    fn account_types_are_guessed_from_the_name() {
+        assert_eq!(AccountType::guess("Sim101"), AccountType::Sim);
+        assert_eq!(AccountType::guess("APEX-12345-01"), AccountType::Live);
+    }
+}
@@ -0,0 +1,88 @@
+use time::Duration;
+
+/// What an API token is allowed to do. Write tokens can read too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ApiScope {
+    Read,
+    Write,
+}
+
+impl ApiScope {
+    pub fn parse(s: &str) -> Result<ApiScope, String> {
+        match s.trim().to_lowercase().as_str() {
+            "read" => Ok(Self::Read),
+            "write" => Ok(Self::Write),
+            other => Err(format!("{} is not a token scope. Use read or write.", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Read => "read",
+            Self::Write => "write",
+        }
+    }
+}
+
+/// A token as requested on the settings page.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewApiToken {
+    pub name: String,
+    pub scope: ApiScope,
+    /// `None` for tokens that don't expire
+    pub expires_in: Option<Duration>,
+}
+
+impl NewApiToken {
+    pub fn parse(name: &str, scope: &str, expires_in_days: &str) -> Result<NewApiToken, String> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > 100 {
+            return Err("Token names must be between 1 and 100 characters.".to_string());
+        }
+        let scope = ApiScope::parse(scope)?;
+        let expires_in = match expires_in_days.trim() {
+            "" | "never" => None,
+            days => match days.parse::<i64>() {
+                Ok(days) if (1..=3650).contains(&days) => Some(Duration::days(days)),
+                _ => return Err("Tokens must expire in 1 to 3650 days, or never.".to_string()),
+            },
+        };
+
+        Ok(NewApiToken {
+            name: name.to_string(),
+            scope,
+            expires_in,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ApiScope, NewApiToken};
+    use claims::assert_err;
+    use time::Duration;
+
+    #[test]
+    fn valid_tokens_are_parsed_successfully() {
+        let token = NewApiToken::parse(" NinjaTrader add-on ", "Write", "90").unwrap();
+        assert_eq!(token.name, "NinjaTrader add-on");
+        assert_eq!(token.scope, ApiScope::Write);
+        assert_eq!(token.expires_in, Some(Duration::days(90)));
+        assert_eq!(NewApiToken::parse("Notebook", "read", "never").unwrap().expires_in, None);
+    }
+
+    #[test]
+    fn invalid_tokens_are_rejected() {
+        assert_err!(NewApiToken::parse("", "read", ""));
+        assert_err!(NewApiToken::parse(&"a".repeat(101), "read", ""));
+        assert_err!(NewApiToken::parse("Script", "admin", ""));
+        assert_err!(NewApiToken::parse("Script", "read", "0"));
+        assert_err!(NewApiToken::parse("Script", "read", "soon"));
+    }
+
+    #[test]
+    fn write_scopes_include_read() {
+        assert!(ApiScope::Write >= ApiScope::Read);
+        assert!(ApiScope::Read < ApiScope::Write);
+    }
+}
@@ -0,0 +1,82 @@
+/// A validated contract specification, ready to be saved to the registry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewContractSpec {
+    pub root: String,
+    pub description: String,
+    pub exchange: String,
+    pub point_value: f64,
+    pub tick_size: f64,
+    pub currency: String,
+}
+
+impl NewContractSpec {
+    pub fn parse(
+        root: &str,
+        description: &str,
+        exchange: &str,
+        point_value: &str,
+        tick_size: &str,
+        currency: &str,
+    ) -> Result<NewContractSpec, String> {
+        let root = root.trim().to_uppercase();
+        if root.is_empty() || root.len() > 10 || !root.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(format!("{} is not a valid instrument root.", root));
+        }
+        let point_value = parse_positive("Point value", point_value)?;
+        let tick_size = parse_positive("Tick size", tick_size)?;
+        let currency = currency.trim().to_uppercase();
+        if currency.len() != 3 || !currency.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(format!("{} is not a valid currency code.", currency));
+        }
+
+        Ok(NewContractSpec {
+            root,
+            description: description.trim().to_string(),
+            exchange: exchange.trim().to_uppercase(),
+            point_value,
+            tick_size,
+            currency,
+        })
+    }
+}
+
+fn parse_positive(name: &str, s: &str) -> Result<f64, String> {
+    match s.trim().parse::<f64>() {
+        Ok(value) if value.is_finite() && value > 0.0 => Ok(value),
+        _ => Err(format!("{} must be a number greater than zero.", name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NewContractSpec;
+    use claims::assert_err;
+
+    #[test]
+    fn valid_spec_is_parsed_successfully() {
+        let spec = NewContractSpec::parse("mes", "Micro E-mini S&P 500", "cme", "5", "0.25", "usd").unwrap();
+        assert_eq!(spec.root, "MES");
+        assert_eq!(spec.exchange, "CME");
+        assert_eq!(spec.point_value, 5.0);
+        assert_eq!(spec.tick_size, 0.25);
+        assert_eq!(spec.currency, "USD");
+    }
+
+    #[test]
+    fn invalid_root_is_rejected() {
+        assert_err!(NewContractSpec::parse("", "", "CME", "5", "0.25", "USD"));
+        assert_err!(NewContractSpec::parse("ES 09-24", "", "CME", "5", "0.25", "USD"));
+    }
+
+    #[test]
+    fn non_positive_values_are_rejected() {
+        assert_err!(NewContractSpec::parse("ES", "", "CME", "0", "0.25", "USD"));
+        assert_err!(NewContractSpec::parse("ES", "", "CME", "50", "-0.25", "USD"));
+        assert_err!(NewContractSpec::parse("ES", "", "CME", "fifty", "0.25", "USD"));
+    }
+
+    #[test]
+    fn invalid_currency_is_rejected() {
+        assert_err!(NewContractSpec::parse("ES", "", "CME", "50", "0.25", "dollars"));
+    }
+}
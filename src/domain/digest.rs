@@ -0,0 +1,99 @@
+use time::{Date, Duration, Weekday};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestKind {
+    /// A recap of the last trading day, sent after the session closes
+    Daily,
+    /// A summary of the trading week, sent after Friday's session closes
+    Weekly,
+}
+
+impl DigestKind {
+    pub const ALL: [DigestKind; 2] = [DigestKind::Daily, DigestKind::Weekly];
+
+    pub fn parse(s: &str) -> Result<DigestKind, String> {
+        match s.trim().to_lowercase().as_str() {
+            "daily" => Ok(Self::Daily),
+            "weekly" => Ok(Self::Weekly),
+            other => Err(format!("{} is not a digest. Use daily or weekly.", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Daily => "daily",
+            Self::Weekly => "weekly",
+        }
+    }
+
+    /// The most recent period that has closed, given the last trading day that
+    /// has closed.
+    pub fn latest_period(&self, last_closed_day: Date) -> DigestPeriod {
+        match self {
+            Self::Daily => DigestPeriod {
+                kind: *self,
+                first_day: last_closed_day,
+                last_day: last_closed_day,
+            },
+            Self::Weekly => {
+                let since_friday = (last_closed_day.weekday().number_days_from_monday() + 7
+                    - Weekday::Friday.number_days_from_monday()) % 7;
+                let friday = last_closed_day - Duration::days(since_friday.into());
+                DigestPeriod {
+                    kind: *self,
+                    first_day: friday - Duration::days(4),
+                    last_day: friday,
+                }
+            }
+        }
+    }
+}
+
+/// The trading days a digest covers, both inclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DigestPeriod {
+    pub kind: DigestKind,
+    pub first_day: Date,
+    pub last_day: Date,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DigestKind, DigestPeriod};
+    use claims::assert_err;
+    use time::macros::date;
+
+    #[test]
+    fn kinds_are_parsed_ignoring_case() {
+        assert_eq!(DigestKind::parse(" Daily ").unwrap(), DigestKind::Daily);
+        assert_eq!(DigestKind::parse("weekly").unwrap(), DigestKind::Weekly);
+        assert_err!(DigestKind::parse("monthly"));
+        for kind in DigestKind::ALL {
+            assert_eq!(DigestKind::parse(kind.as_str()).unwrap(), kind);
+        }
+    }
+
+    #[test]
+    fn daily_digests_cover_the_last_closed_day() {
+        let period = DigestKind::Daily.latest_period(date!(2024-07-23));
+        assert_eq!((period.first_day, period.last_day), (date!(2024-07-23), date!(2024-07-23)));
+    }
+
+    #[test]
+    fn weekly_digests_cover_the_last_week_that_closed_on_friday() {
+        let week = DigestPeriod {
+            kind: DigestKind::Weekly,
+            first_day: date!(2024-07-22),
+            last_day: date!(2024-07-26),
+        };
+        // Friday closes the week, and the weekend doesn't start a new one
+        for last_closed_day in [date!(2024-07-26), date!(2024-07-27), date!(2024-07-28)] {
+            assert_eq!(DigestKind::Weekly.latest_period(last_closed_day), week);
+        }
+        // Until the next Friday closes, the last week is still the latest
+        for last_closed_day in [date!(2024-07-29), date!(2024-08-01)] {
+            assert_eq!(DigestKind::Weekly.latest_period(last_closed_day), week);
+        }
+        assert_eq!(DigestKind::Weekly.latest_period(date!(2024-08-02)).first_day, date!(2024-07-29));
+    }
+}
@@ -0,0 +1,118 @@
+/// How a trailing max drawdown follows the account's high water mark.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DrawdownType {
+    /// Trails the highest balance after any trade
+    #[default]
+    Intraday,
+    /// Trails the highest end of day balance
+    EndOfDay,
+}
+
+impl DrawdownType {
+    pub fn parse(s: &str) -> Result<DrawdownType, String> {
+        match s.trim() {
+            "intraday" => Ok(Self::Intraday),
+            "end_of_day" => Ok(Self::EndOfDay),
+            other => Err(format!("{} is not a drawdown type. Use intraday or end_of_day.", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Intraday => "intraday",
+            Self::EndOfDay => "end_of_day",
+        }
+    }
+}
+
+/// The rules of a prop firm evaluation. Every rule is optional, as firms pick
+/// and choose between them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EvaluationRules {
+    pub profit_target: Option<f64>,
+    pub max_drawdown: Option<f64>,
+    pub drawdown_type: DrawdownType,
+    pub daily_loss_limit: Option<f64>,
+    pub min_trading_days: Option<i32>,
+    /// The most of the total profit, as a percentage, that the best day may make up
+    pub consistency_percent: Option<f64>,
+}
+
+impl EvaluationRules {
+    /// Empty values mean the rule doesn't apply.
+    pub fn parse(
+        profit_target: &str,
+        max_drawdown: &str,
+        drawdown_type: &str,
+        daily_loss_limit: &str,
+        min_trading_days: &str,
+        consistency_percent: &str,
+    ) -> Result<EvaluationRules, String> {
+        let min_trading_days = match min_trading_days.trim() {
+            "" => None,
+            s => match s.parse::<i32>() {
+                Ok(days) if days > 0 => Some(days),
+                _ => return Err("Minimum trading days must be a whole number greater than zero.".to_string()),
+            },
+        };
+        let consistency_percent = match consistency_percent.trim().trim_end_matches('%') {
+            "" => None,
+            s => match s.parse::<f64>() {
+                Ok(percent) if percent > 0.0 && percent <= 100.0 => Some(percent),
+                _ => return Err("The consistency rule must be a percentage from 1 to 100.".to_string()),
+            },
+        };
+
+        Ok(EvaluationRules {
+            profit_target: parse_dollars(profit_target, "Profit target")?,
+            max_drawdown: parse_dollars(max_drawdown, "Max drawdown")?,
+            drawdown_type: DrawdownType::parse(drawdown_type)?,
+            daily_loss_limit: parse_dollars(daily_loss_limit, "Daily loss limit")?,
+            min_trading_days,
+            consistency_percent,
+        })
+    }
+}
+
+fn parse_dollars(s: &str, name: &str) -> Result<Option<f64>, String> {
+    match s.trim().trim_start_matches('$').replace(',', "").as_str() {
+        "" => Ok(None),
+        s => match s.parse::<f64>() {
+            Ok(amount) if amount.is_finite() && amount > 0.0 => Ok(Some(amount)),
+            _ => Err(format!("{} must be a dollar amount greater than zero.", name)),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DrawdownType, EvaluationRules};
+    use claims::assert_err;
+
+    #[test]
+    fn valid_rules_are_parsed_successfully() {
+        let rules = EvaluationRules::parse("$3,000", "2000", "end_of_day", "1000", "5", "50%").unwrap();
+        assert_eq!(rules.profit_target, Some(3000.0));
+        assert_eq!(rules.max_drawdown, Some(2000.0));
+        assert_eq!(rules.drawdown_type, DrawdownType::EndOfDay);
+        assert_eq!(rules.daily_loss_limit, Some(1000.0));
+        assert_eq!(rules.min_trading_days, Some(5));
+        assert_eq!(rules.consistency_percent, Some(50.0));
+    }
+
+    #[test]
+    fn empty_rules_do_not_apply() {
+        let rules = EvaluationRules::parse("", " ", "intraday", "", "", "").unwrap();
+        assert_eq!(rules, EvaluationRules::default());
+    }
+
+    #[test]
+    fn invalid_rules_are_rejected() {
+        assert_err!(EvaluationRules::parse("0", "", "intraday", "", "", ""));
+        assert_err!(EvaluationRules::parse("", "-100", "intraday", "", "", ""));
+        assert_err!(EvaluationRules::parse("", "", "weekly", "", "", ""));
+        assert_err!(EvaluationRules::parse("", "", "intraday", "lots", "", ""));
+        assert_err!(EvaluationRules::parse("", "", "intraday", "", "2.5", ""));
+        assert_err!(EvaluationRules::parse("", "", "intraday", "", "", "150"));
+    }
+}
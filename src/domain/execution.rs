@@ -0,0 +1,344 @@
+use serde::Deserialize;
+use time::{OffsetDateTime, PrimitiveDateTime, UtcOffset};
+use time::format_description::FormatItem;
+use time::macros::format_description;
+use utoipa::ToSchema;
+
+/// NinjaTrader writes execution times using the US short date format of the
+/// machine the export was made on. Both the 12 hour and 24 hour clocks show up.
+const TIME_FORMAT_12H: &[FormatItem<'static>] = format_description!(
+    "[month padding:none]/[day padding:none]/[year] [hour repr:12 padding:none]:[minute]:[second] [period]"
+);
+const TIME_FORMAT_24H: &[FormatItem<'static>] = format_description!(
+    "[month padding:none]/[day padding:none]/[year] [hour padding:none]:[minute]:[second]"
+);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionAction {
+    Buy,
+    Sell,
+}
+
+impl ExecutionAction {
+    pub fn parse(s: &str) -> Result<ExecutionAction, String> {
+        // NinjaTrader 7 exports used "BuyToCover" and "SellShort"
+        match s.trim().to_lowercase().replace(' ', "").as_str() {
+            "buy" | "buytocover" => Ok(ExecutionAction::Buy),
+            "sell" | "sellshort" => Ok(ExecutionAction::Sell),
+            other => Err(format!("{} is not a valid execution action.", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExecutionAction::Buy => "buy",
+            ExecutionAction::Sell => "sell",
+        }
+    }
+}
+
+/// One row of the NinjaTrader "Executions" grid export, as text.
+/// Validated into a `NewExecution` with `NewExecution::parse`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ExecutionFields {
+    #[serde(rename = "Instrument")]
+    pub instrument: String,
+    #[serde(rename = "Action")]
+    pub action: String,
+    #[serde(rename = "Quantity")]
+    pub quantity: String,
+    #[serde(rename = "Price")]
+    pub price: String,
+    #[serde(rename = "Time")]
+    pub time: String,
+    #[serde(rename = "ID")]
+    pub execution_id: String,
+    #[serde(rename = "Order ID")]
+    pub order_id: String,
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Commission")]
+    pub commission: String,
+    #[serde(rename = "Account")]
+    pub account: String,
+    #[serde(rename = "Connection")]
+    pub connection: String,
+}
+
+/// One execution as sent by an add-on as it happens, as JSON.
+/// Validated into a `NewExecution` with `NewExecution::from_event`.
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct ExecutionEvent {
+    /// NinjaTrader's execution ID, which makes sending an execution again harmless
+    pub execution_id: String,
+    pub account: String,
+    pub instrument: String,
+    /// `buy` or `sell`
+    pub action: String,
+    pub quantity: i32,
+    pub price: f64,
+    /// RFC 3339, with the offset of the machine NinjaTrader runs on
+    #[serde(with = "time::serde::rfc3339")]
+    pub executed_at: OffsetDateTime,
+    #[serde(default)]
+    pub order_id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub commission: f64,
+    #[serde(default)]
+    pub connection: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewExecution {
+    pub execution_id: String,
+    pub account: String,
+    pub instrument: String,
+    pub action: ExecutionAction,
+    pub quantity: i32,
+    pub price: f64,
+    pub executed_at: OffsetDateTime,
+    pub order_id: String,
+    pub name: String,
+    pub commission: f64,
+    pub connection: String,
+}
+
+impl NewExecution {
+    /// `utc_offset` is the offset of the machine NinjaTrader exported from,
+    /// since the export does not include one.
+    pub fn parse(fields: ExecutionFields, utc_offset: UtcOffset) -> Result<NewExecution, String> {
+        let execution_id = required("ID", fields.execution_id)?;
+        let account = required("Account", fields.account)?;
+        let instrument = required("Instrument", fields.instrument)?;
+        let action = ExecutionAction::parse(&fields.action)?;
+        let quantity = parse_quantity(&fields.quantity)?;
+        let price = parse_price(&fields.price)?;
+        let executed_at = parse_time(&fields.time, utc_offset)?;
+        let commission = parse_commission(&fields.commission)?;
+
+        Ok(NewExecution {
+            execution_id,
+            account,
+            instrument,
+            action,
+            quantity,
+            price,
+            executed_at,
+            order_id: fields.order_id.trim().to_string(),
+            name: fields.name.trim().to_string(),
+            commission,
+            connection: fields.connection.trim().to_string(),
+        })
+    }
+
+    /// The time already has an offset, unlike in exports.
+    pub fn from_event(event: ExecutionEvent) -> Result<NewExecution, String> {
+        let execution_id = required("execution_id", event.execution_id)?;
+        let account = required("account", event.account)?;
+        let instrument = required("instrument", event.instrument)?;
+        let action = ExecutionAction::parse(&event.action)?;
+        if event.quantity <= 0 {
+            return Err(format!("{} is not a valid quantity.", event.quantity));
+        }
+        if !event.price.is_finite() || event.price < 0.0 {
+            return Err(format!("{} is not a valid price.", event.price));
+        }
+        if !event.commission.is_finite() || event.commission < 0.0 {
+            return Err(format!("{} is not a valid commission.", event.commission));
+        }
+
+        Ok(NewExecution {
+            execution_id,
+            account,
+            instrument,
+            action,
+            quantity: event.quantity,
+            price: event.price,
+            executed_at: event.executed_at,
+            order_id: event.order_id.trim().to_string(),
+            name: event.name.trim().to_string(),
+            commission: event.commission,
+            connection: event.connection.trim().to_string(),
+        })
+    }
+}
+
+fn required(column: &str, value: String) -> Result<String, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(format!("{} is required.", column));
+    }
+    Ok(value.to_string())
+}
+
+fn parse_quantity(s: &str) -> Result<i32, String> {
+    match s.trim().replace(',', "").parse::<i32>() {
+        Ok(quantity) if quantity > 0 => Ok(quantity),
+        _ => Err(format!("{} is not a valid quantity.", s)),
+    }
+}
+
+fn parse_price(s: &str) -> Result<f64, String> {
+    match s.trim().replace(',', "").parse::<f64>() {
+        Ok(price) if price.is_finite() && price >= 0.0 => Ok(price),
+        _ => Err(format!("{} is not a valid price.", s)),
+    }
+}
+
+fn parse_commission(s: &str) -> Result<f64, String> {
+    let trimmed = s.trim().replace(['$', ','], "");
+    if trimmed.is_empty() {
+        return Ok(0.0);
+    }
+    match trimmed.parse::<f64>() {
+        Ok(commission) if commission.is_finite() && commission >= 0.0 => Ok(commission),
+        _ => Err(format!("{} is not a valid commission.", s)),
+    }
+}
+
+fn parse_time(s: &str, utc_offset: UtcOffset) -> Result<OffsetDateTime, String> {
+    let trimmed = s.trim();
+    PrimitiveDateTime::parse(trimmed, TIME_FORMAT_12H)
+        .or_else(|_| PrimitiveDateTime::parse(trimmed, TIME_FORMAT_24H))
+        .map(|time| time.assume_offset(utc_offset))
+        .map_err(|_| format!("{} is not a valid execution time.", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ExecutionAction, ExecutionEvent, ExecutionFields, NewExecution};
+    use claims::{assert_err, assert_ok};
+    use time::macros::{datetime, offset};
+
+    fn valid_fields() -> ExecutionFields {
+        ExecutionFields {
+            instrument: "ES 09-24".to_string(),
+            action: "Buy".to_string(),
+            quantity: "2".to_string(),
+            price: "5565.25".to_string(),
+            time: "7/22/2024 9:31:05 AM".to_string(),
+            execution_id: "a1b2c3d4e5f6".to_string(),
+            order_id: "f6e5d4c3b2a1".to_string(),
+            name: "Entry".to_string(),
+            commission: "$4.18".to_string(),
+            account: "Sim101".to_string(),
+            connection: "Playback Connection".to_string(),
+        }
+    }
+
+    #[test]
+    fn valid_fields_are_parsed_successfully() {
+        let execution = NewExecution::parse(valid_fields(), offset!(UTC)).unwrap();
+        assert_eq!(execution.action, ExecutionAction::Buy);
+        assert_eq!(execution.quantity, 2);
+        assert_eq!(execution.price, 5565.25);
+        assert_eq!(execution.commission, 4.18);
+        assert_eq!(execution.executed_at, datetime!(2024-07-22 9:31:05 UTC));
+    }
+
+    #[test]
+    fn time_is_shifted_by_the_export_offset() {
+        let execution = NewExecution::parse(valid_fields(), offset!(-5)).unwrap();
+        assert_eq!(execution.executed_at, datetime!(2024-07-22 14:31:05 UTC));
+    }
+
+    #[test]
+    fn afternoon_and_24_hour_times_are_parsed() {
+        let mut fields = valid_fields();
+        fields.time = "7/22/2024 1:02:03 PM".to_string();
+        let execution = NewExecution::parse(fields, offset!(UTC)).unwrap();
+        assert_eq!(execution.executed_at, datetime!(2024-07-22 13:02:03 UTC));
+
+        let mut fields = valid_fields();
+        fields.time = "7/22/2024 13:02:03".to_string();
+        let execution = NewExecution::parse(fields, offset!(UTC)).unwrap();
+        assert_eq!(execution.executed_at, datetime!(2024-07-22 13:02:03 UTC));
+    }
+
+    #[test]
+    fn legacy_actions_are_accepted() {
+        assert_eq!(ExecutionAction::parse("SellShort"), Ok(ExecutionAction::Sell));
+        assert_eq!(ExecutionAction::parse("Buy to cover"), Ok(ExecutionAction::Buy));
+        assert_err!(ExecutionAction::parse("Hold"));
+    }
+
+    #[test]
+    fn empty_commission_is_zero() {
+        let mut fields = valid_fields();
+        fields.commission = "".to_string();
+        let execution = assert_ok!(NewExecution::parse(fields, offset!(UTC)));
+        assert_eq!(execution.commission, 0.0);
+    }
+
+    #[test]
+    fn missing_execution_id_is_rejected() {
+        let mut fields = valid_fields();
+        fields.execution_id = " ".to_string();
+        assert_err!(NewExecution::parse(fields, offset!(UTC)));
+    }
+
+    #[test]
+    fn non_positive_quantity_is_rejected() {
+        let mut fields = valid_fields();
+        fields.quantity = "0".to_string();
+        assert_err!(NewExecution::parse(fields, offset!(UTC)));
+    }
+
+    #[test]
+    fn invalid_price_is_rejected() {
+        let mut fields = valid_fields();
+        fields.price = "abc".to_string();
+        assert_err!(NewExecution::parse(fields, offset!(UTC)));
+    }
+
+    #[test]
+    fn invalid_time_is_rejected() {
+        let mut fields = valid_fields();
+        fields.time = "2024-07-22".to_string();
+        assert_err!(NewExecution::parse(fields, offset!(UTC)));
+    }
+
+    fn valid_event() -> ExecutionEvent {
+        serde_json::from_value(serde_json::json!({
+            "execution_id": "a1b2c3d4e5f6",
+            "account": "Sim101",
+            "instrument": "ES 09-24",
+            "action": "Buy",
+            "quantity": 2,
+            "price": 5565.25,
+            "executed_at": "2024-07-22T09:31:05-05:00",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn valid_events_are_parsed_successfully() {
+        let execution = NewExecution::from_event(valid_event()).unwrap();
+        assert_eq!(execution.action, ExecutionAction::Buy);
+        assert_eq!(execution.executed_at, datetime!(2024-07-22 14:31:05 UTC));
+        assert_eq!(execution.commission, 0.0);
+        assert_eq!(execution.order_id, "");
+    }
+
+    #[test]
+    fn invalid_events_are_rejected() {
+        let mut event = valid_event();
+        event.account = "".to_string();
+        assert_err!(NewExecution::from_event(event));
+
+        let mut event = valid_event();
+        event.quantity = 0;
+        assert_err!(NewExecution::from_event(event));
+
+        let mut event = valid_event();
+        event.price = f64::NAN;
+        assert_err!(NewExecution::from_event(event));
+
+        let mut event = valid_event();
+        event.commission = -1.0;
+        assert_err!(NewExecution::from_event(event));
+    }
+}
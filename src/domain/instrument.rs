@@ -0,0 +1,100 @@
+use time::Month;
+
+/// The contract month of a futures symbol, e.g. September 2024 for "ES 09-24".
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContractMonth {
+    pub year: i32,
+    pub month: Month,
+}
+
+/// A NinjaTrader instrument name split into its root symbol and, for futures,
+/// the contract month. "ES 09-24" has root "ES" and expiry September 2024.
+/// Continuous contracts ("ES ##-##") and non futures ("AAPL") have no expiry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instrument {
+    pub root: String,
+    pub expiry: Option<ContractMonth>,
+}
+
+impl Instrument {
+    pub fn parse(s: &str) -> Result<Instrument, String> {
+        let mut parts = s.split_whitespace();
+        let root = match parts.next() {
+            Some(root) if root.chars().all(|c| c.is_ascii_alphanumeric()) => root.to_uppercase(),
+            _ => return Err(format!("{} is not a valid instrument.", s)),
+        };
+
+        let expiry = match parts.next() {
+            None | Some("##-##") => None,
+            Some(month) => Some(parse_contract_month(month).ok_or_else(|| format!("{} is not a valid instrument.", s))?),
+        };
+
+        if parts.next().is_some() {
+            return Err(format!("{} is not a valid instrument.", s));
+        }
+
+        Ok(Instrument { root, expiry })
+    }
+}
+
+/// NinjaTrader writes contract months as "MM-YY".
+fn parse_contract_month(s: &str) -> Option<ContractMonth> {
+    let (month, year) = s.split_once('-')?;
+    if month.len() != 2 || year.len() != 2 {
+        return None;
+    }
+    let month = Month::try_from(month.parse::<u8>().ok()?).ok()?;
+    let year = 2000 + year.parse::<i32>().ok()?;
+    Some(ContractMonth { year, month })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContractMonth, Instrument};
+    use claims::assert_err;
+    use time::Month;
+
+    #[test]
+    fn futures_symbols_are_parsed() {
+        let instrument = Instrument::parse("ES 09-24").unwrap();
+        assert_eq!(instrument.root, "ES");
+        assert_eq!(instrument.expiry, Some(ContractMonth { year: 2024, month: Month::September }));
+
+        let instrument = Instrument::parse("MNQ 12-25").unwrap();
+        assert_eq!(instrument.root, "MNQ");
+        assert_eq!(instrument.expiry, Some(ContractMonth { year: 2025, month: Month::December }));
+    }
+
+    #[test]
+    fn roots_with_digits_are_parsed() {
+        let instrument = Instrument::parse("6E 03-25").unwrap();
+        assert_eq!(instrument.root, "6E");
+    }
+
+    #[test]
+    fn continuous_contracts_have_no_expiry() {
+        let instrument = Instrument::parse("NQ ##-##").unwrap();
+        assert_eq!(instrument.root, "NQ");
+        assert_eq!(instrument.expiry, None);
+    }
+
+    #[test]
+    fn stocks_have_no_expiry() {
+        let instrument = Instrument::parse("aapl").unwrap();
+        assert_eq!(instrument.root, "AAPL");
+        assert_eq!(instrument.expiry, None);
+    }
+
+    #[test]
+    fn empty_string_is_rejected() {
+        assert_err!(Instrument::parse(""));
+    }
+
+    #[test]
+    fn invalid_contract_months_are_rejected() {
+        assert_err!(Instrument::parse("ES 13-24"));
+        assert_err!(Instrument::parse("ES 9-24"));
+        assert_err!(Instrument::parse("ES Sep24"));
+        assert_err!(Instrument::parse("ES 09-24 extra"));
+    }
+}
@@ -0,0 +1,124 @@
+const MAX_NOTES_LENGTH: usize = 20_000;
+const MAX_TAG_LENGTH: usize = 40;
+const MAX_TAGS: usize = 20;
+
+/// A validated journal entry for a trade. `tags` are unique ignoring case,
+/// in the order they were entered.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JournalEntry {
+    pub notes: String,
+    pub rating: Option<i16>,
+    /// Planned dollar risk, the size of 1R
+    pub risk: Option<f64>,
+    pub tags: Vec<String>,
+}
+
+impl JournalEntry {
+    /// `rating` and `risk` may be empty. `tags` is a comma separated list.
+    pub fn parse(notes: &str, rating: &str, risk: &str, tags: &str) -> Result<JournalEntry, String> {
+        let notes = notes.trim();
+        if notes.chars().count() > MAX_NOTES_LENGTH {
+            return Err(format!("Notes must be {} characters or fewer.", MAX_NOTES_LENGTH));
+        }
+
+        let rating = match rating.trim() {
+            "" => None,
+            s => match s.parse::<i16>() {
+                Ok(rating) if (1..=5).contains(&rating) => Some(rating),
+                _ => return Err("Rating must be a whole number from 1 to 5.".to_string()),
+            },
+        };
+
+        let risk = match risk.trim().trim_start_matches('$') {
+            "" => None,
+            s => match s.parse::<f64>() {
+                Ok(risk) if risk.is_finite() && risk > 0.0 => Some(risk),
+                _ => return Err("Risk must be a dollar amount greater than zero.".to_string()),
+            },
+        };
+
+        let mut parsed_tags: Vec<String> = Vec::new();
+        for tag in tags.split(',') {
+            let tag = parse_tag(tag)?;
+            if !tag.is_empty() && !parsed_tags.iter().any(|t| t.eq_ignore_ascii_case(&tag)) {
+                parsed_tags.push(tag);
+            }
+        }
+        if parsed_tags.len() > MAX_TAGS {
+            return Err(format!("A trade can have at most {} tags.", MAX_TAGS));
+        }
+
+        Ok(JournalEntry {
+            notes: notes.to_string(),
+            rating,
+            risk,
+            tags: parsed_tags,
+        })
+    }
+}
+
+/// Collapses inner whitespace so "moved  stop" and "moved stop" are the same tag.
+fn parse_tag(s: &str) -> Result<String, String> {
+    let tag = s.split_whitespace().collect::<Vec<_>>().join(" ");
+    if tag.chars().count() > MAX_TAG_LENGTH {
+        return Err(format!("Tags must be {} characters or fewer.", MAX_TAG_LENGTH));
+    }
+    if tag.chars().any(|c| c.is_control()) {
+        return Err(format!("{} is not a valid tag.", tag));
+    }
+    Ok(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::JournalEntry;
+    use claims::assert_err;
+
+    #[test]
+    fn valid_entry_is_parsed_successfully() {
+        let entry = JournalEntry::parse("  Chased the *breakout*.  ", "2", "$150", "FOMO, moved  stop,,").unwrap();
+        assert_eq!(entry.notes, "Chased the *breakout*.");
+        assert_eq!(entry.rating, Some(2));
+        assert_eq!(entry.risk, Some(150.0));
+        assert_eq!(entry.tags, vec!["FOMO", "moved stop"]);
+    }
+
+    #[test]
+    fn empty_entry_is_valid() {
+        let entry = JournalEntry::parse("", "", "", "").unwrap();
+        assert_eq!(entry.rating, None);
+        assert_eq!(entry.risk, None);
+        assert!(entry.tags.is_empty());
+    }
+
+    #[test]
+    fn duplicate_tags_are_merged_ignoring_case() {
+        let entry = JournalEntry::parse("", "", "", "ORB, orb, Orb").unwrap();
+        assert_eq!(entry.tags, vec!["ORB"]);
+    }
+
+    #[test]
+    fn ratings_outside_one_to_five_are_rejected() {
+        assert_err!(JournalEntry::parse("", "0", "", ""));
+        assert_err!(JournalEntry::parse("", "6", "", ""));
+        assert_err!(JournalEntry::parse("", "3.5", "", ""));
+    }
+
+    #[test]
+    fn risk_must_be_positive() {
+        assert_err!(JournalEntry::parse("", "", "0", ""));
+        assert_err!(JournalEntry::parse("", "", "-50", ""));
+        assert_err!(JournalEntry::parse("", "", "one hundred", ""));
+    }
+
+    #[test]
+    fn long_tags_are_rejected() {
+        assert_err!(JournalEntry::parse("", "", "", &"a".repeat(41)));
+    }
+
+    #[test]
+    fn too_many_tags_are_rejected() {
+        let tags: Vec<String> = (0..21).map(|i| format!("tag{}", i)).collect();
+        assert_err!(JournalEntry::parse("", "", "", &tags.join(",")));
+    }
+}
@@ -1,7 +1,27 @@
+mod account;
+mod api_token;
+mod contract_spec;
+mod digest;
+mod evaluation_rules;
+mod execution;
+mod instrument;
+mod journal;
 mod new_user;
+mod pagination;
+mod trading_session;
 mod user_email;
 mod user_password;
 
+pub use account::{AccountDetails, AccountType};
+pub use api_token::{ApiScope, NewApiToken};
+pub use contract_spec::NewContractSpec;
+pub use digest::{DigestKind, DigestPeriod};
+pub use evaluation_rules::{DrawdownType, EvaluationRules};
+pub use execution::{ExecutionAction, ExecutionEvent, ExecutionFields, NewExecution};
+pub use instrument::{ContractMonth, Instrument};
+pub use journal::JournalEntry;
 pub use new_user::NewUser;
+pub use pagination::Pagination;
+pub use trading_session::TradingSession;
 pub use user_email::UserEmail;
 pub use user_password::UserPassword;
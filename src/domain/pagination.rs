@@ -0,0 +1,75 @@
+/// Which page of a list to return. Pages start at 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pagination {
+    pub page: i64,
+    pub per_page: i64,
+}
+
+impl Pagination {
+    pub const DEFAULT_PER_PAGE: i64 = 50;
+    pub const MAX_PER_PAGE: i64 = 500;
+
+    /// Empty values, or none at all, mean the first page of `DEFAULT_PER_PAGE`.
+    pub fn parse(page: Option<&str>, per_page: Option<&str>) -> Result<Pagination, String> {
+        let page = match page.map(str::trim).filter(|s| !s.is_empty()) {
+            Some(s) => match s.parse::<i64>() {
+                Ok(page) if page >= 1 => page,
+                _ => return Err(format!("{} is not a page number. Pages start at 1.", s)),
+            },
+            None => 1,
+        };
+        let per_page = match per_page.map(str::trim).filter(|s| !s.is_empty()) {
+            Some(s) => match s.parse::<i64>() {
+                Ok(per_page) if (1..=Self::MAX_PER_PAGE).contains(&per_page) => per_page,
+                _ => return Err(format!("per_page must be between 1 and {}.", Self::MAX_PER_PAGE)),
+            },
+            None => Self::DEFAULT_PER_PAGE,
+        };
+
+        Ok(Pagination { page, per_page })
+    }
+
+    pub fn limit(&self) -> i64 {
+        self.per_page
+    }
+
+    pub fn offset(&self) -> i64 {
+        (self.page - 1).saturating_mul(self.per_page)
+    }
+
+    /// How many pages `total` items fill, at least 1 so an empty list has a page.
+    pub fn total_pages(&self, total: i64) -> i64 {
+        ((total + self.per_page - 1) / self.per_page).max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pagination;
+    use claims::assert_err;
+
+    #[test]
+    fn missing_values_mean_the_first_page() {
+        let pagination = Pagination::parse(None, Some("")).unwrap();
+        assert_eq!(pagination, Pagination { page: 1, per_page: Pagination::DEFAULT_PER_PAGE });
+        assert_eq!(pagination.offset(), 0);
+    }
+
+    #[test]
+    fn pages_are_offset_by_their_size() {
+        let pagination = Pagination::parse(Some("3"), Some("25")).unwrap();
+        assert_eq!(pagination.limit(), 25);
+        assert_eq!(pagination.offset(), 50);
+        assert_eq!(pagination.total_pages(0), 1);
+        assert_eq!(pagination.total_pages(50), 2);
+        assert_eq!(pagination.total_pages(51), 3);
+    }
+
+    #[test]
+    fn invalid_pages_are_rejected() {
+        assert_err!(Pagination::parse(Some("0"), None));
+        assert_err!(Pagination::parse(Some("two"), None));
+        assert_err!(Pagination::parse(None, Some("0")));
+        assert_err!(Pagination::parse(None, Some("501")));
+    }
+}
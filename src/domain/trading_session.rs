@@ -0,0 +1,88 @@
+use time::{Duration, Time};
+use time::macros::format_description;
+
+/// When one trading day ends and the next begins, e.g. the CME rollover at
+/// 17:00 America/Chicago. Trades are bucketed into trading days by their exit
+/// time in `timezone`; the time zone itself is resolved by Postgres so
+/// daylight saving is handled there.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TradingSession {
+    pub timezone: String,
+    pub rollover: Time,
+}
+
+impl TradingSession {
+    pub fn parse(timezone: &str, rollover: &str) -> Result<TradingSession, String> {
+        let timezone = timezone.trim();
+        let valid_timezone = !timezone.is_empty()
+            && timezone.len() <= 64
+            && timezone.chars().all(|c| c.is_ascii_alphanumeric() || "/_-+".contains(c));
+        if !valid_timezone {
+            return Err(format!("{} is not a valid time zone.", timezone));
+        }
+        let rollover = Time::parse(rollover.trim(), format_description!("[hour]:[minute]"))
+            .map_err(|_| format!("{} is not a valid rollover time. Use the format HH:MM.", rollover))?;
+
+        Ok(TradingSession {
+            timezone: timezone.to_string(),
+            rollover,
+        })
+    }
+
+    /// How far local times are moved so their calendar date is the trading
+    /// day. A rollover in the afternoon or evening starts the next day's
+    /// session, so times after it are moved forward past midnight. A rollover
+    /// in the early morning belongs to the previous day, so times are moved
+    /// back by it instead.
+    pub fn day_shift(&self) -> Duration {
+        let since_midnight = self.rollover - Time::MIDNIGHT;
+        if self.rollover.hour() >= 12 {
+            Duration::DAY - since_midnight
+        } else {
+            -since_midnight
+        }
+    }
+}
+
+impl Default for TradingSession {
+    /// Midnight UTC
+    fn default() -> Self {
+        TradingSession {
+            timezone: "UTC".to_string(),
+            rollover: Time::MIDNIGHT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TradingSession;
+    use claims::assert_err;
+    use time::Duration;
+
+    #[test]
+    fn cme_rollover_moves_the_evening_into_the_next_day() {
+        let session = TradingSession::parse("America/Chicago", "17:00").unwrap();
+        assert_eq!(session.timezone, "America/Chicago");
+        assert_eq!(session.day_shift(), Duration::hours(7));
+    }
+
+    #[test]
+    fn early_morning_rollover_belongs_to_the_previous_day() {
+        let session = TradingSession::parse("Europe/London", "02:30").unwrap();
+        assert_eq!(session.day_shift(), -Duration::minutes(150));
+    }
+
+    #[test]
+    fn midnight_rollover_is_the_calendar_day() {
+        assert_eq!(TradingSession::default().day_shift(), Duration::ZERO);
+    }
+
+    #[test]
+    fn invalid_sessions_are_rejected() {
+        assert_err!(TradingSession::parse("", "17:00"));
+        assert_err!(TradingSession::parse("America/Chicago'; DROP TABLE trades", "17:00"));
+        assert_err!(TradingSession::parse("America/Chicago", "5pm"));
+        assert_err!(TradingSession::parse("America/Chicago", "25:00"));
+    }
+}
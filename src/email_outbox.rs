@@ -0,0 +1,152 @@
+//! src/email_outbox.rs
+//! Emails waiting to be sent. Handlers queue emails with `enqueue` in the same
+//! transaction as the change they're about, so a rolled back registration
+//! sends nothing and a mail server outage can't fail the request. The worker
+//! started in `startup::run` delivers them and retries failures with backoff.
+//!
+//! Delivery is at least once: an email sent just before the worker stops is
+//! sent again once its claim runs out.
+use sqlx::{FromRow, PgExecutor, PgPool};
+use std::sync::Arc;
+use time::Duration;
+use crate::emailer::{Email, EmailClient};
+
+/// How many emails one worker claims at a time
+const BATCH_SIZE: i64 = 20;
+/// Attempts before an email is given up on
+pub const MAX_ATTEMPTS: i32 = 8;
+const FIRST_RETRY: Duration = Duration::seconds(30);
+const MAX_RETRY: Duration = Duration::hours(1);
+/// How long a claimed batch is left to one worker. Well over the time 20
+/// sends take, even against a slow mail server.
+const CLAIM_TIMEOUT: Duration = Duration::minutes(10);
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+#[derive(Debug, FromRow)]
+struct QueuedEmail {
+    id: uuid::Uuid,
+    recipient: String,
+    subject: String,
+    html_body: String,
+    text_body: String,
+    attempts: i32,
+}
+
+/// Queues `email` to be sent once the surrounding transaction commits.
+pub async fn enqueue<'e, E>(executor: E, email: &Email) -> Result<(), sqlx::Error>
+where
+    E: PgExecutor<'e>,
+{
+    sqlx::query(
+        "INSERT INTO email_outbox (id, recipient, subject, html_body, text_body) VALUES ($1, $2, $3, $4, $5)"
+    )
+        .bind(uuid::Uuid::new_v4())
+        .bind(&email.to)
+        .bind(&email.subject)
+        .bind(&email.html_body)
+        .bind(&email.text_body)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+/// How long to wait before trying again after `attempts` failures. Doubles
+/// from 30 seconds up to an hour.
+pub fn retry_delay(attempts: i32) -> Duration {
+    let doublings = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    (FIRST_RETRY * 2_i32.pow(doublings)).min(MAX_RETRY)
+}
+
+/// Sends the emails that are due. Each batch is claimed by pushing its next
+/// attempt past `CLAIM_TIMEOUT`, so several workers never send the same email
+/// and no row stays locked while the mail server is slow. An email claimed by
+/// a worker that stopped before recording the result is sent again once the
+/// claim runs out. Returns how many were sent.
+pub async fn deliver_pending(db: &PgPool, client: &dyn EmailClient) -> Result<usize, sqlx::Error> {
+    let emails: Vec<QueuedEmail> = sqlx::query_as(
+        "UPDATE email_outbox SET next_attempt_at = NOW() + make_interval(secs => $2)
+        WHERE id IN (
+            SELECT id FROM email_outbox
+            WHERE sent_at IS NULL AND failed_at IS NULL AND next_attempt_at <= NOW()
+            ORDER BY next_attempt_at
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING id, recipient, subject, html_body, text_body, attempts"
+    )
+        .bind(BATCH_SIZE)
+        .bind(CLAIM_TIMEOUT.as_seconds_f64())
+        .fetch_all(db)
+        .await?;
+
+    let mut sent = 0;
+    for queued in emails {
+        let email = Email {
+            to: queued.recipient,
+            subject: queued.subject,
+            html_body: queued.html_body,
+            text_body: queued.text_body,
+        };
+        let attempts = queued.attempts + 1;
+        match client.send(&email).await {
+            Ok(()) => {
+                sqlx::query("UPDATE email_outbox SET sent_at = NOW(), attempts = $1 WHERE id = $2")
+                    .bind(attempts)
+                    .bind(queued.id)
+                    .execute(db)
+                    .await?;
+                sent += 1;
+            }
+            Err(err) => {
+                tracing::warn!(error = %err, email_id = %queued.id, attempts, "Failed to send an email");
+                let gave_up = attempts >= MAX_ATTEMPTS;
+                sqlx::query(
+                    "UPDATE email_outbox SET attempts = $1, last_error = $2,
+                    next_attempt_at = NOW() + make_interval(secs => $3),
+                    failed_at = CASE WHEN $4 THEN NOW() END
+                    WHERE id = $5"
+                )
+                    .bind(attempts)
+                    .bind(err.to_string())
+                    .bind(retry_delay(attempts).as_seconds_f64())
+                    .bind(gave_up)
+                    .bind(queued.id)
+                    .execute(db)
+                    .await?;
+                if gave_up {
+                    tracing::error!(email_id = %queued.id, "Gave up sending an email");
+                }
+            }
+        }
+    }
+
+    Ok(sent)
+}
+
+/// Delivers queued emails until the task is aborted.
+pub async fn run_worker(db: PgPool, client: Arc<dyn EmailClient>) {
+    loop {
+        match deliver_pending(&db, client.as_ref()).await {
+            // Keep going while there's a backlog
+            Ok(sent) if sent as i64 == BATCH_SIZE => continue,
+            Ok(_) => {}
+            Err(err) => tracing::error!(error = %err, "Failed to read the email outbox"),
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::retry_delay;
+    use time::Duration;
+
+    #[test]
+    fn retries_back_off_up_to_an_hour() {
+        assert_eq!(retry_delay(1), Duration::seconds(30));
+        assert_eq!(retry_delay(2), Duration::minutes(1));
+        assert_eq!(retry_delay(4), Duration::minutes(4));
+        assert_eq!(retry_delay(8), Duration::hours(1));
+        assert_eq!(retry_delay(100), Duration::hours(1));
+    }
+}
@@ -0,0 +1,145 @@
+//! src/email_verification.rs
+//! Single use, expiring links that confirm a user owns their email address.
+//! Only a hash of each token is stored.
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use sqlx::{Connection, PgConnection, PgPool};
+use time::Duration;
+use crate::constants::{email_templates, route_paths};
+use crate::email_outbox;
+use crate::emailer::{Email, EmailTemplate};
+use crate::startup::AppState;
+
+const TOKEN_LENGTH: usize = 32;
+pub const TOKEN_LIFETIME: Duration = Duration::hours(24);
+
+#[derive(Debug, serde::Serialize)]
+pub struct ConfirmationEmail {
+    pub email: String,
+    pub confirmation_link: String,
+    pub expires_in: String,
+}
+
+impl EmailTemplate for ConfirmationEmail {
+    const NAME: &'static str = email_templates::EMAIL_VERIFICATION;
+
+    fn sample() -> Self {
+        Self {
+            email: "trader@example.com".to_string(),
+            confirmation_link: format!("https://example.com{}?token=sample", route_paths::CONFIRM),
+            expires_in: format!("{} hours", TOKEN_LIFETIME.whole_hours()),
+        }
+    }
+}
+
+/// A random URL safe token with about 190 bits of entropy.
+pub fn generate_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(TOKEN_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// Creates a token confirming `email` for the user, replacing any unused
+/// tokens they were sent before. Returns the token to put in the link.
+pub async fn create_token(connection: &mut PgConnection, user_id: uuid::Uuid, email: &str) -> Result<String, sqlx::Error> {
+    let token = generate_token();
+    let mut transaction = connection.begin().await?;
+
+    sqlx::query("DELETE FROM email_verification_tokens WHERE user_id = $1 AND used_at IS NULL")
+        .bind(user_id)
+        .execute(&mut *transaction)
+        .await?;
+    sqlx::query(
+        "INSERT INTO email_verification_tokens (token_hash, user_id, email, expires_at)
+        VALUES ($1, $2, $3, NOW() + make_interval(secs => $4))"
+    )
+        .bind(hash_token(&token))
+        .bind(user_id)
+        .bind(email)
+        .bind(TOKEN_LIFETIME.as_seconds_f64())
+        .execute(&mut *transaction)
+        .await?;
+
+    transaction.commit().await?;
+    Ok(token)
+}
+
+/// Uses up the token and marks its address verified. Returns the user's id,
+/// or `None` when the token is unknown, used, expired, or for an address the
+/// user no longer has.
+pub async fn confirm(db: &PgPool, token: &str) -> Result<Option<uuid::Uuid>, sqlx::Error> {
+    let mut transaction = db.begin().await?;
+
+    let confirmed: Option<(uuid::Uuid, String)> = sqlx::query_as(
+        "UPDATE email_verification_tokens SET used_at = NOW()
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+        RETURNING user_id, email"
+    )
+        .bind(hash_token(token))
+        .fetch_optional(&mut *transaction)
+        .await?;
+    let (user_id, email) = match confirmed {
+        Some(confirmed) => confirmed,
+        None => return Ok(None),
+    };
+
+    let verified = sqlx::query("UPDATE users SET email_verified_at = NOW() WHERE id = $1 AND email = $2")
+        .bind(user_id)
+        .bind(&email)
+        .execute(&mut *transaction)
+        .await?;
+    if verified.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    transaction.commit().await?;
+    Ok(Some(user_id))
+}
+
+/// Queues an email with a new confirmation link for `email`. It's sent once
+/// the caller's transaction commits.
+pub async fn queue_confirmation(
+    connection: &mut PgConnection,
+    state: &AppState,
+    user_id: uuid::Uuid,
+    email: &str,
+    subject: &str,
+) -> Result<(), anyhow::Error> {
+    let token = create_token(&mut *connection, user_id, email).await?;
+    let confirmation = ConfirmationEmail {
+        email: email.to_string(),
+        confirmation_link: format!("{}{}?token={}", state.base_url, route_paths::CONFIRM, token),
+        expires_in: format!("{} hours", TOKEN_LIFETIME.whole_hours()),
+    };
+    let message = Email::render(&state.tera, &state.email_settings.branding, email, subject, &confirmation)?;
+    email_outbox::enqueue(connection, &message).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_token, hash_token};
+
+    #[test]
+    fn tokens_are_random_and_url_safe() {
+        let token = generate_token();
+        assert_eq!(token.len(), 32);
+        assert!(token.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_ne!(token, generate_token());
+    }
+
+    #[test]
+    fn tokens_are_hashed_with_sha256() {
+        assert_eq!(
+            hash_token("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
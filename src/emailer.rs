@@ -1,45 +1,174 @@
-// use secrecy::ExposeSecret;
-// use lettre::transport::smtp::authentication::Credentials;
-use lettre::{Message, SmtpTransport, Transport};
-use lettre::message::header::ContentType;
-use std::collections::HashMap;
+//! src/emailer.rs
+//! Rendering emails and the transports that deliver them. Handlers don't send
+//! emails themselves: they queue them with `email_outbox::enqueue`, and the
+//! outbox worker hands them to the `EmailClient` in `AppState`.
+use async_trait::async_trait;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use lettre::message::{header::ContentType, Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use secrecy::ExposeSecret;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use tera::{Context, Tera};
-use crate::configuration::EmailSettings;
+use crate::configuration::{EmailBranding, EmailSettings, EmailTransport, SmtpTls};
 
-pub async fn send_email(
-    to: &str,
-    subject: &str,
-    template_name: &str,
-    context: &HashMap<&str, &str>,
-    tera: &Tera,
-    email_settings: &EmailSettings,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let mut tera_context = Context::new();
-    for (key, value) in context {
-        tera_context.insert(*key, value);
+/// The typed context of an email. `NAME` is a template in `templates/emails`
+/// that has both a `NAME.html` and a `NAME.txt` variant, each extending the
+/// matching `base` layout.
+pub trait EmailTemplate: Serialize {
+    const NAME: &'static str;
+
+    /// Made up data for previewing the template
+    fn sample() -> Self;
+}
+
+/// A rendered email, ready to queue or send.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: String,
+}
+
+impl Email {
+    /// Renders both variants of `T::NAME`. Templates get the context's fields
+    /// and `branding`.
+    pub fn render<T: EmailTemplate>(
+        tera: &Tera,
+        branding: &EmailBranding,
+        to: &str,
+        subject: &str,
+        email: &T,
+    ) -> Result<Email, tera::Error> {
+        let mut context = Context::from_serialize(email)?;
+        context.insert("branding", branding);
+        let subject = match &branding.environment_label {
+            Some(label) => format!("[{}] {}", label, subject),
+            None => subject.to_string(),
+        };
+
+        Ok(Email {
+            to: to.to_string(),
+            subject,
+            html_body: tera.render(&format!("emails/{}.html", T::NAME), &context)?,
+            text_body: tera.render(&format!("emails/{}.txt", T::NAME), &context)?,
+        })
+    }
+
+    fn to_message(&self, from: &Mailbox) -> Result<Message, anyhow::Error> {
+        let builder = Message::builder()
+            .from(from.clone())
+            .to(self.to.parse()?)
+            .subject(&self.subject);
+        // Emails queued before text variants existed only have HTML
+        let message = if self.text_body.is_empty() {
+            builder.header(ContentType::TEXT_HTML).body(self.html_body.clone())?
+        } else {
+            builder.multipart(MultiPart::alternative_plain_html(self.text_body.clone(), self.html_body.clone()))?
+        };
+        Ok(message)
     }
+}
+
+/// Delivers emails. Picked by `email.transport` in the configuration.
+#[async_trait]
+pub trait EmailClient: Send + Sync {
+    async fn send(&self, email: &Email) -> Result<(), anyhow::Error>;
+}
 
-    let email_body = tera.render(template_name, &tera_context)?;
+/// The `EmailClient` for `settings.transport`.
+pub fn client_from_settings(settings: &EmailSettings) -> Result<Arc<dyn EmailClient>, anyhow::Error> {
+    Ok(match settings.transport {
+        EmailTransport::Smtp => Arc::new(SmtpEmailClient::new(settings)?),
+        EmailTransport::File => Arc::new(FileEmailClient::new(settings)?),
+        EmailTransport::Memory => Arc::new(InMemoryEmailClient::default()),
+    })
+}
 
-    let email = Message::builder()
-        .from(email_settings.admin_email.parse()?)
-        .to(to.parse()?)
-        .subject(subject)
-        .header(ContentType::TEXT_HTML)
-        .body(email_body)?;
+/// Sends through an SMTP server, logging in when `smtp_username` is set.
+pub struct SmtpEmailClient {
+    from: Mailbox,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
 
-    // let creds = Credentials::new(email_settings.smtp_username.into(),
-    // email_settings.smtp_password.expose_secret().into());
-    // let mailer = SmtpTransport::relay(email_settings.admin_email)?
-    //     .credentials(creds)
-    //     .build();
-    // Configure the local Python SMTP debugging server as the SMTP server
-    let mailer = SmtpTransport::builder_dangerous(&email_settings.smtp_host)
-        .port(email_settings.smtp_port)
-        .build();
+impl SmtpEmailClient {
+    pub fn new(settings: &EmailSettings) -> Result<Self, anyhow::Error> {
+        let builder = match settings.smtp_tls {
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&settings.smtp_host),
+            SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.smtp_host)?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&settings.smtp_host)?,
+        }
+        .port(settings.smtp_port);
+        let builder = if settings.smtp_username.is_empty() {
+            builder
+        } else {
+            builder.credentials(Credentials::new(
+                settings.smtp_username.clone(),
+                settings.smtp_password.expose_secret().clone(),
+            ))
+        };
 
-    mailer.send(&email)?;
+        Ok(Self {
+            from: settings.admin_email.parse()?,
+            transport: builder.build(),
+        })
+    }
+}
 
-    Ok(())
+#[async_trait]
+impl EmailClient for SmtpEmailClient {
+    async fn send(&self, email: &Email) -> Result<(), anyhow::Error> {
+        self.transport.send(email.to_message(&self.from)?).await?;
+        Ok(())
+    }
 }
 
+/// Writes every email to an `.eml` file in `file_directory`, for development
+/// without a mail server.
+pub struct FileEmailClient {
+    from: Mailbox,
+    directory: PathBuf,
+}
+
+impl FileEmailClient {
+    pub fn new(settings: &EmailSettings) -> Result<Self, anyhow::Error> {
+        Ok(Self {
+            from: settings.admin_email.parse()?,
+            directory: PathBuf::from(&settings.file_directory),
+        })
+    }
+}
+
+#[async_trait]
+impl EmailClient for FileEmailClient {
+    async fn send(&self, email: &Email) -> Result<(), anyhow::Error> {
+        let message = email.to_message(&self.from)?;
+        tokio::fs::create_dir_all(&self.directory).await?;
+        let path = self.directory.join(format!("{}.eml", uuid::Uuid::new_v4()));
+        tokio::fs::write(path, message.formatted()).await?;
+        Ok(())
+    }
+}
+
+/// Keeps emails in memory instead of sending them, for tests.
+#[derive(Debug, Default)]
+pub struct InMemoryEmailClient {
+    sent: Mutex<Vec<Email>>,
+}
+
+impl InMemoryEmailClient {
+    /// Every email sent so far, oldest first.
+    pub fn sent(&self) -> Vec<Email> {
+        self.sent.lock().expect("The sent emails lock was poisoned").clone()
+    }
+}
+
+#[async_trait]
+impl EmailClient for InMemoryEmailClient {
+    async fn send(&self, email: &Email) -> Result<(), anyhow::Error> {
+        self.sent.lock().expect("The sent emails lock was poisoned").push(email.clone());
+        Ok(())
+    }
+}
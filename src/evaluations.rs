@@ -0,0 +1,166 @@
+//! src/evaluations.rs
+//! Prop firm evaluation rules for a user's evaluation accounts, and the rule
+//! breaches recorded from their trades. The rules are checked in
+//! `stats::Evaluation`.
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use time::{Date, OffsetDateTime};
+use crate::accounts::{self, Account};
+use crate::domain::{AccountType, DrawdownType, EvaluationRules, TradingSession};
+use crate::stats::{Breach, Evaluation};
+use crate::trades::{self, TradeFilter};
+
+#[derive(Debug, FromRow)]
+struct RulesRow {
+    profit_target: Option<f64>,
+    max_drawdown: Option<f64>,
+    drawdown_type: String,
+    daily_loss_limit: Option<f64>,
+    min_trading_days: Option<i32>,
+    consistency_percent: Option<f64>,
+}
+
+/// A breach as stored, with when it was first recorded.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct RecordedBreach {
+    pub rule: String,
+    /// `None` when the trade has since been removed
+    pub trade_id: Option<uuid::Uuid>,
+    pub trading_day: Date,
+    pub value: f64,
+    pub breach_limit: f64,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+pub async fn rules(db: &PgPool, account_id: uuid::Uuid) -> Result<Option<EvaluationRules>, sqlx::Error> {
+    let row: Option<RulesRow> = sqlx::query_as(
+        "SELECT profit_target, max_drawdown, drawdown_type, daily_loss_limit, min_trading_days, consistency_percent
+        FROM evaluation_rules WHERE account_id = $1"
+    )
+        .bind(account_id)
+        .fetch_optional(db)
+        .await?;
+
+    Ok(row.map(|row| EvaluationRules {
+        profit_target: row.profit_target,
+        max_drawdown: row.max_drawdown,
+        drawdown_type: DrawdownType::parse(&row.drawdown_type).unwrap_or_default(),
+        daily_loss_limit: row.daily_loss_limit,
+        min_trading_days: row.min_trading_days,
+        consistency_percent: row.consistency_percent,
+    }))
+}
+
+pub async fn save_rules(db: &PgPool, account_id: uuid::Uuid, rules: &EvaluationRules) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO evaluation_rules (account_id, profit_target, max_drawdown, drawdown_type, daily_loss_limit, min_trading_days, consistency_percent)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (account_id) DO UPDATE SET
+            profit_target = EXCLUDED.profit_target,
+            max_drawdown = EXCLUDED.max_drawdown,
+            drawdown_type = EXCLUDED.drawdown_type,
+            daily_loss_limit = EXCLUDED.daily_loss_limit,
+            min_trading_days = EXCLUDED.min_trading_days,
+            consistency_percent = EXCLUDED.consistency_percent"
+    )
+        .bind(account_id)
+        .bind(rules.profit_target)
+        .bind(rules.max_drawdown)
+        .bind(rules.drawdown_type.as_str())
+        .bind(rules.daily_loss_limit)
+        .bind(rules.min_trading_days)
+        .bind(rules.consistency_percent)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+pub async fn breaches(db: &PgPool, account_id: uuid::Uuid) -> Result<Vec<RecordedBreach>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT rule, trade_id, trading_day, value, breach_limit, created_at FROM rule_breaches
+        WHERE account_id = $1 ORDER BY trading_day, rule"
+    )
+        .bind(account_id)
+        .fetch_all(db)
+        .await
+}
+
+/// Checks an account against its rules. `None` when it has no rules.
+pub async fn evaluate(
+    db: &PgPool,
+    user_id: uuid::Uuid,
+    account: &Account,
+    session: &TradingSession,
+) -> Result<Option<Evaluation>, sqlx::Error> {
+    let rules = match rules(db, account.id).await? {
+        Some(rules) => rules,
+        None => return Ok(None),
+    };
+    let filter = TradeFilter {
+        accounts: vec![account.name.clone()],
+        ..Default::default()
+    };
+    let account_trades = trades::for_user(db, user_id, &filter, session).await?;
+    let today = trades::current_trading_day(db, session).await?;
+    Ok(Some(Evaluation::new(&rules, account.starting_balance, &account_trades, today)))
+}
+
+/// Stores the account's current breaches. Breaches recorded earlier keep their
+/// time, and ones the trades or rules no longer cause are removed.
+pub async fn record_breaches(db: &PgPool, account_id: uuid::Uuid, breaches: &[Breach]) -> Result<(), sqlx::Error> {
+    let mut transaction = db.begin().await?;
+
+    let rules: Vec<&str> = breaches.iter().map(|breach| breach.rule).collect();
+    let trading_days: Vec<Date> = breaches.iter().map(|breach| breach.trading_day).collect();
+    sqlx::query(
+        "DELETE FROM rule_breaches WHERE account_id = $1
+        AND (rule, trading_day) NOT IN (SELECT * FROM UNNEST($2::text[], $3::date[]))"
+    )
+        .bind(account_id)
+        .bind(&rules)
+        .bind(&trading_days)
+        .execute(&mut *transaction)
+        .await?;
+
+    for breach in breaches {
+        sqlx::query(
+            "INSERT INTO rule_breaches (id, account_id, rule, trade_id, trading_day, value, breach_limit)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (account_id, rule, trading_day) DO UPDATE SET
+                trade_id = EXCLUDED.trade_id,
+                value = EXCLUDED.value,
+                breach_limit = EXCLUDED.breach_limit"
+        )
+            .bind(uuid::Uuid::new_v4())
+            .bind(account_id)
+            .bind(breach.rule)
+            .bind(breach.trade_id)
+            .bind(breach.trading_day)
+            .bind(breach.value)
+            .bind(breach.limit)
+            .execute(&mut *transaction)
+            .await?;
+    }
+
+    transaction.commit().await
+}
+
+/// Re-checks every evaluation account the user has rules for, after their
+/// trades change.
+pub async fn record_breaches_for_user(
+    db: &PgPool,
+    user_id: uuid::Uuid,
+    session: &TradingSession,
+) -> Result<(), sqlx::Error> {
+    let evaluation_accounts = accounts::for_user(db, user_id)
+        .await?
+        .into_iter()
+        .filter(|account| account.account_type == AccountType::Evaluation.as_str());
+    for account in evaluation_accounts {
+        if let Some(evaluation) = evaluate(db, user_id, &account, session).await? {
+            record_breaches(db, account.id, &evaluation.breaches).await?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use serde::Serialize;
+use sqlx::{FromRow, PgExecutor, PgPool, Postgres, QueryBuilder, Transaction};
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use crate::domain::{ExecutionAction, NewExecution, Pagination, TradingSession};
+use crate::trades::{Fill, TradeFilter};
+
+/// Postgres allows 65535 bind parameters per statement, so inserts are chunked.
+const INSERT_BATCH_SIZE: usize = 1000;
+
+/// Stores the executions for `user_id`, skipping any whose NinjaTrader execution
+/// ID was already imported. Returns how many executions were new. Callers
+/// queue the trade rebuild in the same transaction.
+pub async fn insert_executions(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: uuid::Uuid,
+    executions: &[NewExecution],
+) -> Result<u64, sqlx::Error> {
+    let mut inserted = 0;
+
+    for chunk in executions.chunks(INSERT_BATCH_SIZE) {
+        let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(
+            "INSERT INTO executions (id, user_id, execution_id, account, instrument, action, quantity, price, executed_at, order_id, name, commission, connection) "
+        );
+        query_builder.push_values(chunk, |mut row, execution| {
+            row.push_bind(uuid::Uuid::new_v4())
+                .push_bind(user_id)
+                .push_bind(&execution.execution_id)
+                .push_bind(&execution.account)
+                .push_bind(&execution.instrument)
+                .push_bind(execution.action.as_str())
+                .push_bind(execution.quantity)
+                .push_bind(execution.price)
+                .push_bind(execution.executed_at)
+                .push_bind(&execution.order_id)
+                .push_bind(&execution.name)
+                .push_bind(execution.commission)
+                .push_bind(&execution.connection);
+        });
+        query_builder.push(" ON CONFLICT (user_id, execution_id) DO NOTHING");
+
+        inserted += query_builder
+            .build()
+            .execute(&mut **transaction)
+            .await?
+            .rows_affected();
+    }
+
+    Ok(inserted)
+}
+
+/// A stored execution, as imported from NinjaTrader.
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
+pub struct Execution {
+    pub id: uuid::Uuid,
+    /// NinjaTrader's execution ID
+    pub execution_id: String,
+    pub account: String,
+    pub instrument: String,
+    /// `buy` or `sell`
+    pub action: String,
+    pub quantity: i32,
+    pub price: f64,
+    pub commission: f64,
+    #[serde(with = "time::serde::rfc3339")]
+    pub executed_at: OffsetDateTime,
+    pub order_id: String,
+    pub name: String,
+    pub connection: String,
+}
+
+/// A page of the user's executions that match `filter`, oldest first, and how
+/// many executions match in all.
+pub async fn page_for_user(
+    db: &PgPool,
+    user_id: uuid::Uuid,
+    filter: &TradeFilter,
+    session: &TradingSession,
+    pagination: &Pagination,
+) -> Result<(Vec<Execution>, i64), sqlx::Error> {
+    let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(
+        "SELECT id, execution_id, account, instrument, action, quantity, price, commission, executed_at, order_id, name, connection
+        FROM executions WHERE user_id = "
+    );
+    query_builder.push_bind(user_id);
+    filter.push_execution_conditions(&mut query_builder, session);
+    query_builder
+        .push(" ORDER BY executed_at, created_at, id LIMIT ")
+        .push_bind(pagination.limit())
+        .push(" OFFSET ")
+        .push_bind(pagination.offset());
+    let executions = query_builder.build_query_as().fetch_all(db).await?;
+
+    let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new("SELECT COUNT(*) FROM executions WHERE user_id = ");
+    query_builder.push_bind(user_id);
+    filter.push_execution_conditions(&mut query_builder, session);
+    let total = query_builder.build_query_scalar().fetch_one(db).await?;
+
+    Ok((executions, total))
+}
+
+#[derive(sqlx::FromRow)]
+struct FillRow {
+    execution_id: String,
+    account: String,
+    instrument: String,
+    action: String,
+    quantity: i32,
+    price: f64,
+    commission: f64,
+    executed_at: OffsetDateTime,
+}
+
+/// Every execution the user has imported, oldest first. `streams` limits them
+/// to those `(account, instrument)` pairs.
+pub async fn fills_for_user<'e, E>(
+    executor: E,
+    user_id: uuid::Uuid,
+    streams: Option<&[(String, String)]>,
+) -> Result<Vec<Fill>, sqlx::Error>
+where
+    E: PgExecutor<'e>,
+{
+    let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(
+        "SELECT execution_id, account, instrument, action, quantity, price, commission, executed_at
+        FROM executions WHERE user_id = "
+    );
+    query_builder.push_bind(user_id);
+    if let Some(streams) = streams {
+        push_streams(&mut query_builder, streams);
+    }
+    query_builder.push(" ORDER BY executed_at, created_at");
+    let rows: Vec<FillRow> = query_builder.build_query_as().fetch_all(executor).await?;
+
+    rows.into_iter()
+        .map(|row| {
+            let action = ExecutionAction::parse(&row.action).map_err(|e| sqlx::Error::Decode(e.into()))?;
+            Ok(Fill {
+                execution_id: row.execution_id,
+                account: row.account,
+                instrument: row.instrument,
+                action,
+                quantity: row.quantity,
+                price: row.price,
+                commission: row.commission,
+                executed_at: row.executed_at,
+            })
+        })
+        .collect()
+}
+
+/// Limits a query over `executions` or `trades` that already has a WHERE
+/// clause to the given `(account, instrument)` pairs.
+pub fn push_streams(query_builder: &mut QueryBuilder<'_, Postgres>, streams: &[(String, String)]) {
+    let (accounts, instruments): (Vec<String>, Vec<String>) = streams.iter().cloned().unzip();
+    query_builder
+        .push(" AND (account, instrument) IN (SELECT * FROM UNNEST(")
+        .push_bind(accounts)
+        .push("::TEXT[], ")
+        .push_bind(instruments)
+        .push("::TEXT[]))");
+}
@@ -1,13 +1,68 @@
 use axum::Extension;
+use axum_extra::extract::Query;
 use axum::response::{Html, IntoResponse};
+use axum_messages::Messages;
 use crate::startup::AppState;
-use crate::template_helpers::{render_content, RenderTemplateParams};
+use crate::template_helpers::{flash_messages, render_content, RenderTemplateParams};
 use crate::constants::html_templates;
+use crate::user::AuthSession;
+use crate::utils::e500;
+use crate::stats::{EquityChart, EquityCurve, PerformanceStats};
+use crate::accounts;
+use crate::trades::{self, FilterChoices, TradeFilter, TradeFilterQuery};
 
-pub async fn homepage(Extension(state): Extension<AppState>) -> impl IntoResponse {
+const EQUITY_CHART_WIDTH: f64 = 600.0;
+const EQUITY_CHART_HEIGHT: f64 = 200.0;
+
+pub async fn homepage(
+    auth_session: AuthSession,
+    Extension(state): Extension<AppState>,
+    messages: Messages,
+    Query(query): Query<TradeFilterQuery>,
+) -> impl IntoResponse {
     let mut context = tera::Context::new();
-    let boo = "asdf";
-    context.insert("boo", &boo);
+    context.insert("messages", &flash_messages(messages));
+    let unverified = auth_session.user.as_ref().is_some_and(|user| !user.is_verified());
+    context.insert("unverified", &unverified);
+
+    // Logged in users get their dashboard
+    let mut stats = None;
+    let mut equity_chart = None;
+    let mut balance = None;
+    let mut choices = FilterChoices::default();
+    let mut filter_error = None;
+    if let Some(user) = auth_session.user {
+        let filter = TradeFilter::try_from(query.clone()).unwrap_or_else(|err| {
+            filter_error = Some(err);
+            TradeFilter::default()
+        });
+        let user_trades = match trades::for_user(&state.db, user.id, &filter, &state.trading_session).await {
+            Ok(trades) => trades,
+            Err(err) => return e500(err).into_response(),
+        };
+        choices = match trades::filter_choices(&state.db, user.id).await {
+            Ok(choices) => choices,
+            Err(err) => return e500(err).into_response(),
+        };
+        let performance = PerformanceStats::from_trades(&user_trades);
+        // A balance only adds up over every trade in the accounts
+        let whole_accounts = TradeFilter { accounts: filter.accounts.clone(), ..Default::default() };
+        if filter == whole_accounts {
+            balance = Some(accounts::starting_balance(&choices.accounts, &filter.accounts) + performance.net_pnl);
+        }
+        stats = Some(performance);
+        equity_chart = EquityChart::new(
+            &EquityCurve::from_trades(&user_trades),
+            EQUITY_CHART_WIDTH,
+            EQUITY_CHART_HEIGHT,
+        );
+    }
+    context.insert("stats", &stats);
+    context.insert("equity_chart", &equity_chart);
+    context.insert("balance", &balance);
+    context.insert("filter", &query);
+    context.insert("filter_error", &filter_error);
+    context.insert("choices", &choices);
 
     match render_content(
         &RenderTemplateParams::new(html_templates::HOMEPAGE, &state.tera)
@@ -17,4 +72,3 @@ pub async fn homepage(Extension(state): Extension<AppState>) -> impl IntoRespons
         Err(e) => e.into_response()
     }
 }
-
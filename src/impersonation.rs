@@ -0,0 +1,116 @@
+//! src/impersonation.rs
+//! Lets admins log in as a user to see what they see. The admin's id is kept
+//! in the session so they can switch back, and every page shows a banner
+//! while it's there.
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension,
+};
+use axum_login::tower_sessions::Session;
+use sqlx::PgPool;
+use crate::audit;
+use crate::constants::html_templates;
+use crate::startup::AppState;
+use crate::user::{self, AuthSession, User};
+use crate::utils::e500;
+
+const IMPERSONATOR_KEY: &str = "impersonator_id";
+
+/// The admin impersonating the current user, if any.
+pub async fn impersonator(session: &Session) -> Option<uuid::Uuid> {
+    session.get(IMPERSONATOR_KEY).await.ok().flatten()
+}
+
+/// Logs the admin in as `target`, remembering who they were.
+pub async fn start(
+    auth_session: &mut AuthSession,
+    session: &Session,
+    db: &PgPool,
+    admin: &User,
+    target: &User,
+) -> Result<(), anyhow::Error> {
+    session.insert(IMPERSONATOR_KEY, admin.id).await?;
+    auth_session.login(target).await?;
+    audit::record_by(db, admin.id, target.id, audit::IMPERSONATION_STARTED, None).await?;
+    Ok(())
+}
+
+/// Logs the admin back in as themselves. Returns false when the session
+/// wasn't impersonating anyone.
+pub async fn stop(auth_session: &mut AuthSession, session: &Session, db: &PgPool) -> Result<bool, anyhow::Error> {
+    let admin_id = match impersonator(session).await {
+        Some(admin_id) => admin_id,
+        None => return Ok(false),
+    };
+    let impersonated_id = auth_session.user.as_ref().map(|user| user.id);
+
+    match user::find_by_id(db, admin_id).await? {
+        Some(admin) => auth_session.login(&admin).await?,
+        // The admin was deleted meanwhile, so there's no one to go back to
+        None => auth_session.logout().await.map(|_| ())?,
+    }
+    session.remove::<uuid::Uuid>(IMPERSONATOR_KEY).await?;
+
+    if let Some(impersonated_id) = impersonated_id {
+        audit::record_by(db, admin_id, impersonated_id, audit::IMPERSONATION_STOPPED, None).await?;
+    }
+    Ok(true)
+}
+
+/// Middleware that refuses requests while an admin is impersonating someone,
+/// for admin pages.
+pub async fn forbid(session: Session, request: Request, next: Next) -> Response {
+    if impersonator(&session).await.is_some() {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    next.run(request).await
+}
+
+/// Middleware that adds the impersonation banner to the top of HTML pages, so
+/// no handler has to remember to.
+pub async fn banner(
+    Extension(state): Extension<AppState>,
+    auth_session: AuthSession,
+    session: Session,
+    request: Request,
+    next: Next,
+) -> Response {
+    let impersonator = impersonator(&session).await;
+    let response = next.run(request).await;
+
+    let user = match (impersonator, auth_session.user) {
+        (Some(_), Some(user)) => user,
+        _ => return response,
+    };
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+    if !is_html {
+        return response;
+    }
+
+    let mut context = tera::Context::new();
+    context.insert("email", &user.email);
+    let banner = match state.tera.render(html_templates::IMPERSONATION_BANNER, &context) {
+        Ok(banner) => banner,
+        Err(err) => {
+            tracing::error!(error = %err, "Failed to render the impersonation banner");
+            return response;
+        }
+    };
+
+    let (mut parts, body) = response.into_parts();
+    let html = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => String::from_utf8_lossy(&bytes).into_owned(),
+        Err(err) => return e500(err).into_response(),
+    };
+    let html = html.replacen("<body>", &format!("<body>\n{}", banner), 1);
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(html))
+}
@@ -0,0 +1,264 @@
+//! src/jobs.rs
+//! Background work that doesn't need to finish before the response, like
+//! rebuilding trades. Jobs are typed structs implementing `Job`, queued with
+//! `enqueue` (in the same transaction as the change they follow up on, where
+//! there is one) and run by the workers started in `startup::run`. Failures are
+//! retried with backoff, and jobs that run out of attempts are kept in the
+//! table with `failed_at` set.
+//!
+//! A worker claims a job with a lease, renewed while the job runs, and no
+//! transaction is held open in the meantime. A job runs at least once: one
+//! that was running when the app stopped runs again once its lease runs out,
+//! so jobs should be safe to repeat.
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use sqlx::types::Json;
+use sqlx::{FromRow, PgExecutor};
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use crate::login_throttle::NotifyLockout;
+use crate::password_reset::SendResetLink;
+use crate::startup::AppState;
+use crate::digests::QueueDueDigests;
+use crate::trades::{RebuildTrades, RebuildTradesForRoot};
+
+/// Jobs run at the same time by one app instance
+const WORKERS: usize = 2;
+const FIRST_RETRY: Duration = Duration::seconds(10);
+const MAX_RETRY: Duration = Duration::hours(1);
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+/// How long a claimed job is left to its worker before others may run it
+const LEASE: Duration = Duration::minutes(5);
+/// How often a running job's lease is extended
+const LEASE_RENEWAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// A kind of background work. The struct is the payload, stored as JSON.
+#[async_trait]
+pub trait Job: Serialize + DeserializeOwned + Send + Sync + 'static {
+    /// Stored with each job to find how to run it. Don't rename it while
+    /// jobs of the kind could still be queued.
+    const KIND: &'static str;
+    /// Attempts before the job is given up on
+    const MAX_ATTEMPTS: i32 = 5;
+
+    async fn run(&self, state: &AppState) -> Result<(), anyhow::Error>;
+}
+
+type RunJob = for<'a> fn(
+    &'a AppState,
+    serde_json::Value,
+) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + Send + 'a>>;
+
+/// Add new jobs here so workers can run them
+const JOBS: &[(&str, RunJob)] = &[
+    (RebuildTradesForRoot::KIND, run_job::<RebuildTradesForRoot>),
+    (RebuildTrades::KIND, run_job::<RebuildTrades>),
+    (QueueDueDigests::KIND, run_job::<QueueDueDigests>),
+    (SendResetLink::KIND, run_job::<SendResetLink>),
+    (NotifyLockout::KIND, run_job::<NotifyLockout>),
+];
+
+fn run_job<J: Job>(
+    state: &AppState,
+    payload: serde_json::Value,
+) -> Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + Send + '_>> {
+    Box::pin(async move {
+        let job: J = serde_json::from_value(payload)?;
+        job.run(state).await
+    })
+}
+
+#[derive(Debug, FromRow)]
+struct QueuedJob {
+    id: uuid::Uuid,
+    kind: String,
+    payload: Json<serde_json::Value>,
+    attempts: i32,
+    max_attempts: i32,
+}
+
+/// Queues `job` to run once the surrounding transaction commits.
+pub async fn enqueue<'e, E, J>(executor: E, job: &J) -> Result<(), sqlx::Error>
+where
+    E: PgExecutor<'e>,
+    J: Job,
+{
+    sqlx::query("INSERT INTO jobs (id, kind, payload, max_attempts) VALUES ($1, $2, $3, $4)")
+        .bind(uuid::Uuid::new_v4())
+        .bind(J::KIND)
+        .bind(Json(job))
+        .bind(J::MAX_ATTEMPTS)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+/// Queues `job` unless a job of its kind is already waiting or running, for
+/// work queued on a schedule rather than after a change. Two instances may
+/// still both queue one, so such jobs should be cheap to repeat.
+pub async fn enqueue_unless_queued<'e, E, J>(executor: E, job: &J) -> Result<(), sqlx::Error>
+where
+    E: PgExecutor<'e>,
+    J: Job,
+{
+    sqlx::query(
+        "INSERT INTO jobs (id, kind, payload, max_attempts)
+        SELECT $1, $2, $3, $4
+        WHERE NOT EXISTS (SELECT 1 FROM jobs WHERE kind = $2 AND failed_at IS NULL)"
+    )
+        .bind(uuid::Uuid::new_v4())
+        .bind(J::KIND)
+        .bind(Json(job))
+        .bind(J::MAX_ATTEMPTS)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+/// How long to wait before trying again after `attempts` failures. Doubles
+/// from 10 seconds up to an hour.
+pub fn retry_delay(attempts: i32) -> Duration {
+    let doublings = attempts.saturating_sub(1).clamp(0, 16) as u32;
+    (FIRST_RETRY * 2_i32.pow(doublings)).min(MAX_RETRY)
+}
+
+/// Runs `payload` as a job of `kind` in its own task, so a job that panics
+/// fails like any other instead of taking its worker down.
+async fn run(state: &AppState, kind: &str, payload: serde_json::Value) -> Result<(), anyhow::Error> {
+    // During a rolling deploy an older instance may see jobs from a newer
+    // one, so unknown kinds are retried rather than failed straight away
+    let run_job = JOBS
+        .iter()
+        .find(|(name, _)| *name == kind)
+        .map(|(_, run_job)| *run_job)
+        .ok_or_else(|| anyhow::anyhow!("Unknown job kind {}", kind))?;
+    let state = state.clone();
+    tokio::spawn(async move { run_job(&state, payload).await }).await?
+}
+
+/// Runs the next due job, if there is one. The job is claimed with a lease
+/// in a statement of its own, so several workers never run the same one and
+/// no transaction stays open while it runs. The lease is renewed until the
+/// job finishes, and a job whose worker stopped is run again once its lease
+/// runs out. Returns whether a job was run.
+pub async fn run_next(state: &AppState) -> Result<bool, sqlx::Error> {
+    let job: Option<QueuedJob> = sqlx::query_as(
+        "UPDATE jobs SET locked_until = NOW() + make_interval(secs => $1)
+        WHERE id = (
+            SELECT id FROM jobs
+            WHERE failed_at IS NULL AND run_at <= NOW()
+            AND (locked_until IS NULL OR locked_until < NOW())
+            ORDER BY run_at
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING id, kind, payload, attempts, max_attempts"
+    )
+        .bind(LEASE.as_seconds_f64())
+        .fetch_optional(&state.db)
+        .await?;
+    let job = match job {
+        Some(job) => job,
+        None => return Ok(false),
+    };
+
+    let attempts = job.attempts + 1;
+    let result = tokio::select! {
+        result = run(state, &job.kind, job.payload.0) => result,
+        never = renew_lease(state, job.id) => match never {},
+    };
+    match result {
+        Ok(()) => {
+            sqlx::query("DELETE FROM jobs WHERE id = $1")
+                .bind(job.id)
+                .execute(&state.db)
+                .await?;
+        }
+        Err(err) => {
+            tracing::warn!(error = %err, job_id = %job.id, kind = job.kind, attempts, "A job failed");
+            let gave_up = attempts >= job.max_attempts;
+            sqlx::query(
+                "UPDATE jobs SET attempts = $1, last_error = $2, locked_until = NULL,
+                run_at = NOW() + make_interval(secs => $3),
+                failed_at = CASE WHEN $4 THEN NOW() END
+                WHERE id = $5"
+            )
+                .bind(attempts)
+                .bind(format!("{:#}", err))
+                .bind(retry_delay(attempts).as_seconds_f64())
+                .bind(gave_up)
+                .bind(job.id)
+                .execute(&state.db)
+                .await?;
+            if gave_up {
+                tracing::error!(job_id = %job.id, kind = job.kind, "Gave up on a job");
+            }
+        }
+    }
+
+    Ok(true)
+}
+
+/// Extends the lease on job `id` until this future is dropped
+async fn renew_lease(state: &AppState, id: uuid::Uuid) -> Infallible {
+    loop {
+        tokio::time::sleep(LEASE_RENEWAL).await;
+        let renewed = sqlx::query("UPDATE jobs SET locked_until = NOW() + make_interval(secs => $1) WHERE id = $2")
+            .bind(LEASE.as_seconds_f64())
+            .bind(id)
+            .execute(&state.db)
+            .await;
+        // Tried again at the next renewal, well before the lease runs out
+        if let Err(err) = renewed {
+            tracing::warn!(error = %err, job_id = %id, "Failed to renew a job's lease");
+        }
+    }
+}
+
+/// Runs jobs until `shutdown` is set. Workers then finish the job they're
+/// running and return.
+pub async fn run_workers(state: AppState, shutdown: watch::Receiver<bool>) {
+    let mut workers = JoinSet::new();
+    for _ in 0..WORKERS {
+        workers.spawn(work(state.clone(), shutdown.clone()));
+    }
+    while workers.join_next().await.is_some() {}
+}
+
+async fn work(state: AppState, mut shutdown: watch::Receiver<bool>) {
+    while !*shutdown.borrow() {
+        match run_next(&state).await {
+            // Keep going while there's a backlog
+            Ok(true) => continue,
+            Ok(false) => {}
+            Err(err) => tracing::error!(error = %err, "Failed to read the job queue"),
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(POLL_INTERVAL) => {}
+            changed = shutdown.changed() => {
+                if changed.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::retry_delay;
+    use time::Duration;
+
+    #[test]
+    fn retries_back_off_up_to_an_hour() {
+        assert_eq!(retry_delay(1), Duration::seconds(10));
+        assert_eq!(retry_delay(2), Duration::seconds(20));
+        assert_eq!(retry_delay(5), Duration::seconds(160));
+        assert_eq!(retry_delay(10), Duration::hours(1));
+        assert_eq!(retry_delay(100), Duration::hours(1));
+    }
+}
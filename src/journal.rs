@@ -0,0 +1,229 @@
+//! src/journal.rs
+//! Notes, ratings, tags and chart screenshots a user records against their
+//! trades. Screenshot files live on disk; only their names are stored here.
+use pulldown_cmark::{html, Event, Options, Parser};
+use serde::Serialize;
+use sqlx::{FromRow, PgPool};
+use time::OffsetDateTime;
+use utoipa::ToSchema;
+use crate::domain::JournalEntry;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Screenshot {
+    pub id: uuid::Uuid,
+    pub file_name: String,
+    pub original_name: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub created_at: OffsetDateTime,
+}
+
+/// The markdown notes for one of the user's trades.
+pub async fn notes(db: &PgPool, user_id: uuid::Uuid, trade_id: uuid::Uuid) -> Result<String, sqlx::Error> {
+    sqlx::query_scalar("SELECT notes FROM trades WHERE id = $1 AND user_id = $2")
+        .bind(trade_id)
+        .bind(user_id)
+        .fetch_optional(db)
+        .await
+        .map(Option::unwrap_or_default)
+}
+
+/// Replaces the journal entry for one of the user's trades. Returns false when
+/// the trade doesn't belong to the user. Tags no longer on any trade are removed.
+pub async fn save(
+    db: &PgPool,
+    user_id: uuid::Uuid,
+    trade_id: uuid::Uuid,
+    entry: &JournalEntry,
+) -> Result<bool, sqlx::Error> {
+    let mut transaction = db.begin().await?;
+
+    let updated = sqlx::query("UPDATE trades SET notes = $1, rating = $2, risk = $3 WHERE id = $4 AND user_id = $5")
+        .bind(&entry.notes)
+        .bind(entry.rating)
+        .bind(entry.risk)
+        .bind(trade_id)
+        .bind(user_id)
+        .execute(&mut *transaction)
+        .await?;
+    if updated.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    sqlx::query("DELETE FROM trade_tags WHERE trade_id = $1")
+        .bind(trade_id)
+        .execute(&mut *transaction)
+        .await?;
+    for name in &entry.tags {
+        // Reuses the user's existing spelling of the tag
+        let tag_id: uuid::Uuid = sqlx::query_scalar(
+            "INSERT INTO tags (id, user_id, name) VALUES ($1, $2, $3)
+            ON CONFLICT (user_id, LOWER(name)) DO UPDATE SET name = tags.name
+            RETURNING id"
+        )
+            .bind(uuid::Uuid::new_v4())
+            .bind(user_id)
+            .bind(name)
+            .fetch_one(&mut *transaction)
+            .await?;
+        sqlx::query("INSERT INTO trade_tags (trade_id, tag_id) VALUES ($1, $2)")
+            .bind(trade_id)
+            .bind(tag_id)
+            .execute(&mut *transaction)
+            .await?;
+    }
+    sqlx::query(
+        "DELETE FROM tags WHERE user_id = $1
+        AND NOT EXISTS (SELECT 1 FROM trade_tags WHERE trade_tags.tag_id = tags.id)"
+    )
+        .bind(user_id)
+        .execute(&mut *transaction)
+        .await?;
+
+    transaction.commit().await?;
+    Ok(true)
+}
+
+/// A tag and how many of the user's trades have it.
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
+pub struct TagSummary {
+    pub name: String,
+    pub trade_count: i64,
+}
+
+/// The user's tags in alphabetical order.
+pub async fn tags_for_user(db: &PgPool, user_id: uuid::Uuid) -> Result<Vec<TagSummary>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT tags.name, COUNT(trade_tags.trade_id) AS trade_count
+        FROM tags LEFT JOIN trade_tags ON trade_tags.tag_id = tags.id
+        WHERE tags.user_id = $1
+        GROUP BY tags.id, tags.name
+        ORDER BY LOWER(tags.name)"
+    )
+        .bind(user_id)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn screenshots_for_trade(
+    db: &PgPool,
+    user_id: uuid::Uuid,
+    trade_id: uuid::Uuid,
+) -> Result<Vec<Screenshot>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT id, file_name, original_name, created_at FROM trade_screenshots
+        WHERE trade_id = $1 AND user_id = $2 ORDER BY created_at"
+    )
+        .bind(trade_id)
+        .bind(user_id)
+        .fetch_all(db)
+        .await
+}
+
+/// Records a screenshot of one of the user's trades and returns its id.
+pub async fn add_screenshot(
+    db: &PgPool,
+    user_id: uuid::Uuid,
+    trade_id: uuid::Uuid,
+    file_name: &str,
+    original_name: &str,
+) -> Result<uuid::Uuid, sqlx::Error> {
+    let screenshot_id = uuid::Uuid::new_v4();
+    sqlx::query(
+        "INSERT INTO trade_screenshots (id, trade_id, user_id, file_name, original_name)
+        VALUES ($1, $2, $3, $4, $5)"
+    )
+        .bind(screenshot_id)
+        .bind(trade_id)
+        .bind(user_id)
+        .bind(file_name)
+        .bind(original_name)
+        .execute(db)
+        .await?;
+    Ok(screenshot_id)
+}
+
+/// Removes a screenshot of one of the user's trades and returns its file name
+/// so the caller can delete the file.
+pub async fn delete_screenshot(
+    db: &PgPool,
+    user_id: uuid::Uuid,
+    trade_id: uuid::Uuid,
+    screenshot_id: uuid::Uuid,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar(
+        "DELETE FROM trade_screenshots WHERE id = $1 AND user_id = $2 AND trade_id = $3 RETURNING file_name"
+    )
+        .bind(screenshot_id)
+        .bind(user_id)
+        .bind(trade_id)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn user_owns_screenshot(db: &PgPool, user_id: uuid::Uuid, file_name: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM trade_screenshots WHERE file_name = $1 AND user_id = $2)")
+        .bind(file_name)
+        .bind(user_id)
+        .fetch_one(db)
+        .await
+}
+
+/// The file extension for a PNG, JPEG, GIF or WebP image, detected from its
+/// first bytes rather than trusting the uploaded name.
+pub fn image_extension(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("png")
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("jpg")
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some("gif")
+    } else if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some("webp")
+    } else {
+        None
+    }
+}
+
+/// Renders journal notes to HTML. Raw HTML in the notes is shown as text so
+/// notes can never inject markup into the page.
+pub fn render_markdown(notes: &str) -> String {
+    let parser = Parser::new_ext(notes, Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH)
+        .map(|event| match event {
+            Event::Html(html) | Event::InlineHtml(html) => Event::Text(html),
+            event => event,
+        });
+    let mut output = String::new();
+    html::push_html(&mut output, parser);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{image_extension, render_markdown};
+
+    #[test]
+    fn markdown_is_rendered() {
+        assert_eq!(
+            render_markdown("Entered **late**\n\n- chased"),
+            "<p>Entered <strong>late</strong></p>\n<ul>\n<li>chased</li>\n</ul>\n"
+        );
+    }
+
+    #[test]
+    fn raw_html_is_escaped() {
+        let html = render_markdown("<script>alert(1)</script>\n\nnice <b>trade</b>");
+        assert!(!html.contains("<script>"));
+        assert!(!html.contains("<b>"));
+        assert!(html.contains("&lt;script&gt;"));
+    }
+
+    #[test]
+    fn images_are_detected_by_their_contents() {
+        assert_eq!(image_extension(b"\x89PNG\r\n\x1a\nrest"), Some("png"));
+        assert_eq!(image_extension(&[0xFF, 0xD8, 0xFF, 0xE0]), Some("jpg"));
+        assert_eq!(image_extension(b"GIF89a..."), Some("gif"));
+        assert_eq!(image_extension(b"RIFF\0\0\0\0WEBPVP8 "), Some("webp"));
+        assert_eq!(image_extension(b"<svg></svg>"), None);
+        assert_eq!(image_extension(b""), None);
+    }
+}
@@ -9,4 +9,26 @@ pub mod utils;
 pub mod user;
 pub mod domain;
 pub mod emailer;
+pub mod email_outbox;
 pub mod constants;
+pub mod executions;
+pub mod contract_specs;
+pub mod ninjatrader;
+pub mod trades;
+pub mod stats;
+pub mod journal;
+pub mod accounts;
+pub mod evaluations;
+pub mod email_verification;
+pub mod password_reset;
+pub mod audit;
+pub mod account_settings;
+pub mod admin;
+pub mod impersonation;
+pub mod login_throttle;
+pub mod two_factor;
+pub mod api_tokens;
+pub mod digests;
+pub mod jobs;
+pub mod session_store;
+pub mod stats_cache;
@@ -0,0 +1,379 @@
+//! src/login_throttle.rs
+//! Slows down password guessing. Failed logins are counted per account and per
+//! client IP. After a few free attempts each failure doubles the wait before
+//! the next one, and enough failures lock logins out for a while.
+//!
+//! Accounts are keyed by the email typed in, so unknown emails are throttled
+//! exactly like real ones and the responses don't reveal which is which.
+//!
+//! An attempt is counted as a failure before its password or code is checked,
+//! and taken back if it was right, so parallel attempts can't all get past
+//! the check before any of them fails.
+use std::net::{IpAddr, SocketAddr};
+use async_trait::async_trait;
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::request::Parts;
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool};
+use time::{Duration, OffsetDateTime};
+use crate::constants::{email_templates, job_kinds, route_paths, strings};
+use crate::email_outbox;
+use crate::emailer::{Email, EmailTemplate};
+use crate::jobs::Job;
+use crate::startup::AppState;
+
+const FREE_ATTEMPTS: i32 = 3;
+const MAX_BACKOFF: Duration = Duration::minutes(5);
+const ACCOUNT_LOCKOUT_FAILURES: i32 = 10;
+/// Higher than the account limit, since many users can share an IP
+const IP_LOCKOUT_FAILURES: i32 = 50;
+pub const LOCKOUT: Duration = Duration::minutes(15);
+/// Failures older than this are forgotten
+const FAILURE_WINDOW: Duration = Duration::hours(1);
+
+const ACCOUNT: &str = "account";
+const IP: &str = "ip";
+
+#[derive(Debug, Serialize)]
+pub struct AccountLockedEmail {
+    pub email: String,
+    pub failures: i32,
+    pub lockout: String,
+    pub reset_link: String,
+}
+
+impl EmailTemplate for AccountLockedEmail {
+    const NAME: &'static str = email_templates::ACCOUNT_LOCKED;
+
+    fn sample() -> Self {
+        Self {
+            email: "trader@example.com".to_string(),
+            failures: ACCOUNT_LOCKOUT_FAILURES,
+            lockout: format!("{} minutes", LOCKOUT.whole_minutes()),
+            reset_link: format!("https://example.com{}", route_paths::FORGOT_PASSWORD),
+        }
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct Throttle {
+    failures: i32,
+    last_failure_at: OffsetDateTime,
+    locked_until: Option<OffsetDateTime>,
+}
+
+impl Throttle {
+    /// How long until another attempt is allowed.
+    fn wait(&self, now: OffsetDateTime) -> Duration {
+        if let Some(locked_until) = self.locked_until {
+            if locked_until > now {
+                return locked_until - now;
+            }
+        }
+        if self.last_failure_at + FAILURE_WINDOW < now || self.failures <= FREE_ATTEMPTS {
+            return Duration::ZERO;
+        }
+
+        let doublings = (self.failures - FREE_ATTEMPTS).min(16) as u32;
+        let backoff = Duration::seconds(2_i64.pow(doublings)).min(MAX_BACKOFF);
+        (self.last_failure_at + backoff - now).max(Duration::ZERO)
+    }
+}
+
+/// Emails are matched however they're typed
+fn account_key(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+/// Checks whether the client can try to log in as `email` now and, if so,
+/// counts the attempt as a failure until `release` takes it back. The rows
+/// are locked while checking, so parallel attempts take turns and each one
+/// sees those before it. Returns how long the client has to wait when they
+/// can't try yet.
+pub async fn reserve(db: &PgPool, email: &str, ip: &str) -> Result<Option<Duration>, sqlx::Error> {
+    let account_key = account_key(email);
+    let mut transaction = db.begin().await?;
+    sqlx::query(
+        "INSERT INTO login_throttles (scope, key) VALUES ($1, $2), ($3, $4)
+        ON CONFLICT (scope, key) DO NOTHING"
+    )
+        .bind(ACCOUNT)
+        .bind(&account_key)
+        .bind(IP)
+        .bind(ip)
+        .execute(&mut *transaction)
+        .await?;
+    // Always locked account first, so attempts sharing either row can't deadlock
+    let throttles: Vec<Throttle> = sqlx::query_as(
+        "SELECT failures, last_failure_at, locked_until FROM login_throttles
+        WHERE (scope = $1 AND key = $2) OR (scope = $3 AND key = $4)
+        ORDER BY scope
+        FOR UPDATE"
+    )
+        .bind(ACCOUNT)
+        .bind(&account_key)
+        .bind(IP)
+        .bind(ip)
+        .fetch_all(&mut *transaction)
+        .await?;
+
+    let now = OffsetDateTime::now_utc();
+    let wait = throttles.iter().map(|throttle| throttle.wait(now)).max();
+    if let Some(wait) = wait.filter(|wait| wait.is_positive()) {
+        // Throttled attempts aren't counted
+        return Ok(Some(wait));
+    }
+
+    sqlx::query(
+        "UPDATE login_throttles SET
+            failures = CASE
+                WHEN last_failure_at < NOW() - make_interval(secs => $5) THEN 1
+                ELSE failures + 1
+            END,
+            last_failure_at = NOW()
+        WHERE (scope = $1 AND key = $2) OR (scope = $3 AND key = $4)"
+    )
+        .bind(ACCOUNT)
+        .bind(&account_key)
+        .bind(IP)
+        .bind(ip)
+        .bind(FAILURE_WINDOW.as_seconds_f64())
+        .execute(&mut *transaction)
+        .await?;
+    transaction.commit().await?;
+    Ok(None)
+}
+
+/// Takes back a reserved attempt whose password or code was right.
+pub async fn release(db: &PgPool, email: &str, ip: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE login_throttles SET failures = GREATEST(failures - 1, 0)
+        WHERE (scope = $1 AND key = $2) OR (scope = $3 AND key = $4)"
+    )
+        .bind(ACCOUNT)
+        .bind(account_key(email))
+        .bind(IP)
+        .bind(ip)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// Keeps a reserved attempt counted after it failed, locking the account or
+/// IP when it reached their limit. Returns true when the account was locked,
+/// so the owner can be told.
+pub async fn record_failure(db: &PgPool, email: &str, ip: &str) -> Result<bool, sqlx::Error> {
+    let account_locked = lock_at(db, ACCOUNT, &account_key(email), ACCOUNT_LOCKOUT_FAILURES).await?;
+    lock_at(db, IP, ip, IP_LOCKOUT_FAILURES).await?;
+    Ok(account_locked)
+}
+
+/// Returns true when the key had `lockout_failures` and was locked. Locking
+/// starts the count again, so only one failure locks it, and the next lockout
+/// takes as many failures.
+async fn lock_at(db: &PgPool, scope: &str, key: &str, lockout_failures: i32) -> Result<bool, sqlx::Error> {
+    let locked = sqlx::query(
+        "UPDATE login_throttles SET failures = 0, locked_until = NOW() + make_interval(secs => $3)
+        WHERE scope = $1 AND key = $2 AND failures >= $4"
+    )
+        .bind(scope)
+        .bind(key)
+        .bind(LOCKOUT.as_seconds_f64())
+        .bind(lockout_failures)
+        .execute(db)
+        .await?
+        .rows_affected();
+    Ok(locked > 0)
+}
+
+/// Forgets the account's failures after a successful login. The IP's are kept,
+/// so logging in to one account doesn't reset guessing at others.
+pub async fn clear(db: &PgPool, email: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM login_throttles WHERE scope = $1 AND key = $2")
+        .bind(ACCOUNT)
+        .bind(account_key(email))
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// The client's IP, as far as throttling is concerned. Requests from one of
+/// `application.trusted_proxies` are taken to be from the address the proxies
+/// forwarded them for.
+#[derive(Debug, Clone, Copy)]
+pub struct ClientIp(pub IpAddr);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientIp
+where
+    S: Send + Sync,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let ConnectInfo(peer) = ConnectInfo::<SocketAddr>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let Extension(app_state) = Extension::<AppState>::from_request_parts(parts, state)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        let forwarded_for: Vec<&str> = parts
+            .headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .collect();
+        Ok(ClientIp(client_ip(peer.ip(), &forwarded_for.join(","), &app_state.trusted_proxies)))
+    }
+}
+
+/// Each proxy appends the address it got the request from to
+/// `X-Forwarded-For`, so the client is the last address that isn't one of our
+/// proxies. Anything before it could have been made up by the client.
+pub fn client_ip(peer: IpAddr, forwarded_for: &str, trusted_proxies: &[IpAddr]) -> IpAddr {
+    let mut client = peer;
+    for hop in forwarded_for.rsplit(',') {
+        if !trusted_proxies.contains(&client) {
+            break;
+        }
+        match hop.trim().parse() {
+            Ok(ip) => client = ip,
+            Err(_) => break,
+        }
+    }
+    client
+}
+
+/// "3 seconds" or "15 minutes", rounded up.
+pub fn describe_wait(wait: Duration) -> String {
+    let seconds = wait.as_seconds_f64().ceil().max(1.0) as i64;
+    match seconds {
+        1 => "1 second".to_string(),
+        2..=59 => format!("{} seconds", seconds),
+        _ => {
+            let minutes = (seconds + 59) / 60;
+            if minutes == 1 { "1 minute".to_string() } else { format!("{} minutes", minutes) }
+        }
+    }
+}
+
+/// Queues an email telling the owner their account was locked, if `email` has one.
+pub async fn notify_lockout(state: &AppState, email: &str) -> Result<(), anyhow::Error> {
+    let email: Option<String> = sqlx::query_scalar("SELECT email FROM users WHERE lower(email) = $1")
+        .bind(account_key(email))
+        .fetch_optional(&state.db)
+        .await?;
+    let email = match email {
+        Some(email) => email,
+        None => return Ok(()),
+    };
+
+    let locked = AccountLockedEmail {
+        email: email.clone(),
+        failures: ACCOUNT_LOCKOUT_FAILURES,
+        lockout: format!("{} minutes", LOCKOUT.whole_minutes()),
+        reset_link: format!("{}{}", state.base_url, route_paths::FORGOT_PASSWORD),
+    };
+    let message = Email::render(
+        &state.tera,
+        &state.email_settings.branding,
+        &email,
+        strings::ACCOUNT_LOCKED_EMAIL_SUBJECT,
+        &locked,
+    )?;
+    email_outbox::enqueue(&state.db, &message).await?;
+    Ok(())
+}
+
+/// Tells the owner of `email` their account was locked. Queued for unknown
+/// emails too, so they take as long.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NotifyLockout {
+    pub email: String,
+}
+
+#[async_trait]
+impl Job for NotifyLockout {
+    const KIND: &'static str = job_kinds::NOTIFY_LOCKOUT;
+
+    async fn run(&self, state: &AppState) -> Result<(), anyhow::Error> {
+        notify_lockout(state, &self.email).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{client_ip, describe_wait, Throttle, FAILURE_WINDOW, MAX_BACKOFF};
+    use std::net::IpAddr;
+    use time::macros::datetime;
+    use time::Duration;
+
+    fn throttle(failures: i32) -> Throttle {
+        Throttle {
+            failures,
+            last_failure_at: datetime!(2024-08-13 12:00 UTC),
+            locked_until: None,
+        }
+    }
+
+    #[test]
+    fn the_first_failures_are_free() {
+        let now = datetime!(2024-08-13 12:00 UTC);
+        for failures in 0..=3 {
+            assert_eq!(throttle(failures).wait(now), Duration::ZERO);
+        }
+    }
+
+    #[test]
+    fn each_later_failure_doubles_the_wait() {
+        let now = datetime!(2024-08-13 12:00 UTC);
+        assert_eq!(throttle(4).wait(now), Duration::seconds(2));
+        assert_eq!(throttle(5).wait(now), Duration::seconds(4));
+        assert_eq!(throttle(6).wait(now), Duration::seconds(8));
+        assert_eq!(throttle(40).wait(now), MAX_BACKOFF);
+        assert_eq!(throttle(5).wait(now + Duration::seconds(3)), Duration::seconds(1));
+        assert_eq!(throttle(5).wait(now + Duration::seconds(10)), Duration::ZERO);
+    }
+
+    #[test]
+    fn lockouts_last_until_they_expire() {
+        let now = datetime!(2024-08-13 12:00 UTC);
+        let locked = Throttle {
+            locked_until: Some(now + Duration::minutes(15)),
+            ..throttle(0)
+        };
+        assert_eq!(locked.wait(now), Duration::minutes(15));
+        assert_eq!(locked.wait(now + Duration::minutes(20)), Duration::ZERO);
+    }
+
+    #[test]
+    fn old_failures_are_forgotten() {
+        let now = datetime!(2024-08-13 12:00 UTC) + FAILURE_WINDOW + Duration::seconds(1);
+        assert_eq!(throttle(9).wait(now), Duration::ZERO);
+    }
+
+    #[test]
+    fn forwarded_addresses_are_only_trusted_from_our_proxies() {
+        let proxy: IpAddr = "10.0.0.2".parse().unwrap();
+        let client: IpAddr = "203.0.113.7".parse().unwrap();
+        let spoofed = "198.51.100.1, 203.0.113.7";
+
+        assert_eq!(client_ip(proxy, "", &[]), proxy);
+        assert_eq!(client_ip(client, "198.51.100.1", &[proxy]), client);
+        assert_eq!(client_ip(proxy, spoofed, &[proxy]), client);
+        assert_eq!(client_ip(proxy, "203.0.113.7, 10.0.0.3", &["10.0.0.3".parse().unwrap(), proxy]), client);
+        // Behind a proxy that didn't say who it was forwarding for
+        assert_eq!(client_ip(proxy, "", &[proxy]), proxy);
+        assert_eq!(client_ip(proxy, "unknown", &[proxy]), proxy);
+    }
+
+    #[test]
+    fn waits_are_described_rounded_up() {
+        assert_eq!(describe_wait(Duration::milliseconds(300)), "1 second");
+        assert_eq!(describe_wait(Duration::seconds(8)), "8 seconds");
+        assert_eq!(describe_wait(Duration::seconds(61)), "2 minutes");
+        assert_eq!(describe_wait(Duration::minutes(15)), "15 minutes");
+    }
+}
@@ -0,0 +1,100 @@
+//! src/ninjatrader.rs
+//! Reading the CSV that NinjaTrader writes when exporting the "Executions" grid
+//! from the Control Center.
+use time::UtcOffset;
+use crate::domain::{ExecutionFields, NewExecution};
+
+/// A row of the export that could not be turned into a `NewExecution`.
+/// `line` is the line number in the file, counting the header as line 1.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RowError {
+    pub line: u64,
+    pub message: String,
+}
+
+#[derive(Debug, Default)]
+pub struct ParsedExport {
+    pub executions: Vec<NewExecution>,
+    pub errors: Vec<RowError>,
+}
+
+/// Parses every row of an executions export. Rows that fail validation are
+/// collected in `errors` instead of failing the whole file, but a file without
+/// the expected header is rejected outright.
+pub fn parse_executions_csv(data: &[u8], utc_offset: UtcOffset) -> Result<ParsedExport, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(data);
+
+    let headers = reader.headers().map_err(|e| e.to_string())?.clone();
+    for required in ["Instrument", "Action", "Quantity", "Price", "Time", "ID", "Account"] {
+        if !headers.iter().any(|h| h == required) {
+            return Err(format!("This file is missing the {} column. Export the Executions grid from NinjaTrader.", required));
+        }
+    }
+
+    let mut parsed = ParsedExport::default();
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                let line = e.position().map(|p| p.line()).unwrap_or_default();
+                parsed.errors.push(RowError { line, message: e.to_string() });
+                continue;
+            }
+        };
+        let line = record.position().map(|p| p.line()).unwrap_or_default();
+        if record.iter().all(|field| field.is_empty()) {
+            continue;
+        }
+
+        let result = record
+            .deserialize::<ExecutionFields>(Some(&headers))
+            .map_err(|e| e.to_string())
+            .and_then(|fields| NewExecution::parse(fields, utc_offset));
+        match result {
+            Ok(execution) => parsed.executions.push(execution),
+            Err(message) => parsed.errors.push(RowError { line, message }),
+        }
+    }
+
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_executions_csv;
+    use claims::assert_err;
+    use time::macros::offset;
+
+    const EXPORT: &str = "\
+Instrument,Action,Quantity,Price,Time,ID,E/X,Position,Order ID,Name,Commission,Rate,Account,Connection,
+ES 09-24,Buy,1,5565.25,7/22/2024 9:31:05 AM,e1,Entry,1 L,o1,Entry,$2.09,1,Sim101,Playback Connection,
+ES 09-24,Sell,1,5567.5,7/22/2024 9:45:10 AM,e2,Exit,-,o2,Profit target,$2.09,1,Sim101,Playback Connection,
+";
+
+    #[test]
+    fn export_rows_are_parsed() {
+        let parsed = parse_executions_csv(EXPORT.as_bytes(), offset!(UTC)).unwrap();
+        assert!(parsed.errors.is_empty());
+        assert_eq!(parsed.executions.len(), 2);
+        assert_eq!(parsed.executions[1].execution_id, "e2");
+        assert_eq!(parsed.executions[1].name, "Profit target");
+    }
+
+    #[test]
+    fn invalid_rows_are_reported_with_their_line() {
+        let export = format!("{}ES 09-24,Buy,zero,5565.25,7/22/2024 9:50:00 AM,e3,Entry,1 L,o3,Entry,$2.09,1,Sim101,Playback Connection,\n", EXPORT);
+        let parsed = parse_executions_csv(export.as_bytes(), offset!(UTC)).unwrap();
+        assert_eq!(parsed.executions.len(), 2);
+        assert_eq!(parsed.errors.len(), 1);
+        assert_eq!(parsed.errors[0].line, 4);
+    }
+
+    #[test]
+    fn files_without_execution_columns_are_rejected() {
+        let export = "Date,Open,High,Low,Close\n7/22/2024,1,2,0,1\n";
+        assert_err!(parse_executions_csv(export.as_bytes(), offset!(UTC)));
+    }
+}
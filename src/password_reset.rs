@@ -0,0 +1,154 @@
+//! src/password_reset.rs
+//! Emailed, single use links for users who forgot their password. Tokens are
+//! generated and hashed the same way as email confirmation tokens.
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::{Connection, PgConnection, PgPool};
+use time::Duration;
+use crate::audit;
+use crate::constants::{email_templates, job_kinds, route_paths, strings};
+use crate::email_outbox;
+use crate::email_verification::{generate_token, hash_token};
+use crate::emailer::{Email, EmailTemplate};
+use crate::jobs::Job;
+use crate::startup::AppState;
+
+pub const TOKEN_LIFETIME: Duration = Duration::hours(1);
+
+#[derive(Debug, Serialize)]
+pub struct PasswordResetEmail {
+    pub reset_link: String,
+    pub expires_in: String,
+}
+
+impl EmailTemplate for PasswordResetEmail {
+    const NAME: &'static str = email_templates::PASSWORD_RESET;
+
+    fn sample() -> Self {
+        Self {
+            reset_link: format!("https://example.com{}?token=sample", route_paths::RESET_PASSWORD),
+            expires_in: format!("{} minutes", TOKEN_LIFETIME.whole_minutes()),
+        }
+    }
+}
+
+/// Creates a reset token for the user, replacing any unused ones they were
+/// sent before. Returns the token to put in the link.
+pub async fn create_token(connection: &mut PgConnection, user_id: uuid::Uuid) -> Result<String, sqlx::Error> {
+    let token = generate_token();
+    let mut transaction = connection.begin().await?;
+
+    sqlx::query("DELETE FROM password_reset_tokens WHERE user_id = $1 AND used_at IS NULL")
+        .bind(user_id)
+        .execute(&mut *transaction)
+        .await?;
+    audit::record(&mut *transaction, user_id, audit::PASSWORD_RESET, None).await?;
+    sqlx::query(
+        "INSERT INTO password_reset_tokens (token_hash, user_id, expires_at)
+        VALUES ($1, $2, NOW() + make_interval(secs => $3))"
+    )
+        .bind(hash_token(&token))
+        .bind(user_id)
+        .bind(TOKEN_LIFETIME.as_seconds_f64())
+        .execute(&mut *transaction)
+        .await?;
+
+    transaction.commit().await?;
+    Ok(token)
+}
+
+/// Whether the token can still be used, so the form isn't shown for dead links.
+pub async fn is_valid(db: &PgPool, token: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar(
+        "SELECT EXISTS (
+            SELECT 1 FROM password_reset_tokens
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+        )"
+    )
+        .bind(hash_token(token))
+        .fetch_one(db)
+        .await
+}
+
+/// Uses up the token and sets the user's new password hash. Returns false
+/// when the token is unknown, used or expired.
+///
+/// Sessions are tied to the password hash, so this logs the user out everywhere.
+pub async fn reset_password(db: &PgPool, token: &str, password_hash: &str) -> Result<bool, sqlx::Error> {
+    let mut transaction = db.begin().await?;
+
+    let user_id: Option<uuid::Uuid> = sqlx::query_scalar(
+        "UPDATE password_reset_tokens SET used_at = NOW()
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+        RETURNING user_id"
+    )
+        .bind(hash_token(token))
+        .fetch_optional(&mut *transaction)
+        .await?;
+    let user_id = match user_id {
+        Some(user_id) => user_id,
+        None => return Ok(false),
+    };
+
+    sqlx::query("UPDATE users SET password_hash = $1 WHERE id = $2")
+        .bind(password_hash)
+        .bind(user_id)
+        .execute(&mut *transaction)
+        .await?;
+    sqlx::query("DELETE FROM password_reset_tokens WHERE user_id = $1 AND used_at IS NULL")
+        .bind(user_id)
+        .execute(&mut *transaction)
+        .await?;
+
+    transaction.commit().await?;
+    Ok(true)
+}
+
+/// Queues a reset link if `email` belongs to a user, and does nothing
+/// otherwise, so callers can't tell the difference.
+pub async fn send_reset_link(state: &AppState, email: &str) -> Result<(), anyhow::Error> {
+    let user_id: Option<uuid::Uuid> = sqlx::query_scalar("SELECT id FROM users WHERE email = $1 AND disabled_at IS NULL")
+        .bind(email)
+        .fetch_optional(&state.db)
+        .await?;
+    let user_id = match user_id {
+        Some(user_id) => user_id,
+        None => return Ok(()),
+    };
+
+    let mut transaction = state.db.begin().await?;
+    let token = create_token(&mut transaction, user_id).await?;
+    let reset = PasswordResetEmail {
+        reset_link: format!("{}{}?token={}", state.base_url, route_paths::RESET_PASSWORD, token),
+        expires_in: format!("{} minutes", TOKEN_LIFETIME.whole_minutes()),
+    };
+    let message = Email::render(
+        &state.tera,
+        &state.email_settings.branding,
+        email,
+        strings::PASSWORD_RESET_EMAIL_SUBJECT,
+        &reset,
+    )?;
+    email_outbox::enqueue(&mut *transaction, &message).await?;
+
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Sends a reset link for a forgot password request. Queued whether or not
+/// the address has an account, so the response takes the same time.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SendResetLink {
+    pub email: String,
+}
+
+#[async_trait]
+impl Job for SendResetLink {
+    const KIND: &'static str = job_kinds::SEND_RESET_LINK;
+    // The link is no use once it would have expired
+    const MAX_ATTEMPTS: i32 = 3;
+
+    async fn run(&self, state: &AppState) -> Result<(), anyhow::Error> {
+        send_reset_link(state, &self.email).await
+    }
+}
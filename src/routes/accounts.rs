@@ -0,0 +1,265 @@
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Redirect},
+    routing::{get, post},
+    Form, Router,
+};
+use axum::Extension;
+use axum::response::Html;
+use axum_login::login_required;
+use axum_messages::Messages;
+use serde::{Deserialize, Serialize};
+use crate::startup::AppState;
+use crate::template_helpers::{flash_messages, render_content, RenderTemplateParams};
+use crate::utils::e500;
+use crate::accounts::{self, Account};
+use crate::evaluations;
+use crate::domain::{AccountDetails, AccountType, EvaluationRules};
+
+use crate::user::{AuthSession, Backend};
+use crate::constants::{
+    html_templates,
+    route_paths,
+    strings,
+};
+
+const ACCOUNT_TYPES: [&str; 4] = ["sim", "live", "evaluation", "funded"];
+
+#[derive(Debug, Deserialize)]
+pub struct AccountForm {
+    pub display_name: String,
+    pub account_type: String,
+    pub starting_balance: String,
+    pub currency: String,
+}
+
+impl TryFrom<AccountForm> for AccountDetails {
+    type Error = String;
+
+    fn try_from(value: AccountForm) -> Result<Self, Self::Error> {
+        AccountDetails::parse(
+            &value.display_name,
+            &value.account_type,
+            &value.starting_balance,
+            &value.currency,
+        )
+    }
+}
+
+/// Evaluation rules as entered, also used to fill in the form.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct EvaluationRulesForm {
+    pub profit_target: String,
+    pub max_drawdown: String,
+    pub drawdown_type: String,
+    pub daily_loss_limit: String,
+    pub min_trading_days: String,
+    pub consistency_percent: String,
+}
+
+impl TryFrom<EvaluationRulesForm> for EvaluationRules {
+    type Error = String;
+
+    fn try_from(value: EvaluationRulesForm) -> Result<Self, Self::Error> {
+        EvaluationRules::parse(
+            &value.profit_target,
+            &value.max_drawdown,
+            &value.drawdown_type,
+            &value.daily_loss_limit,
+            &value.min_trading_days,
+            &value.consistency_percent,
+        )
+    }
+}
+
+impl From<&EvaluationRules> for EvaluationRulesForm {
+    fn from(rules: &EvaluationRules) -> Self {
+        let text = |value: Option<f64>| value.map(|v| v.to_string()).unwrap_or_default();
+        EvaluationRulesForm {
+            profit_target: text(rules.profit_target),
+            max_drawdown: text(rules.max_drawdown),
+            drawdown_type: rules.drawdown_type.as_str().to_string(),
+            daily_loss_limit: text(rules.daily_loss_limit),
+            min_trading_days: rules.min_trading_days.map(|days| days.to_string()).unwrap_or_default(),
+            consistency_percent: text(rules.consistency_percent),
+        }
+    }
+}
+
+pub fn routes() -> Router<()> {
+    Router::new()
+        .route(route_paths::ROOT, get(self::get::accounts))
+        .route(route_paths::ACCOUNT, post(self::post::update))
+        .route(
+            route_paths::ACCOUNT_EVALUATION,
+            get(self::get::evaluation).post(self::post::evaluation),
+        )
+        .route_layer(login_required!(Backend, login_url = route_paths::LOGIN))
+}
+
+fn evaluation_path(account_id: uuid::Uuid) -> String {
+    format!("{}/{}/evaluation", route_paths::ACCOUNTS, account_id)
+}
+
+/// Only evaluation accounts have rules. `None` for any other account, or one
+/// that isn't the user's.
+async fn find_evaluation_account(
+    state: &AppState,
+    user_id: uuid::Uuid,
+    account_id: uuid::Uuid,
+) -> Result<Option<Account>, sqlx::Error> {
+    Ok(accounts::find_for_user(&state.db, user_id, account_id)
+        .await?
+        .filter(|account| account.account_type == AccountType::Evaluation.as_str()))
+}
+
+mod post {
+    use super::*;
+
+    pub async fn update(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path(account_id): Path<uuid::Uuid>,
+        Form(form): Form<AccountForm>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+
+        let details = match AccountDetails::try_from(form) {
+            Ok(details) => details,
+            Err(err) => {
+                messages.error(err);
+                return Redirect::to(route_paths::ACCOUNTS).into_response();
+            }
+        };
+
+        match accounts::update(&state.db, user.id, account_id, &details).await {
+            Ok(true) => {
+                messages.success(strings::ACCOUNT_SAVED);
+                Redirect::to(route_paths::ACCOUNTS).into_response()
+            }
+            Ok(false) => StatusCode::NOT_FOUND.into_response(),
+            Err(err) => e500(err).into_response(),
+        }
+    }
+
+    pub async fn evaluation(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path(account_id): Path<uuid::Uuid>,
+        Form(form): Form<EvaluationRulesForm>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+        let account = match find_evaluation_account(&state, user.id, account_id).await {
+            Ok(Some(account)) => account,
+            Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+            Err(err) => return e500(err).into_response(),
+        };
+
+        let rules = match EvaluationRules::try_from(form) {
+            Ok(rules) => rules,
+            Err(err) => {
+                messages.error(err);
+                return Redirect::to(&evaluation_path(account_id)).into_response();
+            }
+        };
+
+        if let Err(err) = evaluations::save_rules(&state.db, account.id, &rules).await {
+            return e500(err).into_response();
+        }
+        let evaluation = match evaluations::evaluate(&state.db, user.id, &account, &state.trading_session).await {
+            Ok(evaluation) => evaluation,
+            Err(err) => return e500(err).into_response(),
+        };
+        if let Some(evaluation) = evaluation {
+            if let Err(err) = evaluations::record_breaches(&state.db, account.id, &evaluation.breaches).await {
+                return e500(err).into_response();
+            }
+        }
+
+        messages.success(strings::EVALUATION_RULES_SAVED);
+        Redirect::to(&evaluation_path(account_id)).into_response()
+    }
+}
+
+mod get {
+    use super::*;
+
+    pub async fn accounts(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+        let user_accounts = match accounts::for_user(&state.db, user.id).await {
+            Ok(accounts) => accounts,
+            Err(err) => return e500(err).into_response(),
+        };
+
+        let mut context = tera::Context::new();
+        context.insert("messages", &flash_messages(messages));
+        context.insert("accounts", &user_accounts);
+        context.insert("account_types", &ACCOUNT_TYPES);
+        match render_content(
+            &RenderTemplateParams::new(html_templates::ACCOUNTS, &state.tera)
+            .with_context(&context)
+        ) {
+            Ok(accounts_template) => Html(accounts_template).into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+
+    pub async fn evaluation(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path(account_id): Path<uuid::Uuid>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+        let account = match find_evaluation_account(&state, user.id, account_id).await {
+            Ok(Some(account)) => account,
+            Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+            Err(err) => return e500(err).into_response(),
+        };
+        let rules = match evaluations::rules(&state.db, account.id).await {
+            Ok(rules) => rules,
+            Err(err) => return e500(err).into_response(),
+        };
+        let evaluation = match evaluations::evaluate(&state.db, user.id, &account, &state.trading_session).await {
+            Ok(evaluation) => evaluation,
+            Err(err) => return e500(err).into_response(),
+        };
+        let breaches = match evaluations::breaches(&state.db, account.id).await {
+            Ok(breaches) => breaches,
+            Err(err) => return e500(err).into_response(),
+        };
+
+        let mut context = tera::Context::new();
+        context.insert("messages", &flash_messages(messages));
+        context.insert("account", &account);
+        context.insert("rules", &rules.as_ref().map(EvaluationRulesForm::from).unwrap_or_default());
+        context.insert("evaluation", &evaluation);
+        context.insert("breaches", &breaches);
+        match render_content(
+            &RenderTemplateParams::new(html_templates::EVALUATION, &state.tera)
+            .with_context(&context)
+        ) {
+            Ok(evaluation_template) => Html(evaluation_template).into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+}
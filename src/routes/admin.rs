@@ -0,0 +1,441 @@
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    response::{IntoResponse, Redirect},
+    routing::{get, post},
+    Form, Router,
+};
+use axum::{middleware, Extension};
+use axum::response::Html;
+use axum_login::login_required;
+use axum_login::tower_sessions::Session;
+use axum_messages::Messages;
+use password_auth::generate_hash;
+use serde::Deserialize;
+use crate::startup::AppState;
+use crate::template_helpers::{flash_messages, render_content, RenderTemplateParams};
+use crate::utils::e500;
+use crate::admin::{self, UserSummary};
+use crate::audit;
+use crate::email_verification::generate_token;
+use crate::impersonation;
+use crate::password_reset;
+use crate::role_required;
+use crate::telemetry;
+
+use crate::user::{self, AuthSession, Backend};
+use crate::constants::{
+    html_templates,
+    roles,
+    route_paths,
+    strings,
+};
+
+const RECENT_EVENTS: i64 = 20;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    q: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RoleForm {
+    pub role: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorRequirementForm {
+    pub required: bool,
+}
+
+pub fn routes() -> Router<()> {
+    Router::new()
+        .route(route_paths::ROOT, get(self::get::users))
+        .route(route_paths::ADMIN_USER, get(self::get::user))
+        .route(route_paths::ADMIN_GRANT_ROLE, post(self::post::grant_role))
+        .route(route_paths::ADMIN_REVOKE_ROLE, post(self::post::revoke_role))
+        .route(route_paths::ADMIN_DISABLE_USER, post(self::post::disable))
+        .route(route_paths::ADMIN_ENABLE_USER, post(self::post::enable))
+        .route(route_paths::ADMIN_DELETE_USER, post(self::post::delete))
+        .route(route_paths::ADMIN_RESET_PASSWORD, post(self::post::reset_password))
+        .route(route_paths::ADMIN_IMPERSONATE, post(self::post::impersonate))
+        .route(route_paths::ADMIN_RESET_TWO_FACTOR, post(self::post::reset_two_factor))
+        .route(route_paths::ADMIN_ROLES, get(self::get::roles))
+        .route(route_paths::ADMIN_ROLE_TWO_FACTOR, post(self::post::two_factor_requirement))
+        .route_layer(role_required!(roles::ADMIN))
+        .route_layer(middleware::from_fn(impersonation::forbid))
+        // While impersonating, the admin is logged in as someone without the role
+        .route(route_paths::STOP_IMPERSONATING, post(self::post::stop_impersonating))
+        .route_layer(login_required!(Backend, login_url = route_paths::LOGIN))
+}
+
+fn user_path(user_id: uuid::Uuid) -> String {
+    format!("{}/users/{}", route_paths::ADMIN, user_id)
+}
+
+fn roles_path() -> String {
+    format!("{}{}", route_paths::ADMIN, route_paths::ADMIN_ROLES)
+}
+
+/// Loads the user an admin action is for. Some actions would lock the admin
+/// out, so they can't be done to their own account.
+async fn find_target(
+    state: &AppState,
+    admin_id: uuid::Uuid,
+    user_id: uuid::Uuid,
+    allow_self: bool,
+    messages: &Messages,
+) -> Result<UserSummary, axum::response::Response> {
+    let target = match admin::find_user(&state.db, user_id).await {
+        Ok(Some(target)) => target,
+        Ok(None) => return Err(StatusCode::NOT_FOUND.into_response()),
+        Err(err) => return Err(e500(err).into_response()),
+    };
+    if !allow_self && target.id == admin_id {
+        messages.clone().error(strings::NOT_ON_YOUR_OWN_ACCOUNT);
+        return Err(Redirect::to(&user_path(user_id)).into_response());
+    }
+    Ok(target)
+}
+
+mod post {
+    use super::*;
+
+    pub async fn grant_role(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path(user_id): Path<uuid::Uuid>,
+        Form(form): Form<RoleForm>,
+    ) -> impl IntoResponse {
+        let admin_user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+        let target = match find_target(&state, admin_user.id, user_id, true, &messages).await {
+            Ok(target) => target,
+            Err(response) => return response,
+        };
+
+        match admin::grant_role(&state.db, admin_user.id, target.id, &form.role).await {
+            Ok(true) => messages.success(strings::ROLE_GRANTED),
+            Ok(false) => messages.error(strings::UNKNOWN_ROLE),
+            Err(err) => return e500(err).into_response(),
+        };
+        Redirect::to(&user_path(target.id)).into_response()
+    }
+
+    pub async fn revoke_role(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path(user_id): Path<uuid::Uuid>,
+        Form(form): Form<RoleForm>,
+    ) -> impl IntoResponse {
+        let admin_user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+        // Admins can drop other roles of their own, but not the one that lets them back in
+        let allow_self = form.role != roles::ADMIN;
+        let target = match find_target(&state, admin_user.id, user_id, allow_self, &messages).await {
+            Ok(target) => target,
+            Err(response) => return response,
+        };
+
+        if let Err(err) = admin::revoke_role(&state.db, admin_user.id, target.id, &form.role).await {
+            return e500(err).into_response();
+        }
+        messages.success(strings::ROLE_REVOKED);
+        Redirect::to(&user_path(target.id)).into_response()
+    }
+
+    pub async fn disable(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path(user_id): Path<uuid::Uuid>,
+    ) -> impl IntoResponse {
+        set_disabled(auth_session, state, messages, user_id, true).await
+    }
+
+    pub async fn enable(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path(user_id): Path<uuid::Uuid>,
+    ) -> impl IntoResponse {
+        set_disabled(auth_session, state, messages, user_id, false).await
+    }
+
+    async fn set_disabled(
+        auth_session: AuthSession,
+        state: AppState,
+        messages: Messages,
+        user_id: uuid::Uuid,
+        disabled: bool,
+    ) -> axum::response::Response {
+        let admin_user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+        let target = match find_target(&state, admin_user.id, user_id, false, &messages).await {
+            Ok(target) => target,
+            Err(response) => return response,
+        };
+
+        if let Err(err) = admin::set_disabled(&state.db, admin_user.id, target.id, disabled).await {
+            return e500(err).into_response();
+        }
+        messages.success(if disabled { strings::USER_DISABLED } else { strings::USER_ENABLED });
+        Redirect::to(&user_path(target.id)).into_response()
+    }
+
+    pub async fn delete(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path(user_id): Path<uuid::Uuid>,
+    ) -> impl IntoResponse {
+        let admin_user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+        let target = match find_target(&state, admin_user.id, user_id, false, &messages).await {
+            Ok(target) => target,
+            Err(response) => return response,
+        };
+
+        let screenshots = match admin::delete_user(&state.db, admin_user.id, &target).await {
+            Ok(screenshots) => screenshots,
+            Err(err) => return e500(err).into_response(),
+        };
+        for file_name in screenshots {
+            if let Err(err) = tokio::fs::remove_file(state.screenshot_directory.join(&file_name)).await {
+                tracing::warn!(file_name, error = %err, "Failed to remove a deleted user's screenshot");
+            }
+        }
+        messages.success(strings::USER_DELETED);
+        Redirect::to(route_paths::ADMIN).into_response()
+    }
+
+    pub async fn reset_password(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path(user_id): Path<uuid::Uuid>,
+    ) -> impl IntoResponse {
+        let admin_user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+        let target = match find_target(&state, admin_user.id, user_id, false, &messages).await {
+            Ok(target) => target,
+            Err(response) => return response,
+        };
+
+        let password_hash = match telemetry::spawn_blocking_with_tracing(|| generate_hash(generate_token())).await {
+            Ok(hash) => hash,
+            Err(err) => return e500(err).into_response(),
+        };
+        if let Err(err) = admin::force_password_reset(&state.db, admin_user.id, target.id, &password_hash).await {
+            return e500(err).into_response();
+        }
+        if let Err(err) = password_reset::send_reset_link(&state, &target.email).await {
+            return e500(err).into_response();
+        }
+        messages.success(strings::PASSWORD_RESET_FORCED);
+        Redirect::to(&user_path(target.id)).into_response()
+    }
+
+    pub async fn reset_two_factor(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path(user_id): Path<uuid::Uuid>,
+    ) -> impl IntoResponse {
+        let admin_user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+        let target = match find_target(&state, admin_user.id, user_id, false, &messages).await {
+            Ok(target) => target,
+            Err(response) => return response,
+        };
+
+        if let Err(err) = admin::reset_two_factor(&state.db, admin_user.id, target.id).await {
+            return e500(err).into_response();
+        }
+        messages.success(strings::TWO_FACTOR_RESET);
+        Redirect::to(&user_path(target.id)).into_response()
+    }
+
+    pub async fn two_factor_requirement(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path(role): Path<String>,
+        Form(form): Form<TwoFactorRequirementForm>,
+    ) -> impl IntoResponse {
+        let admin_user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+
+        match admin::set_two_factor_required(&state.db, admin_user.id, &role, form.required).await {
+            Ok(true) => messages.success(strings::TWO_FACTOR_REQUIREMENT_SAVED),
+            Ok(false) => return StatusCode::NOT_FOUND.into_response(),
+            Err(err) => return e500(err).into_response(),
+        };
+        Redirect::to(&roles_path()).into_response()
+    }
+
+    pub async fn impersonate(
+        mut auth_session: AuthSession,
+        session: Session,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path(user_id): Path<uuid::Uuid>,
+    ) -> impl IntoResponse {
+        let admin_user = match auth_session.user.clone() {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+        // Starting again would replace the admin remembered in the session
+        if impersonation::impersonator(&session).await.is_some() {
+            messages.error(strings::ALREADY_IMPERSONATING);
+            return Redirect::to(route_paths::ROOT).into_response();
+        }
+        let target = match find_target(&state, admin_user.id, user_id, false, &messages).await {
+            Ok(target) => target,
+            Err(response) => return response,
+        };
+        let target = match user::find_by_id(&state.db, target.id).await {
+            Ok(Some(target)) => target,
+            Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+            Err(err) => return e500(err).into_response(),
+        };
+        if target.is_disabled() {
+            messages.error(strings::CANNOT_IMPERSONATE_DISABLED);
+            return Redirect::to(&user_path(target.id)).into_response();
+        }
+        // Admins could use each other's accounts to act with the other's name
+        if target.has_role(roles::ADMIN) {
+            messages.error(strings::CANNOT_IMPERSONATE_ADMIN);
+            return Redirect::to(&user_path(target.id)).into_response();
+        }
+
+        if let Err(err) = impersonation::start(&mut auth_session, &session, &state.db, &admin_user, &target).await {
+            return e500(err).into_response();
+        }
+        Redirect::to(route_paths::ROOT).into_response()
+    }
+
+    pub async fn stop_impersonating(
+        mut auth_session: AuthSession,
+        session: Session,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+    ) -> impl IntoResponse {
+        let impersonated_id = auth_session.user.as_ref().map(|user| user.id);
+        match impersonation::stop(&mut auth_session, &session, &state.db).await {
+            Ok(true) => {},
+            Ok(false) => return Redirect::to(route_paths::ROOT).into_response(),
+            Err(err) => return e500(err).into_response(),
+        }
+
+        messages.success(strings::IMPERSONATION_STOPPED);
+        match impersonated_id {
+            Some(user_id) => Redirect::to(&user_path(user_id)).into_response(),
+            None => Redirect::to(route_paths::ADMIN).into_response(),
+        }
+    }
+}
+
+mod get {
+    use super::*;
+
+    pub async fn users(
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Query(SearchQuery { q }): Query<SearchQuery>,
+    ) -> impl IntoResponse {
+        let users = match admin::search_users(&state.db, q.trim()).await {
+            Ok(users) => users,
+            Err(err) => return e500(err).into_response(),
+        };
+
+        let mut context = tera::Context::new();
+        context.insert("messages", &flash_messages(messages));
+        context.insert("q", &q);
+        context.insert("users", &users);
+        match render_content(
+            &RenderTemplateParams::new(html_templates::ADMIN_USERS, &state.tera)
+            .with_context(&context)
+        ) {
+            Ok(users_template) => Html(users_template).into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+
+    pub async fn user(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path(user_id): Path<uuid::Uuid>,
+    ) -> impl IntoResponse {
+        let admin_user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+        let target = match admin::find_user(&state.db, user_id).await {
+            Ok(Some(target)) => target,
+            Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+            Err(err) => return e500(err).into_response(),
+        };
+        let role_names = match admin::role_names(&state.db).await {
+            Ok(role_names) => role_names,
+            Err(err) => return e500(err).into_response(),
+        };
+        let events = match audit::recent_for_user(&state.db, target.id, RECENT_EVENTS).await {
+            Ok(events) => events,
+            Err(err) => return e500(err).into_response(),
+        };
+
+        let mut context = tera::Context::new();
+        context.insert("messages", &flash_messages(messages));
+        context.insert("user", &target);
+        context.insert("is_self", &(target.id == admin_user.id));
+        context.insert("role_names", &role_names);
+        context.insert("events", &events);
+        match render_content(
+            &RenderTemplateParams::new(html_templates::ADMIN_USER, &state.tera)
+            .with_context(&context)
+        ) {
+            Ok(user_template) => Html(user_template).into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+
+    pub async fn roles(
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+    ) -> impl IntoResponse {
+        let roles = match admin::roles(&state.db).await {
+            Ok(roles) => roles,
+            Err(err) => return e500(err).into_response(),
+        };
+
+        let mut context = tera::Context::new();
+        context.insert("messages", &flash_messages(messages));
+        context.insert("roles", &roles);
+        match render_content(
+            &RenderTemplateParams::new(html_templates::ADMIN_ROLES, &state.tera)
+            .with_context(&context)
+        ) {
+            Ok(roles_template) => Html(roles_template).into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+}
@@ -0,0 +1,562 @@
+use axum::{
+    extract::{
+        rejection::{JsonRejection, PathRejection},
+        FromRequest, FromRequestParts,
+    },
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use axum::Extension;
+use axum_extra::extract::QueryRejection;
+use serde::{Deserialize, Serialize};
+use time::Date;
+use utoipa::{
+    openapi::security::{Http, HttpAuthScheme, SecurityScheme},
+    IntoParams, Modify, OpenApi, ToSchema,
+};
+use crate::accounts::{self, Account};
+use crate::api_tokens::ApiUser;
+use crate::domain::{ApiScope, ExecutionEvent, NewExecution, Pagination};
+use crate::executions::{self, Execution};
+use crate::journal::{self, TagSummary};
+use crate::jobs;
+use crate::startup::AppState;
+use crate::stats_cache;
+use crate::stats::{daily_summaries, Breakdown, BreakdownRow, DailyEquityPoint, DaySummary, EquityCurve, EquityPoint, PerformanceStats};
+use crate::trades::{self, RebuildTrades, Trade, TradeFilter, TradeFilterQuery};
+use crate::utils::{ApiError, ApiErrorBody, ApiErrorDetail};
+
+use crate::constants::{route_paths, strings};
+
+/// Most executions an add-on can send at once. Fills are sent as they happen,
+/// so anything bigger belongs in a CSV import.
+const MAX_EXECUTION_BATCH: usize = 100;
+
+/// JSON over the user's data, for notebooks, scripts and add-ons. Every route
+/// takes an API token or the cookie session through `ApiUser`, and every
+/// failure, including unknown paths, is an `ApiError`.
+pub fn routes() -> Router<()> {
+    Router::new()
+        .route(route_paths::API_OPENAPI, get(self::get::openapi))
+        .route(
+            route_paths::API_EXECUTIONS,
+            get(self::get::executions).post(self::post::executions),
+        )
+        .route(route_paths::API_TRADES, get(self::get::trades))
+        .route(route_paths::API_TRADE, get(self::get::trade))
+        .route(route_paths::API_ACCOUNTS, get(self::get::accounts))
+        .route(route_paths::API_ACCOUNT, get(self::get::account))
+        .route(route_paths::API_TAGS, get(self::get::tags))
+        .route(route_paths::API_STATS, get(self::get::stats))
+        .route(route_paths::API_STATS_EQUITY, get(self::get::equity))
+        .route(route_paths::API_STATS_DAILY, get(self::get::daily))
+        .route(route_paths::API_STATS_BREAKDOWNS, get(self::get::breakdowns))
+        .fallback(|| async { ApiError::not_found("There is no such API endpoint.") })
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "TradeSalsa API", description = "Trades, executions and statistics from TradeSalsa."),
+    servers((url = "/api/v1")),
+    paths(
+        get::executions,
+        post::executions,
+        get::trades,
+        get::trade,
+        get::accounts,
+        get::account,
+        get::tags,
+        get::stats,
+        get::equity,
+        get::daily,
+        get::breakdowns,
+    ),
+    components(schemas(
+        ExecutionPage,
+        TradePage,
+        AccountList,
+        TagList,
+        DailyStatsList,
+        BreakdownList,
+        PageInfo,
+        ExecutionBatch,
+        ExecutionEvent,
+        IngestReport,
+        Execution,
+        Trade,
+        Account,
+        TagSummary,
+        PerformanceStats,
+        EquityCurve,
+        EquityPoint,
+        DailyEquityPoint,
+        DailyStats,
+        DaySummary,
+        Breakdown,
+        BreakdownRow,
+        ApiErrorBody,
+        ApiErrorDetail,
+    )),
+    modifiers(&BearerToken),
+    security(("bearer_token" = [])),
+)]
+pub struct ApiDoc;
+
+struct BearerToken;
+
+impl Modify for BearerToken {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme("bearer_token", SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)));
+        }
+    }
+}
+
+/// `axum::extract::Path` with an `ApiError` rejection.
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(ApiError))]
+pub struct ApiPath<T>(T);
+
+/// `axum_extra::extract::Query` with an `ApiError` rejection.
+#[derive(FromRequestParts)]
+#[from_request(via(axum_extra::extract::Query), rejection(ApiError))]
+pub struct ApiQuery<T>(T);
+
+/// `axum::Json` with an `ApiError` rejection.
+#[derive(FromRequest)]
+#[from_request(via(axum::Json), rejection(ApiError))]
+pub struct ApiJson<T>(T);
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::bad_request(rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError::bad_request(rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::bad_request(rejection.to_string())
+    }
+}
+
+/// Which page of a list to return. Extracted separately from
+/// `TradeFilterQuery`, which can't be flattened with its repeated `account` keys.
+#[derive(Debug, Clone, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PageQuery {
+    /// Starts at 1
+    pub page: Option<String>,
+    /// 1 to 500, 50 by default
+    pub per_page: Option<String>,
+}
+
+impl TryFrom<PageQuery> for Pagination {
+    type Error = String;
+
+    fn try_from(value: PageQuery) -> Result<Self, Self::Error> {
+        Pagination::parse(value.page.as_deref(), value.per_page.as_deref())
+    }
+}
+
+/// A page of a list and where it sits in the whole list.
+#[derive(Debug, Serialize, ToSchema)]
+#[aliases(ExecutionPage = Page<Execution>, TradePage = Page<Trade>)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub pagination: PageInfo,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PageInfo {
+    pub page: i64,
+    pub per_page: i64,
+    /// How many items match the filter across every page
+    pub total: i64,
+    pub total_pages: i64,
+}
+
+impl<T> Page<T> {
+    fn new(data: Vec<T>, pagination: Pagination, total: i64) -> Self {
+        Page {
+            data,
+            pagination: PageInfo {
+                page: pagination.page,
+                per_page: pagination.per_page,
+                total,
+                total_pages: pagination.total_pages(total),
+            },
+        }
+    }
+}
+
+/// A whole list, for lists short enough not to need pages.
+#[derive(Debug, Serialize, ToSchema)]
+#[aliases(
+    AccountList = List<Account>,
+    TagList = List<TagSummary>,
+    DailyStatsList = List<DailyStats>,
+    BreakdownList = List<Breakdown>,
+)]
+pub struct List<T> {
+    pub data: Vec<T>,
+}
+
+/// The statistics of one trading day.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DailyStats {
+    pub date: Date,
+    #[serde(flatten)]
+    pub summary: DaySummary,
+}
+
+/// Executions sent by an add-on as they happen. A batch of one is fine.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ExecutionBatch {
+    pub executions: Vec<ExecutionEvent>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct IngestReport {
+    pub received: usize,
+    /// Executions that weren't stored before
+    pub inserted: u64,
+    /// Executions that were already stored, which are left as they were
+    pub duplicates: u64,
+}
+
+fn parse_query(filter: TradeFilterQuery, page: Option<PageQuery>) -> Result<(TradeFilter, Pagination), ApiError> {
+    let filter = TradeFilter::try_from(filter).map_err(ApiError::bad_request)?;
+    let pagination = Pagination::try_from(page.unwrap_or_default()).map_err(ApiError::bad_request)?;
+    Ok((filter, pagination))
+}
+
+async fn filtered_trades(state: &AppState, api_user: &ApiUser, filter: TradeFilterQuery) -> Result<Vec<Trade>, ApiError> {
+    let (filter, _) = parse_query(filter, None)?;
+    trades::for_user(&state.db, api_user.user.id, &filter, &state.trading_session)
+        .await
+        .map_err(ApiError::internal)
+}
+
+mod post {
+    use super::*;
+
+    /// Stores executions as they happen and queues a rebuild of the trades they
+    /// belong to
+    #[utoipa::path(
+        post,
+        path = "/executions",
+        tag = "executions",
+        request_body = ExecutionBatch,
+        responses(
+            (status = 202, description = "The executions are stored and their trades are rebuilt shortly. Sending them again changes nothing.", body = IngestReport),
+            (status = 400, description = "Invalid executions. None of the batch is stored.", body = ApiErrorBody),
+            (status = 401, description = "Missing or invalid token", body = ApiErrorBody),
+            (status = 403, description = "A read only token, or an unconfirmed email address", body = ApiErrorBody),
+        ),
+    )]
+    pub async fn executions(
+        api_user: ApiUser,
+        Extension(state): Extension<AppState>,
+        ApiJson(batch): ApiJson<ExecutionBatch>,
+    ) -> Result<(StatusCode, Json<IngestReport>), ApiError> {
+        api_user.require(ApiScope::Write)?;
+        let user = api_user.user;
+        if !user.is_verified() {
+            return Err(ApiError::forbidden(strings::EMAIL_NOT_VERIFIED));
+        }
+        if batch.executions.is_empty() || batch.executions.len() > MAX_EXECUTION_BATCH {
+            return Err(ApiError::bad_request(format!(
+                "Send between 1 and {} executions at a time.",
+                MAX_EXECUTION_BATCH
+            )));
+        }
+
+        let received = batch.executions.len();
+        let new_executions = batch
+            .executions
+            .into_iter()
+            .enumerate()
+            .map(|(index, event)| {
+                NewExecution::from_event(event).map_err(|err| format!("executions[{}]: {}", index, err))
+            })
+            .collect::<Result<Vec<NewExecution>, String>>()
+            .map_err(ApiError::bad_request)?;
+
+        let mut streams: Vec<(String, String)> = new_executions
+            .iter()
+            .map(|execution| (execution.account.clone(), execution.instrument.clone()))
+            .collect();
+        streams.sort_unstable();
+        streams.dedup();
+        let mut names: Vec<&str> = streams.iter().map(|(account, _)| account.as_str()).collect();
+        names.dedup();
+
+        // Only the affected pairs are rebuilt, by a job queued with the
+        // executions, so a batch never waits on a rebuild
+        let mut transaction = state.db.begin().await.map_err(ApiError::internal)?;
+        let inserted = executions::insert_executions(&mut transaction, user.id, &new_executions)
+            .await
+            .map_err(ApiError::internal)?;
+        if inserted > 0 {
+            let rebuild = RebuildTrades { user_id: user.id, streams: Some(streams.clone()) };
+            jobs::enqueue(&mut *transaction, &rebuild).await.map_err(ApiError::internal)?;
+        }
+        transaction.commit().await.map_err(ApiError::internal)?;
+        tracing::info!(user_id = %user.id, received, inserted, "Received live executions");
+        accounts::ensure_exist(&state.db, user.id, &names)
+            .await
+            .map_err(ApiError::internal)?;
+
+        Ok((StatusCode::ACCEPTED, Json(IngestReport {
+            received,
+            inserted,
+            duplicates: received as u64 - inserted,
+        })))
+    }
+}
+
+mod get {
+    use super::*;
+
+    /// The OpenAPI spec of this API, public so tools can fetch it
+    pub async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+        Json(ApiDoc::openapi())
+    }
+
+    /// The user's executions, oldest first
+    #[utoipa::path(
+        get,
+        path = "/executions",
+        tag = "executions",
+        params(TradeFilterQuery, PageQuery),
+        responses(
+            (status = 200, description = "A page of executions. A tag matches the executions of trades with that tag.", body = ExecutionPage),
+            (status = 400, description = "Invalid filter or page", body = ApiErrorBody),
+            (status = 401, description = "Missing or invalid token", body = ApiErrorBody),
+        ),
+    )]
+    pub async fn executions(
+        api_user: ApiUser,
+        Extension(state): Extension<AppState>,
+        ApiQuery(filter): ApiQuery<TradeFilterQuery>,
+        ApiQuery(page): ApiQuery<PageQuery>,
+    ) -> Result<Json<Page<Execution>>, ApiError> {
+        let (filter, pagination) = parse_query(filter, Some(page))?;
+        let (executions, total) =
+            executions::page_for_user(&state.db, api_user.user.id, &filter, &state.trading_session, &pagination)
+                .await
+                .map_err(ApiError::internal)?;
+        Ok(Json(Page::new(executions, pagination, total)))
+    }
+
+    /// The user's trades, in the order they were closed
+    #[utoipa::path(
+        get,
+        path = "/trades",
+        tag = "trades",
+        params(TradeFilterQuery, PageQuery),
+        responses(
+            (status = 200, description = "A page of trades", body = TradePage),
+            (status = 400, description = "Invalid filter or page", body = ApiErrorBody),
+            (status = 401, description = "Missing or invalid token", body = ApiErrorBody),
+        ),
+    )]
+    pub async fn trades(
+        api_user: ApiUser,
+        Extension(state): Extension<AppState>,
+        ApiQuery(filter): ApiQuery<TradeFilterQuery>,
+        ApiQuery(page): ApiQuery<PageQuery>,
+    ) -> Result<Json<Page<Trade>>, ApiError> {
+        let (filter, pagination) = parse_query(filter, Some(page))?;
+        let (trades, total) =
+            trades::page_for_user(&state.db, api_user.user.id, &filter, &state.trading_session, &pagination)
+                .await
+                .map_err(ApiError::internal)?;
+        Ok(Json(Page::new(trades, pagination, total)))
+    }
+
+    /// One of the user's trades
+    #[utoipa::path(
+        get,
+        path = "/trades/{id}",
+        tag = "trades",
+        params(("id" = uuid::Uuid, Path, description = "The trade's id")),
+        responses(
+            (status = 200, description = "The trade", body = Trade),
+            (status = 401, description = "Missing or invalid token", body = ApiErrorBody),
+            (status = 404, description = "No such trade", body = ApiErrorBody),
+        ),
+    )]
+    pub async fn trade(
+        api_user: ApiUser,
+        Extension(state): Extension<AppState>,
+        ApiPath(trade_id): ApiPath<uuid::Uuid>,
+    ) -> Result<Json<Trade>, ApiError> {
+        trades::find_for_user(&state.db, api_user.user.id, trade_id, &state.trading_session)
+            .await
+            .map_err(ApiError::internal)?
+            .map(Json)
+            .ok_or_else(|| ApiError::not_found("That trade doesn't exist."))
+    }
+
+    /// The user's accounts
+    #[utoipa::path(
+        get,
+        path = "/accounts",
+        tag = "accounts",
+        responses(
+            (status = 200, description = "Every account", body = AccountList),
+            (status = 401, description = "Missing or invalid token", body = ApiErrorBody),
+        ),
+    )]
+    pub async fn accounts(api_user: ApiUser, Extension(state): Extension<AppState>) -> Result<Json<List<Account>>, ApiError> {
+        let data = accounts::for_user(&state.db, api_user.user.id).await.map_err(ApiError::internal)?;
+        Ok(Json(List { data }))
+    }
+
+    /// One of the user's accounts
+    #[utoipa::path(
+        get,
+        path = "/accounts/{id}",
+        tag = "accounts",
+        params(("id" = uuid::Uuid, Path, description = "The account's id")),
+        responses(
+            (status = 200, description = "The account", body = Account),
+            (status = 401, description = "Missing or invalid token", body = ApiErrorBody),
+            (status = 404, description = "No such account", body = ApiErrorBody),
+        ),
+    )]
+    pub async fn account(
+        api_user: ApiUser,
+        Extension(state): Extension<AppState>,
+        ApiPath(account_id): ApiPath<uuid::Uuid>,
+    ) -> Result<Json<Account>, ApiError> {
+        accounts::find_for_user(&state.db, api_user.user.id, account_id)
+            .await
+            .map_err(ApiError::internal)?
+            .map(Json)
+            .ok_or_else(|| ApiError::not_found("That account doesn't exist."))
+    }
+
+    /// The user's tags and how many trades have each
+    #[utoipa::path(
+        get,
+        path = "/tags",
+        tag = "tags",
+        responses(
+            (status = 200, description = "Every tag", body = TagList),
+            (status = 401, description = "Missing or invalid token", body = ApiErrorBody),
+        ),
+    )]
+    pub async fn tags(api_user: ApiUser, Extension(state): Extension<AppState>) -> Result<Json<List<TagSummary>>, ApiError> {
+        let data = journal::tags_for_user(&state.db, api_user.user.id).await.map_err(ApiError::internal)?;
+        Ok(Json(List { data }))
+    }
+
+    /// Headline statistics of the user's trades
+    #[utoipa::path(
+        get,
+        path = "/stats",
+        tag = "stats",
+        params(TradeFilterQuery),
+        responses(
+            (status = 200, description = "Statistics of the filtered trades", body = PerformanceStats),
+            (status = 400, description = "Invalid filter", body = ApiErrorBody),
+            (status = 401, description = "Missing or invalid token", body = ApiErrorBody),
+        ),
+    )]
+    pub async fn stats(
+        api_user: ApiUser,
+        Extension(state): Extension<AppState>,
+        ApiQuery(filter): ApiQuery<TradeFilterQuery>,
+    ) -> Result<Json<PerformanceStats>, ApiError> {
+        let (filter, _) = parse_query(filter, None)?;
+        let stats = stats_cache::cached(&state, api_user.user.id, "performance", &filter, || async {
+            let trades = trades::for_user(&state.db, api_user.user.id, &filter, &state.trading_session).await?;
+            Ok::<_, sqlx::Error>(PerformanceStats::from_trades(&trades))
+        })
+        .await
+        .map_err(ApiError::internal)?;
+        Ok(Json(stats))
+    }
+
+    /// The equity curve and drawdown of the user's trades, per trade and per day
+    #[utoipa::path(
+        get,
+        path = "/stats/equity",
+        tag = "stats",
+        params(TradeFilterQuery),
+        responses(
+            (status = 200, description = "The equity curve of the filtered trades", body = EquityCurve),
+            (status = 400, description = "Invalid filter", body = ApiErrorBody),
+            (status = 401, description = "Missing or invalid token", body = ApiErrorBody),
+        ),
+    )]
+    pub async fn equity(
+        api_user: ApiUser,
+        Extension(state): Extension<AppState>,
+        ApiQuery(filter): ApiQuery<TradeFilterQuery>,
+    ) -> Result<Json<EquityCurve>, ApiError> {
+        let (filter, _) = parse_query(filter, None)?;
+        let curve = stats_cache::cached(&state, api_user.user.id, "equity", &filter, || async {
+            let trades = trades::for_user(&state.db, api_user.user.id, &filter, &state.trading_session).await?;
+            Ok::<_, sqlx::Error>(EquityCurve::from_trades(&trades))
+        })
+        .await
+        .map_err(ApiError::internal)?;
+        Ok(Json(curve))
+    }
+
+    /// Net PnL, trade count and win rate of every trading day with trades
+    #[utoipa::path(
+        get,
+        path = "/stats/daily",
+        tag = "stats",
+        params(TradeFilterQuery),
+        responses(
+            (status = 200, description = "Every trading day, oldest first", body = DailyStatsList),
+            (status = 400, description = "Invalid filter", body = ApiErrorBody),
+            (status = 401, description = "Missing or invalid token", body = ApiErrorBody),
+        ),
+    )]
+    pub async fn daily(
+        api_user: ApiUser,
+        Extension(state): Extension<AppState>,
+        ApiQuery(filter): ApiQuery<TradeFilterQuery>,
+    ) -> Result<Json<List<DailyStats>>, ApiError> {
+        let trades = filtered_trades(&state, &api_user, filter).await?;
+        let data = daily_summaries(&trades)
+            .into_iter()
+            .map(|(date, summary)| DailyStats { date, summary })
+            .collect();
+        Ok(Json(List { data }))
+    }
+
+    /// Performance by tag, instrument, day of week, hour of entry and duration
+    #[utoipa::path(
+        get,
+        path = "/stats/breakdowns",
+        tag = "stats",
+        params(TradeFilterQuery),
+        responses(
+            (status = 200, description = "Every breakdown, as on the reports page", body = BreakdownList),
+            (status = 400, description = "Invalid filter", body = ApiErrorBody),
+            (status = 401, description = "Missing or invalid token", body = ApiErrorBody),
+        ),
+    )]
+    pub async fn breakdowns(
+        api_user: ApiUser,
+        Extension(state): Extension<AppState>,
+        ApiQuery(filter): ApiQuery<TradeFilterQuery>,
+    ) -> Result<Json<List<Breakdown>>, ApiError> {
+        let trades = filtered_trades(&state, &api_user, filter).await?;
+        Ok(Json(List { data: Breakdown::all(&trades) }))
+    }
+}
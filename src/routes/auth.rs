@@ -7,22 +7,27 @@ use axum::{
 };
 use axum::Extension;
 use axum::response::Html;
+use axum_login::tower_sessions::Session;
 use axum_messages::Messages;
 use serde::Deserialize;
 use crate::startup::AppState;
-use crate::template_helpers::{render_content, RenderTemplateParams};
-use secrecy::Secret;
+use crate::template_helpers::{flash_messages, render_content, RenderTemplateParams};
+use secrecy::{ExposeSecret, Secret};
 use crate::utils::e500;
 use crate::telemetry;
 use password_auth::generate_hash;
 
-use crate::user::{AuthSession, Credentials};
+use crate::user::{self, AuthSession, Credentials, User};
 use crate::domain::{NewUser, UserEmail, UserPassword};
-use crate::emailer;
+use crate::email_verification;
+use crate::jobs;
+use crate::password_reset::{self, SendResetLink};
+use crate::login_throttle::{self, ClientIp, NotifyLockout};
+use crate::two_factor;
 use crate::constants::{
     html_templates,
+    roles,
     route_paths,
-    email_templates,
     strings,
 };
 
@@ -33,6 +38,28 @@ pub struct NextUrl {
     next: Option<String>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ConfirmationQuery {
+    token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ForgotPasswordForm {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordForm {
+    pub token: String,
+    pub password: Secret<String>,
+    pub password_confirmation: Secret<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorForm {
+    pub code: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct RegistrationForm {
     pub email: String,
@@ -58,7 +85,92 @@ pub fn routes() -> Router<()> {
         .route(route_paths::REGISTER, get(self::get::register))
         .route(route_paths::REGISTER, post(self::post::register))
         .route(route_paths::LOGIN, get(self::get::login))
+        .route(route_paths::LOGIN_TWO_FACTOR, get(self::get::two_factor))
+        .route(route_paths::LOGIN_TWO_FACTOR, post(self::post::two_factor))
         .route(route_paths::LOGOUT, get(self::get::logout))
+        .route(route_paths::CONFIRM, get(self::get::confirm))
+        .route(route_paths::RESEND_CONFIRMATION, post(self::post::resend_confirmation))
+        .route(route_paths::FORGOT_PASSWORD, get(self::get::forgot_password))
+        .route(route_paths::FORGOT_PASSWORD, post(self::post::forgot_password))
+        .route(route_paths::RESET_PASSWORD, get(self::get::reset_password))
+        .route(route_paths::RESET_PASSWORD, post(self::post::reset_password))
+}
+
+/// New users start with the basic role. Their confirmation email is queued in
+/// the same transaction, so it's only sent if the user is created.
+async fn create_user(state: &AppState, user_id: uuid::Uuid, email: &str, password_hash: &str) -> Result<(), anyhow::Error> {
+    let mut transaction = state.db.begin().await?;
+
+    sqlx::query("INSERT INTO users (id, email, password_hash) VALUES ($1, $2, $3)")
+        .bind(user_id)
+        .bind(email)
+        .bind(password_hash)
+        .execute(&mut *transaction)
+        .await?;
+    user::assign_role(&mut *transaction, user_id, roles::BASIC).await?;
+    email_verification::queue_confirmation(
+        &mut transaction,
+        state,
+        user_id,
+        email,
+        strings::WELCOME_EMAIL_SUBJECT,
+    ).await?;
+
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// Queues a new confirmation link for a user who lost theirs.
+async fn resend_confirmation(state: &AppState, user: &User) -> Result<(), anyhow::Error> {
+    let mut transaction = state.db.begin().await?;
+    email_verification::queue_confirmation(
+        &mut transaction,
+        state,
+        user.id,
+        &user.email,
+        strings::CONFIRM_EMAIL_SUBJECT,
+    ).await?;
+    transaction.commit().await?;
+    Ok(())
+}
+
+fn too_many_attempts(messages: Messages, wait: time::Duration) {
+    messages.error(format!(
+        "{} {}.",
+        strings::TOO_MANY_LOGIN_ATTEMPTS,
+        login_throttle::describe_wait(wait),
+    ));
+}
+
+/// Keeps a failed password or code counted, telling the owner if it locked
+/// them out.
+async fn record_failed_login(state: &AppState, email: String, ip: &str) -> Result<(), sqlx::Error> {
+    if login_throttle::record_failure(&state.db, &email, ip).await? {
+        jobs::enqueue(&state.db, &NotifyLockout { email }).await?;
+    }
+    Ok(())
+}
+
+/// Logs the user in once they've passed every step.
+async fn complete_login(
+    auth_session: &mut AuthSession,
+    state: &AppState,
+    messages: Messages,
+    user: &User,
+    next: Option<String>,
+) -> axum::response::Response {
+    if let Err(err) = login_throttle::clear(&state.db, &user.email).await {
+        return e500(err).into_response();
+    }
+    if auth_session.login(user).await.is_err() {
+        return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+    if let Err(err) = user::record_login(&state.db, user.id).await {
+        return e500(err).into_response();
+    }
+
+    messages.success(format!("Successfully logged in as {}", user.email));
+    Redirect::to(next.as_deref().unwrap_or(route_paths::ROOT)).into_response()
 }
 
 mod post {
@@ -85,74 +197,196 @@ mod post {
             },
         };
 
-        match sqlx::query(
-            "INSERT INTO users (id, email, password_hash) VALUES ($1, $2, $3) RETURNING id, email, password_hash, created_at, updated_at"
-        )
-            .bind(&user_id)
-            .bind(&new_user.email.email)
-            .bind(&password_hash)
-            .fetch_one(&state.db)
-            .await
-            .map_err(e500) {
-                Ok(user) => user,
-                Err(err) => return err.into_response()
-            };
+        if let Err(err) = create_user(&state, user_id, &new_user.email.email, &password_hash).await {
+            return e500(err).into_response();
+        }
         messages.success(strings::REGISTER_ACCOUNT_SUCCESS);
 
-        let mut context = std::collections::HashMap::new();
-        context.insert("email", "jinius@g.com");
-        context.insert("confirmation_link", "http://example.com/confirm");
-        match emailer::send_email(
-            &new_user.email.email,
-            strings::WELCOME_EMAIL_SUBJECT,
-            email_templates::EMAIL_VERIFICATION,
-            &context,
-            &state.tera,
-            &state.email_settings,
-        ).await.map_err(e500) {
-            Ok(_) => {
-                println!("Email was successfully sent");
-            },
-            Err(err) => {
-                return err.into_response();
-            }
+        Redirect::to(route_paths::ROOT).into_response()
+    }
+
+    pub async fn resend_confirmation(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+        if user.is_verified() {
+            messages.info(strings::EMAIL_ALREADY_CONFIRMED);
+            return Redirect::to(route_paths::ROOT).into_response();
+        }
+
+        if let Err(err) = super::resend_confirmation(&state, &user).await {
+            return e500(err).into_response();
         }
 
+        messages.success(strings::CONFIRMATION_SENT);
         Redirect::to(route_paths::ROOT).into_response()
     }
 
+    pub async fn forgot_password(
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Form(form): Form<ForgotPasswordForm>,
+    ) -> impl IntoResponse {
+        // Queued whether or not the address has an account, so the response
+        // takes the same time either way
+        let job = SendResetLink { email: form.email.trim().to_string() };
+        if let Err(err) = jobs::enqueue(&state.db, &job).await {
+            return e500(err).into_response();
+        }
+
+        messages.info(strings::PASSWORD_RESET_SENT);
+        Redirect::to(route_paths::FORGOT_PASSWORD).into_response()
+    }
+
+    pub async fn reset_password(
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Form(form): Form<ResetPasswordForm>,
+    ) -> impl IntoResponse {
+        let reset_url = format!(
+            "{}?{}",
+            route_paths::RESET_PASSWORD,
+            serde_urlencoded::to_string([("token", &form.token)]).unwrap_or_default(),
+        );
+        if form.password.expose_secret() != form.password_confirmation.expose_secret() {
+            messages.error(strings::PASSWORDS_DO_NOT_MATCH);
+            return Redirect::to(&reset_url).into_response();
+        }
+        let password = match UserPassword::parse(form.password) {
+            Ok(password) => password,
+            Err(err) => {
+                messages.error(err);
+                return Redirect::to(&reset_url).into_response();
+            },
+        };
+        let password_hash = match telemetry::spawn_blocking_with_tracing(move || generate_hash(password)).await {
+            Ok(hash) => hash,
+            Err(err) => return e500(err).into_response(),
+        };
+
+        match password_reset::reset_password(&state.db, &form.token, &password_hash).await {
+            Ok(true) => {
+                messages.success(strings::PASSWORD_RESET);
+                Redirect::to(route_paths::LOGIN).into_response()
+            },
+            Ok(false) => {
+                messages.error(strings::INVALID_RESET_LINK);
+                Redirect::to(route_paths::FORGOT_PASSWORD).into_response()
+            },
+            Err(err) => e500(err).into_response(),
+        }
+    }
+
     pub async fn login(
         mut auth_session: AuthSession,
+        session: Session,
+        Extension(state): Extension<AppState>,
+        ClientIp(client_ip): ClientIp,
         messages: Messages,
         Form(creds): Form<Credentials>,
     ) -> impl IntoResponse {
+        let mut login_url = route_paths::LOGIN.to_string();
+        if let Some(ref next) = creds.next {
+            login_url = format!("{}?next={}", login_url, next);
+        };
+        let ip = client_ip.to_string();
+
+        // Throttled attempts aren't checked at all, so they can't be used to guess
+        match login_throttle::reserve(&state.db, &creds.email, &ip).await {
+            Ok(None) => {},
+            Ok(Some(wait)) => {
+                too_many_attempts(messages, wait);
+                return Redirect::to(&login_url).into_response();
+            },
+            Err(err) => return e500(err).into_response(),
+        }
+
         let user = match auth_session.authenticate(creds.clone()).await {
             Ok(Some(user)) => user,
             Ok(None) => {
+                if let Err(err) = record_failed_login(&state, creds.email, &ip).await {
+                    return e500(err).into_response();
+                }
                 messages.error(strings::INVALID_CREDENTIALS);
-
-                let mut login_url = route_paths::LOGIN.to_string();
-                if let Some(next) = creds.next {
-                    login_url = format!("{}?next={}", login_url, next);
-                };
-
                 return Redirect::to(&login_url).into_response();
             }
             Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
         };
+        if let Err(err) = login_throttle::release(&state.db, &creds.email, &ip).await {
+            return e500(err).into_response();
+        }
 
-        if auth_session.login(&user).await.is_err() {
-            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        // The session isn't logged in until the code is checked too. Failures
+        // are kept until then, so codes are throttled like passwords.
+        if user.two_factor_enabled {
+            if let Err(err) = two_factor::start_login(&session, user.id, creds.next).await {
+                return e500(err).into_response();
+            }
+            return Redirect::to(route_paths::LOGIN_TWO_FACTOR).into_response();
         }
 
-        messages.success(format!("Successfully logged in as {}", user.email));
+        complete_login(&mut auth_session, &state, messages, &user, creds.next).await
+    }
 
-        if let Some(ref next) = creds.next {
-            Redirect::to(next)
-        } else {
-            Redirect::to(route_paths::ROOT)
+    pub async fn two_factor(
+        mut auth_session: AuthSession,
+        session: Session,
+        Extension(state): Extension<AppState>,
+        ClientIp(client_ip): ClientIp,
+        messages: Messages,
+        Form(form): Form<TwoFactorForm>,
+    ) -> impl IntoResponse {
+        let pending = match two_factor::pending_login(&session).await {
+            Some(pending) => pending,
+            None => {
+                messages.error(strings::TWO_FACTOR_LOGIN_EXPIRED);
+                return Redirect::to(route_paths::LOGIN).into_response();
+            },
+        };
+        // The user may have been disabled since entering their password
+        let user = match user::find_by_id(&state.db, pending.user_id).await {
+            Ok(Some(user)) if !user.is_disabled() => user,
+            Ok(_) => {
+                messages.error(strings::TWO_FACTOR_LOGIN_EXPIRED);
+                return Redirect::to(route_paths::LOGIN).into_response();
+            },
+            Err(err) => return e500(err).into_response(),
+        };
+        let ip = client_ip.to_string();
+
+        match login_throttle::reserve(&state.db, &user.email, &ip).await {
+            Ok(None) => {},
+            Ok(Some(wait)) => {
+                too_many_attempts(messages, wait);
+                return Redirect::to(route_paths::LOGIN_TWO_FACTOR).into_response();
+            },
+            Err(err) => return e500(err).into_response(),
+        }
+
+        match two_factor::verify(&state.db, &user, &form.code).await {
+            Ok(true) => {},
+            Ok(false) => {
+                if let Err(err) = record_failed_login(&state, user.email, &ip).await {
+                    return e500(err).into_response();
+                }
+                messages.error(strings::INVALID_TWO_FACTOR_CODE);
+                return Redirect::to(route_paths::LOGIN_TWO_FACTOR).into_response();
+            },
+            Err(err) => return e500(err).into_response(),
+        }
+        if let Err(err) = login_throttle::release(&state.db, &user.email, &ip).await {
+            return e500(err).into_response();
         }
-        .into_response()
+        if let Err(err) = two_factor::finish_login(&session).await {
+            return e500(err).into_response();
+        }
+
+        complete_login(&mut auth_session, &state, messages, &user, pending.next).await
     }
 }
 
@@ -177,13 +411,14 @@ mod get {
 
     pub async fn login(
         Extension(state): Extension<AppState>,
-        _messages: Messages,
+        messages: Messages,
         Query(NextUrl { next }): Query<NextUrl>,
     ) -> impl IntoResponse {
         let mut context = tera::Context::new();
         let boo = "FROM THE LOGIN ROUTE";
         context.insert("boo", &boo);
         context.insert("next", &next);
+        context.insert("messages", &flash_messages(messages));
         match render_content(
             &RenderTemplateParams::new(html_templates::LOGIN, &state.tera)
             .with_context(&context)
@@ -193,6 +428,82 @@ mod get {
         }
     }
 
+    pub async fn two_factor(
+        session: Session,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+    ) -> impl IntoResponse {
+        if two_factor::pending_login(&session).await.is_none() {
+            return Redirect::to(route_paths::LOGIN).into_response();
+        }
+
+        let mut context = tera::Context::new();
+        context.insert("messages", &flash_messages(messages));
+        match render_content(
+            &RenderTemplateParams::new(html_templates::TWO_FACTOR_LOGIN, &state.tera)
+            .with_context(&context)
+        ) {
+            Ok(template) => Html(template).into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+
+    pub async fn confirm(
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Query(ConfirmationQuery { token }): Query<ConfirmationQuery>,
+    ) -> impl IntoResponse {
+        let token = token.unwrap_or_default();
+        match email_verification::confirm(&state.db, &token).await {
+            Ok(Some(_)) => messages.success(strings::EMAIL_CONFIRMED),
+            Ok(None) => messages.error(strings::INVALID_CONFIRMATION_LINK),
+            Err(err) => return e500(err).into_response(),
+        };
+        Redirect::to(route_paths::ROOT).into_response()
+    }
+
+    pub async fn forgot_password(
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+    ) -> impl IntoResponse {
+        let mut context = tera::Context::new();
+        context.insert("messages", &flash_messages(messages));
+        match render_content(
+            &RenderTemplateParams::new(html_templates::FORGOT_PASSWORD, &state.tera)
+            .with_context(&context)
+        ) {
+            Ok(template) => Html(template).into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+
+    pub async fn reset_password(
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Query(ConfirmationQuery { token }): Query<ConfirmationQuery>,
+    ) -> impl IntoResponse {
+        let token = token.unwrap_or_default();
+        match password_reset::is_valid(&state.db, &token).await {
+            Ok(true) => {},
+            Ok(false) => {
+                messages.error(strings::INVALID_RESET_LINK);
+                return Redirect::to(route_paths::FORGOT_PASSWORD).into_response();
+            },
+            Err(err) => return e500(err).into_response(),
+        }
+
+        let mut context = tera::Context::new();
+        context.insert("token", &token);
+        context.insert("messages", &flash_messages(messages));
+        match render_content(
+            &RenderTemplateParams::new(html_templates::RESET_PASSWORD, &state.tera)
+            .with_context(&context)
+        ) {
+            Ok(template) => Html(template).into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+
     pub async fn logout(mut auth_session: AuthSession) -> impl IntoResponse {
         match auth_session.logout().await {
             Ok(_) => Redirect::to(route_paths::ROOT).into_response(),
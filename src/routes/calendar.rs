@@ -0,0 +1,97 @@
+use axum::{
+    response::{IntoResponse, Redirect},
+    routing::get,
+    Router,
+};
+use axum::Extension;
+use axum::response::Html;
+use axum_extra::extract::Query;
+use axum_login::login_required;
+use axum_messages::Messages;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use crate::startup::AppState;
+use crate::template_helpers::{flash_messages, render_content, RenderTemplateParams};
+use crate::utils::e500;
+use crate::stats::CalendarMonth;
+use crate::trades::{self, TradeFilter, TradeFilterQuery};
+
+use crate::user::{AuthSession, Backend};
+use crate::constants::{
+    html_templates,
+    route_paths,
+};
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct CalendarQuery {
+    /// `YYYY-MM`, defaulting to the current month
+    pub month: Option<String>,
+}
+
+pub fn routes() -> Router<()> {
+    Router::new()
+        .route(route_paths::ROOT, get(self::get::calendar))
+        .route_layer(login_required!(Backend, login_url = route_paths::LOGIN))
+}
+
+mod get {
+    use super::*;
+
+    pub async fn calendar(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Query(query): Query<CalendarQuery>,
+        Query(filter_query): Query<TradeFilterQuery>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+
+        let month = match query.month.as_deref().filter(|month| !month.trim().is_empty()) {
+            Some(month) => match CalendarMonth::parse_month(month) {
+                Ok(month) => month,
+                Err(err) => {
+                    messages.error(err);
+                    return Redirect::to(route_paths::CALENDAR).into_response();
+                }
+            },
+            None => OffsetDateTime::now_utc().date(),
+        };
+        // The month picks the dates, only the other filters carry over
+        let filter_query = TradeFilterQuery {
+            from: None,
+            to: None,
+            ..filter_query
+        };
+        let filter = TradeFilter {
+            from: Some(CalendarMonth::first_day(month)),
+            to: Some(CalendarMonth::last_day(month)),
+            ..TradeFilter::try_from(filter_query.clone()).unwrap_or_default()
+        };
+
+        let user_trades = match trades::for_user(&state.db, user.id, &filter, &state.trading_session).await {
+            Ok(trades) => trades,
+            Err(err) => return e500(err).into_response(),
+        };
+        let choices = match trades::filter_choices(&state.db, user.id).await {
+            Ok(choices) => choices,
+            Err(err) => return e500(err).into_response(),
+        };
+
+        let mut context = tera::Context::new();
+        context.insert("messages", &flash_messages(messages));
+        context.insert("calendar", &CalendarMonth::new(month, &user_trades));
+        context.insert("filter", &filter_query);
+        context.insert("filter_params", &filter_query.to_query_string());
+        context.insert("choices", &choices);
+        match render_content(
+            &RenderTemplateParams::new(html_templates::CALENDAR, &state.tera)
+            .with_context(&context)
+        ) {
+            Ok(calendar_template) => Html(calendar_template).into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+}
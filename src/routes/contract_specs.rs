@@ -0,0 +1,155 @@
+use axum::{
+    extract::Path,
+    response::{IntoResponse, Redirect},
+    routing::{get, post},
+    Form, Router,
+};
+use axum::Extension;
+use axum::response::Html;
+use axum_login::{login_required, permission_required, AuthzBackend};
+use axum_messages::Messages;
+use serde::Deserialize;
+use sqlx::{Postgres, Transaction};
+use crate::startup::AppState;
+use crate::template_helpers::{flash_messages, render_content, RenderTemplateParams};
+use crate::utils::e500;
+use crate::contract_specs;
+use crate::jobs;
+use crate::trades::RebuildTradesForRoot;
+use crate::domain::NewContractSpec;
+
+use crate::user::{AuthSession, Backend};
+use crate::constants::{
+    html_templates,
+    permissions,
+    route_paths,
+    strings,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct ContractSpecForm {
+    pub root: String,
+    pub description: String,
+    pub exchange: String,
+    pub point_value: String,
+    pub tick_size: String,
+    pub currency: String,
+}
+
+impl TryFrom<ContractSpecForm> for NewContractSpec {
+    type Error = String;
+
+    fn try_from(value: ContractSpecForm) -> Result<Self, Self::Error> {
+        NewContractSpec::parse(
+            &value.root,
+            &value.description,
+            &value.exchange,
+            &value.point_value,
+            &value.tick_size,
+            &value.currency,
+        )
+    }
+}
+
+pub fn routes() -> Router<()> {
+    // Specs apply to every user's trades, so only some roles can change them
+    Router::new()
+        .route(route_paths::ROOT, post(self::post::save))
+        .route(route_paths::DELETE_CONTRACT_SPEC, post(self::post::delete))
+        .route_layer(permission_required!(Backend, permissions::MANAGE_CONTRACT_SPECS))
+        .route(route_paths::ROOT, get(self::get::contract_specs))
+        .route_layer(login_required!(Backend, login_url = route_paths::LOGIN))
+}
+
+/// Queued in the same transaction as the spec change, so a rolled back change
+/// rebuilds nothing.
+async fn queue_rebuild(transaction: &mut Transaction<'_, Postgres>, root: String) -> Result<(), sqlx::Error> {
+    jobs::enqueue(&mut **transaction, &RebuildTradesForRoot { root }).await
+}
+
+mod post {
+    use super::*;
+
+    pub async fn save(
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Form(form): Form<ContractSpecForm>,
+    ) -> impl IntoResponse {
+        let spec = match NewContractSpec::try_from(form) {
+            Ok(spec) => spec,
+            Err(err) => {
+                messages.error(err);
+                return Redirect::to(route_paths::CONTRACT_SPECS).into_response();
+            }
+        };
+
+        let saved = async {
+            let mut transaction = state.db.begin().await?;
+            contract_specs::upsert(&mut *transaction, &spec).await?;
+            queue_rebuild(&mut transaction, spec.root.clone()).await?;
+            transaction.commit().await
+        };
+        if let Err(err) = saved.await {
+            return e500(err).into_response();
+        }
+
+        messages.success(strings::CONTRACT_SPEC_SAVED);
+        Redirect::to(route_paths::CONTRACT_SPECS).into_response()
+    }
+
+    pub async fn delete(
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path(root): Path<String>,
+    ) -> impl IntoResponse {
+        let deleted = async {
+            let mut transaction = state.db.begin().await?;
+            contract_specs::delete(&mut *transaction, &root).await?;
+            queue_rebuild(&mut transaction, root.clone()).await?;
+            transaction.commit().await
+        };
+        if let Err(err) = deleted.await {
+            return e500(err).into_response();
+        }
+
+        messages.success(strings::CONTRACT_SPEC_DELETED);
+        Redirect::to(route_paths::CONTRACT_SPECS).into_response()
+    }
+}
+
+mod get {
+    use super::*;
+
+    pub async fn contract_specs(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+    ) -> impl IntoResponse {
+        let specs = match contract_specs::all(&state.db).await {
+            Ok(specs) => specs,
+            Err(err) => return e500(err).into_response(),
+        };
+        let can_manage = match auth_session.user {
+            Some(ref user) => auth_session.backend.has_perm(user, permissions::MANAGE_CONTRACT_SPECS.into()).await,
+            None => Ok(false),
+        };
+        let can_manage = match can_manage {
+            Ok(can_manage) => can_manage,
+            Err(err) => return e500(err).into_response(),
+        };
+        let tick_values: Vec<f64> = specs.iter().map(|spec| spec.tick_value()).collect();
+
+        let mut context = tera::Context::new();
+        context.insert("messages", &flash_messages(messages));
+        context.insert("specs", &specs);
+        context.insert("tick_values", &tick_values);
+        context.insert("can_manage", &can_manage);
+        match render_content(
+            &RenderTemplateParams::new(html_templates::CONTRACT_SPECS, &state.tera)
+            .with_context(&context)
+        ) {
+            Ok(contract_specs_template) => Html(contract_specs_template).into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//! src/routes/digests.rs
+//! Unsubscribing from digest emails through the signed link at the bottom of
+//! each one, without logging in. Opening the link only asks for confirmation,
+//! so mail scanners that follow links don't unsubscribe anyone.
+use axum::{
+    extract::Query,
+    http::StatusCode,
+    response::{Html, IntoResponse},
+    routing::get,
+    Form, Router,
+};
+use axum::Extension;
+use serde::{Deserialize, Serialize};
+use crate::digests;
+use crate::domain::DigestKind;
+use crate::startup::AppState;
+use crate::template_helpers::{render_content, RenderTemplateParams};
+use crate::utils::e500;
+use crate::constants::{html_templates, route_paths, strings};
+
+/// The fields of an unsubscribe link, sent back by the confirmation form.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct UnsubscribeParams {
+    user: uuid::Uuid,
+    kind: String,
+    signature: String,
+}
+
+impl UnsubscribeParams {
+    /// The digest to stop sending, if the link was signed by this app.
+    fn verify(&self, state: &AppState) -> Option<DigestKind> {
+        let kind = DigestKind::parse(&self.kind).ok()?;
+        digests::verify_unsubscribe_signature(&state.hmac_secret, self.user, kind, &self.signature)
+            .then_some(kind)
+    }
+}
+
+pub fn routes() -> Router<()> {
+    Router::new()
+        .route(
+            route_paths::DIGEST_UNSUBSCRIBE,
+            get(self::get::unsubscribe).post(self::post::unsubscribe),
+        )
+}
+
+fn render_unsubscribe(state: &AppState, context: &tera::Context) -> axum::response::Response {
+    match render_content(
+        &RenderTemplateParams::new(html_templates::DIGEST_UNSUBSCRIBE, &state.tera)
+        .with_context(context)
+    ) {
+        Ok(template) => Html(template).into_response(),
+        Err(e) => e.into_response()
+    }
+}
+
+fn render_invalid_link(state: &AppState) -> axum::response::Response {
+    let mut context = tera::Context::new();
+    context.insert("error", strings::INVALID_UNSUBSCRIBE_LINK);
+    (StatusCode::BAD_REQUEST, render_unsubscribe(state, &context)).into_response()
+}
+
+fn digest_name(kind: DigestKind) -> &'static str {
+    match kind {
+        DigestKind::Daily => "daily recap",
+        DigestKind::Weekly => "weekly summary",
+    }
+}
+
+mod get {
+    use super::*;
+
+    pub async fn unsubscribe(
+        Extension(state): Extension<AppState>,
+        Query(params): Query<UnsubscribeParams>,
+    ) -> impl IntoResponse {
+        let kind = match params.verify(&state) {
+            Some(kind) => kind,
+            None => return render_invalid_link(&state),
+        };
+
+        let mut context = tera::Context::new();
+        context.insert("digest", digest_name(kind));
+        context.insert("link", &params);
+        render_unsubscribe(&state, &context)
+    }
+}
+
+mod post {
+    use super::*;
+
+    pub async fn unsubscribe(
+        Extension(state): Extension<AppState>,
+        Form(params): Form<UnsubscribeParams>,
+    ) -> impl IntoResponse {
+        let kind = match params.verify(&state) {
+            Some(kind) => kind,
+            None => return render_invalid_link(&state),
+        };
+        if let Err(err) = digests::unsubscribe(&state.db, params.user, kind).await {
+            return e500(err).into_response();
+        }
+
+        let mut context = tera::Context::new();
+        context.insert("digest", digest_name(kind));
+        context.insert("unsubscribed", &true);
+        render_unsubscribe(&state, &context)
+    }
+}
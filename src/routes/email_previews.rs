@@ -0,0 +1,126 @@
+//! src/routes/email_previews.rs
+//! Every email template rendered with sample data, for checking how they look
+//! in a browser. Only served when `email.previews` is on.
+use axum::{
+    extract::{Path, Query},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse},
+    routing::get,
+    Router,
+};
+use axum::Extension;
+use serde::Deserialize;
+use tera::Tera;
+use time::{Date, PrimitiveDateTime};
+use crate::account_settings::EmailChangedEmail;
+use crate::configuration::EmailBranding;
+use crate::digests::DigestEmail;
+use crate::email_verification::ConfirmationEmail;
+use crate::emailer::{Email, EmailTemplate};
+use crate::login_throttle::AccountLockedEmail;
+use crate::password_reset::PasswordResetEmail;
+use crate::startup::AppState;
+use crate::trades::Trade;
+use crate::template_helpers::{render_content, RenderTemplateParams};
+use crate::utils::e500;
+use crate::constants::{html_templates, route_paths};
+
+type Preview = fn(&Tera, &EmailBranding) -> Result<Email, tera::Error>;
+
+/// Add new email templates here so they can be previewed
+const PREVIEWS: &[(&str, Preview)] = &[
+    (ConfirmationEmail::NAME, preview::<ConfirmationEmail>),
+    (PasswordResetEmail::NAME, preview::<PasswordResetEmail>),
+    (EmailChangedEmail::NAME, preview::<EmailChangedEmail>),
+    (AccountLockedEmail::NAME, preview::<AccountLockedEmail>),
+    (DigestEmail::NAME, preview::<DigestEmail>),
+];
+
+/// A one lot trade for previews of emails that list trades, closed at 15:00
+/// UTC on `trading_day`.
+pub(crate) fn sample_trade(instrument: &str, net_pnl: f64, trading_day: Date) -> Trade {
+    let exit_time = trading_day.with_hms(15, 0, 0).expect("A valid time").assume_utc();
+    Trade {
+        id: uuid::Uuid::new_v4(),
+        account: "Sim101".to_string(),
+        instrument: instrument.to_string(),
+        direction: "long".to_string(),
+        entry_time: exit_time,
+        exit_time,
+        trading_day,
+        local_entry_time: PrimitiveDateTime::new(trading_day, exit_time.time()),
+        quantity: 1,
+        max_position: 1,
+        entry_price: 0.0,
+        exit_price: 0.0,
+        gross_points: 0.0,
+        gross_ticks: None,
+        gross_pnl: Some(net_pnl),
+        commission: 0.0,
+        net_pnl: Some(net_pnl),
+        rating: None,
+        risk: None,
+        r_multiple: None,
+        tags: vec!["ORB".to_string()],
+    }
+}
+
+fn preview<T: EmailTemplate>(tera: &Tera, branding: &EmailBranding) -> Result<Email, tera::Error> {
+    Email::render(tera, branding, "trader@example.com", T::NAME, &T::sample())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PreviewQuery {
+    /// `text` for the plain text variant, HTML otherwise
+    format: Option<String>,
+}
+
+pub fn routes() -> Router<()> {
+    Router::new()
+        .route(route_paths::ROOT, get(self::get::previews))
+        .route(route_paths::EMAIL_PREVIEW, get(self::get::preview))
+}
+
+mod get {
+    use super::*;
+
+    pub async fn previews(Extension(state): Extension<AppState>) -> impl IntoResponse {
+        if !state.email_settings.previews {
+            return StatusCode::NOT_FOUND.into_response();
+        }
+
+        let names: Vec<&str> = PREVIEWS.iter().map(|(name, _)| *name).collect();
+        let mut context = tera::Context::new();
+        context.insert("names", &names);
+        match render_content(
+            &RenderTemplateParams::new(html_templates::EMAIL_PREVIEWS, &state.tera)
+            .with_context(&context)
+        ) {
+            Ok(previews_template) => Html(previews_template).into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+
+    pub async fn preview(
+        Extension(state): Extension<AppState>,
+        Path(name): Path<String>,
+        Query(query): Query<PreviewQuery>,
+    ) -> impl IntoResponse {
+        if !state.email_settings.previews {
+            return StatusCode::NOT_FOUND.into_response();
+        }
+        let render = match PREVIEWS.iter().find(|(preview_name, _)| *preview_name == name) {
+            Some((_, render)) => render,
+            None => return StatusCode::NOT_FOUND.into_response(),
+        };
+
+        let email = match render(&state.tera, &state.email_settings.branding) {
+            Ok(email) => email,
+            Err(err) => return e500(err).into_response(),
+        };
+        match query.format.as_deref() {
+            Some("text") => ([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], email.text_body).into_response(),
+            _ => Html(email.html_body).into_response(),
+        }
+    }
+}
@@ -0,0 +1,48 @@
+use axum::{
+    response::IntoResponse,
+    routing::get,
+    Json, Router,
+};
+use axum::Extension;
+use axum_extra::extract::Query;
+use crate::api_tokens::ApiUser;
+use crate::startup::AppState;
+use crate::stats_cache;
+use crate::utils::ApiError;
+use crate::stats::EquityCurve;
+use crate::trades::{self, TradeFilter, TradeFilterQuery};
+
+use crate::constants::route_paths;
+
+/// JSON, so it works with a session or an API token instead of `login_required!`
+pub fn routes() -> Router<()> {
+    Router::new()
+        .route(route_paths::ROOT, get(self::get::equity))
+}
+
+mod get {
+    use super::*;
+
+    /// The equity curve and drawdown of the user's trades as JSON
+    pub async fn equity(
+        api_user: ApiUser,
+        Extension(state): Extension<AppState>,
+        Query(query): Query<TradeFilterQuery>,
+    ) -> impl IntoResponse {
+        let filter = match TradeFilter::try_from(query) {
+            Ok(filter) => filter,
+            Err(err) => return ApiError::bad_request(err).into_response(),
+        };
+
+        // Shares its cache entries with the API's `/stats/equity`
+        let curve = stats_cache::cached(&state, api_user.user.id, "equity", &filter, || async {
+            let trades = trades::for_user(&state.db, api_user.user.id, &filter, &state.trading_session).await?;
+            Ok::<_, sqlx::Error>(EquityCurve::from_trades(&trades))
+        })
+        .await;
+        match curve {
+            Ok(curve) => Json(curve).into_response(),
+            Err(err) => ApiError::internal(err).into_response(),
+        }
+    }
+}
@@ -0,0 +1,204 @@
+use axum::{
+    extract::{DefaultBodyLimit, Multipart},
+    response::{IntoResponse, Redirect},
+    routing::get,
+    Router,
+};
+use axum::Extension;
+use axum::response::Html;
+use axum_login::login_required;
+use axum_messages::Messages;
+use time::UtcOffset;
+use time::macros::format_description;
+use crate::startup::AppState;
+use crate::template_helpers::{flash_messages, render_content, RenderTemplateParams};
+use crate::utils::e500;
+use crate::telemetry;
+use crate::domain::NewExecution;
+use crate::executions;
+use crate::accounts;
+use crate::jobs;
+use crate::trades::RebuildTrades;
+use crate::ninjatrader::{self, RowError};
+
+use crate::user::{AuthSession, Backend};
+use crate::constants::{
+    html_templates,
+    route_paths,
+    strings,
+};
+
+/// NinjaTrader exports can get large for active accounts.
+const MAX_IMPORT_SIZE: usize = 10 * 1024 * 1024;
+
+#[derive(Debug, serde::Serialize)]
+pub struct ImportReport {
+    pub file_name: String,
+    pub rows: usize,
+    pub imported: u64,
+    pub duplicates: u64,
+    pub errors: Vec<RowError>,
+}
+
+pub fn routes() -> Router<()> {
+    Router::new()
+        .route(
+            route_paths::IMPORT,
+            get(self::get::import).post(self::post::import),
+        )
+        .layer(DefaultBodyLimit::max(MAX_IMPORT_SIZE))
+        .route_layer(login_required!(Backend, login_url = route_paths::LOGIN))
+}
+
+fn parse_utc_offset(s: &str) -> Result<UtcOffset, String> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Ok(UtcOffset::UTC);
+    }
+    UtcOffset::parse(s, format_description!("[offset_hour sign:mandatory]:[offset_minute]"))
+        .map_err(|_| format!("{} is not a valid UTC offset. Use the format -05:00.", s))
+}
+
+async fn store_executions(state: &AppState, user_id: uuid::Uuid, new_executions: &[NewExecution]) -> Result<u64, sqlx::Error> {
+    let mut transaction = state.db.begin().await?;
+    let imported = executions::insert_executions(&mut transaction, user_id, new_executions).await?;
+    jobs::enqueue(&mut *transaction, &RebuildTrades { user_id, streams: None }).await?;
+    transaction.commit().await?;
+    Ok(imported)
+}
+
+mod post {
+    use super::*;
+
+    pub async fn import(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        mut multipart: Multipart,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+        let import_path = format!("{}{}", route_paths::EXECUTIONS, route_paths::IMPORT);
+        if !user.is_verified() {
+            messages.error(strings::EMAIL_NOT_VERIFIED);
+            return Redirect::to(&import_path).into_response();
+        }
+
+        let mut utc_offset = UtcOffset::UTC;
+        let mut file: Option<(String, axum::body::Bytes)> = None;
+        loop {
+            let field = match multipart.next_field().await {
+                Ok(Some(field)) => field,
+                Ok(None) => break,
+                Err(err) => {
+                    messages.error(err.body_text());
+                    return Redirect::to(&import_path).into_response();
+                }
+            };
+            match field.name() {
+                Some("utc_offset") => {
+                    let text = field.text().await.unwrap_or_default();
+                    utc_offset = match parse_utc_offset(&text) {
+                        Ok(offset) => offset,
+                        Err(err) => {
+                            messages.error(err);
+                            return Redirect::to(&import_path).into_response();
+                        }
+                    };
+                }
+                Some("file") => {
+                    let file_name = field.file_name().unwrap_or_default().to_string();
+                    match field.bytes().await {
+                        Ok(bytes) => file = Some((file_name, bytes)),
+                        Err(err) => {
+                            messages.error(err.body_text());
+                            return Redirect::to(&import_path).into_response();
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let (file_name, bytes) = match file {
+            Some(file) if !file.1.is_empty() => file,
+            _ => {
+                messages.error(strings::IMPORT_FILE_REQUIRED);
+                return Redirect::to(&import_path).into_response();
+            }
+        };
+
+        let parsed = match telemetry::spawn_blocking_with_tracing(move || {
+            ninjatrader::parse_executions_csv(&bytes, utc_offset)
+        }).await {
+            Ok(Ok(parsed)) => parsed,
+            Ok(Err(err)) => {
+                messages.error(err);
+                return Redirect::to(&import_path).into_response();
+            }
+            Err(err) => return e500(err).into_response(),
+        };
+
+        // Large exports take a while to rebuild, so trades are rebuilt by a
+        // job queued with the executions
+        let imported = match store_executions(&state, user.id, &parsed.executions).await {
+            Ok(imported) => imported,
+            Err(err) => return e500(err).into_response(),
+        };
+        tracing::info!(user_id = %user.id, imported, "Imported NinjaTrader executions");
+
+        let mut account_names: Vec<&str> = parsed.executions.iter().map(|e| e.account.as_str()).collect();
+        account_names.sort_unstable();
+        account_names.dedup();
+        if let Err(err) = accounts::ensure_exist(&state.db, user.id, &account_names).await {
+            return e500(err).into_response();
+        }
+
+        let rows = parsed.executions.len() + parsed.errors.len();
+        let report = ImportReport {
+            file_name,
+            rows,
+            imported,
+            duplicates: parsed.executions.len() as u64 - imported,
+            errors: parsed.errors,
+        };
+
+        let mut context = tera::Context::new();
+        context.insert("messages", &flash_messages(messages));
+        context.insert("report", &Some(report));
+        context.insert("unverified", &false);
+        match render_content(
+            &RenderTemplateParams::new(html_templates::IMPORT_EXECUTIONS, &state.tera)
+            .with_context(&context)
+        ) {
+            Ok(import_template) => Html(import_template).into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+}
+
+mod get {
+    use super::*;
+
+    pub async fn import(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+    ) -> impl IntoResponse {
+        let unverified = auth_session.user.as_ref().is_some_and(|user| !user.is_verified());
+
+        let mut context = tera::Context::new();
+        context.insert("messages", &flash_messages(messages));
+        context.insert("report", &None::<ImportReport>);
+        context.insert("unverified", &unverified);
+        match render_content(
+            &RenderTemplateParams::new(html_templates::IMPORT_EXECUTIONS, &state.tera)
+            .with_context(&context)
+        ) {
+            Ok(import_template) => Html(import_template).into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+}
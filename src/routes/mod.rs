@@ -5,6 +5,19 @@ mod health_check;
 mod homepage;
 mod auth;
 mod protected;
+mod executions;
+mod contract_specs;
+mod equity;
+mod calendar;
+mod trades;
+mod screenshots;
+mod reports;
+mod accounts;
+mod settings;
+mod admin;
+mod api_v1;
+pub(crate) mod email_previews;
+mod digests;
 
 pub fn homepage_routes() -> Router {
     Router::new().nest(route_paths::ROOT, homepage::routes())
@@ -21,3 +34,55 @@ pub fn health_check_routes() -> Router {
 pub fn protected_routes() -> Router {
     Router::new().nest(route_paths::PROTECTED, protected::routes())
 }
+
+pub fn executions_routes() -> Router {
+    Router::new().nest(route_paths::EXECUTIONS, executions::routes())
+}
+
+pub fn contract_specs_routes() -> Router {
+    Router::new().nest(route_paths::CONTRACT_SPECS, contract_specs::routes())
+}
+
+pub fn equity_routes() -> Router {
+    Router::new().nest(route_paths::EQUITY, equity::routes())
+}
+
+pub fn calendar_routes() -> Router {
+    Router::new().nest(route_paths::CALENDAR, calendar::routes())
+}
+
+pub fn trades_routes() -> Router {
+    Router::new().nest(route_paths::TRADES, trades::routes())
+}
+
+pub fn screenshots_routes() -> Router {
+    Router::new().nest(route_paths::SCREENSHOTS, screenshots::routes())
+}
+
+pub fn reports_routes() -> Router {
+    Router::new().nest(route_paths::REPORTS, reports::routes())
+}
+
+pub fn accounts_routes() -> Router {
+    Router::new().nest(route_paths::ACCOUNTS, accounts::routes())
+}
+
+pub fn settings_routes() -> Router {
+    Router::new().nest(route_paths::SETTINGS, settings::routes())
+}
+
+pub fn admin_routes() -> Router {
+    Router::new().nest(route_paths::ADMIN, admin::routes())
+}
+
+pub fn api_v1_routes() -> Router {
+    Router::new().nest(route_paths::API_V1, api_v1::routes())
+}
+
+pub fn email_previews_routes() -> Router {
+    Router::new().nest(route_paths::EMAIL_PREVIEWS, email_previews::routes())
+}
+
+pub fn digests_routes() -> Router {
+    Router::new().nest(route_paths::DIGESTS, digests::routes())
+}
@@ -0,0 +1,145 @@
+use axum::{
+    response::{IntoResponse, Redirect},
+    routing::get,
+    Router,
+};
+use axum::Extension;
+use axum::response::Html;
+use axum_extra::extract::Query;
+use axum_login::login_required;
+use axum_messages::Messages;
+use serde::{Deserialize, Serialize};
+use crate::startup::AppState;
+use crate::template_helpers::{flash_messages, render_content, RenderTemplateParams};
+use crate::utils::e500;
+use crate::stats::{Breakdown, BreakdownSort};
+use crate::trades::{self, TradeFilter, TradeFilterQuery};
+
+use crate::user::{AuthSession, Backend};
+use crate::constants::{
+    html_templates,
+    route_paths,
+};
+
+const COLUMNS: [(BreakdownSort, &str); 6] = [
+    (BreakdownSort::Group, "Group"),
+    (BreakdownSort::Trades, "Trades"),
+    (BreakdownSort::WinRate, "Win Rate"),
+    (BreakdownSort::NetPnl, "Net PnL"),
+    (BreakdownSort::Expectancy, "Expectancy"),
+    (BreakdownSort::AverageR, "Average R"),
+];
+
+/// How the report is sorted. The filter is extracted separately, as
+/// `TradeFilterQuery` can't be flattened with its repeated `account` keys.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ReportQuery {
+    pub sort: Option<String>,
+    /// `asc` or `desc`
+    pub order: Option<String>,
+}
+
+/// A table header that sorts by its column, keeping the current filters.
+#[derive(Debug, Serialize)]
+pub struct SortLink {
+    pub label: &'static str,
+    pub href: String,
+    /// "asc" or "desc" when the table is sorted by this column
+    pub active: Option<&'static str>,
+}
+
+pub fn routes() -> Router<()> {
+    Router::new()
+        .route(route_paths::ROOT, get(self::get::reports))
+        .route_layer(login_required!(Backend, login_url = route_paths::LOGIN))
+}
+
+fn sort_links(filter: &TradeFilterQuery, sort: BreakdownSort, descending: bool) -> Vec<SortLink> {
+    COLUMNS
+        .iter()
+        .map(|(column, label)| {
+            let active = (*column == sort).then_some(if descending { "desc" } else { "asc" });
+            // Clicking the active column flips it, other columns start with the biggest values
+            let order = match active {
+                Some("desc") => "asc",
+                Some(_) => "desc",
+                None if *column == BreakdownSort::Group => "asc",
+                None => "desc",
+            };
+            let filter_params = filter.to_query_string();
+            let separator = if filter_params.is_empty() { "" } else { "&" };
+            SortLink {
+                label,
+                href: format!(
+                    "{}?{}{}sort={}&order={}",
+                    route_paths::REPORTS,
+                    filter_params,
+                    separator,
+                    column.as_str(),
+                    order
+                ),
+                active,
+            }
+        })
+        .collect()
+}
+
+mod get {
+    use super::*;
+
+    pub async fn reports(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Query(query): Query<ReportQuery>,
+        Query(filter_query): Query<TradeFilterQuery>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+
+        let sort = match query.sort.as_deref().map(BreakdownSort::parse) {
+            Some(Ok(sort)) => sort,
+            Some(Err(err)) => {
+                messages.error(err);
+                return Redirect::to(route_paths::REPORTS).into_response();
+            }
+            None => BreakdownSort::default(),
+        };
+        let descending = query.order.as_deref() == Some("desc");
+
+        let mut filter_error = None;
+        let filter = TradeFilter::try_from(filter_query.clone()).unwrap_or_else(|err| {
+            filter_error = Some(err);
+            TradeFilter::default()
+        });
+        let user_trades = match trades::for_user(&state.db, user.id, &filter, &state.trading_session).await {
+            Ok(trades) => trades,
+            Err(err) => return e500(err).into_response(),
+        };
+        let choices = match trades::filter_choices(&state.db, user.id).await {
+            Ok(choices) => choices,
+            Err(err) => return e500(err).into_response(),
+        };
+
+        let mut breakdowns = Breakdown::all(&user_trades);
+        breakdowns.iter_mut().for_each(|breakdown| breakdown.sort(sort, descending));
+
+        let mut context = tera::Context::new();
+        context.insert("messages", &flash_messages(messages));
+        context.insert("breakdowns", &breakdowns);
+        context.insert("sort_links", &sort_links(&filter_query, sort, descending));
+        context.insert("trade_count", &user_trades.len());
+        context.insert("filter", &filter_query);
+        context.insert("filter_error", &filter_error);
+        context.insert("choices", &choices);
+        match render_content(
+            &RenderTemplateParams::new(html_templates::REPORTS, &state.tera)
+            .with_context(&context)
+        ) {
+            Ok(reports_template) => Html(reports_template).into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+}
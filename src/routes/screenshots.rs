@@ -0,0 +1,53 @@
+use axum::{
+    body::Body,
+    extract::Path,
+    http::{Request, StatusCode},
+    response::IntoResponse,
+    routing::get,
+    Router,
+};
+use axum::Extension;
+use axum_login::login_required;
+use tower::ServiceExt;
+use tower_http::services::ServeDir;
+use crate::startup::AppState;
+use crate::utils::e500;
+use crate::journal;
+
+use crate::user::{AuthSession, Backend};
+use crate::constants::route_paths;
+
+pub fn routes() -> Router<()> {
+    Router::new()
+        .route(route_paths::SCREENSHOT, get(self::get::screenshot))
+        .route_layer(login_required!(Backend))
+}
+
+mod get {
+    use super::*;
+
+    /// Serves the screenshot directory like `/public`, but only the logged in
+    /// user's own screenshots. Everything else is a 404 so file names of
+    /// other users' screenshots can't be confirmed.
+    pub async fn screenshot(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        Path(file_name): Path<String>,
+        request: Request<Body>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return StatusCode::UNAUTHORIZED.into_response(),
+        };
+        match journal::user_owns_screenshot(&state.db, user.id, &file_name).await {
+            Ok(true) => {},
+            Ok(false) => return StatusCode::NOT_FOUND.into_response(),
+            Err(err) => return e500(err).into_response(),
+        }
+
+        match ServeDir::new(&state.screenshot_directory).oneshot(request).await {
+            Ok(response) => response.into_response(),
+            Err(err) => e500(err).into_response(),
+        }
+    }
+}
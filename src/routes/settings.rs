@@ -0,0 +1,480 @@
+use axum::{
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Redirect},
+    routing::{get, post},
+    Form, Router,
+};
+use axum::Extension;
+use axum::response::Html;
+use axum_login::login_required;
+use axum_login::tower_sessions::Session;
+use axum_messages::Messages;
+use password_auth::generate_hash;
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+use crate::startup::AppState;
+use crate::template_helpers::{flash_messages, render_content, RenderTemplateParams};
+use crate::utils::e500;
+use crate::telemetry;
+use crate::account_settings;
+use crate::api_tokens;
+use crate::audit;
+use crate::digests;
+use crate::impersonation;
+use crate::two_factor;
+use crate::domain::{NewApiToken, UserEmail, UserPassword};
+
+use crate::user::{AuthSession, Backend, User};
+use crate::constants::{
+    html_templates,
+    route_paths,
+    strings,
+};
+
+const RECENT_EVENTS: i64 = 10;
+
+#[derive(Debug, Deserialize)]
+pub struct ChangePasswordForm {
+    pub current_password: Secret<String>,
+    pub password: Secret<String>,
+    pub password_confirmation: Secret<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangeEmailForm {
+    pub email: String,
+    pub current_password: Secret<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EnableTwoFactorForm {
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CurrentPasswordForm {
+    pub current_password: Secret<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ApiTokenForm {
+    pub name: String,
+    pub scope: String,
+    #[serde(default)]
+    pub expires_in_days: String,
+}
+
+/// Unchecked boxes aren't sent at all
+#[derive(Debug, Deserialize)]
+pub struct DigestsForm {
+    #[serde(default)]
+    pub daily: Option<String>,
+    #[serde(default)]
+    pub weekly: Option<String>,
+}
+
+impl From<DigestsForm> for digests::Subscriptions {
+    fn from(value: DigestsForm) -> Self {
+        digests::Subscriptions {
+            daily: value.daily.is_some(),
+            weekly: value.weekly.is_some(),
+        }
+    }
+}
+
+impl TryFrom<ApiTokenForm> for NewApiToken {
+    type Error = String;
+
+    fn try_from(value: ApiTokenForm) -> Result<Self, Self::Error> {
+        NewApiToken::parse(&value.name, &value.scope, &value.expires_in_days)
+    }
+}
+
+pub fn routes() -> Router<()> {
+    Router::new()
+        .route(route_paths::ROOT, get(self::get::settings))
+        .route(route_paths::SETTINGS_PASSWORD, post(self::post::password))
+        .route(route_paths::SETTINGS_EMAIL, post(self::post::email))
+        .route(route_paths::SETTINGS_DIGESTS, post(self::post::digests))
+        .route(route_paths::SETTINGS_TWO_FACTOR, get(self::get::two_factor))
+        .route(route_paths::SETTINGS_TWO_FACTOR_ENABLE, post(self::post::enable_two_factor))
+        .route(route_paths::SETTINGS_TWO_FACTOR_DISABLE, post(self::post::disable_two_factor))
+        .route(route_paths::SETTINGS_RECOVERY_CODES, post(self::post::recovery_codes))
+        .route(
+            route_paths::SETTINGS_API_TOKENS,
+            get(self::get::api_tokens).post(self::post::create_api_token),
+        )
+        .route(route_paths::SETTINGS_REVOKE_API_TOKEN, post(self::post::revoke_api_token))
+        .route_layer(login_required!(Backend, login_url = route_paths::LOGIN))
+}
+
+/// Recovery codes are only ever shown on the page right after they're made.
+fn render_recovery_codes(state: &AppState, recovery_codes: &[String]) -> axum::response::Response {
+    let mut context = tera::Context::new();
+    context.insert("recovery_codes", recovery_codes);
+    match render_content(
+        &RenderTemplateParams::new(html_templates::RECOVERY_CODES, &state.tera)
+        .with_context(&context)
+    ) {
+        Ok(template) => Html(template).into_response(),
+        Err(e) => e.into_response()
+    }
+}
+
+/// Lists the user's tokens. A token that was just created is shown once, at
+/// the top.
+async fn render_api_tokens(
+    state: &AppState,
+    user: &User,
+    messages: Messages,
+    new_token: Option<String>,
+) -> axum::response::Response {
+    let tokens = match api_tokens::for_user(&state.db, user.id).await {
+        Ok(tokens) => tokens,
+        Err(err) => return e500(err).into_response(),
+    };
+
+    let mut context = tera::Context::new();
+    context.insert("messages", &flash_messages(messages));
+    context.insert("tokens", &tokens);
+    context.insert("new_token", &new_token);
+    match render_content(
+        &RenderTemplateParams::new(html_templates::API_TOKENS, &state.tera)
+        .with_context(&context)
+    ) {
+        Ok(template) => Html(template).into_response(),
+        Err(e) => e.into_response()
+    }
+}
+
+mod post {
+    use super::*;
+
+    pub async fn password(
+        mut auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Form(form): Form<ChangePasswordForm>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user.clone() {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+
+        match user.check_password(form.current_password).await {
+            Ok(true) => {},
+            Ok(false) => {
+                messages.error(strings::INCORRECT_PASSWORD);
+                return Redirect::to(route_paths::SETTINGS).into_response();
+            },
+            Err(err) => return e500(err).into_response(),
+        }
+        if form.password.expose_secret() != form.password_confirmation.expose_secret() {
+            messages.error(strings::PASSWORDS_DO_NOT_MATCH);
+            return Redirect::to(route_paths::SETTINGS).into_response();
+        }
+        let password = match UserPassword::parse(form.password) {
+            Ok(password) => password,
+            Err(err) => {
+                messages.error(err);
+                return Redirect::to(route_paths::SETTINGS).into_response();
+            },
+        };
+        let password_hash = match telemetry::spawn_blocking_with_tracing(move || generate_hash(password)).await {
+            Ok(hash) => hash,
+            Err(err) => return e500(err).into_response(),
+        };
+
+        let user = match account_settings::change_password(&state.db, user.id, &password_hash).await {
+            Ok(user) => user,
+            Err(err) => return e500(err).into_response(),
+        };
+        // Logging in again stores the new password hash in this session, so it
+        // stays valid while sessions holding the old hash are rejected.
+        if let Err(err) = auth_session.login(&user).await {
+            return e500(err).into_response();
+        }
+
+        messages.success(strings::PASSWORD_CHANGED);
+        Redirect::to(route_paths::SETTINGS).into_response()
+    }
+
+    pub async fn email(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Form(form): Form<ChangeEmailForm>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+
+        let new_email = match UserEmail::parse(form.email.trim().to_string()) {
+            Ok(email) => email.email,
+            Err(err) => {
+                messages.error(err);
+                return Redirect::to(route_paths::SETTINGS).into_response();
+            },
+        };
+        if new_email == user.email {
+            messages.error(strings::EMAIL_UNCHANGED);
+            return Redirect::to(route_paths::SETTINGS).into_response();
+        }
+        match user.check_password(form.current_password).await {
+            Ok(true) => {},
+            Ok(false) => {
+                messages.error(strings::INCORRECT_PASSWORD);
+                return Redirect::to(route_paths::SETTINGS).into_response();
+            },
+            Err(err) => return e500(err).into_response(),
+        }
+
+        match account_settings::change_email(&state, &user, &new_email).await {
+            Ok(true) => {},
+            Ok(false) => {
+                messages.error(strings::EMAIL_TAKEN);
+                return Redirect::to(route_paths::SETTINGS).into_response();
+            },
+            Err(err) => return e500(err).into_response(),
+        }
+
+        messages.success(strings::EMAIL_CHANGED);
+        Redirect::to(route_paths::SETTINGS).into_response()
+    }
+
+    pub async fn digests(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Form(form): Form<DigestsForm>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+
+        if let Err(err) = digests::save_subscriptions(&state.db, user.id, form.into(), &state.trading_session).await {
+            return e500(err).into_response();
+        }
+        messages.success(strings::DIGESTS_SAVED);
+        Redirect::to(route_paths::SETTINGS).into_response()
+    }
+
+    pub async fn enable_two_factor(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Form(form): Form<EnableTwoFactorForm>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+        if user.two_factor_enabled {
+            return Redirect::to(route_paths::TWO_FACTOR_SETUP).into_response();
+        }
+
+        match two_factor::enable(&state.db, &user, &form.code).await {
+            Ok(Some(recovery_codes)) => render_recovery_codes(&state, &recovery_codes),
+            Ok(None) => {
+                messages.error(strings::INVALID_TWO_FACTOR_CODE);
+                Redirect::to(route_paths::TWO_FACTOR_SETUP).into_response()
+            },
+            Err(err) => e500(err).into_response(),
+        }
+    }
+
+    pub async fn disable_two_factor(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Form(form): Form<CurrentPasswordForm>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+        if user.two_factor_required {
+            messages.error(strings::TWO_FACTOR_REQUIRED_BY_ROLE);
+            return Redirect::to(route_paths::TWO_FACTOR_SETUP).into_response();
+        }
+        match user.check_password(form.current_password).await {
+            Ok(true) => {},
+            Ok(false) => {
+                messages.error(strings::INCORRECT_PASSWORD);
+                return Redirect::to(route_paths::TWO_FACTOR_SETUP).into_response();
+            },
+            Err(err) => return e500(err).into_response(),
+        }
+
+        if let Err(err) = two_factor::disable(&state.db, user.id).await {
+            return e500(err).into_response();
+        }
+        messages.success(strings::TWO_FACTOR_DISABLED);
+        Redirect::to(route_paths::TWO_FACTOR_SETUP).into_response()
+    }
+
+    pub async fn recovery_codes(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Form(form): Form<CurrentPasswordForm>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+        if !user.two_factor_enabled {
+            return Redirect::to(route_paths::TWO_FACTOR_SETUP).into_response();
+        }
+        match user.check_password(form.current_password).await {
+            Ok(true) => {},
+            Ok(false) => {
+                messages.error(strings::INCORRECT_PASSWORD);
+                return Redirect::to(route_paths::TWO_FACTOR_SETUP).into_response();
+            },
+            Err(err) => return e500(err).into_response(),
+        }
+
+        match two_factor::regenerate_recovery_codes(&state.db, user.id).await {
+            Ok(recovery_codes) => render_recovery_codes(&state, &recovery_codes),
+            Err(err) => e500(err).into_response(),
+        }
+    }
+
+    pub async fn create_api_token(
+        auth_session: AuthSession,
+        session: Session,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Form(form): Form<ApiTokenForm>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+        // A token would keep working after the admin stopped impersonating
+        if impersonation::impersonator(&session).await.is_some() {
+            messages.error(strings::NO_API_TOKENS_WHILE_IMPERSONATING);
+            return Redirect::to(route_paths::API_TOKENS).into_response();
+        }
+        let new_token = match NewApiToken::try_from(form) {
+            Ok(new_token) => new_token,
+            Err(err) => {
+                messages.error(err);
+                return Redirect::to(route_paths::API_TOKENS).into_response();
+            },
+        };
+
+        match api_tokens::create(&state.db, user.id, &new_token).await {
+            Ok(token) => render_api_tokens(&state, &user, messages, Some(token)).await,
+            Err(err) => e500(err).into_response(),
+        }
+    }
+
+    pub async fn revoke_api_token(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path(token_id): Path<uuid::Uuid>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+
+        match api_tokens::revoke(&state.db, user.id, token_id).await {
+            Ok(true) => {},
+            Ok(false) => return StatusCode::NOT_FOUND.into_response(),
+            Err(err) => return e500(err).into_response(),
+        }
+        messages.success(strings::API_TOKEN_REVOKED);
+        Redirect::to(route_paths::API_TOKENS).into_response()
+    }
+}
+
+mod get {
+    use super::*;
+
+    pub async fn api_tokens(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+        render_api_tokens(&state, &user, messages, None).await
+    }
+
+    pub async fn settings(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+        let events = match audit::recent_for_user(&state.db, user.id, RECENT_EVENTS).await {
+            Ok(events) => events,
+            Err(err) => return e500(err).into_response(),
+        };
+        let subscriptions = match digests::subscriptions(&state.db, user.id).await {
+            Ok(subscriptions) => subscriptions,
+            Err(err) => return e500(err).into_response(),
+        };
+
+        let mut context = tera::Context::new();
+        context.insert("messages", &flash_messages(messages));
+        context.insert("email", &user.email);
+        context.insert("digests", &subscriptions);
+        context.insert("unverified", &!user.is_verified());
+        context.insert("events", &events);
+        context.insert("two_factor_enabled", &user.two_factor_enabled);
+        match render_content(
+            &RenderTemplateParams::new(html_templates::SETTINGS, &state.tera)
+            .with_context(&context)
+        ) {
+            Ok(settings_template) => Html(settings_template).into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+
+    pub async fn two_factor(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+
+        let mut context = tera::Context::new();
+        context.insert("messages", &flash_messages(messages));
+        context.insert("enabled", &user.two_factor_enabled);
+        context.insert("required", &user.two_factor_required);
+        if user.two_factor_enabled {
+            match two_factor::unused_recovery_codes(&state.db, user.id).await {
+                Ok(count) => context.insert("unused_recovery_codes", &count),
+                Err(err) => return e500(err).into_response(),
+            }
+        } else {
+            match two_factor::start_enrollment(&state.db, &user).await {
+                Ok(enrollment) => context.insert("enrollment", &enrollment),
+                Err(err) => return e500(err).into_response(),
+            }
+        }
+        match render_content(
+            &RenderTemplateParams::new(html_templates::TWO_FACTOR_SETTINGS, &state.tera)
+            .with_context(&context)
+        ) {
+            Ok(template) => Html(template).into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+}
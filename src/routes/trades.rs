@@ -0,0 +1,277 @@
+use axum::{
+    extract::{DefaultBodyLimit, Multipart, Path},
+    http::StatusCode,
+    response::{IntoResponse, Redirect},
+    routing::{get, post},
+    Form, Router,
+};
+use axum::Extension;
+use axum::response::Html;
+use axum_extra::extract::Query;
+use axum_login::login_required;
+use axum_messages::Messages;
+use serde::Deserialize;
+use crate::startup::AppState;
+use crate::template_helpers::{flash_messages, render_content, RenderTemplateParams};
+use crate::utils::e500;
+use crate::journal;
+use crate::stats_cache;
+use crate::trades::{self, TradeFilter, TradeFilterQuery};
+use crate::domain::JournalEntry;
+
+use crate::user::{AuthSession, Backend};
+use crate::constants::{
+    html_templates,
+    route_paths,
+    strings,
+};
+
+/// Full screen chart captures are usually well under this
+const MAX_SCREENSHOT_SIZE: usize = 5 * 1024 * 1024;
+
+#[derive(Debug, Deserialize)]
+pub struct JournalForm {
+    pub notes: String,
+    pub rating: String,
+    pub risk: String,
+    pub tags: String,
+}
+
+impl TryFrom<JournalForm> for JournalEntry {
+    type Error = String;
+
+    fn try_from(value: JournalForm) -> Result<Self, Self::Error> {
+        JournalEntry::parse(&value.notes, &value.rating, &value.risk, &value.tags)
+    }
+}
+
+pub fn routes() -> Router<()> {
+    Router::new()
+        .route(route_paths::ROOT, get(self::get::trades))
+        .route(route_paths::TRADE, get(self::get::trade))
+        .route(route_paths::TRADE_JOURNAL, post(self::post::journal))
+        .route(
+            route_paths::TRADE_SCREENSHOTS,
+            post(self::post::screenshot).layer(DefaultBodyLimit::max(MAX_SCREENSHOT_SIZE)),
+        )
+        .route(route_paths::DELETE_TRADE_SCREENSHOT, post(self::post::delete_screenshot))
+        .route_layer(login_required!(Backend, login_url = route_paths::LOGIN))
+}
+
+fn trade_path(trade_id: uuid::Uuid) -> String {
+    format!("{}/{}", route_paths::TRADES, trade_id)
+}
+
+mod post {
+    use super::*;
+
+    pub async fn journal(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path(trade_id): Path<uuid::Uuid>,
+        Form(form): Form<JournalForm>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+
+        let entry = match JournalEntry::try_from(form) {
+            Ok(entry) => entry,
+            Err(err) => {
+                messages.error(err);
+                return Redirect::to(&trade_path(trade_id)).into_response();
+            }
+        };
+
+        match journal::save(&state.db, user.id, trade_id, &entry).await {
+            Ok(true) => {
+                // Statistics can be filtered by tag
+                stats_cache::invalidate(&state, user.id).await;
+                messages.success(strings::JOURNAL_SAVED);
+                Redirect::to(&trade_path(trade_id)).into_response()
+            }
+            Ok(false) => StatusCode::NOT_FOUND.into_response(),
+            Err(err) => e500(err).into_response(),
+        }
+    }
+
+    pub async fn screenshot(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path(trade_id): Path<uuid::Uuid>,
+        mut multipart: Multipart,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+        match trades::find_for_user(&state.db, user.id, trade_id, &state.trading_session).await {
+            Ok(Some(_)) => {},
+            Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+            Err(err) => return e500(err).into_response(),
+        }
+
+        let mut file: Option<(String, axum::body::Bytes)> = None;
+        loop {
+            let field = match multipart.next_field().await {
+                Ok(Some(field)) => field,
+                Ok(None) => break,
+                Err(err) => {
+                    messages.error(err.body_text());
+                    return Redirect::to(&trade_path(trade_id)).into_response();
+                }
+            };
+            if field.name() == Some("screenshot") {
+                let original_name = field.file_name().unwrap_or_default().to_string();
+                match field.bytes().await {
+                    Ok(bytes) => file = Some((original_name, bytes)),
+                    Err(err) => {
+                        messages.error(err.body_text());
+                        return Redirect::to(&trade_path(trade_id)).into_response();
+                    }
+                }
+            }
+        }
+
+        let (original_name, bytes) = match file {
+            Some(file) if !file.1.is_empty() => file,
+            _ => {
+                messages.error(strings::SCREENSHOT_REQUIRED);
+                return Redirect::to(&trade_path(trade_id)).into_response();
+            }
+        };
+        let extension = match journal::image_extension(&bytes) {
+            Some(extension) => extension,
+            None => {
+                messages.error(strings::SCREENSHOT_NOT_AN_IMAGE);
+                return Redirect::to(&trade_path(trade_id)).into_response();
+            }
+        };
+
+        // Recorded before the file is written, so a failed insert leaves no
+        // file behind
+        let file_name = format!("{}.{}", uuid::Uuid::new_v4(), extension);
+        let screenshot_id = match journal::add_screenshot(&state.db, user.id, trade_id, &file_name, &original_name).await {
+            Ok(screenshot_id) => screenshot_id,
+            Err(err) => return e500(err).into_response(),
+        };
+        if let Err(err) = tokio::fs::write(state.screenshot_directory.join(&file_name), &bytes).await {
+            if let Err(delete_err) = journal::delete_screenshot(&state.db, user.id, trade_id, screenshot_id).await {
+                tracing::error!(%screenshot_id, error = %delete_err, "Failed to remove a screenshot whose file wasn't written");
+            }
+            return e500(err).into_response();
+        }
+
+        messages.success(strings::SCREENSHOT_UPLOADED);
+        Redirect::to(&trade_path(trade_id)).into_response()
+    }
+
+    pub async fn delete_screenshot(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path((trade_id, screenshot_id)): Path<(uuid::Uuid, uuid::Uuid)>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+
+        let file_name = match journal::delete_screenshot(&state.db, user.id, trade_id, screenshot_id).await {
+            Ok(Some(file_name)) => file_name,
+            Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+            Err(err) => return e500(err).into_response(),
+        };
+        if let Err(err) = tokio::fs::remove_file(state.screenshot_directory.join(&file_name)).await {
+            tracing::warn!(file_name, error = %err, "Failed to remove a deleted screenshot");
+        }
+
+        messages.success(strings::SCREENSHOT_DELETED);
+        Redirect::to(&trade_path(trade_id)).into_response()
+    }
+}
+
+mod get {
+    use super::*;
+
+    pub async fn trades(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        Query(query): Query<TradeFilterQuery>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+
+        let mut filter_error = None;
+        let filter = TradeFilter::try_from(query.clone()).unwrap_or_else(|err| {
+            filter_error = Some(err);
+            TradeFilter::default()
+        });
+        let user_trades = match trades::for_user(&state.db, user.id, &filter, &state.trading_session).await {
+            Ok(trades) => trades,
+            Err(err) => return e500(err).into_response(),
+        };
+        let choices = match trades::filter_choices(&state.db, user.id).await {
+            Ok(choices) => choices,
+            Err(err) => return e500(err).into_response(),
+        };
+
+        let mut context = tera::Context::new();
+        context.insert("trades", &user_trades);
+        context.insert("filter", &query);
+        context.insert("filter_error", &filter_error);
+        context.insert("choices", &choices);
+        match render_content(
+            &RenderTemplateParams::new(html_templates::TRADES, &state.tera)
+            .with_context(&context)
+        ) {
+            Ok(trades_template) => Html(trades_template).into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+
+    pub async fn trade(
+        auth_session: AuthSession,
+        Extension(state): Extension<AppState>,
+        messages: Messages,
+        Path(trade_id): Path<uuid::Uuid>,
+    ) -> impl IntoResponse {
+        let user = match auth_session.user {
+            Some(user) => user,
+            None => return Redirect::to(route_paths::LOGIN).into_response(),
+        };
+
+        let trade = match trades::find_for_user(&state.db, user.id, trade_id, &state.trading_session).await {
+            Ok(Some(trade)) => trade,
+            Ok(None) => return StatusCode::NOT_FOUND.into_response(),
+            Err(err) => return e500(err).into_response(),
+        };
+        let notes = match journal::notes(&state.db, user.id, trade_id).await {
+            Ok(notes) => notes,
+            Err(err) => return e500(err).into_response(),
+        };
+        let screenshots = match journal::screenshots_for_trade(&state.db, user.id, trade_id).await {
+            Ok(screenshots) => screenshots,
+            Err(err) => return e500(err).into_response(),
+        };
+
+        let mut context = tera::Context::new();
+        context.insert("messages", &flash_messages(messages));
+        context.insert("trade", &trade);
+        context.insert("notes", &notes);
+        context.insert("notes_html", &journal::render_markdown(&notes));
+        context.insert("screenshots", &screenshots);
+        match render_content(
+            &RenderTemplateParams::new(html_templates::TRADE, &state.tera)
+            .with_context(&context)
+        ) {
+            Ok(trade_template) => Html(trade_template).into_response(),
+            Err(e) => e.into_response()
+        }
+    }
+}
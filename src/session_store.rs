@@ -0,0 +1,84 @@
+//! src/session_store.rs
+//! The session store picked by `session_store` in the configuration. Layers
+//! are generic over their store, so this wraps each kind in one type.
+use async_trait::async_trait;
+use axum_login::tower_sessions::session::{Id, Record};
+use axum_login::tower_sessions::session_store::{self, ExpiredDeletion, SessionStore};
+use axum_login::tower_sessions::MemoryStore;
+use sqlx::PgPool;
+use tower_sessions_redis_store::fred::prelude::RedisPool;
+use tower_sessions_redis_store::RedisStore;
+use tower_sessions_sqlx_store::PostgresStore;
+use crate::configuration::SessionStoreKind;
+
+#[derive(Debug, Clone)]
+pub enum AppSessionStore {
+    Postgres(PostgresStore),
+    Redis(RedisStore<RedisPool>),
+    Memory(MemoryStore),
+}
+
+impl AppSessionStore {
+    /// The store for `kind`. `redis` must be connected when `kind` is Redis.
+    pub async fn new(kind: SessionStoreKind, db: &PgPool, redis: Option<&RedisPool>) -> Result<Self, anyhow::Error> {
+        Ok(match kind {
+            SessionStoreKind::Postgres => {
+                let store = PostgresStore::new(db.clone());
+                store.migrate().await?;
+                AppSessionStore::Postgres(store)
+            }
+            SessionStoreKind::Redis => {
+                let redis = redis.ok_or_else(|| anyhow::anyhow!("The Redis session store needs a Redis connection"))?;
+                AppSessionStore::Redis(RedisStore::new(redis.clone()))
+            }
+            SessionStoreKind::Memory => AppSessionStore::Memory(MemoryStore::default()),
+        })
+    }
+}
+
+#[async_trait]
+impl SessionStore for AppSessionStore {
+    async fn create(&self, record: &mut Record) -> session_store::Result<()> {
+        match self {
+            AppSessionStore::Postgres(store) => store.create(record).await,
+            AppSessionStore::Redis(store) => store.create(record).await,
+            AppSessionStore::Memory(store) => store.create(record).await,
+        }
+    }
+
+    async fn save(&self, record: &Record) -> session_store::Result<()> {
+        match self {
+            AppSessionStore::Postgres(store) => store.save(record).await,
+            AppSessionStore::Redis(store) => store.save(record).await,
+            AppSessionStore::Memory(store) => store.save(record).await,
+        }
+    }
+
+    async fn load(&self, session_id: &Id) -> session_store::Result<Option<Record>> {
+        match self {
+            AppSessionStore::Postgres(store) => store.load(session_id).await,
+            AppSessionStore::Redis(store) => store.load(session_id).await,
+            AppSessionStore::Memory(store) => store.load(session_id).await,
+        }
+    }
+
+    async fn delete(&self, session_id: &Id) -> session_store::Result<()> {
+        match self {
+            AppSessionStore::Postgres(store) => store.delete(session_id).await,
+            AppSessionStore::Redis(store) => store.delete(session_id).await,
+            AppSessionStore::Memory(store) => store.delete(session_id).await,
+        }
+    }
+}
+
+#[async_trait]
+impl ExpiredDeletion for AppSessionStore {
+    /// Redis expires sessions by itself, and the memory store ignores expired
+    /// ones when they're loaded.
+    async fn delete_expired(&self) -> session_store::Result<()> {
+        match self {
+            AppSessionStore::Postgres(store) => store.delete_expired().await,
+            AppSessionStore::Redis(_) | AppSessionStore::Memory(_) => Ok(()),
+        }
+    }
+}
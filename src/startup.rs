@@ -3,40 +3,79 @@
 // PgPool is sqlx's version
 use sqlx::PgPool;
 use sqlx::postgres::PgPoolOptions;
-use secrecy::Secret;
-use axum::{Extension,Router};
+use secrecy::{ExposeSecret, Secret};
+use axum::{middleware, Extension, Router};
 use tokio::net::TcpListener;
 use tower_http::trace::TraceLayer;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
 use tera::Tera;
 use tower_http::services::{ServeDir, ServeFile};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use axum_login::{
-    login_required,
-    tower_sessions::{ExpiredDeletion, Expiry, SessionManagerLayer, cookie::Key},
+    tower_sessions::{ExpiredDeletion, Expiry, SessionManagerLayer},
     AuthManagerLayerBuilder,
 };
 use axum_messages::MessagesManagerLayer;
-use tokio::{signal, task::AbortHandle};
-use tower_sessions_sqlx_store::PostgresStore;
+use tokio::{signal, sync::watch, task::AbortHandle};
+use tower_sessions_redis_store::fred::prelude::{ClientLike, RedisConfig, RedisPool};
 
 use crate::configuration::Settings;
 use crate::configuration::DatabaseSettings;
 use crate::configuration::EmailSettings;
+use crate::configuration::{SessionStoreKind, StatsCacheBackend};
+use crate::domain::TradingSession;
+use crate::digests;
+use crate::email_outbox;
+use crate::jobs;
+use crate::session_store::AppSessionStore;
+use crate::stats_cache::{self, StatsCache};
+use crate::emailer::{self, EmailClient};
 use crate::routes::health_check_routes;
 use crate::routes::homepage_routes;
 use crate::routes::auth_routes;
 use crate::routes::protected_routes;
+use crate::routes::executions_routes;
+use crate::routes::contract_specs_routes;
+use crate::routes::equity_routes;
+use crate::routes::calendar_routes;
+use crate::routes::trades_routes;
+use crate::routes::screenshots_routes;
+use crate::routes::reports_routes;
+use crate::routes::accounts_routes;
+use crate::routes::settings_routes;
+use crate::routes::admin_routes;
+use crate::routes::api_v1_routes;
+use crate::routes::email_previews_routes;
+use crate::routes::digests_routes;
 use crate::user::Backend;
+use crate::impersonation;
+use crate::two_factor;
 use crate::constants::strings;
+use crate::template_helpers;
+
+/// Screenshots are kept in this directory under `upload_directory`
+const SCREENSHOTS_DIRECTORY: &str = "screenshots";
+/// How long running jobs get to finish when the app is stopped
+const JOB_SHUTDOWN_GRACE: std::time::Duration = std::time::Duration::from_secs(30);
+/// Connections to Redis, shared by sessions and the stats cache
+const REDIS_POOL_SIZE: usize = 4;
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: PgPool,
+    /// Where the app is reachable from outside, for links in emails
+    pub base_url: String,
     pub hmac_secret: Secret<String>,
     pub tera: Arc<Tera>,
     pub email_settings: EmailSettings,
+    pub email_client: Arc<dyn EmailClient>,
+    pub trading_session: TradingSession,
+    pub screenshot_directory: PathBuf,
+    pub stats_cache: Arc<dyn StatsCache>,
+    /// See `application.trusted_proxies`
+    pub trusted_proxies: Vec<IpAddr>,
 }
 
 pub struct Application {
@@ -45,16 +84,41 @@ pub struct Application {
     tera: Arc<Tera>,
     listener: TcpListener,
     base_url: String,
-    redis_uri: Secret<String>,
+    session_store: AppSessionStore,
     hmac_secret: Secret<String>,
     email_settings: EmailSettings,
+    email_client: Arc<dyn EmailClient>,
+    trading_session: TradingSession,
+    screenshot_directory: PathBuf,
+    stats_cache: Arc<dyn StatsCache>,
+    trusted_proxies: Vec<IpAddr>,
 }
 
 impl Application {
     pub async fn build(configuration: Settings) -> Result<Self, anyhow::Error> {
+        let email_client = emailer::client_from_settings(&configuration.email)?;
+        Self::build_with_email_client(configuration, email_client).await
+    }
+
+    /// Like `build`, but sends emails through `email_client` instead of the
+    /// configured transport, so tests can read what was sent.
+    pub async fn build_with_email_client(configuration: Settings, email_client: Arc<dyn EmailClient>) -> Result<Self, anyhow::Error> {
         // Compile SCSS files to CSS at runtime
         compile_scss_to_css("scss", "public/css");
         let connection_pool = get_connection_pool(&configuration.database);
+        let trading_session = configuration.trading_session.parse().map_err(anyhow::Error::msg)?;
+        let screenshot_directory = Path::new(&configuration.application.upload_directory).join(SCREENSHOTS_DIRECTORY);
+        fs::create_dir_all(&screenshot_directory)?;
+        // Redis is only connected to when something is configured to use it
+        let redis_pool = if configuration.session_store == SessionStoreKind::Redis
+            || configuration.stats_cache.backend == StatsCacheBackend::Redis
+        {
+            Some(get_redis_pool(&configuration.redis_uri).await?)
+        } else {
+            None
+        };
+        let session_store = AppSessionStore::new(configuration.session_store, &connection_pool, redis_pool.as_ref()).await?;
+        let stats_cache = stats_cache::cache_from_settings(&configuration.stats_cache, redis_pool.as_ref())?;
 
         let address = format!(
             "{}:{}",
@@ -62,7 +126,10 @@ impl Application {
         );
         let listener = TcpListener::bind(address).await?;
         let port = listener.local_addr().unwrap().port();
-        let tera = Tera::new("templates/**/*html")?;
+        // Emails also have plain text variants
+        let mut tera = Tera::new("templates/**/*.{html,txt}")?;
+        tera.register_filter("currency_format", template_helpers::currency_format);
+        tera.register_filter("round_hundreths", template_helpers::round_hundreths);
         let tera = Arc::new(tera);
 
         Ok(Self {
@@ -71,9 +138,14 @@ impl Application {
             listener,
             db_pool: connection_pool,
             base_url: configuration.application.base_url,
-            redis_uri: configuration.redis_uri,
+            session_store,
             hmac_secret: configuration.application.hmac_secret,
             email_settings: configuration.email,
+            email_client,
+            trading_session,
+            screenshot_directory,
+            stats_cache,
+            trusted_proxies: configuration.application.trusted_proxies,
         })
     }
 
@@ -81,9 +153,26 @@ impl Application {
         self.port
     }
 
+    /// The state the app's handlers and background tasks will share, for
+    /// running those tasks directly in tests.
+    pub fn state(&self) -> AppState {
+        AppState {
+            db: self.db_pool.clone(),
+            base_url: self.base_url.clone(),
+            hmac_secret: self.hmac_secret.clone(),
+            tera: self.tera.clone(),
+            email_settings: self.email_settings.clone(),
+            email_client: self.email_client.clone(),
+            trading_session: self.trading_session.clone(),
+            screenshot_directory: self.screenshot_directory.clone(),
+            stats_cache: self.stats_cache.clone(),
+            trusted_proxies: self.trusted_proxies.clone(),
+        }
+    }
+
     pub async fn run_until_stopped(self) -> Result<(), anyhow::Error> {
         run(
-            self.db_pool, self.listener, self.base_url, self.redis_uri, self.hmac_secret, self.tera, self.email_settings
+            self.db_pool, self.listener, self.base_url, self.session_store, self.hmac_secret, self.tera, self.email_settings, self.email_client, self.trading_session, self.screenshot_directory, self.stats_cache, self.trusted_proxies
             ).await
     }
 }
@@ -96,20 +185,28 @@ pub fn get_connection_pool(
         .connect_lazy_with(configuration.with_db())
 }
 
+/// Connects to Redis at `redis_uri`, failing if it can't be reached.
+pub async fn get_redis_pool(redis_uri: &Secret<String>) -> Result<RedisPool, anyhow::Error> {
+    let config = RedisConfig::from_url(redis_uri.expose_secret())?;
+    let pool = RedisPool::new(config, None, None, None, REDIS_POOL_SIZE)?;
+    pool.init().await?;
+    Ok(pool)
+}
+
 pub struct ApplicationBaseUrl(pub String);
 
-pub async fn run(db_pool: PgPool, listener: TcpListener, _base_url: String, _redis_uri: Secret<String>, hmac_secret: Secret<String>, tera: Arc<Tera>, email_settings: EmailSettings) -> Result<(), anyhow::Error> {
+#[allow(clippy::too_many_arguments)]
+pub async fn run(db_pool: PgPool, listener: TcpListener, base_url: String, session_store: AppSessionStore, hmac_secret: Secret<String>, tera: Arc<Tera>, email_settings: EmailSettings, email_client: Arc<dyn EmailClient>, trading_session: TradingSession, screenshot_directory: PathBuf, stats_cache: Arc<dyn StatsCache>, trusted_proxies: Vec<IpAddr>) -> Result<(), anyhow::Error> {
     // Session layer.
     //
     // This uses `tower-sessions` to establish a layer that will provide the session
     // as a request extension.
-    let session_store = PostgresStore::new(db_pool.clone());
-    session_store.migrate().await?;
     let deletion_task = tokio::task::spawn(
         session_store
         .clone()
         .continuously_delete_expired(tokio::time::Duration::from_secs(60)),
     );
+    let email_task = tokio::task::spawn(email_outbox::run_worker(db_pool.clone(), email_client.clone()));
 
     // Generate a cryptographic key to sign the session cookie.
     // let key = Key::generate();
@@ -125,24 +222,45 @@ pub async fn run(db_pool: PgPool, listener: TcpListener, _base_url: String, _red
     let backend = Backend::new(db_pool.clone());
     let auth_layer = AuthManagerLayerBuilder::new(backend, session_layer).build();
 
+    let state = AppState {
+        db: db_pool,
+        base_url,
+        hmac_secret,
+        tera,
+        email_settings,
+        email_client,
+        trading_session,
+        screenshot_directory,
+        stats_cache,
+        trusted_proxies,
+    };
+    let digest_task = tokio::task::spawn(digests::run_scheduler(state.db.clone()));
+    let (stop_jobs, jobs_stopped) = watch::channel(false);
+    let job_task = tokio::task::spawn(jobs::run_workers(state.clone(), jobs_stopped));
+
     let app = api_router()
+        .layer(middleware::from_fn(two_factor::require_enrollment))
+        .layer(middleware::from_fn(impersonation::banner))
         .layer(TraceLayer::new_for_http())
-        .layer(
-            Extension(
-                AppState {
-                    db: db_pool,
-                    hmac_secret,
-                    tera,
-                    email_settings,
-                }
-            )
-        )
+        .layer(Extension(state))
         .layer(MessagesManagerLayer)
         .layer(auth_layer);
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal(deletion_task.abort_handle()))
+    // Logins are throttled per client IP, which needs the connection's address
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown_signal(deletion_task.abort_handle(), stop_jobs))
         .await?;
 
+    // Anything left in the outbox is sent when the app starts again, and the
+    // digest scheduler only queues jobs
+    email_task.abort();
+    digest_task.abort();
+    // Jobs still running after the grace period are run again once their
+    // lease runs out
+    let job_abort_handle = job_task.abort_handle();
+    if tokio::time::timeout(JOB_SHUTDOWN_GRACE, job_task).await.is_err() {
+        tracing::warn!("Stopped with jobs still running");
+        job_abort_handle.abort();
+    }
     deletion_task.await??;
     Ok(())
 }
@@ -159,6 +277,19 @@ fn api_router() -> Router {
         .merge(homepage_routes())
         .merge(protected_routes())
         .merge(auth_routes())
+        .merge(executions_routes())
+        .merge(contract_specs_routes())
+        .merge(equity_routes())
+        .merge(calendar_routes())
+        .merge(trades_routes())
+        .merge(screenshots_routes())
+        .merge(reports_routes())
+        .merge(accounts_routes())
+        .merge(settings_routes())
+        .merge(admin_routes())
+        .merge(api_v1_routes())
+        .merge(email_previews_routes())
+        .merge(digests_routes())
 }
 
 fn compile_scss_to_css(scss_dir: &str, css_dir: &str) {
@@ -179,7 +310,7 @@ fn compile_scss_to_css(scss_dir: &str, css_dir: &str) {
     }
 }
 
-async fn shutdown_signal(deletion_task_abort_handle: AbortHandle) {
+async fn shutdown_signal(deletion_task_abort_handle: AbortHandle, stop_jobs: watch::Sender<bool>) {
     let ctrl_c = async {
         signal::ctrl_c()
             .await
@@ -198,8 +329,11 @@ async fn shutdown_signal(deletion_task_abort_handle: AbortHandle) {
     let terminate = std::future::pending::<()>();
 
     tokio::select! {
-        _ = ctrl_c => { deletion_task_abort_handle.abort() },
-        _ = terminate => { deletion_task_abort_handle.abort() },
+        _ = ctrl_c => {},
+        _ = terminate => {},
     }
+    deletion_task_abort_handle.abort();
+    // Job workers finish the job they're running and stop taking new ones
+    let _ = stop_jobs.send(true);
 }
 
@@ -0,0 +1,297 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use serde::Serialize;
+use utoipa::ToSchema;
+use crate::trades::{self, Trade};
+
+const UNTAGGED: &str = "Untagged";
+
+/// Upper bounds in seconds, and labels, of the trade duration buckets
+const DURATION_BUCKETS: [(i64, &str); 6] = [
+    (60, "Under 1 minute"),
+    (5 * 60, "1 to 5 minutes"),
+    (15 * 60, "5 to 15 minutes"),
+    (60 * 60, "15 to 60 minutes"),
+    (4 * 60 * 60, "1 to 4 hours"),
+    (i64::MAX, "Over 4 hours"),
+];
+
+/// The column a breakdown table is sorted by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BreakdownSort {
+    /// The natural order of the groups, e.g. Monday to Sunday
+    #[default]
+    Group,
+    Trades,
+    WinRate,
+    NetPnl,
+    Expectancy,
+    AverageR,
+}
+
+impl BreakdownSort {
+    pub fn parse(s: &str) -> Result<BreakdownSort, String> {
+        match s {
+            "group" => Ok(Self::Group),
+            "trades" => Ok(Self::Trades),
+            "win_rate" => Ok(Self::WinRate),
+            "net_pnl" => Ok(Self::NetPnl),
+            "expectancy" => Ok(Self::Expectancy),
+            "average_r" => Ok(Self::AverageR),
+            other => Err(format!("{} is not a column that can be sorted.", other)),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Group => "group",
+            Self::Trades => "trades",
+            Self::WinRate => "win_rate",
+            Self::NetPnl => "net_pnl",
+            Self::Expectancy => "expectancy",
+            Self::AverageR => "average_r",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, ToSchema)]
+pub struct BreakdownRow {
+    pub group: String,
+    #[serde(skip)]
+    position: u32,
+    pub trade_count: usize,
+    pub wins: usize,
+    /// Percentage of trades that were winners, 0 to 100
+    pub win_rate: f64,
+    pub net_pnl: f64,
+    /// Average net PnL per trade
+    pub expectancy: f64,
+    /// Average R multiple of the trades with a planned risk. `None` when
+    /// none of them have one.
+    pub average_r: Option<f64>,
+    /// How many trades `average_r` is over
+    pub r_count: usize,
+}
+
+impl BreakdownRow {
+    fn add(&mut self, trade: &Trade, net_pnl: f64) {
+        self.trade_count += 1;
+        self.net_pnl += net_pnl;
+        if net_pnl > 0.0 {
+            self.wins += 1;
+        }
+        if let Some(r_multiple) = trade.r_multiple {
+            self.average_r = Some(self.average_r.unwrap_or(0.0) + r_multiple);
+            self.r_count += 1;
+        }
+    }
+
+    fn finish(&mut self) {
+        self.win_rate = self.wins as f64 / self.trade_count as f64 * 100.0;
+        self.expectancy = self.net_pnl / self.trade_count as f64;
+        self.average_r = self.average_r.map(|total| total / self.r_count as f64);
+    }
+}
+
+/// Statistics for groups of trades, one row per group.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct Breakdown {
+    pub title: &'static str,
+    pub rows: Vec<BreakdownRow>,
+}
+
+impl Breakdown {
+    /// `groups` returns the position and name of every group a trade belongs to.
+    fn group<F>(title: &'static str, trades: &[Trade], groups: F) -> Self
+    where
+        F: Fn(&Trade) -> Vec<(u32, String)>,
+    {
+        let mut rows: BTreeMap<(u32, String), BreakdownRow> = BTreeMap::new();
+        for (trade, net_pnl) in trades::priced(trades) {
+            for (position, group) in groups(trade) {
+                rows.entry((position, group.clone()))
+                    .or_insert_with(|| BreakdownRow {
+                        group,
+                        position,
+                        ..Default::default()
+                    })
+                    .add(trade, net_pnl);
+            }
+        }
+
+        let mut rows: Vec<BreakdownRow> = rows.into_values().collect();
+        rows.iter_mut().for_each(BreakdownRow::finish);
+        Breakdown { title, rows }
+    }
+
+    /// Trades with several tags count towards each of them.
+    pub fn by_tag(trades: &[Trade]) -> Self {
+        Self::group("Tag", trades, |trade| {
+            if trade.tags.is_empty() {
+                vec![(1, UNTAGGED.to_string())]
+            } else {
+                trade.tags.iter().map(|tag| (0, tag.clone())).collect()
+            }
+        })
+    }
+
+    pub fn by_instrument(trades: &[Trade]) -> Self {
+        Self::group("Instrument", trades, |trade| vec![(0, trade.instrument.clone())])
+    }
+
+    /// By the weekday of the trading day the trade closed in
+    pub fn by_weekday(trades: &[Trade]) -> Self {
+        Self::group("Day of Week", trades, |trade| {
+            let weekday = trade.trading_day.weekday();
+            vec![(weekday.number_from_monday().into(), weekday.to_string())]
+        })
+    }
+
+    /// By the hour the trade was entered, on the session's clock
+    pub fn by_hour(trades: &[Trade]) -> Self {
+        Self::group("Hour of Day", trades, |trade| {
+            let hour = trade.local_entry_time.hour();
+            vec![(hour.into(), format!("{:02}:00", hour))]
+        })
+    }
+
+    pub fn by_duration(trades: &[Trade]) -> Self {
+        Self::group("Duration", trades, |trade| {
+            let seconds = (trade.exit_time - trade.entry_time).whole_seconds();
+            let position = DURATION_BUCKETS
+                .iter()
+                .position(|(limit, _)| seconds < *limit)
+                .unwrap_or(DURATION_BUCKETS.len() - 1);
+            vec![(position as u32, DURATION_BUCKETS[position].1.to_string())]
+        })
+    }
+
+    /// Every breakdown in the report
+    pub fn all(trades: &[Trade]) -> Vec<Breakdown> {
+        vec![
+            Self::by_tag(trades),
+            Self::by_instrument(trades),
+            Self::by_weekday(trades),
+            Self::by_hour(trades),
+            Self::by_duration(trades),
+        ]
+    }
+
+    /// Rows without an average R always sort last.
+    pub fn sort(&mut self, sort: BreakdownSort, descending: bool) {
+        let direction = |ordering: Ordering| if descending { ordering.reverse() } else { ordering };
+        self.rows.sort_by(|a, b| match sort {
+            BreakdownSort::Group => direction((a.position, &a.group).cmp(&(b.position, &b.group))),
+            BreakdownSort::Trades => direction(a.trade_count.cmp(&b.trade_count)),
+            BreakdownSort::WinRate => direction(a.win_rate.total_cmp(&b.win_rate)),
+            BreakdownSort::NetPnl => direction(a.net_pnl.total_cmp(&b.net_pnl)),
+            BreakdownSort::Expectancy => direction(a.expectancy.total_cmp(&b.expectancy)),
+            BreakdownSort::AverageR => match (a.average_r, b.average_r) {
+                (Some(a), Some(b)) => direction(a.total_cmp(&b)),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            },
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Breakdown, BreakdownSort};
+    use crate::trades::test_support::TradeBuilder;
+    use crate::trades::Trade;
+    use claims::assert_err;
+    use time::macros::datetime;
+    use time::OffsetDateTime;
+
+    fn trade(instrument: &str, tags: &[&str], net_pnl: f64, entry_time: OffsetDateTime, minutes: i64) -> Trade {
+        TradeBuilder::new()
+            .instrument(instrument)
+            .tags(tags)
+            .net_pnl(net_pnl)
+            .entered(entry_time, minutes)
+            .build()
+    }
+
+    fn trades() -> Vec<Trade> {
+        let mut risked = trade("NQ 09-24", &["ORB"], 300.0, datetime!(2024-07-23 09:35 UTC), 90);
+        risked.r_multiple = Some(2.0);
+        vec![
+            // Monday
+            trade("ES 09-24", &["ORB", "FOMO"], 100.0, datetime!(2024-07-22 09:31 UTC), 3),
+            trade("ES 09-24", &[], -50.0, datetime!(2024-07-22 14:05 UTC), 30),
+            // Tuesday
+            risked,
+            trade("ES 09-24", &["FOMO"], -250.0, datetime!(2024-07-23 09:50 UTC), 0),
+        ]
+    }
+
+    fn groups(breakdown: &Breakdown) -> Vec<&str> {
+        breakdown.rows.iter().map(|row| row.group.as_str()).collect()
+    }
+
+    #[test]
+    fn trades_count_towards_each_of_their_tags() {
+        let breakdown = Breakdown::by_tag(&trades());
+
+        assert_eq!(groups(&breakdown), vec!["FOMO", "ORB", "Untagged"]);
+        let fomo = &breakdown.rows[0];
+        assert_eq!(fomo.trade_count, 2);
+        assert_eq!(fomo.wins, 1);
+        assert_eq!(fomo.win_rate, 50.0);
+        assert_eq!(fomo.net_pnl, -150.0);
+        assert_eq!(fomo.expectancy, -75.0);
+        assert_eq!(fomo.average_r, None);
+
+        let orb = &breakdown.rows[1];
+        assert_eq!(orb.trade_count, 2);
+        assert_eq!(orb.average_r, Some(2.0));
+        assert_eq!(orb.r_count, 1);
+    }
+
+    #[test]
+    fn weekdays_hours_and_durations_are_in_natural_order() {
+        let trades = trades();
+        assert_eq!(groups(&Breakdown::by_weekday(&trades)), vec!["Monday", "Tuesday"]);
+        assert_eq!(groups(&Breakdown::by_hour(&trades)), vec!["09:00", "14:00"]);
+        assert_eq!(
+            groups(&Breakdown::by_duration(&trades)),
+            vec!["Under 1 minute", "1 to 5 minutes", "15 to 60 minutes", "1 to 4 hours"]
+        );
+        assert_eq!(groups(&Breakdown::by_instrument(&trades)), vec!["ES 09-24", "NQ 09-24"]);
+    }
+
+    #[test]
+    fn rows_are_sorted_by_any_column() {
+        let mut breakdown = Breakdown::by_tag(&trades());
+
+        breakdown.sort(BreakdownSort::NetPnl, true);
+        assert_eq!(groups(&breakdown), vec!["ORB", "Untagged", "FOMO"]);
+
+        breakdown.sort(BreakdownSort::NetPnl, false);
+        assert_eq!(groups(&breakdown), vec!["FOMO", "Untagged", "ORB"]);
+
+        breakdown.sort(BreakdownSort::Group, false);
+        assert_eq!(groups(&breakdown), vec!["FOMO", "ORB", "Untagged"]);
+    }
+
+    #[test]
+    fn rows_without_r_sort_last_both_ways() {
+        let mut breakdown = Breakdown::by_tag(&trades());
+
+        breakdown.sort(BreakdownSort::AverageR, true);
+        assert_eq!(breakdown.rows[0].group, "ORB");
+        breakdown.sort(BreakdownSort::AverageR, false);
+        assert_eq!(breakdown.rows[0].group, "ORB");
+    }
+
+    #[test]
+    fn sort_columns_are_parsed() {
+        for sort in ["group", "trades", "win_rate", "net_pnl", "expectancy", "average_r"] {
+            assert_eq!(BreakdownSort::parse(sort).unwrap().as_str(), sort);
+        }
+        assert_err!(BreakdownSort::parse("1; DROP TABLE trades"));
+        assert_err!(BreakdownSort::parse("profit"));
+    }
+}
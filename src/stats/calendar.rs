@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+use serde::Serialize;
+use time::{Date, Duration, Month};
+use time::macros::format_description;
+use utoipa::ToSchema;
+use crate::trades::{self, Trade};
+
+/// Number of color steps for profitable and losing days
+const INTENSITY_LEVELS: f64 = 4.0;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, ToSchema)]
+pub struct DaySummary {
+    pub trade_count: usize,
+    pub wins: usize,
+    /// Percentage of the day's trades that were winners, 0 to 100
+    pub win_rate: f64,
+    pub net_pnl: f64,
+}
+
+/// Net PnL, trade count and win rate for every trading day with priced trades.
+pub fn daily_summaries(trades: &[Trade]) -> BTreeMap<Date, DaySummary> {
+    let mut days: BTreeMap<Date, DaySummary> = BTreeMap::new();
+    for (trade, net_pnl) in trades::priced(trades) {
+        let day = days.entry(trade.trading_day).or_default();
+        day.trade_count += 1;
+        day.net_pnl += net_pnl;
+        if net_pnl > 0.0 {
+            day.wins += 1;
+        }
+    }
+    for day in days.values_mut() {
+        day.win_rate = day.wins as f64 / day.trade_count as f64 * 100.0;
+    }
+    days
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CalendarDay {
+    pub date: Date,
+    pub day: u8,
+    /// False for the days of the neighbouring months that fill out the weeks
+    pub in_month: bool,
+    pub summary: Option<DaySummary>,
+    /// 0 for days without PnL, otherwise 1 to 4 by the size of the day's
+    /// net PnL relative to the biggest day of the month
+    pub intensity: u8,
+}
+
+/// A month of trading days laid out in weeks starting on Sunday.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CalendarMonth {
+    /// e.g. "July 2024"
+    pub title: String,
+    /// The `YYYY-MM` of this and the neighbouring months, for navigation
+    pub month: String,
+    pub previous_month: String,
+    pub next_month: String,
+    pub weeks: Vec<Vec<CalendarDay>>,
+    pub trading_days: usize,
+    pub trade_count: usize,
+    pub net_pnl: f64,
+}
+
+impl CalendarMonth {
+    /// Parses `YYYY-MM` into the first day of that month.
+    pub fn parse_month(s: &str) -> Result<Date, String> {
+        let invalid = || format!("{} is not a valid month. Use the format YYYY-MM.", s);
+        let (year, month) = s.trim().split_once('-').ok_or_else(invalid)?;
+        let year: i32 = year.parse().map_err(|_| invalid())?;
+        let month: u8 = month.parse().map_err(|_| invalid())?;
+        let month = Month::try_from(month).map_err(|_| invalid())?;
+        Date::from_calendar_date(year, month, 1).map_err(|_| invalid())
+    }
+
+    pub fn first_day(date: Date) -> Date {
+        date.replace_day(1).expect("Every month has a first day")
+    }
+
+    pub fn last_day(date: Date) -> Date {
+        let days = time::util::days_in_year_month(date.year(), date.month());
+        date.replace_day(days).expect("The last day is within the month")
+    }
+
+    /// `trades` should be those closed in the month of `date`; any others are ignored.
+    pub fn new(date: Date, trades: &[Trade]) -> Self {
+        let first = Self::first_day(date);
+        let last = Self::last_day(date);
+        let summaries: BTreeMap<Date, DaySummary> = daily_summaries(trades)
+            .into_iter()
+            .filter(|(day, _)| (first..=last).contains(day))
+            .collect();
+        let largest = summaries.values().map(|day| day.net_pnl.abs()).fold(0.0, f64::max);
+
+        let mut weeks = Vec::new();
+        let mut day = first - Duration::days(first.weekday().number_days_from_sunday().into());
+        while day <= last {
+            let mut week = Vec::with_capacity(7);
+            for _ in 0..7 {
+                let summary = summaries.get(&day).cloned();
+                let intensity = match summary {
+                    Some(ref summary) if largest > 0.0 && summary.net_pnl != 0.0 => {
+                        (summary.net_pnl.abs() / largest * INTENSITY_LEVELS).ceil().clamp(1.0, INTENSITY_LEVELS) as u8
+                    }
+                    _ => 0,
+                };
+                week.push(CalendarDay {
+                    date: day,
+                    day: day.day(),
+                    in_month: day.month() == first.month(),
+                    summary,
+                    intensity,
+                });
+                day = day.next_day().expect("Calendar dates are in range");
+            }
+            weeks.push(week);
+        }
+
+        let previous = first.previous_day().expect("Calendar dates are in range");
+        let next = last.next_day().expect("Calendar dates are in range");
+        CalendarMonth {
+            title: format!("{} {}", first.month(), first.year()),
+            month: format_month(first),
+            previous_month: format_month(previous),
+            next_month: format_month(next),
+            weeks,
+            trading_days: summaries.len(),
+            trade_count: summaries.values().map(|day| day.trade_count).sum(),
+            net_pnl: summaries.values().map(|day| day.net_pnl).sum(),
+        }
+    }
+}
+
+fn format_month(date: Date) -> String {
+    date.format(format_description!("[year]-[month]"))
+        .expect("Dates can always be formatted")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{daily_summaries, CalendarMonth};
+    use crate::trades::test_support::TradeBuilder;
+    use crate::trades::Trade;
+    use claims::assert_err;
+    use time::macros::date;
+    use time::Date;
+
+    fn trade(net_pnl: f64, trading_day: Date) -> Trade {
+        TradeBuilder::new().net_pnl(net_pnl).trading_day(trading_day).build()
+    }
+
+    fn trades() -> Vec<Trade> {
+        vec![
+            trade(100.0, date!(2024-07-22)),
+            trade(-20.0, date!(2024-07-22)),
+            trade(-400.0, date!(2024-07-23)),
+            trade(50.0, date!(2024-08-01)),
+        ]
+    }
+
+    #[test]
+    fn trades_are_summarized_by_trading_day() {
+        let days = daily_summaries(&trades());
+        let day = &days[&date!(2024-07-22)];
+        assert_eq!(day.trade_count, 2);
+        assert_eq!(day.wins, 1);
+        assert_eq!(day.win_rate, 50.0);
+        assert_eq!(day.net_pnl, 80.0);
+        assert_eq!(days.len(), 3);
+    }
+
+    #[test]
+    fn months_are_laid_out_in_full_weeks() {
+        let month = CalendarMonth::new(date!(2024-07-15), &trades());
+
+        assert_eq!(month.title, "July 2024");
+        assert_eq!(month.month, "2024-07");
+        assert_eq!(month.previous_month, "2024-06");
+        assert_eq!(month.next_month, "2024-08");
+        // July 2024 starts on a Monday and ends on a Wednesday
+        assert_eq!(month.weeks.len(), 5);
+        assert_eq!(month.weeks[0][0].date, date!(2024-06-30));
+        assert!(!month.weeks[0][0].in_month);
+        assert_eq!(month.weeks[4][6].date, date!(2024-08-03));
+    }
+
+    #[test]
+    fn only_the_months_trades_are_counted() {
+        let month = CalendarMonth::new(date!(2024-07-01), &trades());
+        assert_eq!(month.trading_days, 2);
+        assert_eq!(month.trade_count, 3);
+        assert_eq!(month.net_pnl, -320.0);
+
+        // August 1st shows in the last week of July but without its PnL
+        assert_eq!(month.weeks[4][4].date, date!(2024-08-01));
+        assert_eq!(month.weeks[4][4].summary, None);
+    }
+
+    #[test]
+    fn intensity_is_relative_to_the_biggest_day() {
+        let month = CalendarMonth::new(date!(2024-07-01), &trades());
+        let day = |date: Date| month.weeks.iter().flatten().find(|day| day.date == date).unwrap();
+
+        assert_eq!(day(date!(2024-07-23)).intensity, 4);
+        // 80 is a fifth of 400
+        assert_eq!(day(date!(2024-07-22)).intensity, 1);
+        assert_eq!(day(date!(2024-07-24)).intensity, 0);
+    }
+
+    #[test]
+    fn months_are_parsed() {
+        assert_eq!(CalendarMonth::parse_month("2024-07"), Ok(date!(2024-07-01)));
+        assert_err!(CalendarMonth::parse_month("2024-13"));
+        assert_err!(CalendarMonth::parse_month("July"));
+        assert_err!(CalendarMonth::parse_month("07/2024"));
+    }
+}
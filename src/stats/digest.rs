@@ -0,0 +1,96 @@
+use std::collections::HashSet;
+use serde::Serialize;
+use crate::trades::{self, Trade};
+use super::PerformanceStats;
+
+/// What a digest email reports about a period's trades.
+#[derive(Debug, Clone, Serialize)]
+pub struct DigestStats {
+    pub performance: PerformanceStats,
+    /// The best trade with dollar PnL
+    pub best_trade: Option<Trade>,
+    /// `None` with fewer than two priced trades, so one trade isn't both
+    pub worst_trade: Option<Trade>,
+    /// Trades with notes, a rating or tags
+    pub journaled: usize,
+    /// Percentage of trades journaled, 0 to 100
+    pub journal_completion: f64,
+}
+
+impl DigestStats {
+    /// `trades` must be in the order they were closed. `noted` holds the ids
+    /// of trades with journal notes, which `Trade` doesn't carry.
+    pub fn new(trades: &[Trade], noted: &HashSet<uuid::Uuid>) -> Self {
+        let priced: Vec<(&Trade, f64)> = trades::priced(trades).collect();
+        let by_net_pnl = |a: &&(&Trade, f64), b: &&(&Trade, f64)| a.1.total_cmp(&b.1);
+        let best_trade = priced.iter().max_by(by_net_pnl).map(|(trade, _)| (*trade).clone());
+        let worst_trade = if priced.len() > 1 {
+            priced.iter().min_by(by_net_pnl).map(|(trade, _)| (*trade).clone())
+        } else {
+            None
+        };
+        let journaled = trades
+            .iter()
+            .filter(|trade| noted.contains(&trade.id) || trade.rating.is_some() || !trade.tags.is_empty())
+            .count();
+        let journal_completion = if trades.is_empty() {
+            0.0
+        } else {
+            journaled as f64 / trades.len() as f64 * 100.0
+        };
+
+        DigestStats {
+            performance: PerformanceStats::from_trades(trades),
+            best_trade,
+            worst_trade,
+            journaled,
+            journal_completion,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DigestStats;
+    use crate::trades::test_support::TradeBuilder;
+    use crate::trades::Trade;
+    use std::collections::HashSet;
+
+    fn trade(net_pnl: f64) -> Trade {
+        TradeBuilder::new().net_pnl(net_pnl).build()
+    }
+
+    #[test]
+    fn best_and_worst_trades_and_journal_completion_are_found() {
+        let mut rated = trade(-50.0);
+        rated.rating = Some(2);
+        let mut tagged = trade(120.0);
+        tagged.tags = vec!["ORB".to_string()];
+        let noted = trade(-200.0);
+        let trades = vec![trade(10.0), rated, tagged, noted.clone()];
+
+        let digest = DigestStats::new(&trades, &HashSet::from([noted.id]));
+        assert_eq!(digest.performance.trade_count, 4);
+        assert_eq!(digest.performance.net_pnl, -120.0);
+        assert_eq!(digest.best_trade.unwrap().net_pnl, Some(120.0));
+        assert_eq!(digest.worst_trade.unwrap().id, noted.id);
+        assert_eq!(digest.journaled, 3);
+        assert_eq!(digest.journal_completion, 75.0);
+    }
+
+    #[test]
+    fn one_trade_is_only_the_best() {
+        let digest = DigestStats::new(&[trade(-30.0)], &HashSet::new());
+        assert_eq!(digest.best_trade.unwrap().net_pnl, Some(-30.0));
+        assert!(digest.worst_trade.is_none());
+        assert_eq!(digest.journal_completion, 0.0);
+    }
+
+    #[test]
+    fn no_trades_have_nothing_to_report() {
+        let digest = DigestStats::new(&[], &HashSet::new());
+        assert_eq!(digest.performance.trade_count, 0);
+        assert!(digest.best_trade.is_none());
+        assert_eq!(digest.journal_completion, 0.0);
+    }
+}
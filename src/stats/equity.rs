@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+use time::{Date, OffsetDateTime};
+use utoipa::ToSchema;
+use crate::trades::{self, Trade};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct EquityPoint {
+    pub trade_id: uuid::Uuid,
+    #[serde(with = "time::serde::rfc3339")]
+    pub time: OffsetDateTime,
+    pub net_pnl: f64,
+    /// Cumulative net PnL after this trade
+    pub equity: f64,
+    /// Distance below the running peak of `equity`, zero or negative
+    pub drawdown: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct DailyEquityPoint {
+    pub date: Date,
+    pub trade_count: usize,
+    pub net_pnl: f64,
+    pub equity: f64,
+    pub drawdown: f64,
+}
+
+/// Cumulative net PnL after every trade and at the end of every day.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct EquityCurve {
+    pub per_trade: Vec<EquityPoint>,
+    pub per_day: Vec<DailyEquityPoint>,
+}
+
+impl EquityCurve {
+    /// `trades` must be in the order they were closed. Days are trading days.
+    pub fn from_trades(trades: &[Trade]) -> Self {
+        let mut curve = EquityCurve::default();
+        let mut equity = 0.0_f64;
+        let mut peak = 0.0_f64;
+
+        for (trade, net_pnl) in trades::priced(trades) {
+            equity += net_pnl;
+            peak = peak.max(equity);
+            let drawdown = equity - peak;
+
+            curve.per_trade.push(EquityPoint {
+                trade_id: trade.id,
+                time: trade.exit_time,
+                net_pnl,
+                equity,
+                drawdown,
+            });
+
+            let date = trade.trading_day;
+            match curve.per_day.last_mut() {
+                Some(day) if day.date == date => {
+                    day.trade_count += 1;
+                    day.net_pnl += net_pnl;
+                    day.equity = equity;
+                    day.drawdown = drawdown;
+                }
+                _ => curve.per_day.push(DailyEquityPoint {
+                    date,
+                    trade_count: 1,
+                    net_pnl,
+                    equity,
+                    drawdown,
+                }),
+            }
+        }
+
+        curve
+    }
+}
+
+/// SVG polyline coordinates for the per trade equity and drawdown series,
+/// scaled to a `width` by `height` viewBox with y growing downwards.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EquityChart {
+    pub width: f64,
+    pub height: f64,
+    pub equity_points: String,
+    pub drawdown_points: String,
+    /// Where the zero line sits
+    pub zero_y: f64,
+    pub max_value: f64,
+    pub min_value: f64,
+}
+
+impl EquityChart {
+    /// Returns `None` when there are no trades to plot.
+    pub fn new(curve: &EquityCurve, width: f64, height: f64) -> Option<Self> {
+        if curve.per_trade.is_empty() {
+            return None;
+        }
+
+        let max_value = curve.per_trade.iter().map(|p| p.equity).fold(0.0, f64::max);
+        let min_value = curve
+            .per_trade
+            .iter()
+            .map(|p| p.equity.min(p.drawdown))
+            .fold(0.0, f64::min);
+        let range = if max_value > min_value { max_value - min_value } else { 1.0 };
+        let steps = curve.per_trade.len() as f64;
+
+        let x = |i: usize| i as f64 / steps * width;
+        let y = |value: f64| (max_value - value) / range * height;
+
+        // Both series start flat at zero before the first trade
+        let mut equity_points = vec![format!("{:.1},{:.1}", x(0), y(0.0))];
+        let mut drawdown_points = equity_points.clone();
+        for (i, point) in curve.per_trade.iter().enumerate() {
+            equity_points.push(format!("{:.1},{:.1}", x(i + 1), y(point.equity)));
+            drawdown_points.push(format!("{:.1},{:.1}", x(i + 1), y(point.drawdown)));
+        }
+
+        Some(EquityChart {
+            width,
+            height,
+            equity_points: equity_points.join(" "),
+            drawdown_points: drawdown_points.join(" "),
+            zero_y: y(0.0),
+            max_value,
+            min_value,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EquityChart, EquityCurve};
+    use crate::trades::test_support::TradeBuilder;
+    use crate::trades::Trade;
+    use time::macros::{date, datetime};
+    use time::OffsetDateTime;
+
+    fn trade(net_pnl: f64, exit_time: OffsetDateTime) -> Trade {
+        TradeBuilder::new().net_pnl(net_pnl).exited(exit_time).build()
+    }
+
+    fn trades() -> Vec<Trade> {
+        vec![
+            trade(100.0, datetime!(2024-07-22 14:00 UTC)),
+            trade(-150.0, datetime!(2024-07-22 15:00 UTC)),
+            trade(20.0, datetime!(2024-07-23 14:00 UTC)),
+            trade(200.0, datetime!(2024-07-25 14:00 UTC)),
+        ]
+    }
+
+    #[test]
+    fn equity_and_drawdown_accumulate_per_trade() {
+        let curve = EquityCurve::from_trades(&trades());
+
+        let equity: Vec<f64> = curve.per_trade.iter().map(|p| p.equity).collect();
+        let drawdown: Vec<f64> = curve.per_trade.iter().map(|p| p.drawdown).collect();
+        assert_eq!(equity, vec![100.0, -50.0, -30.0, 170.0]);
+        assert_eq!(drawdown, vec![0.0, -150.0, -130.0, 0.0]);
+    }
+
+    #[test]
+    fn days_hold_their_closing_equity() {
+        let curve = EquityCurve::from_trades(&trades());
+
+        assert_eq!(curve.per_day.len(), 3);
+        assert_eq!(curve.per_day[0].date, date!(2024-07-22));
+        assert_eq!(curve.per_day[0].trade_count, 2);
+        assert_eq!(curve.per_day[0].net_pnl, -50.0);
+        assert_eq!(curve.per_day[0].equity, -50.0);
+        assert_eq!(curve.per_day[0].drawdown, -150.0);
+        assert_eq!(curve.per_day[2].date, date!(2024-07-25));
+        assert_eq!(curve.per_day[2].equity, 170.0);
+    }
+
+    #[test]
+    fn no_trades_have_no_chart() {
+        assert_eq!(EquityChart::new(&EquityCurve::default(), 600.0, 200.0), None);
+    }
+
+    #[test]
+    fn chart_is_scaled_to_the_view_box() {
+        let curve = EquityCurve::from_trades(&trades());
+        let chart = EquityChart::new(&curve, 400.0, 320.0).unwrap();
+
+        // Values run from -150 (bottom) to 170 (top)
+        assert_eq!(chart.max_value, 170.0);
+        assert_eq!(chart.min_value, -150.0);
+        assert_eq!(chart.zero_y, 170.0);
+        assert_eq!(
+            chart.equity_points,
+            "0.0,170.0 100.0,70.0 200.0,220.0 300.0,200.0 400.0,0.0"
+        );
+        assert_eq!(
+            chart.drawdown_points,
+            "0.0,170.0 100.0,170.0 200.0,320.0 300.0,300.0 400.0,170.0"
+        );
+    }
+}
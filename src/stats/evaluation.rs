@@ -0,0 +1,340 @@
+use serde::Serialize;
+use time::Date;
+use crate::domain::{DrawdownType, EvaluationRules};
+use crate::trades::{self, Trade};
+
+pub const PROFIT_TARGET: &str = "profit_target";
+pub const MAX_DRAWDOWN: &str = "max_drawdown";
+pub const DAILY_LOSS_LIMIT: &str = "daily_loss_limit";
+pub const MIN_TRADING_DAYS: &str = "min_trading_days";
+pub const CONSISTENCY: &str = "consistency";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Standing {
+    /// The rule is met, or hasn't been broken
+    Passing,
+    /// The rule was broken. Evaluations can't recover from this.
+    Failed,
+    /// The rule hasn't been met yet
+    InProgress,
+}
+
+/// Where the account stands against one rule.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RuleStatus {
+    pub rule: &'static str,
+    pub standing: Standing,
+    /// The rule's setting, in dollars, days or percent
+    pub limit: f64,
+    /// The account's current value against `limit`
+    pub current: f64,
+    /// Room left before a limit is broken, or how far is left to a target.
+    /// `None` when it can't be worked out yet.
+    pub remaining: Option<f64>,
+}
+
+/// A trade that broke a rule.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Breach {
+    pub rule: &'static str,
+    pub trade_id: uuid::Uuid,
+    pub trading_day: Date,
+    /// The balance for drawdowns, or the day's net PnL for daily losses
+    pub value: f64,
+    /// The level that was broken
+    pub limit: f64,
+}
+
+/// An evaluation account checked against its rules.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Evaluation {
+    pub standing: Standing,
+    pub balance: f64,
+    pub rules: Vec<RuleStatus>,
+    /// Drawdown breaches happen once. Daily loss breaches happen at most
+    /// once a trading day.
+    pub breaches: Vec<Breach>,
+}
+
+impl Evaluation {
+    /// `trades` must be in the order they were closed. `today` is the current
+    /// trading day, whose daily loss and end of day balance are still open.
+    ///
+    /// Drawdowns are checked on closed trades only, as executions don't
+    /// include the open PnL in between.
+    pub fn new(rules: &EvaluationRules, starting_balance: f64, trades: &[Trade], today: Date) -> Self {
+        let mut balance = starting_balance;
+        let mut high_water_mark = starting_balance;
+        let mut end_of_day_high_water_mark = starting_balance;
+        let mut day_pnls: Vec<(Date, f64)> = Vec::new();
+        let mut breaches = Vec::new();
+        let mut drawdown_breached = false;
+
+        for (trade, net_pnl) in trades::priced(trades) {
+            match day_pnls.last_mut() {
+                Some((day, pnl)) if *day == trade.trading_day => *pnl += net_pnl,
+                _ => {
+                    end_of_day_high_water_mark = end_of_day_high_water_mark.max(balance);
+                    day_pnls.push((trade.trading_day, net_pnl));
+                }
+            }
+            balance += net_pnl;
+
+            if let Some(max_drawdown) = rules.max_drawdown {
+                let peak = match rules.drawdown_type {
+                    DrawdownType::Intraday => high_water_mark,
+                    DrawdownType::EndOfDay => end_of_day_high_water_mark,
+                };
+                if !drawdown_breached && balance <= peak - max_drawdown {
+                    drawdown_breached = true;
+                    breaches.push(Breach {
+                        rule: MAX_DRAWDOWN,
+                        trade_id: trade.id,
+                        trading_day: trade.trading_day,
+                        value: balance,
+                        limit: peak - max_drawdown,
+                    });
+                }
+            }
+            high_water_mark = high_water_mark.max(balance);
+
+            if let Some(daily_loss_limit) = rules.daily_loss_limit {
+                let day_pnl = day_pnls.last().map(|(_, pnl)| *pnl).unwrap_or_default();
+                let breached_today = breaches
+                    .iter()
+                    .any(|breach| breach.rule == DAILY_LOSS_LIMIT && breach.trading_day == trade.trading_day);
+                if !breached_today && day_pnl <= -daily_loss_limit {
+                    breaches.push(Breach {
+                        rule: DAILY_LOSS_LIMIT,
+                        trade_id: trade.id,
+                        trading_day: trade.trading_day,
+                        value: day_pnl,
+                        limit: -daily_loss_limit,
+                    });
+                }
+            }
+        }
+        if day_pnls.last().is_some_and(|(day, _)| *day != today) {
+            end_of_day_high_water_mark = end_of_day_high_water_mark.max(balance);
+        }
+
+        let net_pnl = balance - starting_balance;
+        let breached = |rule: &str| breaches.iter().any(|breach| breach.rule == rule);
+        let mut statuses = Vec::new();
+
+        if let Some(target) = rules.profit_target {
+            statuses.push(RuleStatus {
+                rule: PROFIT_TARGET,
+                standing: if net_pnl >= target { Standing::Passing } else { Standing::InProgress },
+                limit: target,
+                current: net_pnl,
+                remaining: Some((target - net_pnl).max(0.0)),
+            });
+        }
+        if let Some(max_drawdown) = rules.max_drawdown {
+            let peak = match rules.drawdown_type {
+                DrawdownType::Intraday => high_water_mark,
+                DrawdownType::EndOfDay => end_of_day_high_water_mark,
+            };
+            let floor = peak - max_drawdown;
+            statuses.push(RuleStatus {
+                rule: MAX_DRAWDOWN,
+                standing: if breached(MAX_DRAWDOWN) { Standing::Failed } else { Standing::Passing },
+                limit: floor,
+                current: balance,
+                remaining: Some((balance - floor).max(0.0)),
+            });
+        }
+        if let Some(daily_loss_limit) = rules.daily_loss_limit {
+            let today_pnl = day_pnls
+                .last()
+                .filter(|(day, _)| *day == today)
+                .map(|(_, pnl)| *pnl)
+                .unwrap_or_default();
+            statuses.push(RuleStatus {
+                rule: DAILY_LOSS_LIMIT,
+                standing: if breached(DAILY_LOSS_LIMIT) { Standing::Failed } else { Standing::Passing },
+                limit: daily_loss_limit,
+                current: today_pnl,
+                remaining: Some((daily_loss_limit + today_pnl).max(0.0)),
+            });
+        }
+        if let Some(min_trading_days) = rules.min_trading_days {
+            let trading_days = day_pnls.len() as f64;
+            let min_trading_days = f64::from(min_trading_days);
+            statuses.push(RuleStatus {
+                rule: MIN_TRADING_DAYS,
+                standing: if trading_days >= min_trading_days { Standing::Passing } else { Standing::InProgress },
+                limit: min_trading_days,
+                current: trading_days,
+                remaining: Some((min_trading_days - trading_days).max(0.0)),
+            });
+        }
+        if let Some(consistency_percent) = rules.consistency_percent {
+            let best_day = day_pnls.iter().map(|(_, pnl)| *pnl).fold(0.0, f64::max);
+            // The total profit needed for the best day to be a small enough share of it
+            let needed = best_day / (consistency_percent / 100.0);
+            let (standing, current) = if net_pnl > 0.0 {
+                let share = best_day / net_pnl * 100.0;
+                let standing = if share <= consistency_percent { Standing::Passing } else { Standing::InProgress };
+                (standing, share)
+            } else {
+                (Standing::InProgress, 0.0)
+            };
+            statuses.push(RuleStatus {
+                rule: CONSISTENCY,
+                standing,
+                limit: consistency_percent,
+                current,
+                remaining: (net_pnl > 0.0).then_some((needed - net_pnl).max(0.0)),
+            });
+        }
+
+        let standing = if statuses.iter().any(|status| status.standing == Standing::Failed) {
+            Standing::Failed
+        } else if statuses.iter().all(|status| status.standing == Standing::Passing) {
+            Standing::Passing
+        } else {
+            Standing::InProgress
+        };
+
+        Evaluation {
+            standing,
+            balance,
+            rules: statuses,
+            breaches,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Evaluation, Standing, CONSISTENCY, DAILY_LOSS_LIMIT, MAX_DRAWDOWN};
+    use crate::domain::{DrawdownType, EvaluationRules};
+    use crate::trades::test_support::TradeBuilder;
+    use crate::trades::Trade;
+    use time::macros::date;
+    use time::Date;
+
+    const STARTING_BALANCE: f64 = 50_000.0;
+
+    fn trade(trading_day: Date, net_pnl: f64) -> Trade {
+        TradeBuilder::new().net_pnl(net_pnl).trading_day(trading_day).build()
+    }
+
+    fn rules() -> EvaluationRules {
+        EvaluationRules {
+            profit_target: Some(3000.0),
+            max_drawdown: Some(2000.0),
+            drawdown_type: DrawdownType::Intraday,
+            daily_loss_limit: Some(1000.0),
+            min_trading_days: Some(3),
+            consistency_percent: Some(50.0),
+        }
+    }
+
+    fn status<'a>(evaluation: &'a Evaluation, rule: &str) -> &'a super::RuleStatus {
+        evaluation.rules.iter().find(|status| status.rule == rule).unwrap()
+    }
+
+    #[test]
+    fn evaluation_passes_when_every_rule_is_met() {
+        let trades = vec![
+            trade(date!(2024-07-22), 1200.0),
+            trade(date!(2024-07-23), 1000.0),
+            trade(date!(2024-07-24), 900.0),
+        ];
+        let evaluation = Evaluation::new(&rules(), STARTING_BALANCE, &trades, date!(2024-07-25));
+
+        assert_eq!(evaluation.standing, Standing::Passing);
+        assert_eq!(evaluation.balance, 53_100.0);
+        assert!(evaluation.breaches.is_empty());
+        // 1,200 of 3,100 is under half
+        assert_eq!(status(&evaluation, CONSISTENCY).remaining, Some(0.0));
+    }
+
+    #[test]
+    fn evaluation_is_in_progress_until_the_target_and_days_are_reached() {
+        let trades = vec![trade(date!(2024-07-22), 2500.0)];
+        let evaluation = Evaluation::new(&rules(), STARTING_BALANCE, &trades, date!(2024-07-22));
+
+        assert_eq!(evaluation.standing, Standing::InProgress);
+        let consistency = status(&evaluation, CONSISTENCY);
+        assert_eq!(consistency.current, 100.0);
+        // The best day needs 5,000 of total profit to be half of it
+        assert_eq!(consistency.remaining, Some(2500.0));
+        assert_eq!(evaluation.rules[0].remaining, Some(500.0));
+    }
+
+    #[test]
+    fn intraday_drawdown_trails_the_highest_balance() {
+        let trades = vec![
+            trade(date!(2024-07-22), 1500.0),
+            trade(date!(2024-07-22), -900.0),
+            trade(date!(2024-07-23), -700.0),
+            trade(date!(2024-07-23), -400.0),
+        ];
+        let mut rules = rules();
+        rules.daily_loss_limit = None;
+        let evaluation = Evaluation::new(&rules, STARTING_BALANCE, &trades, date!(2024-07-23));
+
+        assert_eq!(evaluation.standing, Standing::Failed);
+        assert_eq!(evaluation.breaches.len(), 1);
+        let breach = &evaluation.breaches[0];
+        assert_eq!(breach.rule, MAX_DRAWDOWN);
+        assert_eq!(breach.trade_id, trades[3].id);
+        assert_eq!(breach.limit, 49_500.0);
+        assert_eq!(breach.value, 49_500.0);
+    }
+
+    #[test]
+    fn end_of_day_drawdown_trails_the_highest_close() {
+        // Peaks intraday at 51,500 but closes the day at 50,600
+        let trades = vec![
+            trade(date!(2024-07-22), 1500.0),
+            trade(date!(2024-07-22), -900.0),
+            trade(date!(2024-07-23), -700.0),
+            trade(date!(2024-07-23), -400.0),
+        ];
+        let mut rules = rules();
+        rules.daily_loss_limit = None;
+        rules.drawdown_type = DrawdownType::EndOfDay;
+        let evaluation = Evaluation::new(&rules, STARTING_BALANCE, &trades, date!(2024-07-23));
+
+        assert!(evaluation.breaches.is_empty());
+        let drawdown = status(&evaluation, MAX_DRAWDOWN);
+        assert_eq!(drawdown.standing, Standing::Passing);
+        assert_eq!(drawdown.limit, 48_600.0);
+        assert_eq!(drawdown.remaining, Some(900.0));
+    }
+
+    #[test]
+    fn daily_loss_limit_is_breached_once_a_day() {
+        let trades = vec![
+            trade(date!(2024-07-22), -600.0),
+            trade(date!(2024-07-22), -500.0),
+            trade(date!(2024-07-22), -100.0),
+            trade(date!(2024-07-23), -300.0),
+        ];
+        let mut rules = rules();
+        rules.max_drawdown = None;
+        let evaluation = Evaluation::new(&rules, STARTING_BALANCE, &trades, date!(2024-07-23));
+
+        assert_eq!(evaluation.breaches.len(), 1);
+        assert_eq!(evaluation.breaches[0].rule, DAILY_LOSS_LIMIT);
+        assert_eq!(evaluation.breaches[0].trade_id, trades[1].id);
+        assert_eq!(evaluation.breaches[0].value, -1100.0);
+        let daily_loss = status(&evaluation, DAILY_LOSS_LIMIT);
+        assert_eq!(daily_loss.standing, Standing::Failed);
+        assert_eq!(daily_loss.current, -300.0);
+        assert_eq!(daily_loss.remaining, Some(700.0));
+    }
+
+    #[test]
+    fn accounts_without_rules_pass() {
+        let evaluation = Evaluation::new(&EvaluationRules::default(), STARTING_BALANCE, &[], date!(2024-07-22));
+        assert_eq!(evaluation.standing, Standing::Passing);
+        assert!(evaluation.rules.is_empty());
+    }
+}
@@ -0,0 +1,16 @@
+//! src/stats/mod.rs
+//! Statistics computed from a user's trades. Nothing in here touches the
+//! database, so callers load the trades first.
+mod breakdown;
+mod calendar;
+mod digest;
+mod equity;
+mod evaluation;
+mod performance;
+
+pub use breakdown::{Breakdown, BreakdownRow, BreakdownSort};
+pub use calendar::{daily_summaries, CalendarDay, CalendarMonth, DaySummary};
+pub use digest::DigestStats;
+pub use equity::{DailyEquityPoint, EquityChart, EquityCurve, EquityPoint};
+pub use evaluation::{Breach, Evaluation, RuleStatus, Standing};
+pub use performance::PerformanceStats;
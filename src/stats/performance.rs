@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use crate::trades::Trade;
+
+/// Headline statistics for a set of trades, all in dollars after commission.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct PerformanceStats {
+    pub trade_count: usize,
+    pub wins: usize,
+    pub losses: usize,
+    pub breakeven: usize,
+    /// Percentage of trades that were winners, 0 to 100
+    pub win_rate: f64,
+    pub net_pnl: f64,
+    pub gross_profit: f64,
+    pub gross_loss: f64,
+    /// Gross profit over gross loss. `None` when there are no losing trades.
+    pub profit_factor: Option<f64>,
+    pub average_win: f64,
+    pub average_loss: f64,
+    /// Average net PnL per trade
+    pub expectancy: f64,
+    pub largest_win: f64,
+    pub largest_loss: f64,
+    /// Largest drop from a peak of the cumulative net PnL, zero or negative
+    pub max_drawdown: f64,
+    pub longest_win_streak: usize,
+    pub longest_loss_streak: usize,
+}
+
+impl PerformanceStats {
+    /// `trades` must be in the order they were closed for drawdown and streaks.
+    /// Trades without dollar PnL are left out.
+    pub fn from_trades(trades: &[Trade]) -> Self {
+        Self::from_net_pnls(trades.iter().filter_map(|trade| trade.net_pnl))
+    }
+
+    pub fn from_net_pnls<I>(net_pnls: I) -> Self
+    where
+        I: IntoIterator<Item = f64>,
+    {
+        let mut stats = PerformanceStats::default();
+        let mut equity = 0.0_f64;
+        let mut peak = 0.0_f64;
+        let mut win_streak = 0;
+        let mut loss_streak = 0;
+
+        for net_pnl in net_pnls {
+            stats.trade_count += 1;
+            stats.net_pnl += net_pnl;
+
+            if net_pnl > 0.0 {
+                stats.wins += 1;
+                stats.gross_profit += net_pnl;
+                stats.largest_win = stats.largest_win.max(net_pnl);
+                win_streak += 1;
+                loss_streak = 0;
+            } else if net_pnl < 0.0 {
+                stats.losses += 1;
+                stats.gross_loss += net_pnl.abs();
+                stats.largest_loss = stats.largest_loss.min(net_pnl);
+                loss_streak += 1;
+                win_streak = 0;
+            } else {
+                stats.breakeven += 1;
+                win_streak = 0;
+                loss_streak = 0;
+            }
+            stats.longest_win_streak = stats.longest_win_streak.max(win_streak);
+            stats.longest_loss_streak = stats.longest_loss_streak.max(loss_streak);
+
+            equity += net_pnl;
+            peak = peak.max(equity);
+            stats.max_drawdown = stats.max_drawdown.min(equity - peak);
+        }
+
+        if stats.trade_count > 0 {
+            stats.win_rate = stats.wins as f64 / stats.trade_count as f64 * 100.0;
+            stats.expectancy = stats.net_pnl / stats.trade_count as f64;
+        }
+        if stats.wins > 0 {
+            stats.average_win = stats.gross_profit / stats.wins as f64;
+        }
+        if stats.losses > 0 {
+            stats.average_loss = -stats.gross_loss / stats.losses as f64;
+            stats.profit_factor = Some(stats.gross_profit / stats.gross_loss);
+        }
+
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PerformanceStats;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "expected {} but got {}", expected, actual);
+    }
+
+    #[test]
+    fn no_trades_have_empty_stats() {
+        let stats = PerformanceStats::from_net_pnls(Vec::new());
+        assert_eq!(stats, PerformanceStats::default());
+    }
+
+    #[test]
+    fn wins_and_losses_are_summarized() {
+        let stats = PerformanceStats::from_net_pnls(vec![100.0, -50.0, 200.0, -25.0, 0.0]);
+
+        assert_eq!(stats.trade_count, 5);
+        assert_eq!(stats.wins, 2);
+        assert_eq!(stats.losses, 2);
+        assert_eq!(stats.breakeven, 1);
+        assert_close(stats.win_rate, 40.0);
+        assert_close(stats.net_pnl, 225.0);
+        assert_close(stats.gross_profit, 300.0);
+        assert_close(stats.gross_loss, 75.0);
+        assert_close(stats.profit_factor.unwrap(), 4.0);
+        assert_close(stats.average_win, 150.0);
+        assert_close(stats.average_loss, -37.5);
+        assert_close(stats.expectancy, 45.0);
+        assert_close(stats.largest_win, 200.0);
+        assert_close(stats.largest_loss, -50.0);
+    }
+
+    #[test]
+    fn profit_factor_is_undefined_without_losses() {
+        let stats = PerformanceStats::from_net_pnls(vec![10.0, 20.0]);
+        assert_eq!(stats.profit_factor, None);
+        assert_close(stats.average_loss, 0.0);
+    }
+
+    #[test]
+    fn max_drawdown_is_measured_from_the_running_peak() {
+        // Equity: 100, 40, 90, -10, 50
+        let stats = PerformanceStats::from_net_pnls(vec![100.0, -60.0, 50.0, -100.0, 60.0]);
+        assert_close(stats.max_drawdown, -110.0);
+    }
+
+    #[test]
+    fn drawdown_counts_losses_from_the_start() {
+        let stats = PerformanceStats::from_net_pnls(vec![-30.0, -20.0, 10.0]);
+        assert_close(stats.max_drawdown, -50.0);
+    }
+
+    #[test]
+    fn streaks_are_reset_by_other_outcomes() {
+        let stats = PerformanceStats::from_net_pnls(vec![1.0, 1.0, -1.0, 1.0, 1.0, 1.0, 0.0, -1.0, -1.0]);
+        assert_eq!(stats.longest_win_streak, 3);
+        assert_eq!(stats.longest_loss_streak, 2);
+    }
+}
@@ -0,0 +1,219 @@
+//! src/stats_cache.rs
+//! Computed statistics cached per user and trade filter, so charts don't
+//! recompute them from every trade on each request. Anything that changes a
+//! user's trades calls `invalidate`, and entries also expire after
+//! `stats_cache.ttl_seconds` in case a change is missed.
+//!
+//! The cache is only ever a shortcut: when it can't be reached, statistics
+//! are computed as if nothing was cached.
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tower_sessions_redis_store::fred::prelude::{HashesInterface, KeysInterface, RedisPool};
+use uuid::Uuid;
+use crate::configuration::{StatsCacheBackend, StatsCacheSettings};
+use crate::startup::AppState;
+use crate::trades::TradeFilter;
+
+/// Where cached statistics are kept. Picked by `stats_cache.backend` in the
+/// configuration.
+#[async_trait]
+pub trait StatsCache: Send + Sync {
+    async fn get(&self, user_id: Uuid, key: &str) -> Result<Option<String>, anyhow::Error>;
+
+    async fn set(&self, user_id: Uuid, key: &str, value: String) -> Result<(), anyhow::Error>;
+
+    /// Forgets everything cached for the user.
+    async fn invalidate(&self, user_id: Uuid) -> Result<(), anyhow::Error>;
+}
+
+/// The `StatsCache` for `settings.backend`. `redis` must be connected when
+/// the backend is Redis.
+pub fn cache_from_settings(
+    settings: &StatsCacheSettings,
+    redis: Option<&RedisPool>,
+) -> Result<Arc<dyn StatsCache>, anyhow::Error> {
+    let ttl = Duration::from_secs(settings.ttl_seconds);
+    Ok(match settings.backend {
+        StatsCacheBackend::None => Arc::new(NoStatsCache),
+        StatsCacheBackend::Memory => Arc::new(InMemoryStatsCache::new(ttl)),
+        StatsCacheBackend::Redis => {
+            let redis = redis.ok_or_else(|| anyhow::anyhow!("The Redis stats cache needs a Redis connection"))?;
+            Arc::new(RedisStatsCache::new(redis.clone(), ttl))
+        }
+    })
+}
+
+/// The statistics called `name` for the user's trades matching `filter`,
+/// from the cache if they're there, or from `compute` and then cached.
+pub async fn cached<T, E, F, Fut>(
+    state: &AppState,
+    user_id: Uuid,
+    name: &str,
+    filter: &TradeFilter,
+    compute: F,
+) -> Result<T, E>
+where
+    T: Serialize + DeserializeOwned,
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let key = format!("{}:{}", name, serde_json::to_string(filter).unwrap_or_default());
+    match state.stats_cache.get(user_id, &key).await {
+        Ok(Some(value)) => match serde_json::from_str(&value) {
+            Ok(stats) => return Ok(stats),
+            // Left from an older version of the statistics
+            Err(err) => tracing::warn!(error = %err, key, "Ignored unreadable cached statistics"),
+        },
+        Ok(None) => {}
+        Err(err) => tracing::warn!(error = %err, "Failed to read the stats cache"),
+    }
+
+    let stats = compute().await?;
+    match serde_json::to_string(&stats) {
+        Ok(value) => {
+            if let Err(err) = state.stats_cache.set(user_id, &key, value).await {
+                tracing::warn!(error = %err, "Failed to write to the stats cache");
+            }
+        }
+        Err(err) => tracing::warn!(error = %err, key, "Failed to serialize statistics for the cache"),
+    }
+    Ok(stats)
+}
+
+/// Forgets the user's cached statistics after their trades changed. The change
+/// has already happened, so a cache that can't be reached is only logged.
+pub async fn invalidate(state: &AppState, user_id: Uuid) {
+    if let Err(err) = state.stats_cache.invalidate(user_id).await {
+        tracing::error!(error = %err, %user_id, "Failed to clear cached statistics");
+    }
+}
+
+/// Caches nothing.
+pub struct NoStatsCache;
+
+#[async_trait]
+impl StatsCache for NoStatsCache {
+    async fn get(&self, _user_id: Uuid, _key: &str) -> Result<Option<String>, anyhow::Error> {
+        Ok(None)
+    }
+
+    async fn set(&self, _user_id: Uuid, _key: &str, _value: String) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+
+    async fn invalidate(&self, _user_id: Uuid) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+}
+
+/// A user's cached statistics by key, with when each was cached
+type UserEntries = HashMap<String, (Instant, String)>;
+
+/// Keeps statistics in this app instance's memory, for a single instance or
+/// tests without Redis.
+pub struct InMemoryStatsCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<Uuid, UserEntries>>,
+}
+
+impl InMemoryStatsCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+#[async_trait]
+impl StatsCache for InMemoryStatsCache {
+    async fn get(&self, user_id: Uuid, key: &str) -> Result<Option<String>, anyhow::Error> {
+        let entries = self.entries.lock().expect("The stats cache lock was poisoned");
+        Ok(entries
+            .get(&user_id)
+            .and_then(|user_entries| user_entries.get(key))
+            .filter(|(cached_at, _)| cached_at.elapsed() < self.ttl)
+            .map(|(_, value)| value.clone()))
+    }
+
+    async fn set(&self, user_id: Uuid, key: &str, value: String) -> Result<(), anyhow::Error> {
+        let mut entries = self.entries.lock().expect("The stats cache lock was poisoned");
+        let user_entries = entries.entry(user_id).or_default();
+        user_entries.retain(|_, (cached_at, _)| cached_at.elapsed() < self.ttl);
+        user_entries.insert(key.to_string(), (Instant::now(), value));
+        Ok(())
+    }
+
+    async fn invalidate(&self, user_id: Uuid) -> Result<(), anyhow::Error> {
+        self.entries.lock().expect("The stats cache lock was poisoned").remove(&user_id);
+        Ok(())
+    }
+}
+
+/// Keeps each user's statistics in one Redis hash, so invalidating them is a
+/// single `DEL`. The hash expires `ttl` after it was last written.
+pub struct RedisStatsCache {
+    pool: RedisPool,
+    ttl: Duration,
+}
+
+impl RedisStatsCache {
+    pub fn new(pool: RedisPool, ttl: Duration) -> Self {
+        Self { pool, ttl }
+    }
+
+    fn hash_key(user_id: Uuid) -> String {
+        format!("stats:{}", user_id)
+    }
+}
+
+#[async_trait]
+impl StatsCache for RedisStatsCache {
+    async fn get(&self, user_id: Uuid, key: &str) -> Result<Option<String>, anyhow::Error> {
+        Ok(self.pool.hget(Self::hash_key(user_id), key).await?)
+    }
+
+    async fn set(&self, user_id: Uuid, key: &str, value: String) -> Result<(), anyhow::Error> {
+        let hash_key = Self::hash_key(user_id);
+        self.pool.hset::<(), _, _>(&hash_key, (key, value)).await?;
+        self.pool.expire::<(), _>(&hash_key, self.ttl.as_secs() as i64).await?;
+        Ok(())
+    }
+
+    async fn invalidate(&self, user_id: Uuid) -> Result<(), anyhow::Error> {
+        self.pool.del::<(), _>(Self::hash_key(user_id)).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InMemoryStatsCache, StatsCache};
+    use std::time::Duration;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn the_memory_cache_forgets_a_users_entries_when_invalidated() {
+        let cache = InMemoryStatsCache::new(Duration::from_secs(60));
+        let (user, other_user) = (Uuid::new_v4(), Uuid::new_v4());
+        cache.set(user, "equity", "1".to_string()).await.unwrap();
+        cache.set(other_user, "equity", "2".to_string()).await.unwrap();
+        assert_eq!(cache.get(user, "equity").await.unwrap().as_deref(), Some("1"));
+
+        cache.invalidate(user).await.unwrap();
+        assert_eq!(cache.get(user, "equity").await.unwrap(), None);
+        assert_eq!(cache.get(other_user, "equity").await.unwrap().as_deref(), Some("2"));
+    }
+
+    #[tokio::test]
+    async fn memory_cache_entries_expire() {
+        let cache = InMemoryStatsCache::new(Duration::ZERO);
+        let user = Uuid::new_v4();
+        cache.set(user, "equity", "1".to_string()).await.unwrap();
+        assert_eq!(cache.get(user, "equity").await.unwrap(), None);
+    }
+}
@@ -35,7 +35,25 @@ pub fn render_content(render_template_params: &RenderTemplateParams<'_>) -> Resu
         context = tera::Context::new();
     }
 
-    Ok(render_template_params.tera_store.render(&render_template_params.template_path, &context).map_err(e500)?)
+    render_template_params.tera_store.render(render_template_params.template_path, &context).map_err(e500)
+}
+
+/// `axum_messages::Message` serializes with single letter keys, so messages are
+/// copied into this before being handed to templates.
+#[derive(Debug, serde::Serialize)]
+pub struct FlashMessage {
+    pub level: String,
+    pub message: String,
+}
+
+pub fn flash_messages(messages: axum_messages::Messages) -> Vec<FlashMessage> {
+    messages
+        .into_iter()
+        .map(|message| FlashMessage {
+            level: message.level.to_string().to_lowercase(),
+            message: message.message,
+        })
+        .collect()
 }
 
 pub fn err_500_template<E: std::fmt::Display>(tr: &Arc<tera::Tera>, error: E) -> String {
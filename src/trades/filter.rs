@@ -0,0 +1,214 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, QueryBuilder};
+use time::Date;
+use time::macros::format_description;
+use utoipa::IntoParams;
+use crate::domain::TradingSession;
+use super::push_trading_day;
+
+/// Query string filters shared by the dashboard and the stats endpoints. Empty
+/// values, as submitted by an HTML form, mean no filter. `account` may be
+/// repeated to aggregate several accounts, so handlers extract this with
+/// `axum_extra::extract::Query`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TradeFilterQuery {
+    /// First trading day to include, as `YYYY-MM-DD`
+    pub from: Option<String>,
+    /// Last trading day to include, as `YYYY-MM-DD`
+    pub to: Option<String>,
+    /// Repeat to include several accounts
+    #[serde(default)]
+    pub account: Vec<String>,
+    pub instrument: Option<String>,
+    /// Only trades with this tag, ignoring case
+    pub tag: Option<String>,
+}
+
+/// Which trades to include, by the trading day they were closed in.
+/// `from` and `to` are both inclusive. Serialized as part of stats cache keys.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TradeFilter {
+    pub from: Option<Date>,
+    pub to: Option<Date>,
+    /// Trades in any of these accounts, or every account when empty
+    pub accounts: Vec<String>,
+    pub instrument: Option<String>,
+    /// Only trades with this tag, ignoring case
+    pub tag: Option<String>,
+}
+
+impl TryFrom<TradeFilterQuery> for TradeFilter {
+    type Error = String;
+
+    fn try_from(value: TradeFilterQuery) -> Result<Self, Self::Error> {
+        let from = parse_date(value.from)?;
+        let to = parse_date(value.to)?;
+        if let (Some(from), Some(to)) = (from, to) {
+            if from > to {
+                return Err("The start date must be before the end date.".to_string());
+            }
+        }
+
+        Ok(TradeFilter {
+            from,
+            to,
+            accounts: value.account.into_iter().filter_map(|account| non_empty(Some(account))).collect(),
+            instrument: non_empty(value.instrument),
+            tag: non_empty(value.tag),
+        })
+    }
+}
+
+impl TradeFilterQuery {
+    /// The filter as a query string, without the leading `?`, for building
+    /// links that keep the current filter. Empty values are left out.
+    pub fn to_query_string(&self) -> String {
+        let mut pairs: Vec<(&str, &str)> = Vec::new();
+        let values = [("from", &self.from), ("to", &self.to)];
+        pairs.extend(values.iter().filter_map(|(key, value)| value.as_deref().map(|v| (*key, v))));
+        pairs.extend(self.account.iter().map(|account| ("account", account.as_str())));
+        pairs.extend(self.instrument.as_deref().map(|instrument| ("instrument", instrument)));
+        pairs.extend(self.tag.as_deref().map(|tag| ("tag", tag)));
+        pairs.retain(|(_, value)| !value.trim().is_empty());
+        serde_urlencoded::to_string(pairs).unwrap_or_default()
+    }
+}
+
+impl TradeFilter {
+    /// Adds the filter to a query over `trades` that already has a WHERE clause.
+    pub fn push_conditions(&self, query_builder: &mut QueryBuilder<'_, Postgres>, session: &TradingSession) {
+        self.push_common_conditions(query_builder, "exit_time", session);
+        if let Some(ref tag) = self.tag {
+            query_builder.push(" AND id IN (");
+            push_tagged_trade_ids(query_builder, tag);
+            query_builder.push(")");
+        }
+    }
+
+    /// Adds the filter to a query over `executions` that already has a WHERE
+    /// clause. Dates are the trading day of the execution itself, and a tag
+    /// matches the executions of trades with that tag.
+    pub fn push_execution_conditions(&self, query_builder: &mut QueryBuilder<'_, Postgres>, session: &TradingSession) {
+        self.push_common_conditions(query_builder, "executed_at", session);
+        if let Some(ref tag) = self.tag {
+            query_builder.push(
+                " AND execution_id IN (SELECT UNNEST(execution_ids) FROM trades WHERE trades.user_id = executions.user_id AND trades.id IN ("
+            );
+            push_tagged_trade_ids(query_builder, tag);
+            query_builder.push("))");
+        }
+    }
+
+    fn push_common_conditions(
+        &self,
+        query_builder: &mut QueryBuilder<'_, Postgres>,
+        time_column: &'static str,
+        session: &TradingSession,
+    ) {
+        if let Some(from) = self.from {
+            query_builder.push(" AND ");
+            push_trading_day(query_builder, time_column, session);
+            query_builder.push(" >= ").push_bind(from);
+        }
+        if let Some(to) = self.to {
+            query_builder.push(" AND ");
+            push_trading_day(query_builder, time_column, session);
+            query_builder.push(" <= ").push_bind(to);
+        }
+        if !self.accounts.is_empty() {
+            query_builder.push(" AND account = ANY(").push_bind(self.accounts.clone()).push(")");
+        }
+        if let Some(ref instrument) = self.instrument {
+            query_builder.push(" AND instrument = ").push_bind(instrument.clone());
+        }
+    }
+}
+
+/// Pushes a subquery for the ids of trades tagged `tag`, ignoring case.
+fn push_tagged_trade_ids(query_builder: &mut QueryBuilder<'_, Postgres>, tag: &str) {
+    query_builder
+        .push("SELECT trade_tags.trade_id FROM trade_tags JOIN tags ON tags.id = trade_tags.tag_id WHERE LOWER(tags.name) = LOWER(")
+        .push_bind(tag.to_string())
+        .push(")");
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.map(|s| s.trim().to_string()).filter(|s| !s.is_empty())
+}
+
+fn parse_date(value: Option<String>) -> Result<Option<Date>, String> {
+    match non_empty(value) {
+        Some(s) => Date::parse(&s, format_description!("[year]-[month]-[day]"))
+            .map(Some)
+            .map_err(|_| format!("{} is not a valid date. Use the format YYYY-MM-DD.", s)),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TradeFilter, TradeFilterQuery};
+    use claims::assert_err;
+    use time::macros::date;
+
+    #[test]
+    fn empty_query_has_no_filters() {
+        let query = TradeFilterQuery {
+            from: Some("".to_string()),
+            to: None,
+            account: vec![" ".to_string()],
+            instrument: Some("".to_string()),
+            tag: Some("".to_string()),
+        };
+        assert_eq!(TradeFilter::try_from(query), Ok(TradeFilter::default()));
+    }
+
+    #[test]
+    fn dates_and_names_are_parsed() {
+        let query = TradeFilterQuery {
+            from: Some("2024-07-01".to_string()),
+            to: Some("2024-07-31".to_string()),
+            account: vec!["Sim101".to_string(), "".to_string(), "APEX-1".to_string()],
+            instrument: Some("ES 09-24".to_string()),
+            tag: Some(" FOMO ".to_string()),
+        };
+        let filter = TradeFilter::try_from(query).unwrap();
+        assert_eq!(filter.from, Some(date!(2024-07-01)));
+        assert_eq!(filter.to, Some(date!(2024-07-31)));
+        assert_eq!(filter.accounts, vec!["Sim101", "APEX-1"]);
+        assert_eq!(filter.instrument.as_deref(), Some("ES 09-24"));
+        assert_eq!(filter.tag.as_deref(), Some("FOMO"));
+    }
+
+    #[test]
+    fn invalid_dates_are_rejected() {
+        let query = TradeFilterQuery {
+            from: Some("07/01/2024".to_string()),
+            ..Default::default()
+        };
+        assert_err!(TradeFilter::try_from(query));
+    }
+
+    #[test]
+    fn reversed_date_range_is_rejected() {
+        let query = TradeFilterQuery {
+            from: Some("2024-08-01".to_string()),
+            to: Some("2024-07-01".to_string()),
+            ..Default::default()
+        };
+        assert_err!(TradeFilter::try_from(query));
+    }
+
+    #[test]
+    fn query_string_repeats_accounts_and_skips_empty_values() {
+        let query = TradeFilterQuery {
+            from: Some("2024-07-01".to_string()),
+            to: Some("".to_string()),
+            account: vec!["Sim101".to_string(), "APEX 1".to_string()],
+            instrument: None,
+            tag: Some("A&B".to_string()),
+        };
+        assert_eq!(query.to_query_string(), "from=2024-07-01&account=Sim101&account=APEX+1&tag=A%26B");
+    }
+}
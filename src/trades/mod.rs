@@ -0,0 +1,414 @@
+//! src/trades/mod.rs
+//! Round trips rebuilt from a user's executions. The matching itself lives in
+//! `reconstruct` and never touches the database.
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, PgPool, Postgres, QueryBuilder, Transaction};
+use time::{Date, OffsetDateTime, PrimitiveDateTime};
+use utoipa::ToSchema;
+use crate::accounts::{self, Account};
+use crate::constants::job_kinds;
+use crate::contract_specs::{self, ContractSpecRegistry};
+use crate::domain::{Pagination, TradingSession};
+use crate::evaluations;
+use crate::executions;
+use crate::jobs::Job;
+use crate::startup::AppState;
+use crate::stats_cache;
+
+mod filter;
+mod reconstruct;
+#[cfg(test)]
+pub mod test_support;
+
+pub use filter::{TradeFilter, TradeFilterQuery};
+pub use reconstruct::{reconstruct_trades, ContractMultiplier, Direction, Fill, RoundTrip};
+
+const UPSERT_BATCH_SIZE: usize = 500;
+
+/// A stored round trip.
+#[derive(Debug, Clone, Serialize, FromRow, ToSchema)]
+pub struct Trade {
+    pub id: uuid::Uuid,
+    pub account: String,
+    pub instrument: String,
+    pub direction: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub entry_time: OffsetDateTime,
+    #[serde(with = "time::serde::rfc3339")]
+    pub exit_time: OffsetDateTime,
+    /// The session `exit_time` falls in
+    pub trading_day: Date,
+    /// `entry_time` on the session's clock, for time of day breakdowns
+    #[schema(value_type = String, example = "2024-07-01 09:30:00.0")]
+    pub local_entry_time: PrimitiveDateTime,
+    pub quantity: i32,
+    pub max_position: i32,
+    pub entry_price: f64,
+    pub exit_price: f64,
+    pub gross_points: f64,
+    pub gross_ticks: Option<f64>,
+    /// `None` until the instrument has a contract spec
+    pub gross_pnl: Option<f64>,
+    pub commission: f64,
+    pub net_pnl: Option<f64>,
+    /// The trader's own 1 to 5 rating from their journal
+    pub rating: Option<i16>,
+    /// Planned dollar risk from the journal
+    pub risk: Option<f64>,
+    /// Net PnL in multiples of `risk`
+    pub r_multiple: Option<f64>,
+    pub tags: Vec<String>,
+}
+
+/// The trades with dollar PnL, along with it. Trades in instruments without a
+/// contract spec are left out of dollar statistics until one is added.
+pub fn priced(trades: &[Trade]) -> impl Iterator<Item = (&Trade, f64)> {
+    trades.iter().filter_map(|trade| trade.net_pnl.map(|net_pnl| (trade, net_pnl)))
+}
+
+/// The user's trades that match `filter`, in the order they were closed.
+pub async fn for_user(
+    db: &PgPool,
+    user_id: uuid::Uuid,
+    filter: &TradeFilter,
+    session: &TradingSession,
+) -> Result<Vec<Trade>, sqlx::Error> {
+    let mut query_builder = select_trades(user_id, session);
+    filter.push_conditions(&mut query_builder, session);
+    query_builder.push(" ORDER BY exit_time, entry_time");
+
+    query_builder.build_query_as().fetch_all(db).await
+}
+
+/// A page of the user's trades that match `filter`, in the order they were
+/// closed, and how many trades match in all.
+pub async fn page_for_user(
+    db: &PgPool,
+    user_id: uuid::Uuid,
+    filter: &TradeFilter,
+    session: &TradingSession,
+    pagination: &Pagination,
+) -> Result<(Vec<Trade>, i64), sqlx::Error> {
+    let mut query_builder = select_trades(user_id, session);
+    filter.push_conditions(&mut query_builder, session);
+    query_builder
+        .push(" ORDER BY exit_time, entry_time, id LIMIT ")
+        .push_bind(pagination.limit())
+        .push(" OFFSET ")
+        .push_bind(pagination.offset());
+    let trades = query_builder.build_query_as().fetch_all(db).await?;
+
+    let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new("SELECT COUNT(*) FROM trades WHERE user_id = ");
+    query_builder.push_bind(user_id);
+    filter.push_conditions(&mut query_builder, session);
+    let total = query_builder.build_query_scalar().fetch_one(db).await?;
+
+    Ok((trades, total))
+}
+
+/// One of the user's trades. `None` when it doesn't exist or belongs to someone else.
+pub async fn find_for_user(
+    db: &PgPool,
+    user_id: uuid::Uuid,
+    trade_id: uuid::Uuid,
+    session: &TradingSession,
+) -> Result<Option<Trade>, sqlx::Error> {
+    let mut query_builder = select_trades(user_id, session);
+    query_builder.push(" AND id = ").push_bind(trade_id);
+
+    query_builder.build_query_as().fetch_optional(db).await
+}
+
+fn select_trades<'a>(user_id: uuid::Uuid, session: &TradingSession) -> QueryBuilder<'a, Postgres> {
+    let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(
+        "SELECT id, account, instrument, direction, entry_time, exit_time, quantity, max_position,
+            entry_price, exit_price, gross_points, gross_ticks, gross_pnl, commission, net_pnl, rating,
+            risk, net_pnl / risk AS r_multiple,
+            ARRAY(
+                SELECT tags.name FROM trade_tags JOIN tags ON tags.id = trade_tags.tag_id
+                WHERE trade_tags.trade_id = trades.id ORDER BY tags.name
+            ) AS tags, entry_time AT TIME ZONE "
+    );
+    query_builder.push_bind(session.timezone.clone());
+    query_builder.push(" AS local_entry_time, ");
+    push_trading_day(&mut query_builder, "exit_time", session);
+    query_builder.push(" AS trading_day FROM trades WHERE user_id = ");
+    query_builder.push_bind(user_id);
+    query_builder
+}
+
+/// The trading day in progress right now.
+pub async fn current_trading_day(db: &PgPool, session: &TradingSession) -> Result<Date, sqlx::Error> {
+    sqlx::query_scalar("SELECT ((NOW() AT TIME ZONE $1) + make_interval(secs => $2))::date")
+        .bind(session.timezone.clone())
+        .bind(session.day_shift().as_seconds_f64())
+        .fetch_one(db)
+        .await
+}
+
+/// Pushes the trading day of a timestamp column as a SQL date expression. The
+/// time zone is bound rather than formatted in, so it can never change the query.
+fn push_trading_day(query_builder: &mut QueryBuilder<'_, Postgres>, column: &'static str, session: &TradingSession) {
+    query_builder
+        .push("((")
+        .push(column)
+        .push(" AT TIME ZONE ")
+        .push_bind(session.timezone.clone())
+        .push(") + make_interval(secs => ")
+        .push_bind(session.day_shift().as_seconds_f64())
+        .push("))::date");
+}
+
+/// What the user can filter their trades by.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FilterChoices {
+    pub accounts: Vec<Account>,
+    pub instruments: Vec<String>,
+    pub tags: Vec<String>,
+}
+
+pub async fn filter_choices(db: &PgPool, user_id: uuid::Uuid) -> Result<FilterChoices, sqlx::Error> {
+    let accounts = accounts::for_user(db, user_id).await?;
+    let instruments = sqlx::query_scalar("SELECT DISTINCT instrument FROM trades WHERE user_id = $1 ORDER BY instrument")
+        .bind(user_id)
+        .fetch_all(db)
+        .await?;
+    let tags = sqlx::query_scalar("SELECT name FROM tags WHERE user_id = $1 ORDER BY LOWER(name)")
+        .bind(user_id)
+        .fetch_all(db)
+        .await?;
+    Ok(FilterChoices {
+        accounts,
+        instruments,
+        tags,
+    })
+}
+
+/// Rebuilds every trade for the user from their executions. Trades keep their
+/// id across rebuilds, and trades that no longer exist are removed after
+/// their journal is moved to the trade that took over their executions.
+/// Returns the number of trades.
+pub async fn rebuild_trades(db: &PgPool, user_id: uuid::Uuid) -> Result<usize, sqlx::Error> {
+    rebuild(db, user_id, None).await
+}
+
+/// Rebuilds only the user's trades in the given `(account, instrument)` pairs,
+/// for when a few executions arrive at a time. Each pair is rebuilt from all
+/// of its executions, so executions may arrive in any order.
+/// Returns the number of trades in those pairs.
+pub async fn rebuild_trades_for_streams(
+    db: &PgPool,
+    user_id: uuid::Uuid,
+    streams: &[(String, String)],
+) -> Result<usize, sqlx::Error> {
+    rebuild(db, user_id, Some(streams)).await
+}
+
+async fn rebuild(db: &PgPool, user_id: uuid::Uuid, streams: Option<&[(String, String)]>) -> Result<usize, sqlx::Error> {
+    let registry = ContractSpecRegistry::load(db).await?;
+    let mut transaction = db.begin().await?;
+
+    // Rebuilds for the same user take turns, so one that read the executions
+    // before another import committed can't overwrite the newer trades
+    sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended($1::TEXT, 0))")
+        .bind(user_id)
+        .execute(&mut *transaction)
+        .await?;
+    let fills = executions::fills_for_user(&mut *transaction, user_id, streams).await?;
+    let trades = reconstruct_trades(&fills, |instrument| registry.multiplier(instrument));
+    save_trades(&mut transaction, user_id, &trades, streams).await?;
+
+    transaction.commit().await?;
+    Ok(trades.len())
+}
+
+/// Upserts `trades` and removes the user's other trades, within `streams` when given.
+async fn save_trades(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: uuid::Uuid,
+    trades: &[RoundTrip],
+    streams: Option<&[(String, String)]>,
+) -> Result<(), sqlx::Error> {
+    for chunk in trades.chunks(UPSERT_BATCH_SIZE) {
+        let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(
+            "INSERT INTO trades (id, user_id, account, instrument, direction, opening_execution_id, execution_ids, entry_time, exit_time, quantity, max_position, entry_price, exit_price, gross_points, gross_ticks, gross_pnl, commission, net_pnl) "
+        );
+        query_builder.push_values(chunk, |mut row, trade| {
+            row.push_bind(uuid::Uuid::new_v4())
+                .push_bind(user_id)
+                .push_bind(&trade.account)
+                .push_bind(&trade.instrument)
+                .push_bind(trade.direction.as_str())
+                .push_bind(&trade.opening_execution_id)
+                .push_bind(&trade.execution_ids)
+                .push_bind(trade.entry_time)
+                .push_bind(trade.exit_time)
+                .push_bind(trade.quantity)
+                .push_bind(trade.max_position)
+                .push_bind(trade.entry_price)
+                .push_bind(trade.exit_price)
+                .push_bind(trade.gross_points)
+                .push_bind(trade.gross_ticks)
+                .push_bind(trade.gross_pnl)
+                .push_bind(trade.commission)
+                .push_bind(trade.net_pnl);
+        });
+        query_builder.push(
+            " ON CONFLICT (user_id, opening_execution_id) DO UPDATE SET
+            account = EXCLUDED.account,
+            instrument = EXCLUDED.instrument,
+            direction = EXCLUDED.direction,
+            execution_ids = EXCLUDED.execution_ids,
+            entry_time = EXCLUDED.entry_time,
+            exit_time = EXCLUDED.exit_time,
+            quantity = EXCLUDED.quantity,
+            max_position = EXCLUDED.max_position,
+            entry_price = EXCLUDED.entry_price,
+            exit_price = EXCLUDED.exit_price,
+            gross_points = EXCLUDED.gross_points,
+            gross_ticks = EXCLUDED.gross_ticks,
+            gross_pnl = EXCLUDED.gross_pnl,
+            commission = EXCLUDED.commission,
+            net_pnl = EXCLUDED.net_pnl"
+        );
+        query_builder.build().execute(&mut **transaction).await?;
+    }
+
+    let opening_execution_ids: Vec<&str> = trades
+        .iter()
+        .map(|trade| trade.opening_execution_id.as_str())
+        .collect();
+    carry_over_journals(transaction, user_id, &opening_execution_ids, streams).await?;
+
+    let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new("DELETE FROM trades WHERE user_id = ");
+    query_builder
+        .push_bind(user_id)
+        .push(" AND NOT (opening_execution_id = ANY(")
+        .push_bind(opening_execution_ids)
+        .push("))");
+    if let Some(streams) = streams {
+        executions::push_streams(&mut query_builder, streams);
+    }
+    query_builder.build().execute(&mut **transaction).await?;
+    Ok(())
+}
+
+/// Moves the journal of each trade about to be removed onto the new trade
+/// that took over its first executions, e.g. when an earlier fill arrives late
+/// and the trade now opens with a different execution. Notes, rating and risk
+/// already written on the new trade are kept.
+async fn carry_over_journals(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: uuid::Uuid,
+    opening_execution_ids: &[&str],
+    streams: Option<&[(String, String)]>,
+) -> Result<(), sqlx::Error> {
+    let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(
+        "SELECT DISTINCT ON (stale.id) stale.id, fresh.id FROM (SELECT id, execution_ids FROM trades WHERE user_id = "
+    );
+    query_builder
+        .push_bind(user_id)
+        .push(" AND NOT (opening_execution_id = ANY(")
+        .push_bind(opening_execution_ids)
+        .push("))");
+    if let Some(streams) = streams {
+        executions::push_streams(&mut query_builder, streams);
+    }
+    query_builder
+        .push(") stale JOIN trades fresh ON fresh.user_id = ")
+        .push_bind(user_id)
+        .push(" AND fresh.opening_execution_id = ANY(")
+        .push_bind(opening_execution_ids)
+        .push(") AND fresh.execution_ids && stale.execution_ids ORDER BY stale.id, fresh.entry_time");
+    let moves: Vec<(uuid::Uuid, uuid::Uuid)> = query_builder.build_query_as().fetch_all(&mut **transaction).await?;
+    if moves.is_empty() {
+        return Ok(());
+    }
+    let (stale_ids, fresh_ids): (Vec<uuid::Uuid>, Vec<uuid::Uuid>) = moves.into_iter().unzip();
+
+    sqlx::query(
+        "UPDATE trades fresh SET
+            notes = CASE WHEN fresh.notes = '' THEN stale.notes ELSE fresh.notes END,
+            rating = COALESCE(fresh.rating, stale.rating),
+            risk = COALESCE(fresh.risk, stale.risk)
+        FROM UNNEST($1::UUID[], $2::UUID[]) AS moves (stale_id, fresh_id)
+        JOIN trades stale ON stale.id = moves.stale_id
+        WHERE fresh.id = moves.fresh_id"
+    )
+        .bind(&stale_ids)
+        .bind(&fresh_ids)
+        .execute(&mut **transaction)
+        .await?;
+    sqlx::query(
+        "INSERT INTO trade_tags (trade_id, tag_id)
+        SELECT moves.fresh_id, trade_tags.tag_id
+        FROM UNNEST($1::UUID[], $2::UUID[]) AS moves (stale_id, fresh_id)
+        JOIN trade_tags ON trade_tags.trade_id = moves.stale_id
+        ON CONFLICT DO NOTHING"
+    )
+        .bind(&stale_ids)
+        .bind(&fresh_ids)
+        .execute(&mut **transaction)
+        .await?;
+    sqlx::query(
+        "UPDATE trade_screenshots SET trade_id = moves.fresh_id
+        FROM UNNEST($1::UUID[], $2::UUID[]) AS moves (stale_id, fresh_id)
+        WHERE trade_screenshots.trade_id = moves.stale_id"
+    )
+        .bind(&stale_ids)
+        .bind(&fresh_ids)
+        .execute(&mut **transaction)
+        .await?;
+    Ok(())
+}
+
+/// Trades store dollar PnL, so every trade in `root` is recalculated after
+/// its contract spec changes.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RebuildTradesForRoot {
+    pub root: String,
+}
+
+#[async_trait]
+impl Job for RebuildTradesForRoot {
+    const KIND: &'static str = job_kinds::REBUILD_TRADES_FOR_ROOT;
+
+    async fn run(&self, state: &AppState) -> Result<(), anyhow::Error> {
+        for user_id in contract_specs::users_trading_root(&state.db, &self.root).await? {
+            refresh_trades(state, user_id, None).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Rebuilds the user's trades after new executions arrive.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RebuildTrades {
+    pub user_id: uuid::Uuid,
+    /// The `(account, instrument)` pairs to rebuild, or every pair when `None`
+    #[serde(default)]
+    pub streams: Option<Vec<(String, String)>>,
+}
+
+#[async_trait]
+impl Job for RebuildTrades {
+    const KIND: &'static str = job_kinds::REBUILD_TRADES;
+
+    async fn run(&self, state: &AppState) -> Result<(), anyhow::Error> {
+        refresh_trades(state, self.user_id, self.streams.as_deref()).await
+    }
+}
+
+/// Rebuilds the user's trades, then what's worked out from them: cached
+/// statistics and evaluation rule breaches.
+async fn refresh_trades(
+    state: &AppState,
+    user_id: uuid::Uuid,
+    streams: Option<&[(String, String)]>,
+) -> Result<(), anyhow::Error> {
+    rebuild(&state.db, user_id, streams).await?;
+    stats_cache::invalidate(state, user_id).await;
+    evaluations::record_breaches_for_user(&state.db, user_id, &state.trading_session).await?;
+    Ok(())
+}
@@ -0,0 +1,486 @@
+use std::collections::{BTreeMap, VecDeque};
+use time::OffsetDateTime;
+use crate::domain::ExecutionAction;
+
+/// A single fill, as far as trade reconstruction is concerned.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fill {
+    pub execution_id: String,
+    pub account: String,
+    pub instrument: String,
+    pub action: ExecutionAction,
+    pub quantity: i32,
+    pub price: f64,
+    pub commission: f64,
+    pub executed_at: OffsetDateTime,
+}
+
+impl Fill {
+    fn signed_quantity(&self) -> i32 {
+        match self.action {
+            ExecutionAction::Buy => self.quantity,
+            ExecutionAction::Sell => -self.quantity,
+        }
+    }
+}
+
+/// What one point and one tick of an instrument are worth.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContractMultiplier {
+    pub point_value: f64,
+    pub tick_size: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Long,
+    Short,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Long => "long",
+            Direction::Short => "short",
+        }
+    }
+
+    fn sign(&self) -> f64 {
+        match self {
+            Direction::Long => 1.0,
+            Direction::Short => -1.0,
+        }
+    }
+}
+
+/// A flat-to-flat round trip on one account and instrument.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoundTrip {
+    pub account: String,
+    pub instrument: String,
+    pub direction: Direction,
+    /// NinjaTrader ID of the fill that took the position off flat. It identifies
+    /// the trade across rebuilds.
+    pub opening_execution_id: String,
+    pub execution_ids: Vec<String>,
+    pub entry_time: OffsetDateTime,
+    pub exit_time: OffsetDateTime,
+    /// Total contracts entered, which is also the total exited.
+    pub quantity: i32,
+    pub max_position: i32,
+    pub entry_price: f64,
+    pub exit_price: f64,
+    /// Points captured, summed over every contract.
+    pub gross_points: f64,
+    /// `gross_points` in ticks, when the instrument's tick size is known.
+    pub gross_ticks: Option<f64>,
+    /// Dollar PnL, when the instrument's point value is known
+    pub gross_pnl: Option<f64>,
+    pub commission: f64,
+    pub net_pnl: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Lot {
+    quantity: i32,
+    price: f64,
+}
+
+/// The round trip being built while the position is off flat.
+#[derive(Debug)]
+struct OpenTrade {
+    direction: Direction,
+    opening_execution_id: String,
+    execution_ids: Vec<String>,
+    entry_time: OffsetDateTime,
+    lots: VecDeque<Lot>,
+    position: i32,
+    max_position: i32,
+    entered: i32,
+    entry_notional: f64,
+    exited: i32,
+    exit_notional: f64,
+    gross_points: f64,
+    commission: f64,
+}
+
+impl OpenTrade {
+    fn open(fill: &Fill, quantity: i32, commission: f64) -> Self {
+        let direction = match fill.action {
+            ExecutionAction::Buy => Direction::Long,
+            ExecutionAction::Sell => Direction::Short,
+        };
+        let mut trade = OpenTrade {
+            direction,
+            opening_execution_id: fill.execution_id.clone(),
+            execution_ids: Vec::new(),
+            entry_time: fill.executed_at,
+            lots: VecDeque::new(),
+            position: 0,
+            max_position: 0,
+            entered: 0,
+            entry_notional: 0.0,
+            exited: 0,
+            exit_notional: 0.0,
+            gross_points: 0.0,
+            commission: 0.0,
+        };
+        trade.add(fill, quantity, commission);
+        trade
+    }
+
+    /// Opens or scales into the position.
+    fn add(&mut self, fill: &Fill, quantity: i32, commission: f64) {
+        self.execution_ids.push(fill.execution_id.clone());
+        self.lots.push_back(Lot { quantity, price: fill.price });
+        self.position += quantity;
+        self.max_position = self.max_position.max(self.position);
+        self.entered += quantity;
+        self.entry_notional += fill.price * quantity as f64;
+        self.commission += commission;
+    }
+
+    /// Closes `quantity` contracts against the oldest lots first.
+    fn reduce(&mut self, fill: &Fill, quantity: i32, commission: f64) {
+        self.execution_ids.push(fill.execution_id.clone());
+        let mut remaining = quantity;
+        while remaining > 0 {
+            let lot = match self.lots.front_mut() {
+                Some(lot) => lot,
+                None => break,
+            };
+            let matched = remaining.min(lot.quantity);
+            self.gross_points += (fill.price - lot.price) * matched as f64 * self.direction.sign();
+            lot.quantity -= matched;
+            remaining -= matched;
+            if lot.quantity == 0 {
+                self.lots.pop_front();
+            }
+        }
+        self.position -= quantity;
+        self.exited += quantity;
+        self.exit_notional += fill.price * quantity as f64;
+        self.commission += commission;
+    }
+
+    fn close(self, account: &str, instrument: &str, exit_time: OffsetDateTime, multiplier: Option<ContractMultiplier>) -> RoundTrip {
+        // Points can't be turned into dollars without a multiplier
+        let gross_pnl = multiplier.map(|m| self.gross_points * m.point_value);
+        let gross_ticks = multiplier.map(|m| self.gross_points / m.tick_size);
+        RoundTrip {
+            account: account.to_string(),
+            instrument: instrument.to_string(),
+            direction: self.direction,
+            opening_execution_id: self.opening_execution_id,
+            execution_ids: self.execution_ids,
+            entry_time: self.entry_time,
+            exit_time,
+            quantity: self.entered,
+            max_position: self.max_position,
+            entry_price: self.entry_notional / self.entered as f64,
+            exit_price: self.exit_notional / self.exited as f64,
+            gross_points: self.gross_points,
+            gross_ticks,
+            gross_pnl,
+            commission: self.commission,
+            net_pnl: gross_pnl.map(|gross_pnl| gross_pnl - self.commission),
+        }
+    }
+}
+
+/// Groups fills by account and instrument and matches them FIFO into flat-to-flat
+/// round trips. A fill that flips the position closes the current trade and
+/// opens the next one with the remainder, splitting its commission by quantity.
+/// Positions that are still open after the last fill are not returned.
+///
+/// `multiplier` looks up the point value and tick size for an instrument.
+/// Trades in instruments without one only have PnL in points.
+pub fn reconstruct_trades<F>(fills: &[Fill], multiplier: F) -> Vec<RoundTrip>
+where
+    F: Fn(&str) -> Option<ContractMultiplier>,
+{
+    let mut groups: BTreeMap<(&str, &str), Vec<&Fill>> = BTreeMap::new();
+    for fill in fills {
+        groups
+            .entry((fill.account.as_str(), fill.instrument.as_str()))
+            .or_default()
+            .push(fill);
+    }
+
+    let mut trades = Vec::new();
+    for ((account, instrument), mut group) in groups {
+        // Stable, so fills with the same timestamp keep their given order
+        group.sort_by_key(|fill| fill.executed_at);
+        let multiplier = multiplier(instrument);
+
+        let mut open: Option<OpenTrade> = None;
+        for fill in group {
+            let mut quantity = fill.quantity;
+            let commission_per_contract = fill.commission / fill.quantity as f64;
+
+            let mut trade = match open.take() {
+                Some(trade) => trade,
+                None => {
+                    open = Some(OpenTrade::open(fill, quantity, fill.commission));
+                    continue;
+                }
+            };
+
+            let same_direction = (fill.signed_quantity() > 0) == (trade.direction == Direction::Long);
+            if same_direction {
+                trade.add(fill, quantity, fill.commission);
+                open = Some(trade);
+                continue;
+            }
+
+            let closing = quantity.min(trade.position);
+            trade.reduce(fill, closing, commission_per_contract * closing as f64);
+            quantity -= closing;
+
+            if trade.position > 0 {
+                open = Some(trade);
+                continue;
+            }
+
+            trades.push(trade.close(account, instrument, fill.executed_at, multiplier));
+            if quantity > 0 {
+                open = Some(OpenTrade::open(fill, quantity, commission_per_contract * quantity as f64));
+            }
+        }
+    }
+
+    trades.sort_by_key(|trade| trade.exit_time);
+    trades
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{reconstruct_trades, ContractMultiplier, Direction, Fill};
+    use crate::domain::ExecutionAction;
+    use time::macros::datetime;
+    use time::{Duration, OffsetDateTime};
+
+    const START: OffsetDateTime = datetime!(2024-07-22 14:30:00 UTC);
+
+    fn fill(id: &str, action: ExecutionAction, quantity: i32, price: f64, minute: i64) -> Fill {
+        Fill {
+            execution_id: id.to_string(),
+            account: "Sim101".to_string(),
+            instrument: "ES 09-24".to_string(),
+            action,
+            quantity,
+            price,
+            commission: 2.0 * quantity as f64,
+            executed_at: START + Duration::minutes(minute),
+        }
+    }
+
+    fn buy(id: &str, quantity: i32, price: f64, minute: i64) -> Fill {
+        fill(id, ExecutionAction::Buy, quantity, price, minute)
+    }
+
+    fn sell(id: &str, quantity: i32, price: f64, minute: i64) -> Fill {
+        fill(id, ExecutionAction::Sell, quantity, price, minute)
+    }
+
+    fn points(_: &str) -> Option<ContractMultiplier> {
+        Some(ContractMultiplier { point_value: 1.0, tick_size: 0.25 })
+    }
+
+    fn es(_: &str) -> Option<ContractMultiplier> {
+        Some(ContractMultiplier { point_value: 50.0, tick_size: 0.25 })
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "expected {} but got {}", expected, actual);
+    }
+
+    #[test]
+    fn no_fills_make_no_trades() {
+        assert!(reconstruct_trades(&[], points).is_empty());
+    }
+
+    #[test]
+    fn single_long_round_trip() {
+        let fills = vec![buy("e1", 1, 5565.25, 0), sell("e2", 1, 5567.5, 5)];
+        let trades = reconstruct_trades(&fills, es);
+
+        assert_eq!(trades.len(), 1);
+        let trade = &trades[0];
+        assert_eq!(trade.direction, Direction::Long);
+        assert_eq!(trade.opening_execution_id, "e1");
+        assert_eq!(trade.execution_ids, vec!["e1", "e2"]);
+        assert_eq!(trade.quantity, 1);
+        assert_eq!(trade.max_position, 1);
+        assert_eq!(trade.entry_time, START);
+        assert_eq!(trade.exit_time, START + Duration::minutes(5));
+        assert_close(trade.gross_points, 2.25);
+        assert_close(trade.gross_ticks.unwrap(), 9.0);
+        assert_close(trade.gross_pnl.unwrap(), 112.5);
+        assert_close(trade.commission, 4.0);
+        assert_close(trade.net_pnl.unwrap(), 108.5);
+    }
+
+    #[test]
+    fn single_short_round_trip() {
+        let fills = vec![sell("e1", 2, 100.0, 0), buy("e2", 2, 97.0, 1)];
+        let trades = reconstruct_trades(&fills, points);
+
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].direction, Direction::Short);
+        assert_close(trades[0].gross_pnl.unwrap(), 6.0);
+        assert_close(trades[0].net_pnl.unwrap(), 6.0 - 8.0);
+    }
+
+    #[test]
+    fn scale_in_averages_the_entry() {
+        let fills = vec![
+            buy("e1", 1, 100.0, 0),
+            buy("e2", 3, 96.0, 1),
+            sell("e3", 4, 99.0, 2),
+        ];
+        let trades = reconstruct_trades(&fills, points);
+
+        assert_eq!(trades.len(), 1);
+        let trade = &trades[0];
+        assert_eq!(trade.quantity, 4);
+        assert_eq!(trade.max_position, 4);
+        assert_close(trade.entry_price, 97.0);
+        assert_close(trade.exit_price, 99.0);
+        assert_close(trade.gross_pnl.unwrap(), -1.0 + 9.0);
+    }
+
+    #[test]
+    fn partial_exits_average_the_exit() {
+        let fills = vec![
+            buy("e1", 3, 100.0, 0),
+            sell("e2", 1, 101.0, 1),
+            sell("e3", 1, 102.0, 2),
+            sell("e4", 1, 106.0, 3),
+        ];
+        let trades = reconstruct_trades(&fills, points);
+
+        assert_eq!(trades.len(), 1);
+        let trade = &trades[0];
+        assert_eq!(trade.execution_ids.len(), 4);
+        assert_close(trade.exit_price, 103.0);
+        assert_close(trade.gross_pnl.unwrap(), 9.0);
+        assert_eq!(trade.exit_time, START + Duration::minutes(3));
+    }
+
+    #[test]
+    fn exits_match_the_oldest_lots_first() {
+        let fills = vec![
+            buy("e1", 1, 100.0, 0),
+            buy("e2", 1, 110.0, 1),
+            sell("e3", 1, 105.0, 2),
+            buy("e4", 1, 90.0, 3),
+            sell("e5", 2, 100.0, 4),
+        ];
+        let trades = reconstruct_trades(&fills, points);
+
+        assert_eq!(trades.len(), 1);
+        let trade = &trades[0];
+        // e3 closes the 100 lot (+5), e5 closes the 110 lot (-10) and the 90 lot (+10)
+        assert_close(trade.gross_points, 5.0);
+        assert_eq!(trade.quantity, 3);
+        assert_eq!(trade.max_position, 2);
+    }
+
+    #[test]
+    fn reversal_closes_the_trade_and_opens_the_next() {
+        let fills = vec![
+            buy("e1", 2, 100.0, 0),
+            sell("e2", 5, 104.0, 1),
+            buy("e3", 3, 101.0, 2),
+        ];
+        let trades = reconstruct_trades(&fills, points);
+
+        assert_eq!(trades.len(), 2);
+        let long = &trades[0];
+        assert_eq!(long.direction, Direction::Long);
+        assert_eq!(long.quantity, 2);
+        assert_close(long.gross_pnl.unwrap(), 8.0);
+        // 4.0 for e1, plus two of e2's five contracts
+        assert_close(long.commission, 4.0 + 4.0);
+
+        let short = &trades[1];
+        assert_eq!(short.direction, Direction::Short);
+        assert_eq!(short.opening_execution_id, "e2");
+        assert_eq!(short.quantity, 3);
+        assert_eq!(short.entry_time, START + Duration::minutes(1));
+        assert_close(short.entry_price, 104.0);
+        assert_close(short.gross_pnl.unwrap(), 9.0);
+        assert_close(short.commission, 6.0 + 6.0);
+    }
+
+    #[test]
+    fn open_positions_are_not_trades() {
+        let fills = vec![
+            buy("e1", 1, 100.0, 0),
+            sell("e2", 1, 101.0, 1),
+            buy("e3", 1, 102.0, 2),
+        ];
+        let trades = reconstruct_trades(&fills, points);
+
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].opening_execution_id, "e1");
+    }
+
+    #[test]
+    fn fills_are_grouped_by_account_and_instrument() {
+        let mut nq_buy = buy("n1", 1, 20000.0, 1);
+        nq_buy.instrument = "NQ 09-24".to_string();
+        let mut nq_sell = sell("n2", 1, 20010.0, 3);
+        nq_sell.instrument = "NQ 09-24".to_string();
+        let mut other_account = sell("a1", 1, 100.0, 2);
+        other_account.account = "Apex-001".to_string();
+
+        let fills = vec![buy("e1", 1, 100.0, 0), nq_buy, other_account, nq_sell, sell("e2", 1, 101.0, 4)];
+        let trades = reconstruct_trades(&fills, |instrument| {
+            let point_value = if instrument.starts_with("NQ") { 20.0 } else { 50.0 };
+            Some(ContractMultiplier { point_value, tick_size: 0.25 })
+        });
+
+        assert_eq!(trades.len(), 2);
+        assert_eq!(trades[0].instrument, "NQ 09-24");
+        assert_close(trades[0].gross_pnl.unwrap(), 200.0);
+        assert_eq!(trades[1].instrument, "ES 09-24");
+        assert_close(trades[1].gross_pnl.unwrap(), 50.0);
+    }
+
+    #[test]
+    fn unknown_instruments_are_left_in_points() {
+        let fills = vec![buy("e1", 2, 100.0, 0), sell("e2", 2, 101.5, 1)];
+        let trades = reconstruct_trades(&fills, |_| None);
+
+        assert_close(trades[0].gross_points, 3.0);
+        assert_eq!(trades[0].gross_ticks, None);
+        assert_eq!(trades[0].gross_pnl, None);
+        assert_eq!(trades[0].net_pnl, None);
+    }
+
+    #[test]
+    fn fills_are_ordered_by_time() {
+        let fills = vec![sell("e2", 1, 101.0, 5), buy("e1", 1, 100.0, 0)];
+        let trades = reconstruct_trades(&fills, points);
+
+        assert_eq!(trades.len(), 1);
+        assert_eq!(trades[0].direction, Direction::Long);
+        assert_close(trades[0].gross_pnl.unwrap(), 1.0);
+    }
+
+    #[test]
+    fn trades_are_returned_in_exit_order() {
+        let mut nq_buy = buy("n1", 1, 1.0, 0);
+        nq_buy.instrument = "NQ 09-24".to_string();
+        let mut nq_sell = sell("n2", 1, 1.0, 10);
+        nq_sell.instrument = "NQ 09-24".to_string();
+
+        let fills = vec![nq_buy, nq_sell, buy("e1", 1, 1.0, 1), sell("e2", 1, 1.0, 2)];
+        let trades = reconstruct_trades(&fills, points);
+
+        assert_eq!(trades[0].instrument, "ES 09-24");
+        assert_eq!(trades[1].instrument, "NQ 09-24");
+    }
+}
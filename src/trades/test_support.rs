@@ -0,0 +1,92 @@
+//! src/trades/test_support.rs
+//! `Trade`s for unit tests. Tests set only what they look at, so a new
+//! `Trade` field is added here and nowhere else.
+use time::macros::datetime;
+use time::{Date, Duration, OffsetDateTime, PrimitiveDateTime};
+use super::Trade;
+
+/// Builds a one lot ES long that closed at 14:00 UTC on 2024-07-22 after 5
+/// minutes, breaking even.
+pub struct TradeBuilder {
+    trade: Trade,
+}
+
+impl TradeBuilder {
+    pub fn new() -> Self {
+        let exit_time = datetime!(2024-07-22 14:00 UTC);
+        let entry_time = exit_time - Duration::minutes(5);
+        TradeBuilder {
+            trade: Trade {
+                id: uuid::Uuid::new_v4(),
+                account: "Sim101".to_string(),
+                instrument: "ES 09-24".to_string(),
+                direction: "long".to_string(),
+                entry_time,
+                exit_time,
+                trading_day: exit_time.date(),
+                local_entry_time: PrimitiveDateTime::new(entry_time.date(), entry_time.time()),
+                quantity: 1,
+                max_position: 1,
+                entry_price: 0.0,
+                exit_price: 0.0,
+                gross_points: 0.0,
+                gross_ticks: None,
+                gross_pnl: Some(0.0),
+                commission: 0.0,
+                net_pnl: Some(0.0),
+                rating: None,
+                risk: None,
+                r_multiple: None,
+                tags: Vec::new(),
+            },
+        }
+    }
+
+    /// Without commission, so gross and net PnL are the same
+    pub fn net_pnl(mut self, net_pnl: f64) -> Self {
+        self.trade.gross_pnl = Some(net_pnl);
+        self.trade.net_pnl = Some(net_pnl);
+        self
+    }
+
+    pub fn instrument(mut self, instrument: &str) -> Self {
+        self.trade.instrument = instrument.to_string();
+        self
+    }
+
+    /// Entered at `entry_time` and held for `minutes`, in the trading day of
+    /// the UTC date it closed on.
+    pub fn entered(mut self, entry_time: OffsetDateTime, minutes: i64) -> Self {
+        self.trade.entry_time = entry_time;
+        self.trade.local_entry_time = PrimitiveDateTime::new(entry_time.date(), entry_time.time());
+        self.trade.exit_time = entry_time + Duration::minutes(minutes);
+        self.trade.trading_day = self.trade.exit_time.date();
+        self
+    }
+
+    /// Closed at `exit_time` after 5 minutes
+    pub fn exited(self, exit_time: OffsetDateTime) -> Self {
+        self.entered(exit_time - Duration::minutes(5), 5)
+    }
+
+    /// Keeps the times, for trading days that don't follow the UTC date
+    pub fn trading_day(mut self, trading_day: Date) -> Self {
+        self.trade.trading_day = trading_day;
+        self
+    }
+
+    pub fn tags(mut self, tags: &[&str]) -> Self {
+        self.trade.tags = tags.iter().map(|tag| tag.to_string()).collect();
+        self
+    }
+
+    pub fn build(self) -> Trade {
+        self.trade
+    }
+}
+
+impl Default for TradeBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,386 @@
+//! src/two_factor.rs
+//! Optional TOTP two factor authentication. Users enroll by scanning a QR code
+//! into an authenticator app and entering its first code, and get single use
+//! recovery codes for when they lose the app. Only hashes of those are stored.
+//!
+//! Logging in with a password only marks the login as pending in the session.
+//! The user is logged in once they enter a code on the second step.
+use axum::{
+    extract::Request,
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+    RequestExt,
+};
+use axum_login::tower_sessions::Session;
+use axum_messages::Messages;
+use qrcode::render::svg;
+use qrcode::QrCode;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
+use time::{Duration, OffsetDateTime};
+use totp_rs::{Algorithm, Secret, TOTP};
+use crate::audit;
+use crate::constants::{route_paths, strings};
+use crate::email_verification::hash_token;
+use crate::impersonation;
+use crate::user::{AuthSession, User};
+
+const ISSUER: &str = "TradeSalsa";
+const DIGITS: usize = 6;
+const STEP_SECONDS: u64 = 30;
+/// Codes from one step either side are accepted, for clocks that drift
+const SKEW: u64 = 1;
+
+const RECOVERY_CODE_COUNT: usize = 10;
+/// Letters and digits that can't be mistaken for each other
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+const PENDING_LOGIN_KEY: &str = "two_factor_pending";
+/// How long the user has to enter a code after their password
+pub const PENDING_LOGIN_LIFETIME: Duration = Duration::minutes(5);
+
+/// Paths users who still have to enroll can use
+const ALLOWED_BEFORE_ENROLLMENT: [&str; 4] = [
+    route_paths::TWO_FACTOR_SETUP,
+    route_paths::LOGOUT,
+    route_paths::HEALTH,
+    "/public",
+];
+
+/// What the enrollment page shows.
+#[derive(Debug, Serialize)]
+pub struct Enrollment {
+    /// For typing into apps that can't scan the QR code
+    pub secret: String,
+    pub provisioning_uri: String,
+    pub qr_code_svg: String,
+}
+
+/// A password login waiting for its code.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PendingLogin {
+    pub user_id: uuid::Uuid,
+    pub next: Option<String>,
+    started_at: i64,
+}
+
+fn totp(secret: &str, email: &str) -> Result<TOTP, anyhow::Error> {
+    let secret = Secret::Encoded(secret.to_string()).to_bytes()?;
+    Ok(TOTP::new(
+        Algorithm::SHA1,
+        DIGITS,
+        SKEW as u8,
+        STEP_SECONDS,
+        secret,
+        Some(ISSUER.to_string()),
+        email.to_string(),
+    )?)
+}
+
+/// The time step `code` was generated for, if it's one of the accepted ones
+/// around `unix_time`. Steps let a used code be rejected when it's replayed.
+fn matching_step(totp: &TOTP, code: &str, unix_time: u64) -> Option<u64> {
+    let current = unix_time / totp.step;
+    (current.saturating_sub(SKEW)..=current + SKEW)
+        .find(|step| totp.generate(step * totp.step) == code)
+}
+
+fn now() -> u64 {
+    OffsetDateTime::now_utc().unix_timestamp() as u64
+}
+
+/// Codes are typed in all sorts of ways, like "123 456" or "ABCDE-FGHIJ".
+fn normalize(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn is_totp_code(code: &str) -> bool {
+    code.len() == DIGITS && code.chars().all(|c| c.is_ascii_digit())
+}
+
+/// A recovery code like "k7m2p-x9qrt".
+fn generate_recovery_code() -> String {
+    let mut rng = rand::thread_rng();
+    let mut code: String = (0..10)
+        .map(|_| RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char)
+        .collect();
+    code.insert(5, '-');
+    code
+}
+
+/// Renders `data` as an SVG QR code that can be put straight into a page.
+fn qr_code_svg(data: &str) -> Result<String, anyhow::Error> {
+    let svg = QrCode::new(data.as_bytes())?
+        .render::<svg::Color>()
+        .min_dimensions(200, 200)
+        .build();
+    // Drop the XML declaration, which doesn't belong inside HTML
+    Ok(match svg.split_once("?>") {
+        Some((_, svg)) => svg.to_string(),
+        None => svg,
+    })
+}
+
+/// The secret the user is enrolling with, created the first time they open
+/// the page. It's kept until they finish, so reloading doesn't change the QR
+/// code they scanned.
+pub async fn start_enrollment(db: &PgPool, user: &User) -> Result<Enrollment, anyhow::Error> {
+    let secret = match Secret::generate_secret().to_encoded() {
+        Secret::Encoded(secret) => secret,
+        Secret::Raw(_) => unreachable!("to_encoded always returns an encoded secret"),
+    };
+    let secret: String = sqlx::query_scalar(
+        "INSERT INTO two_factor (user_id, secret) VALUES ($1, $2)
+        ON CONFLICT (user_id) DO UPDATE SET secret = two_factor.secret
+        RETURNING secret"
+    )
+        .bind(user.id)
+        .bind(secret)
+        .fetch_one(db)
+        .await?;
+
+    let provisioning_uri = totp(&secret, &user.email)?.get_url();
+    let qr_code_svg = qr_code_svg(&provisioning_uri)?;
+    Ok(Enrollment { secret, provisioning_uri, qr_code_svg })
+}
+
+/// Turns two factor on when `code` is right for the secret being enrolled.
+/// Returns the new recovery codes to show the user once, or `None` when the
+/// code is wrong.
+pub async fn enable(db: &PgPool, user: &User, code: &str) -> Result<Option<Vec<String>>, anyhow::Error> {
+    let secret: Option<String> = sqlx::query_scalar(
+        "SELECT secret FROM two_factor WHERE user_id = $1 AND enabled_at IS NULL"
+    )
+        .bind(user.id)
+        .fetch_optional(db)
+        .await?;
+    let secret = match secret {
+        Some(secret) => secret,
+        None => return Ok(None),
+    };
+    let step = match matching_step(&totp(&secret, &user.email)?, &normalize(code), now()) {
+        Some(step) => step,
+        None => return Ok(None),
+    };
+
+    let mut transaction = db.begin().await?;
+    sqlx::query("UPDATE two_factor SET enabled_at = NOW(), last_used_step = $2 WHERE user_id = $1")
+        .bind(user.id)
+        .bind(step as i64)
+        .execute(&mut *transaction)
+        .await?;
+    let recovery_codes = replace_recovery_codes(&mut transaction, user.id).await?;
+    audit::record(&mut *transaction, user.id, audit::TWO_FACTOR_ENABLED, None).await?;
+    transaction.commit().await?;
+    Ok(Some(recovery_codes))
+}
+
+/// Turns two factor off and forgets the secret and recovery codes.
+pub async fn disable(db: &PgPool, user_id: uuid::Uuid) -> Result<(), sqlx::Error> {
+    let mut transaction = db.begin().await?;
+    remove(&mut transaction, user_id).await?;
+    audit::record(&mut *transaction, user_id, audit::TWO_FACTOR_DISABLED, None).await?;
+    transaction.commit().await
+}
+
+pub async fn remove(transaction: &mut Transaction<'_, Postgres>, user_id: uuid::Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM two_factor_recovery_codes WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut **transaction)
+        .await?;
+    sqlx::query("DELETE FROM two_factor WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut **transaction)
+        .await?;
+    Ok(())
+}
+
+/// Replaces the user's recovery codes with new ones, returned to show once.
+pub async fn regenerate_recovery_codes(db: &PgPool, user_id: uuid::Uuid) -> Result<Vec<String>, sqlx::Error> {
+    let mut transaction = db.begin().await?;
+    let recovery_codes = replace_recovery_codes(&mut transaction, user_id).await?;
+    audit::record(&mut *transaction, user_id, audit::RECOVERY_CODES_REGENERATED, None).await?;
+    transaction.commit().await?;
+    Ok(recovery_codes)
+}
+
+async fn replace_recovery_codes(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: uuid::Uuid,
+) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query("DELETE FROM two_factor_recovery_codes WHERE user_id = $1")
+        .bind(user_id)
+        .execute(&mut **transaction)
+        .await?;
+
+    let recovery_codes: Vec<String> = (0..RECOVERY_CODE_COUNT).map(|_| generate_recovery_code()).collect();
+    let hashes: Vec<String> = recovery_codes.iter().map(|code| hash_token(&normalize(code))).collect();
+    sqlx::query(
+        "INSERT INTO two_factor_recovery_codes (user_id, code_hash)
+        SELECT $1, * FROM UNNEST($2::text[])"
+    )
+        .bind(user_id)
+        .bind(hashes)
+        .execute(&mut **transaction)
+        .await?;
+    Ok(recovery_codes)
+}
+
+pub async fn unused_recovery_codes<'c>(executor: impl PgExecutor<'c>, user_id: uuid::Uuid) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar("SELECT COUNT(*) FROM two_factor_recovery_codes WHERE user_id = $1 AND used_at IS NULL")
+        .bind(user_id)
+        .fetch_one(executor)
+        .await
+}
+
+/// Checks a code from the user's authenticator app, or one of their recovery
+/// codes, which is used up. Each app code is only accepted once.
+pub async fn verify(db: &PgPool, user: &User, code: &str) -> Result<bool, anyhow::Error> {
+    let code = normalize(code);
+    if !is_totp_code(&code) {
+        return use_recovery_code(db, user.id, &code).await;
+    }
+
+    let secret: Option<String> = sqlx::query_scalar(
+        "SELECT secret FROM two_factor WHERE user_id = $1 AND enabled_at IS NOT NULL"
+    )
+        .bind(user.id)
+        .fetch_optional(db)
+        .await?;
+    let secret = match secret {
+        Some(secret) => secret,
+        None => return Ok(false),
+    };
+    let step = match matching_step(&totp(&secret, &user.email)?, &code, now()) {
+        Some(step) => step,
+        None => return Ok(false),
+    };
+
+    let accepted = sqlx::query(
+        "UPDATE two_factor SET last_used_step = $2
+        WHERE user_id = $1 AND (last_used_step IS NULL OR last_used_step < $2)"
+    )
+        .bind(user.id)
+        .bind(step as i64)
+        .execute(db)
+        .await?;
+    Ok(accepted.rows_affected() == 1)
+}
+
+async fn use_recovery_code(db: &PgPool, user_id: uuid::Uuid, code: &str) -> Result<bool, anyhow::Error> {
+    let mut transaction = db.begin().await?;
+    let used = sqlx::query(
+        "UPDATE two_factor_recovery_codes SET used_at = NOW()
+        WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL"
+    )
+        .bind(user_id)
+        .bind(hash_token(code))
+        .execute(&mut *transaction)
+        .await?;
+    if used.rows_affected() == 0 {
+        return Ok(false);
+    }
+
+    let remaining = unused_recovery_codes(&mut *transaction, user_id).await?;
+    let detail = format!("{} left", remaining);
+    audit::record(&mut *transaction, user_id, audit::RECOVERY_CODE_USED, Some(&detail)).await?;
+    transaction.commit().await?;
+    Ok(true)
+}
+
+/// Remembers that the user got their password right, for the second step.
+pub async fn start_login(session: &Session, user_id: uuid::Uuid, next: Option<String>) -> Result<(), anyhow::Error> {
+    let pending = PendingLogin {
+        user_id,
+        next,
+        started_at: OffsetDateTime::now_utc().unix_timestamp(),
+    };
+    session.insert(PENDING_LOGIN_KEY, pending).await?;
+    Ok(())
+}
+
+/// The login waiting for a code, unless it has expired.
+pub async fn pending_login(session: &Session) -> Option<PendingLogin> {
+    let pending: PendingLogin = session.get(PENDING_LOGIN_KEY).await.ok().flatten()?;
+    let started_at = OffsetDateTime::from_unix_timestamp(pending.started_at).ok()?;
+    (started_at + PENDING_LOGIN_LIFETIME > OffsetDateTime::now_utc()).then_some(pending)
+}
+
+pub async fn finish_login(session: &Session) -> Result<(), anyhow::Error> {
+    session.remove::<PendingLogin>(PENDING_LOGIN_KEY).await?;
+    Ok(())
+}
+
+/// Middleware that sends users whose roles require two factor to set it up
+/// before they can do anything else. Admins impersonating them aren't stopped.
+pub async fn require_enrollment(
+    auth_session: AuthSession,
+    session: Session,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let must_enroll = auth_session
+        .user
+        .as_ref()
+        .is_some_and(|user| user.two_factor_required && !user.two_factor_enabled);
+    let path = request.uri().path();
+    let allowed = ALLOWED_BEFORE_ENROLLMENT.iter().any(|prefix| path.starts_with(prefix));
+    if !must_enroll || allowed || impersonation::impersonator(&session).await.is_some() {
+        return next.run(request).await;
+    }
+
+    // Only taken when redirecting, since taking it reads the pending messages
+    if let Ok(messages) = request.extract_parts::<Messages>().await {
+        messages.info(strings::TWO_FACTOR_REQUIRED);
+    }
+    Redirect::to(route_paths::TWO_FACTOR_SETUP).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_recovery_code, is_totp_code, matching_step, normalize, totp};
+
+    const SECRET: &str = "OBWGC2LOFVZXI4TJNZTS243FMNZGK5BN";
+
+    #[test]
+    fn codes_are_accepted_one_step_either_side() {
+        let totp = totp(SECRET, "trader@example.com").unwrap();
+        let now = 1_723_600_000;
+        let step = now / 30;
+        assert_eq!(matching_step(&totp, &totp.generate(now), now), Some(step));
+        assert_eq!(matching_step(&totp, &totp.generate(now - 30), now), Some(step - 1));
+        assert_eq!(matching_step(&totp, &totp.generate(now + 30), now), Some(step + 1));
+        assert_eq!(matching_step(&totp, &totp.generate(now - 90), now), None);
+        assert_eq!(matching_step(&totp, "000000x", now), None);
+    }
+
+    #[test]
+    fn codes_are_normalized_however_they_are_typed() {
+        assert_eq!(normalize(" 123 456 "), "123456");
+        assert_eq!(normalize("ABCDE-FGHIJ"), "abcdefghij");
+        assert!(is_totp_code(&normalize("123 456")));
+        assert!(!is_totp_code(&normalize("abcde-fghij")));
+        assert!(!is_totp_code("12345"));
+    }
+
+    #[test]
+    fn recovery_codes_are_random_and_readable() {
+        let code = generate_recovery_code();
+        assert_eq!(code.len(), 11);
+        assert_eq!(code.chars().nth(5), Some('-'));
+        assert!(!code.contains(['0', 'o', '1', 'l', 'i']));
+        assert_ne!(code, generate_recovery_code());
+        assert_eq!(normalize(&code).len(), 10);
+    }
+
+    #[test]
+    fn provisioning_uris_name_the_issuer_and_account() {
+        let uri = totp(SECRET, "trader@example.com").unwrap().get_url();
+        assert!(uri.starts_with("otpauth://totp/TradeSalsa:trader%40example.com?"));
+        assert!(uri.contains(&format!("secret={}", SECRET)));
+    }
+}
@@ -1,15 +1,74 @@
+use std::collections::HashSet;
 use async_trait::async_trait;
-use axum_login::{AuthUser, AuthnBackend, UserId};
+use axum_login::{AuthUser, AuthnBackend, AuthzBackend, UserId};
 use password_auth::verify_password;
+use secrecy::{ExposeSecret, Secret};
 use serde::{Deserialize, Serialize};
-use sqlx::{FromRow, PgPool};
+use sqlx::{FromRow, PgExecutor, PgPool};
+use time::OffsetDateTime;
 use tokio::task;
 
+/// An argon2 hash with the same parameters as real ones, of a password no one uses.
+const DUMMY_PASSWORD_HASH: &str =
+    "$argon2id$v=19$m=19456,t=2,p=1$O6jkyKAM7qnVeLGs0h3f5w$dvxeNsjniXsl/7Z+r5D45EKpcKr440lSlbyUUJiLbnU";
+
+/// Users are always loaded with the names of their roles. Callers add a
+/// `WHERE` clause and `GROUP BY users.id`.
+const SELECT_USER: &str = "SELECT users.*,
+    COALESCE(array_agg(roles.name::text) FILTER (WHERE roles.name IS NOT NULL), '{}') AS roles,
+    COALESCE(bool_or(roles.requires_two_factor), FALSE) AS two_factor_required,
+    EXISTS (
+        SELECT 1 FROM two_factor WHERE two_factor.user_id = users.id AND two_factor.enabled_at IS NOT NULL
+    ) AS two_factor_enabled
+FROM users
+LEFT JOIN user_roles ON user_roles.user_id = users.id
+LEFT JOIN roles ON roles.id = user_roles.role_id";
+
 #[derive(Clone, Serialize, Deserialize, FromRow)]
 pub struct User {
-    id: uuid::Uuid,
+    pub id: uuid::Uuid,
     pub email: String,
     password_hash: String,
+    /// `None` until the user confirms their email address
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub email_verified_at: Option<OffsetDateTime>,
+    /// Set by an admin. Disabled users can't log in.
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub disabled_at: Option<OffsetDateTime>,
+    /// Names from the `roles` table. Empty when the query didn't select them.
+    #[sqlx(default)]
+    pub roles: Vec<String>,
+    /// Whether the user finished enrolling in two factor authentication
+    #[sqlx(default)]
+    pub two_factor_enabled: bool,
+    /// Whether one of the user's roles requires two factor authentication
+    #[sqlx(default)]
+    pub two_factor_required: bool,
+}
+
+impl User {
+    pub fn is_verified(&self) -> bool {
+        self.email_verified_at.is_some()
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        self.disabled_at.is_some()
+    }
+
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.iter().any(|name| name == role)
+    }
+
+    /// Checks `password` against the user's hash. Like `Backend::authenticate`,
+    /// the slow verification runs on a blocking thread.
+    pub async fn check_password(&self, password: Secret<String>) -> Result<bool, Error> {
+        let password_hash = self.password_hash.clone();
+        let matches = task::spawn_blocking(move || {
+            verify_password(password.expose_secret(), &password_hash).is_ok()
+        })
+        .await?;
+        Ok(matches)
+    }
 }
 
 // Here we've implemented `Debug` manually to avoid accidentally logging the
@@ -20,6 +79,11 @@ impl std::fmt::Debug for User {
             .field("id", &self.id)
             .field("email", &self.email)
             .field("password_hash", &"[redacted]")
+            .field("email_verified_at", &self.email_verified_at)
+            .field("disabled_at", &self.disabled_at)
+            .field("roles", &self.roles)
+            .field("two_factor_enabled", &self.two_factor_enabled)
+            .field("two_factor_required", &self.two_factor_required)
             .finish()
     }
 }
@@ -78,7 +142,7 @@ impl AuthnBackend for Backend {
         &self,
         creds: Self::Credentials,
     ) -> Result<Option<Self::User>, Self::Error> {
-        let user: Option<Self::User> = sqlx::query_as("SELECT * FROM users WHERE email = $1")
+        let user: Option<Self::User> = sqlx::query_as(&format!("{} WHERE users.email = $1 AND users.disabled_at IS NULL GROUP BY users.id", SELECT_USER))
             .bind(&creds.email)
             .fetch_optional(&self.db)
             .await?;
@@ -88,21 +152,118 @@ impl AuthnBackend for Backend {
         task::spawn_blocking(|| {
             // We're using password-based authentication--this works by comparing our form
             // input with an argon2 password hash.
-            Ok(user.filter(|user| verify_password(creds.password, &user.password_hash).is_ok()))
+            match user {
+                Some(user) => Ok(verify_password(creds.password, &user.password_hash).is_ok().then_some(user)),
+                // Unknown emails are verified against a dummy hash, so they take as
+                // long as wrong passwords and response times don't reveal which
+                // emails have accounts.
+                None => {
+                    let _ = verify_password(creds.password, DUMMY_PASSWORD_HASH);
+                    Ok(None)
+                }
+            }
         })
         .await?
     }
 
     async fn get_user(&self, user_id: &UserId<Self>) -> Result<Option<Self::User>, Self::Error> {
-        let user = sqlx::query_as("SELECT * FROM users WHERE id = $1")
-            .bind(&user_id)
-            .fetch_optional(&self.db)
+        // Disabling a user ends their sessions
+        let user = find_by_id(&self.db, *user_id).await?;
+        Ok(user.filter(|user| !user.is_disabled()))
+    }
+}
+
+/// Something a role allows, like `contract_specs.manage`. The names are in
+/// `constants::permissions`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Permission {
+    pub name: String,
+}
+
+impl From<&str> for Permission {
+    fn from(name: &str) -> Self {
+        Permission { name: name.to_string() }
+    }
+}
+
+#[async_trait]
+impl AuthzBackend for Backend {
+    type Permission = Permission;
+
+    async fn get_group_permissions(
+        &self,
+        user: &Self::User,
+    ) -> Result<HashSet<Self::Permission>, Self::Error> {
+        let names: Vec<String> = sqlx::query_scalar(
+            "SELECT DISTINCT permissions.name::text
+            FROM user_roles
+            JOIN role_permissions ON role_permissions.role_id = user_roles.role_id
+            JOIN permissions ON permissions.id = role_permissions.permission_id
+            WHERE user_roles.user_id = $1"
+        )
+            .bind(user.id)
+            .fetch_all(&self.db)
             .await?;
 
-        Ok(user)
+        Ok(names.into_iter().map(|name| Permission { name }).collect())
     }
 }
 
+pub async fn find_by_id<'c>(executor: impl PgExecutor<'c>, user_id: uuid::Uuid) -> Result<Option<User>, sqlx::Error> {
+    sqlx::query_as(&format!("{} WHERE users.id = $1 GROUP BY users.id", SELECT_USER))
+        .bind(user_id)
+        .fetch_optional(executor)
+        .await
+}
+
+pub async fn record_login(db: &PgPool, user_id: uuid::Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE users SET last_login_at = NOW() WHERE id = $1")
+        .bind(user_id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+/// Gives the user the named role. Does nothing if they already have it.
+pub async fn assign_role<'c>(executor: impl PgExecutor<'c>, user_id: uuid::Uuid, role: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO user_roles (user_id, role_id)
+        SELECT $1, id FROM roles WHERE name = $2
+        ON CONFLICT DO NOTHING"
+    )
+        .bind(user_id)
+        .bind(role)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+pub async fn revoke_role<'c>(executor: impl PgExecutor<'c>, user_id: uuid::Uuid, role: &str) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "DELETE FROM user_roles
+        WHERE user_id = $1 AND role_id = (SELECT id FROM roles WHERE name = $2)"
+    )
+        .bind(user_id)
+        .bind(role)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+/// Route layer that only lets through users with the named role, and responds
+/// `403 Forbidden` to everyone else. Pair it with `login_required!` so logged
+/// out users are sent to log in instead.
+#[macro_export]
+macro_rules! role_required {
+    ($role:expr) => {{
+        async fn has_role(auth_session: $crate::user::AuthSession) -> bool {
+            auth_session.user.as_ref().is_some_and(|user| user.has_role($role))
+        }
+
+        axum_login::predicate_required!(has_role, axum::http::StatusCode::FORBIDDEN)
+    }};
+}
+
 // We use a type alias for convenience.
 //
 // Note that we've supplied our concrete backend here.
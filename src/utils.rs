@@ -2,7 +2,10 @@ use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
 };
+use axum::Json;
+use serde::Serialize;
 use std::fmt::Debug;
+use utoipa::ToSchema;
 
 // Custom error handler function
 pub fn e500<T>(e: T) -> ErrorResponse
@@ -33,3 +36,70 @@ impl IntoResponse for ErrorResponse {
         (self.status_code, self.message).into_response()
     }
 }
+
+/// Errors from JSON routes. Every one has the same body, like
+/// `{"error": {"code": "unauthorized", "message": "..."}}`, so clients can
+/// handle them without parsing text.
+#[derive(Debug)]
+pub struct ApiError {
+    status_code: StatusCode,
+    code: &'static str,
+    message: String,
+}
+
+/// The body of every `ApiError`, public for the OpenAPI spec.
+#[derive(Serialize, ToSchema)]
+pub struct ApiErrorBody<'a> {
+    error: ApiErrorDetail<'a>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ApiErrorDetail<'a> {
+    /// e.g. `unauthorized`, `bad_request` or `not_found`
+    code: &'a str,
+    message: &'a str,
+}
+
+impl ApiError {
+    pub fn new(status_code: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            status_code,
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, "unauthorized", message)
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, "forbidden", message)
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "bad_request", message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, "not_found", message)
+    }
+
+    /// Unlike `e500`, the cause is logged rather than sent to the client.
+    pub fn internal<T: std::fmt::Display>(err: T) -> Self {
+        tracing::error!(error = %err, "API request failed");
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "internal_error", "Something went wrong.")
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ApiErrorBody {
+            error: ApiErrorDetail {
+                code: self.code,
+                message: &self.message,
+            },
+        };
+        (self.status_code, Json(body)).into_response()
+    }
+}
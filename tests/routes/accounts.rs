@@ -0,0 +1,140 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use crate::executions::EXECUTIONS_CSV;
+
+/// A +$48.32 ES trade in a prop firm evaluation account
+pub const EVALUATION_CSV: &str = "\
+Instrument,Action,Quantity,Price,Time,ID,E/X,Position,Order ID,Name,Commission,Rate,Account,Connection,
+ES 09-24,Buy,1,5560,7/23/2024 9:31:00 AM,a1,Entry,1 L,ao1,Entry,$0.84,1,APEX-12345-01,Rithmic,
+ES 09-24,Sell,1,5561,7/23/2024 9:40:00 AM,a2,Exit,-,ao2,Profit target,$0.84,1,APEX-12345-01,Rithmic,
+";
+
+pub fn account_form(display_name: &str, account_type: &str, starting_balance: &str) -> serde_json::Value {
+    serde_json::json!({
+        "display_name": display_name,
+        "account_type": account_type,
+        "starting_balance": starting_balance,
+        "currency": "usd",
+    })
+}
+
+#[tokio::test]
+async fn accounts_require_login() {
+    let app = spawn_app().await;
+
+    let response = app.get_accounts().await;
+    assert!(response.status().is_redirection());
+    let location = response.headers().get("Location").unwrap().to_str().unwrap();
+    assert!(location.starts_with("/login"));
+}
+
+#[tokio::test]
+async fn importing_executions_creates_their_accounts_once() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    app.post_import_executions(EVALUATION_CSV, "+00:00").await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+
+    let accounts = sqlx::query!(
+        "SELECT name, account_type FROM accounts WHERE user_id = $1 ORDER BY name",
+        app.test_user.user_id,
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    let accounts: Vec<(&str, &str)> = accounts
+        .iter()
+        .map(|account| (account.name.as_str(), account.account_type.as_str()))
+        .collect();
+    assert_eq!(accounts, vec![("APEX-12345-01", "live"), ("Sim101", "sim")]);
+
+    let html_page = app.get_accounts().await.text().await.unwrap();
+    assert!(html_page.contains("<td>Sim101</td>"));
+    assert!(html_page.contains("<td>APEX-12345-01</td>"));
+}
+
+#[tokio::test]
+async fn account_details_can_be_edited() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EVALUATION_CSV, "+00:00").await;
+    let account_id = app.account_id("APEX-12345-01").await;
+
+    let response = app.post_account(account_id, &account_form("Apex 50K", "evaluation", "$50,000")).await;
+    assert_is_redirect_to(&response, "/accounts");
+
+    let html_page = app.get_accounts().await.text().await.unwrap();
+    assert!(html_page.contains("Account saved."));
+    assert!(html_page.contains(r#"value="Apex 50K""#));
+    assert!(html_page.contains(r#"<option value="evaluation" selected>"#));
+    assert!(html_page.contains(r#"value="USD""#));
+
+    // The filter shows the display name, and the dashboard adds the starting balance
+    let html_page = app.get_homepage_html().await.text().await.unwrap();
+    assert!(html_page.contains(r#"<option value="APEX-12345-01">Apex 50K</option>"#));
+    assert!(html_page.contains("+$50048.32"));
+}
+
+#[tokio::test]
+async fn invalid_account_details_are_rejected() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    let account_id = app.account_id("Sim101").await;
+
+    let response = app.post_account(account_id, &account_form("Sim", "paper", "0")).await;
+    assert_is_redirect_to(&response, "/accounts");
+
+    let html_page = app.get_accounts().await.text().await.unwrap();
+    assert!(html_page.contains("paper is not an account type."));
+    assert!(html_page.contains(r#"<option value="sim" selected>"#));
+}
+
+#[tokio::test]
+async fn other_users_accounts_cannot_be_edited() {
+    let mut app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    let account_id = app.account_id("Sim101").await;
+
+    app.login_as_new_user().await;
+    let response = app.post_account(account_id, &account_form("Mine now", "live", "0")).await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn stats_can_aggregate_several_accounts() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    app.post_import_executions(EVALUATION_CSV, "+00:00").await;
+
+    for (params, trades) in [
+        (vec![("account", "Sim101")], 1),
+        (vec![("account", "APEX-12345-01")], 1),
+        (vec![("account", "Sim101"), ("account", "APEX-12345-01")], 2),
+        (vec![], 2),
+    ] {
+        let response = app.get_equity(&params).await;
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["per_trade"].as_array().unwrap().len(), trades);
+    }
+
+    let html_page = app
+        .get_trades(&[("account", "Sim101"), ("account", "APEX-12345-01")])
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains(r#"<option value="Sim101" selected>"#));
+    assert!(html_page.contains(r#"<option value="APEX-12345-01" selected>"#));
+
+    // Calendar links keep both accounts
+    let html_page = app
+        .get_calendar(&[("month", "2024-07"), ("account", "Sim101"), ("account", "APEX-12345-01")])
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("/calendar?month=2024-08&account=Sim101&account=APEX-12345-01"));
+}
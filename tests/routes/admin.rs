@@ -0,0 +1,328 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp, TestUser};
+use crate::executions::EXECUTIONS_CSV;
+
+async fn admin_app() -> TestApp {
+    let app = spawn_app().await;
+    app.grant_role("admin").await;
+    app.login().await;
+    app
+}
+
+async fn audit_events(app: &TestApp, user_id: uuid::Uuid) -> Vec<(String, Option<String>, Option<uuid::Uuid>)> {
+    sqlx::query!(
+        "SELECT action, detail, actor_id FROM audit_events WHERE user_id = $1 ORDER BY created_at",
+        user_id,
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|event| (event.action, event.detail, event.actor_id))
+    .collect()
+}
+
+async fn is_logged_in(app: &TestApp, client: &reqwest::Client) -> bool {
+    let response = client
+        .get(format!("{}/settings", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    response.status() == reqwest::StatusCode::OK
+}
+
+async fn can_login(app: &TestApp, user: &TestUser) -> bool {
+    let body = serde_json::json!({ "email": user.email, "password": user.password });
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    let response = client
+        .post(format!("{}/login", &app.address))
+        .form(&body)
+        .send()
+        .await
+        .unwrap();
+    response.headers().get("Location").unwrap() == "/"
+}
+
+fn user_path(user: &TestUser, action: &str) -> String {
+    format!("/admin/users/{}{}", user.user_id, action)
+}
+
+#[tokio::test]
+async fn the_admin_console_is_only_for_admins() {
+    let app = spawn_app().await;
+
+    let response = app.get_path("/admin").await;
+    assert!(response.status().is_redirection());
+    let location = response.headers().get("Location").unwrap().to_str().unwrap();
+    assert!(location.starts_with("/login"));
+
+    app.login().await;
+    let response = app.get_path("/admin").await;
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    let other = app.store_user().await;
+    let response = app.post_path(&user_path(&other, "/disable")).await;
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+    app.grant_role("admin").await;
+    let response = app.get_path("/admin").await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn users_can_be_searched_by_email() {
+    let app = admin_app().await;
+    let other = app.store_user().await;
+
+    let html_page = app.get_path("/admin").await.text().await.unwrap();
+    assert!(html_page.contains(&app.test_user.email));
+    assert!(html_page.contains(&other.email));
+
+    let html_page = app
+        .get_path(&format!("/admin?q={}", other.email.to_uppercase()))
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains(&format!(r#"<a href="/admin/users/{}">{}</a>"#, other.user_id, other.email)));
+    assert!(!html_page.contains(&app.test_user.email));
+
+    // The admin logged in, the other user never has
+    let html_page = app.get_path(&user_path(&other, "")).await.text().await.unwrap();
+    assert!(html_page.contains("<tr><th>Last Login</th><td>Never</td></tr>"));
+    let last_login = sqlx::query_scalar!("SELECT last_login_at FROM users WHERE id = $1", app.test_user.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(last_login.is_some());
+}
+
+#[tokio::test]
+async fn roles_can_be_granted_and_revoked() {
+    let app = admin_app().await;
+    let other = app.store_user().await;
+
+    let response = app
+        .api_client
+        .post(format!("{}{}", app.address, user_path(&other, "/roles/grant")))
+        .form(&[("role", "admin")])
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, &user_path(&other, ""));
+    let html_page = app.get_path(&user_path(&other, "")).await.text().await.unwrap();
+    assert!(html_page.contains("Role granted."));
+    assert!(html_page.contains(&format!(r#"action="{}""#, user_path(&other, "/roles/revoke"))));
+
+    app.api_client
+        .post(format!("{}{}", app.address, user_path(&other, "/roles/revoke")))
+        .form(&[("role", "admin")])
+        .send()
+        .await
+        .unwrap();
+    app.api_client
+        .post(format!("{}{}", app.address, user_path(&other, "/roles/grant")))
+        .form(&[("role", "superuser")])
+        .send()
+        .await
+        .unwrap();
+    let html_page = app.get_path(&user_path(&other, "")).await.text().await.unwrap();
+    assert!(html_page.contains("That role doesn&#x27;t exist."));
+
+    let admin_id = Some(app.test_user.user_id);
+    assert_eq!(
+        audit_events(&app, other.user_id).await,
+        vec![
+            ("role_granted".to_string(), Some("admin".to_string()), admin_id),
+            ("role_revoked".to_string(), Some("admin".to_string()), admin_id),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn disabled_users_are_logged_out_and_cannot_log_in() {
+    let app = admin_app().await;
+    let other = app.store_user().await;
+    let other_client = app.client_logged_in_as(&other).await;
+
+    let response = app.post_path(&user_path(&other, "/disable")).await;
+    assert_is_redirect_to(&response, &user_path(&other, ""));
+    assert!(!is_logged_in(&app, &other_client).await);
+    assert!(!can_login(&app, &other).await);
+
+    app.post_path(&user_path(&other, "/enable")).await;
+    assert!(can_login(&app, &other).await);
+
+    let actions: Vec<String> = audit_events(&app, other.user_id).await.into_iter().map(|event| event.0).collect();
+    assert_eq!(actions, vec!["account_disabled", "account_enabled"]);
+}
+
+#[tokio::test]
+async fn admins_cannot_lock_themselves_out() {
+    let app = admin_app().await;
+    let admin = TestUser {
+        user_id: app.test_user.user_id,
+        email: app.test_user.email.clone(),
+        password: app.test_user.password.clone(),
+    };
+
+    for action in ["/disable", "/delete", "/reset-password", "/impersonate"] {
+        let response = app.post_path(&user_path(&admin, action)).await;
+        assert_is_redirect_to(&response, &user_path(&admin, ""));
+    }
+    let response = app
+        .api_client
+        .post(format!("{}{}", app.address, user_path(&admin, "/roles/revoke")))
+        .form(&[("role", "admin")])
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, &user_path(&admin, ""));
+
+    let html_page = app.get_path(&user_path(&admin, "")).await.text().await.unwrap();
+    assert!(html_page.contains("You can&#x27;t do that to your own account."));
+    assert!(audit_events(&app, admin.user_id).await.is_empty());
+    assert!(can_login(&app, &admin).await);
+}
+
+#[tokio::test]
+async fn deleting_a_user_removes_them() {
+    let app = admin_app().await;
+    let other = app.store_user().await;
+
+    let response = app.post_path(&user_path(&other, "/delete")).await;
+    assert_is_redirect_to(&response, "/admin");
+
+    let exists = sqlx::query_scalar!("SELECT EXISTS (SELECT 1 FROM users WHERE id = $1)", other.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(exists, Some(false));
+    assert_eq!(
+        audit_events(&app, app.test_user.user_id).await,
+        vec![("account_deleted".to_string(), Some(other.email.clone()), Some(app.test_user.user_id))]
+    );
+    let response = app.get_path(&user_path(&other, "")).await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn deleting_a_user_removes_their_screenshot_files() {
+    let mut app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    let trade_id = app.trade_ids().await[0];
+    app.post_screenshot(trade_id, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR", "chart.png").await;
+    let file_name = sqlx::query_scalar!("SELECT file_name FROM trade_screenshots").fetch_one(&app.db_pool).await.unwrap();
+    let path = app.state.screenshot_directory.join(file_name);
+    assert!(path.exists());
+
+    let owner_id = app.test_user.user_id;
+    app.login_as_new_user().await;
+    app.grant_role("admin").await;
+    let response = app.post_path(&format!("/admin/users/{}/delete", owner_id)).await;
+    assert_is_redirect_to(&response, "/admin");
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn forcing_a_password_reset_clears_the_password() {
+    let app = admin_app().await;
+    let other = app.store_user().await;
+    let other_client = app.client_logged_in_as(&other).await;
+
+    let response = app.post_path(&user_path(&other, "/reset-password")).await;
+    assert_is_redirect_to(&response, &user_path(&other, ""));
+
+    assert!(!is_logged_in(&app, &other_client).await);
+    assert!(!can_login(&app, &other).await);
+    // A real hash, so failed logins take as long as for anyone else
+    let password_hash = sqlx::query_scalar!("SELECT password_hash FROM users WHERE id = $1", other.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(password_hash.starts_with("$argon2"));
+    let tokens = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM password_reset_tokens WHERE user_id = $1 AND used_at IS NULL",
+        other.user_id,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(tokens, Some(1));
+}
+
+#[tokio::test]
+async fn admins_can_impersonate_users_and_switch_back() {
+    let app = admin_app().await;
+    let other = app.store_user().await;
+
+    let response = app.post_path(&user_path(&other, "/impersonate")).await;
+    assert_is_redirect_to(&response, "/");
+
+    // Every page says who the admin is acting as
+    for path in ["/", "/settings"] {
+        let html_page = app.get_path(path).await.text().await.unwrap();
+        assert!(html_page.contains(&format!("You're impersonating {}.", other.email)));
+    }
+    let html_page = app.get_path("/settings").await.text().await.unwrap();
+    assert!(html_page.contains(&format!("You're signed in as {}.", other.email)));
+    assert_eq!(app.get_path("/admin").await.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let response = app.post_path("/admin/impersonation/stop").await;
+    assert_is_redirect_to(&response, &user_path(&other, ""));
+    let html_page = app.get_path(&user_path(&other, "")).await.text().await.unwrap();
+    assert!(html_page.contains("You&#x27;re back in your own account."));
+    assert!(!html_page.contains("impersonating"));
+
+    let admin_id = Some(app.test_user.user_id);
+    assert_eq!(
+        audit_events(&app, other.user_id).await,
+        vec![
+            ("impersonation_started".to_string(), None, admin_id),
+            ("impersonation_stopped".to_string(), None, admin_id),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn impersonation_cannot_be_chained_through_another_admin() {
+    let app = admin_app().await;
+    let other_admin = app.store_user().await;
+    sqlx::query!(
+        "INSERT INTO user_roles (user_id, role_id) SELECT $1, id FROM roles WHERE name = 'admin'",
+        other_admin.user_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    let other = app.store_user().await;
+
+    let response = app.post_path(&user_path(&other_admin, "/impersonate")).await;
+    assert_is_redirect_to(&response, &user_path(&other_admin, ""));
+    let html_page = app.get_path(&user_path(&other_admin, "")).await.text().await.unwrap();
+    assert!(html_page.contains("Other admins can&#x27;t be impersonated."));
+
+    // Admin pages, including starting another impersonation, are closed
+    // until the admin switches back
+    app.post_path(&user_path(&other, "/impersonate")).await;
+    let response = app.post_path(&user_path(&other_admin, "/impersonate")).await;
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let response = app.post_path("/admin/impersonation/stop").await;
+    assert_is_redirect_to(&response, &user_path(&other, ""));
+    let html_page = app.get_path("/settings").await.text().await.unwrap();
+    assert!(html_page.contains(&format!("You're signed in as {}.", app.test_user.email)));
+    assert!(!html_page.contains("impersonating"));
+
+    assert!(audit_events(&app, other_admin.user_id).await.is_empty());
+    let admin_id = Some(app.test_user.user_id);
+    assert_eq!(
+        audit_events(&app, other.user_id).await,
+        vec![
+            ("impersonation_started".to_string(), None, admin_id),
+            ("impersonation_stopped".to_string(), None, admin_id),
+        ]
+    );
+}
@@ -0,0 +1,158 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use crate::executions::EXECUTIONS_CSV;
+
+/// Creates a token on the settings page and returns it
+pub async fn create_token(app: &TestApp, scope: &str, expires_in_days: &str) -> String {
+    let response = app
+        .post_settings("/api-tokens", &[("name", "Script"), ("scope", scope), ("expires_in_days", expires_in_days)])
+        .await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let html = response.text().await.unwrap();
+    html.split("<p><code>")
+        .nth(1)
+        .and_then(|part| part.split("</code>").next())
+        .expect("The new token isn't shown")
+        .to_string()
+}
+
+/// A request without the test client's session cookie
+async fn get_equity_with(app: &TestApp, authorization: Option<&str>) -> reqwest::Response {
+    let mut request = reqwest::Client::new().get(format!("{}/equity", &app.address));
+    if let Some(authorization) = authorization {
+        request = request.header("Authorization", authorization);
+    }
+    request.send().await.expect("Failed to execute request.")
+}
+
+#[tokio::test]
+async fn tokens_are_shown_once_and_stored_hashed() {
+    let app = spawn_app().await;
+    app.login().await;
+
+    let token = create_token(&app, "read", "30").await;
+    assert!(token.starts_with("tsk_"));
+    assert_eq!(token.len(), 36);
+
+    let html = app.get_path("/settings/api-tokens").await.text().await.unwrap();
+    assert!(!html.contains(&token));
+    assert!(html.contains(&token[..8]));
+    assert!(html.contains("Script"));
+
+    let row = sqlx::query!(
+        "SELECT token_hash, scope, expires_at > NOW() + INTERVAL '29 days' AS expires_later FROM api_tokens WHERE user_id = $1",
+        app.test_user.user_id,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_ne!(row.token_hash, token);
+    assert_eq!(row.token_hash.len(), 64);
+    assert_eq!(row.scope, "read");
+    assert_eq!(row.expires_later, Some(true));
+
+    let action = sqlx::query_scalar!("SELECT action FROM audit_events WHERE user_id = $1", app.test_user.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(action, "api_token_created");
+}
+
+#[tokio::test]
+async fn invalid_tokens_are_not_created() {
+    let app = spawn_app().await;
+    app.login().await;
+
+    for (name, scope, expires_in_days) in [("", "read", "30"), ("Script", "admin", "30"), ("Script", "read", "0")] {
+        let response = app
+            .post_settings("/api-tokens", &[("name", name), ("scope", scope), ("expires_in_days", expires_in_days)])
+            .await;
+        assert_is_redirect_to(&response, "/settings/api-tokens");
+    }
+    let count = sqlx::query_scalar!("SELECT COUNT(*) FROM api_tokens").fetch_one(&app.db_pool).await.unwrap();
+    assert_eq!(count, Some(0));
+}
+
+#[tokio::test]
+async fn json_routes_accept_a_bearer_token_instead_of_a_session() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    let token = create_token(&app, "read", "never").await;
+
+    let response = get_equity_with(&app, Some(&format!("Bearer {}", token))).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["per_trade"].as_array().unwrap().len(), 1);
+
+    let last_used = sqlx::query_scalar!("SELECT last_used_at FROM api_tokens WHERE user_id = $1", app.test_user.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(last_used.is_some());
+}
+
+#[tokio::test]
+async fn missing_and_unknown_tokens_get_a_json_error() {
+    let app = spawn_app().await;
+
+    for authorization in [None, Some("Bearer tsk_nope"), Some("Basic dXNlcjpwYXNz")] {
+        let response = get_equity_with(&app, authorization).await;
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["error"]["code"], "unauthorized");
+        assert!(body["error"]["message"].is_string());
+    }
+}
+
+#[tokio::test]
+async fn revoked_and_expired_tokens_stop_working() {
+    let app = spawn_app().await;
+    app.login().await;
+    let revoked = create_token(&app, "read", "30").await;
+    let expired = create_token(&app, "write", "30").await;
+
+    let token_id = sqlx::query_scalar!("SELECT id FROM api_tokens WHERE scope = 'read'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let response = app.post_path(&format!("/settings/api-tokens/{}/revoke", token_id)).await;
+    assert_is_redirect_to(&response, "/settings/api-tokens");
+    sqlx::query!("UPDATE api_tokens SET expires_at = NOW() - INTERVAL '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    for token in [revoked, expired] {
+        let response = get_equity_with(&app, Some(&format!("Bearer {}", token))).await;
+        assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    }
+}
+
+#[tokio::test]
+async fn users_can_only_revoke_their_own_tokens() {
+    let mut app = spawn_app().await;
+    app.login().await;
+    let token = create_token(&app, "read", "30").await;
+    let token_id = sqlx::query_scalar!("SELECT id FROM api_tokens").fetch_one(&app.db_pool).await.unwrap();
+
+    app.login_as_new_user().await;
+    let response = app.post_path(&format!("/settings/api-tokens/{}/revoke", token_id)).await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let response = get_equity_with(&app, Some(&format!("Bearer {}", token))).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn disabled_users_tokens_stop_working() {
+    let app = spawn_app().await;
+    app.login().await;
+    let token = create_token(&app, "read", "30").await;
+
+    sqlx::query!("UPDATE users SET disabled_at = NOW() WHERE id = $1", app.test_user.user_id)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let response = get_equity_with(&app, Some(&format!("Bearer {}", token))).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
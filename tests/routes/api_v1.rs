@@ -0,0 +1,296 @@
+use crate::helpers::{spawn_app, TestApp};
+use crate::api_tokens::create_token;
+use crate::executions::EXECUTIONS_CSV;
+
+async fn get_json(app: &TestApp, path: &str) -> (reqwest::StatusCode, serde_json::Value) {
+    let response = app.get_path(&format!("/api/v1{}", path)).await;
+    let status = response.status();
+    let body = response.json().await.expect("The response isn't JSON");
+    (status, body)
+}
+
+async fn app_with_trades() -> TestApp {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    app
+}
+
+#[tokio::test]
+async fn the_api_requires_a_token_or_session() {
+    let app = spawn_app().await;
+
+    for path in ["/trades", "/executions", "/accounts", "/tags", "/stats"] {
+        let (status, body) = get_json(&app, path).await;
+        assert_eq!(status, reqwest::StatusCode::UNAUTHORIZED);
+        assert_eq!(body["error"]["code"], "unauthorized");
+    }
+}
+
+#[tokio::test]
+async fn executions_are_paginated() {
+    let app = app_with_trades().await;
+
+    let (status, body) = get_json(&app, "/executions?per_page=1").await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    assert_eq!(body["data"].as_array().unwrap().len(), 1);
+    assert_eq!(body["data"][0]["execution_id"], "e1");
+    assert_eq!(body["data"][0]["executed_at"], "2024-07-22T09:31:05Z");
+    assert_eq!(
+        body["pagination"],
+        serde_json::json!({ "page": 1, "per_page": 1, "total": 2, "total_pages": 2 })
+    );
+
+    let (_, body) = get_json(&app, "/executions?per_page=1&page=2").await;
+    assert_eq!(body["data"][0]["execution_id"], "e2");
+    let (_, body) = get_json(&app, "/executions?per_page=1&page=3").await;
+    assert!(body["data"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn trades_are_listed_filtered_and_found_by_id() {
+    let app = app_with_trades().await;
+
+    let (status, body) = get_json(&app, "/trades").await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    assert_eq!(body["pagination"]["total"], 1);
+    let trade = &body["data"][0];
+    assert_eq!(trade["account"], "Sim101");
+    assert!((trade["net_pnl"].as_f64().unwrap() - 108.32).abs() < 1e-9);
+
+    let (status, found) = get_json(&app, &format!("/trades/{}", trade["id"].as_str().unwrap())).await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+    assert_eq!(&found, trade);
+
+    for query in ["?from=2030-01-01", "?account=Sim999", "?tag=ORB"] {
+        let (_, body) = get_json(&app, &format!("/trades{}", query)).await;
+        assert_eq!(body["pagination"]["total"], 0);
+    }
+    let (_, body) = get_json(&app, "/executions?account=Sim101&account=Sim999&to=2024-07-22").await;
+    assert_eq!(body["pagination"]["total"], 2);
+}
+
+#[tokio::test]
+async fn errors_have_a_consistent_body() {
+    let app = app_with_trades().await;
+
+    for path in ["/trades?from=07/01/2024", "/trades?page=0", "/executions?per_page=501", "/trades/not-a-uuid"] {
+        let (status, body) = get_json(&app, path).await;
+        assert_eq!(status, reqwest::StatusCode::BAD_REQUEST, "{}", path);
+        assert_eq!(body["error"]["code"], "bad_request");
+        assert!(body["error"]["message"].is_string());
+    }
+    for path in [format!("/trades/{}", uuid::Uuid::new_v4()), "/orders".to_string()] {
+        let (status, body) = get_json(&app, &path).await;
+        assert_eq!(status, reqwest::StatusCode::NOT_FOUND, "{}", path);
+        assert_eq!(body["error"]["code"], "not_found");
+    }
+}
+
+#[tokio::test]
+async fn accounts_tags_and_stats_are_returned() {
+    let app = app_with_trades().await;
+    let trade_id = app.trade_ids().await[0];
+    let body = serde_json::json!({ "notes": "", "rating": "", "risk": "", "tags": "ORB, patience" });
+    app.post_journal(trade_id, &body).await;
+
+    let (_, body) = get_json(&app, "/accounts").await;
+    assert_eq!(body["data"][0]["name"], "Sim101");
+    let account_id = body["data"][0]["id"].as_str().unwrap().to_string();
+    let (status, _) = get_json(&app, &format!("/accounts/{}", account_id)).await;
+    assert_eq!(status, reqwest::StatusCode::OK);
+
+    let (_, body) = get_json(&app, "/tags").await;
+    assert_eq!(
+        body["data"],
+        serde_json::json!([{ "name": "ORB", "trade_count": 1 }, { "name": "patience", "trade_count": 1 }])
+    );
+
+    let (_, body) = get_json(&app, "/stats?tag=orb").await;
+    assert_eq!(body["trade_count"], 1);
+    assert_eq!(body["win_rate"], 100.0);
+    let (_, body) = get_json(&app, "/stats/equity").await;
+    assert_eq!(body["per_trade"].as_array().unwrap().len(), 1);
+    let (_, body) = get_json(&app, "/stats/daily").await;
+    assert_eq!(body["data"][0]["date"], "2024-07-22");
+    assert_eq!(body["data"][0]["trade_count"], 1);
+    let (_, body) = get_json(&app, "/stats/breakdowns").await;
+    assert_eq!(body["data"][0]["title"], "Tag");
+}
+
+#[tokio::test]
+async fn the_openapi_spec_is_public() {
+    let app = spawn_app().await;
+
+    let response = reqwest::get(format!("{}/api/v1/openapi.json", &app.address)).await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let spec: serde_json::Value = response.json().await.unwrap();
+    assert!(spec["openapi"].as_str().unwrap().starts_with("3."));
+    assert_eq!(spec["servers"][0]["url"], "/api/v1");
+    for path in ["/executions", "/trades", "/trades/{id}", "/accounts", "/tags", "/stats", "/stats/equity"] {
+        assert!(spec["paths"][path]["get"].is_object(), "{} isn't documented", path);
+    }
+    assert!(spec["components"]["schemas"]["TradePage"].is_object());
+    assert!(spec["components"]["securitySchemes"]["bearer_token"].is_object());
+}
+
+fn execution(execution_id: &str, action: &str, price: f64, executed_at: &str) -> serde_json::Value {
+    serde_json::json!({
+        "execution_id": execution_id,
+        "account": "Sim101",
+        "instrument": "ES 09-24",
+        "action": action,
+        "quantity": 1,
+        "price": price,
+        "executed_at": executed_at,
+        "commission": 2.09,
+    })
+}
+
+async fn post_executions(app: &TestApp, executions: &[serde_json::Value]) -> (reqwest::StatusCode, serde_json::Value) {
+    let response = app
+        .api_client
+        .post(format!("{}/api/v1/executions", &app.address))
+        .json(&serde_json::json!({ "executions": executions }))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    let status = response.status();
+    let body = response.json().await.expect("The response isn't JSON");
+    app.run_jobs().await;
+    (status, body)
+}
+
+#[tokio::test]
+async fn live_executions_tolerate_duplicates_and_any_order() {
+    let app = spawn_app().await;
+    app.login().await;
+    let entry = execution("e1", "Buy", 5565.25, "2024-07-22T04:31:05-05:00");
+    let exit = execution("e2", "Sell", 5567.5, "2024-07-22T09:45:10Z");
+
+    // The exit arrives first, then the entry twice
+    let (status, report) = post_executions(&app, std::slice::from_ref(&exit)).await;
+    assert_eq!(status, reqwest::StatusCode::ACCEPTED);
+    assert_eq!(report, serde_json::json!({ "received": 1, "inserted": 1, "duplicates": 0 }));
+    let (_, report) = post_executions(&app, &[entry.clone(), entry.clone()]).await;
+    assert_eq!(report, serde_json::json!({ "received": 2, "inserted": 1, "duplicates": 1 }));
+    let (_, report) = post_executions(&app, &[entry, exit]).await;
+    assert_eq!(report["duplicates"], 2);
+
+    let (_, body) = get_json(&app, "/trades").await;
+    assert_eq!(body["pagination"]["total"], 1);
+    assert_eq!(body["data"][0]["direction"], "long");
+    assert_eq!(body["data"][0]["entry_time"], "2024-07-22T09:31:05Z");
+    assert!((body["data"][0]["net_pnl"].as_f64().unwrap() - 108.32).abs() < 1e-9);
+    let (_, body) = get_json(&app, "/accounts").await;
+    assert_eq!(body["data"][0]["name"], "Sim101");
+}
+
+#[tokio::test]
+async fn live_executions_only_rebuild_their_own_trades() {
+    let app = app_with_trades().await;
+    let trade_id = app.trade_ids().await[0];
+    let body = serde_json::json!({ "notes": "Keep this", "rating": "", "risk": "", "tags": "ORB" });
+    app.post_journal(trade_id, &body).await;
+
+    let mut entry = execution("n1", "Sell", 18000.0, "2024-07-23T14:00:00Z");
+    let mut exit = execution("n2", "Buy", 17990.0, "2024-07-23T14:05:00Z");
+    for event in [&mut entry, &mut exit] {
+        event["instrument"] = "NQ 09-24".into();
+    }
+    let (status, report) = post_executions(&app, &[entry, exit]).await;
+    assert_eq!(status, reqwest::StatusCode::ACCEPTED);
+    assert_eq!(report["inserted"], 2);
+
+    let (_, body) = get_json(&app, "/trades").await;
+    assert_eq!(body["pagination"]["total"], 2);
+    assert_eq!(body["data"][0]["id"], trade_id.to_string());
+    assert_eq!(body["data"][0]["tags"], serde_json::json!(["ORB"]));
+    assert_eq!(body["data"][1]["direction"], "short");
+}
+
+#[tokio::test]
+async fn journals_follow_trades_when_an_earlier_fill_arrives_late() {
+    let app = spawn_app().await;
+    app.login().await;
+    let late_entry = execution("e1", "Sell", 5566.0, "2024-07-22T14:00:00Z");
+    let buy = execution("e2", "Buy", 5565.0, "2024-07-22T14:05:00Z");
+    let sell = execution("e3", "Sell", 5567.0, "2024-07-22T14:10:00Z");
+    post_executions(&app, &[buy.clone(), sell]).await;
+    let trade_id = app.trade_ids().await[0];
+    let body = serde_json::json!({ "notes": "Waited for the pullback", "rating": "4", "risk": "100", "tags": "ORB" });
+    app.post_journal(trade_id, &body).await;
+    app.post_screenshot(trade_id, b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR", "chart.png").await;
+
+    // The buy now closes a short opened by the late sell
+    let (_, report) = post_executions(&app, &[late_entry]).await;
+    assert_eq!(report["inserted"], 1);
+
+    let trade = sqlx::query!(
+        "SELECT id, opening_execution_id, notes, rating, risk FROM trades WHERE $1 = ANY(execution_ids)",
+        "e2",
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_ne!(trade.id, trade_id);
+    assert_eq!(trade.opening_execution_id, "e1");
+    assert_eq!(trade.notes, "Waited for the pullback");
+    assert_eq!(trade.rating, Some(4));
+    assert_eq!(trade.risk, Some(100.0));
+    let (_, body) = get_json(&app, "/trades").await;
+    assert_eq!(body["data"][0]["tags"], serde_json::json!(["ORB"]));
+    let screenshots = sqlx::query_scalar!("SELECT trade_id FROM trade_screenshots")
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(screenshots, vec![trade.id]);
+}
+
+#[tokio::test]
+async fn invalid_live_executions_are_rejected() {
+    let app = spawn_app().await;
+    app.login().await;
+    let valid = execution("e1", "Buy", 5565.25, "2024-07-22T09:31:05Z");
+    let mut invalid = valid.clone();
+    invalid["quantity"] = 0.into();
+
+    let (status, body) = post_executions(&app, &[valid.clone(), invalid]).await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+    assert!(body["error"]["message"].as_str().unwrap().starts_with("executions[1]:"));
+    let (status, _) = post_executions(&app, &[]).await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+    let (status, _) = post_executions(&app, &vec![valid; 101]).await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+
+    // A time without an offset could be in any time zone
+    let no_offset = execution("e1", "Buy", 5565.25, "2024-07-22T09:31:05");
+    let (status, body) = post_executions(&app, &[no_offset]).await;
+    assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+    assert_eq!(body["error"]["code"], "bad_request");
+
+    let count = sqlx::query_scalar!("SELECT COUNT(*) FROM executions").fetch_one(&app.db_pool).await.unwrap();
+    assert_eq!(count, Some(0));
+}
+
+#[tokio::test]
+async fn live_executions_need_a_write_token_and_a_confirmed_email() {
+    let app = spawn_app().await;
+    app.login().await;
+    let token = create_token(&app, "read", "never").await;
+    let body = serde_json::json!({ "executions": [execution("e1", "Buy", 5565.25, "2024-07-22T09:31:05Z")] });
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/api/v1/executions", &app.address))
+        .bearer_auth(&token)
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+    app.unverify_email().await;
+    let (status, body) = post_executions(&app, &[execution("e1", "Buy", 5565.25, "2024-07-22T09:31:05Z")]).await;
+    assert_eq!(status, reqwest::StatusCode::FORBIDDEN);
+    assert_eq!(body["error"]["message"], "Confirm your email address before importing trades.");
+}
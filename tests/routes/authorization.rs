@@ -0,0 +1,44 @@
+use axum_login::{AuthnBackend, AuthzBackend};
+use crate::helpers::{assert_is_redirect_to, fake_email, spawn_app};
+use tradesalsa::user::Backend;
+
+#[tokio::test]
+async fn registering_assigns_the_basic_role() {
+    let app = spawn_app().await;
+    let email = fake_email();
+    let body = serde_json::json!({ "email": email, "password": "Tr4ding!days" });
+
+    let response = app.post_register(&body).await;
+    assert_is_redirect_to(&response, "/");
+
+    let roles = sqlx::query_scalar!(
+        "SELECT roles.name FROM users
+        JOIN user_roles ON user_roles.user_id = users.id
+        JOIN roles ON roles.id = user_roles.role_id
+        WHERE users.email = $1",
+        email,
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(roles, vec!["basic"]);
+}
+
+#[tokio::test]
+async fn users_are_loaded_with_their_roles_and_permissions() {
+    let app = spawn_app().await;
+    let backend = Backend::new(app.db_pool.clone());
+
+    let user = backend.get_user(&app.test_user.user_id).await.unwrap().unwrap();
+    assert!(user.roles.is_empty());
+    assert!(!backend.has_perm(&user, "contract_specs.manage".into()).await.unwrap());
+
+    app.grant_role("basic").await;
+    app.grant_role("admin").await;
+    let user = backend.get_user(&app.test_user.user_id).await.unwrap().unwrap();
+    let mut roles = user.roles.clone();
+    roles.sort();
+    assert_eq!(roles, vec!["admin", "basic"]);
+    assert!(user.has_role("admin"));
+    assert!(backend.has_perm(&user, "contract_specs.manage".into()).await.unwrap());
+}
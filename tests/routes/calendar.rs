@@ -0,0 +1,52 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use crate::executions::EXECUTIONS_CSV;
+
+/// Closed at 18:00 in Chicago, after the 17:00 CME rollover
+pub const EVENING_SESSION_CSV: &str = "\
+Instrument,Action,Quantity,Price,Time,ID,E/X,Position,Order ID,Name,Commission,Rate,Account,Connection,
+ES 09-24,Sell,1,5570,7/22/2024 10:30:00 PM,e3,Entry,1 S,o3,Entry,$2.09,1,Sim101,Playback Connection,
+ES 09-24,Buy,1,5572,7/22/2024 11:00:00 PM,e4,Exit,-,o4,Stop,$2.09,1,Sim101,Playback Connection,
+";
+
+#[tokio::test]
+async fn calendar_requires_login() {
+    let app = spawn_app().await;
+
+    let response = app.get_calendar(&[]).await;
+    assert!(response.status().is_redirection());
+    let location = response.headers().get("Location").unwrap().to_str().unwrap();
+    assert!(location.starts_with("/login"));
+}
+
+#[tokio::test]
+async fn calendar_shows_each_trading_day() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    app.post_import_executions(EVENING_SESSION_CSV, "+00:00").await;
+
+    let response = app.get_calendar(&[("month", "2024-07")]).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let html_page = response.text().await.unwrap();
+
+    assert!(html_page.contains("<h2>July 2024</h2>"));
+    assert!(html_page.contains("/calendar?month=2024-06"));
+    assert!(html_page.contains("/calendar?month=2024-08"));
+    // The evening trade belongs to the next trading day
+    assert!(html_page.contains("/trades?from=2024-07-22&to=2024-07-22"));
+    assert!(html_page.contains("/trades?from=2024-07-23&to=2024-07-23"));
+    assert!(html_page.contains("+$108.32"));
+    assert!(html_page.contains("-$104.18"));
+}
+
+#[tokio::test]
+async fn calendar_rejects_invalid_months() {
+    let app = spawn_app().await;
+    app.login().await;
+
+    let response = app.get_calendar(&[("month", "2024-13")]).await;
+    assert_is_redirect_to(&response, "/calendar");
+
+    let html_page = app.get_calendar(&[]).await.text().await.unwrap();
+    assert!(html_page.contains("2024-13 is not a valid month."));
+}
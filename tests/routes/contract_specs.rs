@@ -0,0 +1,139 @@
+use crate::helpers::{spawn_app, assert_is_redirect_to};
+use crate::executions::EXECUTIONS_CSV;
+
+fn es_spec(point_value: &str) -> serde_json::Value {
+    serde_json::json!({
+        "root": "es",
+        "description": "E-mini S&P 500",
+        "exchange": "CME",
+        "point_value": point_value,
+        "tick_size": "0.25",
+        "currency": "USD",
+    })
+}
+
+#[tokio::test]
+async fn contract_specs_are_listed() {
+    let app = spawn_app().await;
+    app.login().await;
+
+    let response = app.get_contract_specs().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let html_page = response.text().await.expect("Failed to read the response body");
+    assert!(html_page.contains("<td>E-mini S&amp;P 500</td>"));
+    assert!(html_page.contains("<td>MNQ</td>"));
+    assert!(!html_page.contains(r#"<input type="submit" value="Save""#));
+}
+
+#[tokio::test]
+async fn only_admins_can_edit_contract_specs() {
+    let app = spawn_app().await;
+    app.login().await;
+
+    let response = app.post_contract_spec(&es_spec("10")).await;
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let point_value = sqlx::query_scalar!("SELECT point_value FROM contract_specs WHERE root = 'ES'")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch contract spec.");
+    assert_eq!(point_value, 50.0);
+}
+
+#[tokio::test]
+async fn invalid_contract_specs_are_rejected() {
+    let app = spawn_app().await;
+    app.grant_role("admin").await;
+    app.login().await;
+
+    let response = app.post_contract_spec(&es_spec("-1")).await;
+    assert_is_redirect_to(&response, "/contract-specs");
+
+    let response = app.get_contract_specs().await;
+    let html_page = response.text().await.expect("Failed to read the response body");
+    assert!(html_page.contains("Point value must be a number greater than zero."));
+}
+
+#[tokio::test]
+async fn editing_a_contract_spec_recalculates_trades() {
+    let app = spawn_app().await;
+    app.grant_role("admin").await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+
+    let response = app.post_contract_spec(&es_spec("5")).await;
+    assert_is_redirect_to(&response, "/contract-specs");
+
+    // Trades are rebuilt in the background
+    let mut gross_pnl = None;
+    for _ in 0..50 {
+        gross_pnl = sqlx::query_scalar!("SELECT gross_pnl FROM trades")
+            .fetch_one(&app.db_pool)
+            .await
+            .expect("Failed to fetch trade.");
+        if gross_pnl == Some(11.25) {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(gross_pnl, Some(11.25));
+}
+
+#[tokio::test]
+async fn trades_without_a_contract_spec_are_left_out_of_dollar_stats_until_one_is_added() {
+    let app = spawn_app().await;
+    app.grant_role("admin").await;
+    app.login().await;
+    sqlx::query!("DELETE FROM contract_specs WHERE root = 'ES'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+
+    let trade = sqlx::query!("SELECT gross_points, gross_pnl, net_pnl FROM trades")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch trade.");
+    assert_eq!(trade.gross_points, 2.25);
+    assert_eq!(trade.gross_pnl, None);
+    assert_eq!(trade.net_pnl, None);
+    let body: serde_json::Value = app.get_equity(&[]).await.json().await.unwrap();
+    assert!(body["per_trade"].as_array().unwrap().is_empty());
+
+    app.post_contract_spec(&es_spec("50")).await;
+    app.run_jobs().await;
+    let gross_pnl = sqlx::query_scalar!("SELECT gross_pnl FROM trades")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch trade.");
+    assert_eq!(gross_pnl, Some(112.5));
+    let body: serde_json::Value = app.get_equity(&[]).await.json().await.unwrap();
+    assert_eq!(body["per_trade"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn editing_contract_specs_needs_the_permission_not_the_role_name() {
+    let app = spawn_app().await;
+    app.grant_role("admin").await;
+    app.login().await;
+    sqlx::query!("DELETE FROM role_permissions")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    let response = app.post_contract_spec(&es_spec("10")).await;
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    let html_page = app.get_contract_specs().await.text().await.unwrap();
+    assert!(!html_page.contains(r#"<input type="submit" value="Save""#));
+}
+
+#[tokio::test]
+async fn logged_out_users_are_sent_to_log_in() {
+    let app = spawn_app().await;
+
+    let response = app.post_contract_spec(&es_spec("10")).await;
+    assert!(response.status().is_redirection());
+    let location = response.headers().get("Location").unwrap().to_str().unwrap();
+    assert!(location.starts_with("/login"));
+}
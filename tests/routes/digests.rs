@@ -0,0 +1,156 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use crate::executions::EXECUTIONS_CSV;
+use time::macros::date;
+use tradesalsa::digests;
+
+/// Subscribes the test user to the daily digest as if they'd done it before
+/// the trades in `EXECUTIONS_CSV` were made.
+async fn subscribe_to_daily_digest(app: &TestApp) {
+    let response = app.post_settings("/digests", &serde_json::json!({ "daily": "on" })).await;
+    assert_is_redirect_to(&response, "/settings");
+    sqlx::query!(
+        "UPDATE digest_subscriptions SET last_period_start = '2024-01-01' WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+async fn subscribed_kinds(app: &TestApp) -> Vec<String> {
+    sqlx::query_scalar!(
+        "SELECT kind FROM digest_subscriptions WHERE user_id = $1 ORDER BY kind",
+        app.test_user.user_id
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+}
+
+/// The unsubscribe link from the plain text body of a digest, pointed at the
+/// test app rather than the configured `base_url`.
+fn unsubscribe_link(app: &TestApp, text_body: &str) -> reqwest::Url {
+    let link = text_body
+        .lines()
+        .find_map(|line| line.strip_prefix("Unsubscribe from this one: "))
+        .expect("The digest has no unsubscribe link");
+    let link = reqwest::Url::parse(link).unwrap();
+    let mut url = reqwest::Url::parse(&app.address).unwrap().join(link.path()).unwrap();
+    url.set_query(link.query());
+    url
+}
+
+#[tokio::test]
+async fn digests_are_turned_on_and_off_in_settings() {
+    let app = spawn_app().await;
+    app.login().await;
+
+    let response = app.post_settings("/digests", &serde_json::json!({ "daily": "on", "weekly": "on" })).await;
+    assert_is_redirect_to(&response, "/settings");
+    assert_eq!(subscribed_kinds(&app).await, ["daily", "weekly"]);
+    let html_page = app.get_settings().await.text().await.unwrap();
+    assert!(html_page.contains("Email digest settings saved."));
+    assert!(html_page.contains(r#"id="digest_weekly" type="checkbox" value="on" checked"#));
+
+    app.post_settings("/digests", &serde_json::json!({ "weekly": "on" })).await;
+    assert_eq!(subscribed_kinds(&app).await, ["weekly"]);
+    app.post_settings("/digests", &serde_json::json!({})).await;
+    assert!(subscribed_kinds(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn a_daily_digest_is_queued_once_for_a_day_with_trades() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    subscribe_to_daily_digest(&app).await;
+
+    let queued = digests::queue_due(&app.state, date!(2024-07-22)).await.unwrap();
+    assert_eq!(queued, 1);
+    let emails = app.sent_emails().await;
+    assert_eq!(emails.len(), 1);
+    assert_eq!(emails[0].to, app.test_user.email);
+    assert_eq!(emails[0].subject, "Your TradeSalsa daily recap for Monday, July 22, 2024");
+    assert!(emails[0].text_body.contains("108.32"));
+    assert!(emails[0].text_body.contains("Journaled: 0 of 1 trades (0%)"));
+    assert!(emails[0].html_body.contains("Daily recap"));
+
+    // The day has been sent, and the next one has no trades
+    assert_eq!(digests::queue_due(&app.state, date!(2024-07-22)).await.unwrap(), 0);
+    assert_eq!(digests::queue_due(&app.state, date!(2024-07-23)).await.unwrap(), 0);
+    assert_eq!(app.sent_emails().await.len(), 1);
+}
+
+#[tokio::test]
+async fn app_instances_checking_at_once_queue_a_digest_once() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    subscribe_to_daily_digest(&app).await;
+
+    let (first, second) = tokio::join!(
+        digests::queue_due(&app.state, date!(2024-07-22)),
+        digests::queue_due(&app.state, date!(2024-07-22)),
+    );
+    assert_eq!(first.unwrap() + second.unwrap(), 1);
+    assert_eq!(app.sent_emails().await.len(), 1);
+}
+
+#[tokio::test]
+async fn digests_are_not_sent_to_unconfirmed_addresses() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    subscribe_to_daily_digest(&app).await;
+    app.unverify_email().await;
+
+    assert_eq!(digests::queue_due(&app.state, date!(2024-07-22)).await.unwrap(), 0);
+    assert!(app.sent_emails().await.is_empty());
+}
+
+#[tokio::test]
+async fn the_unsubscribe_link_works_without_logging_in() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    subscribe_to_daily_digest(&app).await;
+    digests::queue_due(&app.state, date!(2024-07-22)).await.unwrap();
+    let url = unsubscribe_link(&app, &app.sent_emails().await[0].text_body);
+    let client = reqwest::Client::new();
+
+    // Following the link only asks for confirmation
+    let html_page = client.get(url.clone()).send().await.unwrap().text().await.unwrap();
+    assert!(html_page.contains("Stop sending the daily recap email?"));
+    assert_eq!(subscribed_kinds(&app).await, ["daily"]);
+
+    let form: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+    let response = client.post(url.clone()).form(&form).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert!(response.text().await.unwrap().contains("You won't get the daily recap email anymore."));
+    assert!(subscribed_kinds(&app).await.is_empty());
+}
+
+#[tokio::test]
+async fn tampered_unsubscribe_links_are_rejected() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_settings("/digests", &serde_json::json!({ "daily": "on", "weekly": "on" })).await;
+    let signature = digests::unsubscribe_signature(
+        &app.state.hmac_secret,
+        app.test_user.user_id,
+        tradesalsa::domain::DigestKind::Daily,
+    );
+    let url = format!("{}/digests/unsubscribe", &app.address);
+    let client = reqwest::Client::new();
+
+    let user_id = app.test_user.user_id.to_string();
+    for (kind, signature) in [("weekly", signature.as_str()), ("daily", "00"), ("monthly", signature.as_str())] {
+        let form = [("user", user_id.as_str()), ("kind", kind), ("signature", signature)];
+        let response = client.get(&url).query(&form).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+        let response = client.post(&url).form(&form).send().await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+        assert!(response.text().await.unwrap().contains("This unsubscribe link is invalid."));
+    }
+    assert_eq!(subscribed_kinds(&app).await, ["daily", "weekly"]);
+}
@@ -0,0 +1,158 @@
+use crate::helpers::{assert_is_redirect_to, fake_email, spawn_app, spawn_database};
+use async_trait::async_trait;
+use tradesalsa::email_outbox::{self, MAX_ATTEMPTS};
+use tradesalsa::emailer::{Email, EmailClient, FileEmailClient};
+
+struct FailingEmailClient;
+
+#[async_trait]
+impl EmailClient for FailingEmailClient {
+    async fn send(&self, _email: &Email) -> Result<(), anyhow::Error> {
+        Err(anyhow::anyhow!("Connection refused"))
+    }
+}
+
+/// Checks that the email being sent is claimed but not locked.
+struct ClaimCheckingEmailClient(sqlx::PgPool);
+
+#[async_trait]
+impl EmailClient for ClaimCheckingEmailClient {
+    async fn send(&self, email: &Email) -> Result<(), anyhow::Error> {
+        let claimed: Option<bool> = sqlx::query_scalar(
+            "SELECT next_attempt_at > NOW() FROM email_outbox WHERE recipient = $1 FOR UPDATE NOWAIT"
+        )
+            .bind(&email.to)
+            .fetch_one(&self.0)
+            .await?;
+        anyhow::ensure!(claimed == Some(true), "The email wasn't claimed");
+        Ok(())
+    }
+}
+
+fn email() -> Email {
+    Email {
+        to: fake_email(),
+        subject: "Hello".to_string(),
+        html_body: "<p>Hello</p>".to_string(),
+        text_body: "Hello".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn registering_queues_a_working_confirmation_link() {
+    let app = spawn_app().await;
+    let email = fake_email();
+
+    let body = serde_json::json!({ "email": email, "password": "Str0ng-trading-pass!" });
+    let response = app.post_register(&body).await;
+    assert_is_redirect_to(&response, "/");
+
+    let sent = app.sent_emails().await;
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].to, email);
+    assert_eq!(sent[0].subject, "Welcome to TradeSalsa!");
+    assert!(sent[0].text_body.contains("/confirm?token="));
+    assert!(!sent[0].text_body.contains("<p>"));
+    let token = sent[0]
+        .html_body
+        .split("?token=")
+        .nth(1)
+        .and_then(|rest| rest.split('"').next())
+        .expect("The email has no confirmation link");
+
+    app.get_confirm(token).await;
+    let verified = sqlx::query_scalar!("SELECT email_verified_at IS NOT NULL FROM users WHERE email = $1", email)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(verified, Some(true));
+}
+
+#[tokio::test]
+async fn changing_the_email_notifies_both_addresses() {
+    let app = spawn_app().await;
+    app.login().await;
+    let new_email = fake_email();
+
+    let form = serde_json::json!({ "email": new_email, "current_password": app.test_user.password });
+    app.post_settings("/email", &form).await;
+
+    let mut recipients: Vec<String> = app.sent_emails().await.into_iter().map(|email| email.to).collect();
+    recipients.sort();
+    let mut expected = vec![app.test_user.email.clone(), new_email];
+    expected.sort();
+    assert_eq!(recipients, expected);
+}
+
+#[tokio::test]
+async fn failed_emails_are_retried_then_given_up_on() {
+    let db = spawn_database().await;
+    email_outbox::enqueue(&db, &email()).await.unwrap();
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        let sent = email_outbox::deliver_pending(&db, &FailingEmailClient).await.unwrap();
+        assert_eq!(sent, 0);
+
+        let row = sqlx::query!(
+            "SELECT attempts, last_error, failed_at IS NOT NULL AS given_up,
+            next_attempt_at > NOW() + INTERVAL '29 seconds' AS backed_off
+            FROM email_outbox"
+        )
+        .fetch_one(&db)
+        .await
+        .unwrap();
+        assert_eq!(row.attempts, attempt);
+        assert_eq!(row.last_error.as_deref(), Some("Connection refused"));
+        assert_eq!(row.given_up, Some(attempt == MAX_ATTEMPTS));
+        assert_eq!(row.backed_off, Some(true));
+
+        // Nothing is due until the backoff passes
+        assert_eq!(email_outbox::deliver_pending(&db, &FailingEmailClient).await.unwrap(), 0);
+        sqlx::query!("UPDATE email_outbox SET next_attempt_at = NOW()").execute(&db).await.unwrap();
+    }
+
+    // Given up emails aren't sent again
+    let client = tradesalsa::emailer::InMemoryEmailClient::default();
+    assert_eq!(email_outbox::deliver_pending(&db, &client).await.unwrap(), 0);
+    assert!(client.sent().is_empty());
+}
+
+#[tokio::test]
+async fn emails_are_sent_once() {
+    let db = spawn_database().await;
+    let queued = email();
+    email_outbox::enqueue(&db, &queued).await.unwrap();
+
+    let client = tradesalsa::emailer::InMemoryEmailClient::default();
+    assert_eq!(email_outbox::deliver_pending(&db, &client).await.unwrap(), 1);
+    assert_eq!(email_outbox::deliver_pending(&db, &client).await.unwrap(), 0);
+    assert_eq!(client.sent(), vec![queued]);
+}
+
+#[tokio::test]
+async fn emails_are_sent_as_html_and_text() {
+    let directory = std::env::temp_dir().join(format!("tradesalsa-test-emails-{}", uuid::Uuid::new_v4()));
+    let mut settings = tradesalsa::configuration::get_configuration().unwrap().email;
+    settings.file_directory = directory.to_string_lossy().to_string();
+    let client = FileEmailClient::new(&settings).unwrap();
+
+    client.send(&email()).await.unwrap();
+
+    let mut files = std::fs::read_dir(&directory).unwrap();
+    let message = std::fs::read_to_string(files.next().unwrap().unwrap().path()).unwrap();
+    assert!(message.contains("multipart/alternative"));
+    assert!(message.contains("text/plain"));
+    assert!(message.contains("text/html"));
+    std::fs::remove_dir_all(directory).unwrap();
+}
+
+#[tokio::test]
+async fn emails_are_claimed_rather_than_locked_while_they_are_sent() {
+    let db = spawn_database().await;
+    email_outbox::enqueue(&db, &email()).await.unwrap();
+
+    let client = ClaimCheckingEmailClient(db.clone());
+    assert_eq!(email_outbox::deliver_pending(&db, &client).await.unwrap(), 1);
+    let last_error = sqlx::query_scalar!("SELECT last_error FROM email_outbox").fetch_one(&db).await.unwrap();
+    assert_eq!(last_error, None);
+}
@@ -0,0 +1,52 @@
+use crate::helpers::spawn_app;
+use tradesalsa::configuration::EmailBranding;
+use tradesalsa::email_verification::ConfirmationEmail;
+use tradesalsa::emailer::{Email, EmailTemplate};
+
+const TEMPLATES: [&str; 5] = ["email_verification", "password_reset", "email_changed", "account_locked", "digest"];
+
+#[tokio::test]
+async fn every_email_template_can_be_previewed() {
+    let app = spawn_app().await;
+
+    let html_page = app.get_path("/dev/emails").await.text().await.unwrap();
+    for name in TEMPLATES {
+        assert!(html_page.contains(&format!(r#"<a href="/dev/emails/{}">HTML</a>"#, name)));
+
+        let response = app.get_path(&format!("/dev/emails/{}", name)).await;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let html = response.text().await.unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"), "{}", name);
+        assert!(html.contains("TradeSalsa"));
+
+        let response = app.get_path(&format!("/dev/emails/{}?format=text", name)).await;
+        assert_eq!(response.headers()["content-type"], "text/plain; charset=utf-8");
+        let text = response.text().await.unwrap();
+        assert!(text.starts_with("TradeSalsa"), "{}", name);
+        assert!(!text.contains('<'), "{}", name);
+    }
+
+    let response = app.get_path("/dev/emails/not_an_email").await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn branding_labels_emails_from_other_environments() {
+    let tera = tera::Tera::new("templates/**/*.{html,txt}").unwrap();
+    let branding = EmailBranding {
+        product_name: "TradeSalsa Staging".to_string(),
+        accent_color: "#336699".to_string(),
+        environment_label: Some("Staging".to_string()),
+    };
+
+    let email = Email::render(&tera, &branding, "trader@example.com", "Confirm", &ConfirmationEmail::sample()).unwrap();
+    assert_eq!(email.subject, "[Staging] Confirm");
+    assert!(email.html_body.contains("Sent from the Staging environment"));
+    assert!(email.html_body.contains("border-top: 4px solid #336699"));
+    assert!(email.text_body.starts_with("[Sent from the Staging environment]\n\nTradeSalsa Staging\n\nHello trader@example.com,"));
+    assert!(email.text_body.contains("https://example.com/confirm?token=sample"));
+
+    let email = Email::render(&tera, &EmailBranding::default(), "trader@example.com", "Confirm", &ConfirmationEmail::sample()).unwrap();
+    assert_eq!(email.subject, "Confirm");
+    assert!(!email.html_body.contains("environment"));
+}
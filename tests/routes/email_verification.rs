@@ -0,0 +1,135 @@
+use crate::helpers::{assert_is_redirect_to, fake_email, spawn_app, TestApp};
+use crate::executions::EXECUTIONS_CSV;
+use tradesalsa::email_verification;
+
+async fn create_token(app: &TestApp) -> String {
+    email_verification::create_token(&mut app.db_pool.acquire().await.unwrap(), app.test_user.user_id, &app.test_user.email)
+        .await
+        .expect("Failed to create a token.")
+}
+
+async fn is_verified(app: &TestApp) -> bool {
+    sqlx::query_scalar!(
+        "SELECT email_verified_at IS NOT NULL FROM users WHERE id = $1",
+        app.test_user.user_id,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .unwrap()
+}
+
+#[tokio::test]
+async fn registering_sends_an_expiring_confirmation_token() {
+    let app = spawn_app().await;
+    let email = fake_email();
+    let body = serde_json::json!({ "email": email, "password": "Tr4ding!days" });
+
+    let response = app.post_register(&body).await;
+    assert_is_redirect_to(&response, "/");
+
+    let token = sqlx::query!(
+        "SELECT users.email_verified_at, tokens.token_hash,
+            tokens.expires_at > NOW() + INTERVAL '23 hours' AS lasts_a_day
+        FROM users JOIN email_verification_tokens AS tokens ON tokens.user_id = users.id
+        WHERE users.email = $1",
+        email,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch the token.");
+    assert!(token.email_verified_at.is_none());
+    assert_eq!(token.token_hash.len(), 64);
+    assert_eq!(token.lasts_a_day, Some(true));
+}
+
+#[tokio::test]
+async fn confirmation_links_verify_the_email_once() {
+    let app = spawn_app().await;
+    app.unverify_email().await;
+    let token = create_token(&app).await;
+
+    let response = app.get_confirm(&token).await;
+    assert_is_redirect_to(&response, "/");
+    assert!(is_verified(&app).await);
+    let html_page = app.get_homepage_html().await.text().await.unwrap();
+    assert!(html_page.contains("Your email address is confirmed."));
+
+    app.unverify_email().await;
+    app.get_confirm(&token).await;
+    assert!(!is_verified(&app).await);
+    let html_page = app.get_homepage_html().await.text().await.unwrap();
+    assert!(html_page.contains("This confirmation link is invalid or has expired."));
+}
+
+#[tokio::test]
+async fn expired_and_unknown_tokens_are_rejected() {
+    let app = spawn_app().await;
+    app.unverify_email().await;
+    let token = create_token(&app).await;
+    sqlx::query!(
+        "UPDATE email_verification_tokens SET expires_at = NOW() - INTERVAL '1 minute' WHERE user_id = $1",
+        app.test_user.user_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    app.get_confirm(&token).await;
+    app.get_confirm("not-a-token").await;
+    app.get_confirm("").await;
+    assert!(!is_verified(&app).await);
+}
+
+#[tokio::test]
+async fn tokens_only_confirm_the_address_they_were_sent_to() {
+    let app = spawn_app().await;
+    app.unverify_email().await;
+    let token = create_token(&app).await;
+    sqlx::query!("UPDATE users SET email = $1 WHERE id = $2", fake_email(), app.test_user.user_id)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    app.get_confirm(&token).await;
+    assert!(!is_verified(&app).await);
+}
+
+#[tokio::test]
+async fn unverified_users_cannot_import_trades() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.unverify_email().await;
+
+    let html_page = app.get_import_executions().await.text().await.unwrap();
+    assert!(html_page.contains("Confirm your email address to import trades."));
+    assert!(!html_page.contains(r#"<input name="file""#));
+
+    let response = app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    assert_is_redirect_to(&response, "/executions/import");
+    assert!(app.trade_ids().await.is_empty());
+}
+
+#[tokio::test]
+async fn resending_replaces_the_previous_link() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.unverify_email().await;
+    let old_token = create_token(&app).await;
+
+    let response = app.post_path("/confirm/resend").await;
+    assert_is_redirect_to(&response, "/");
+    let html_page = app.get_homepage_html().await.text().await.unwrap();
+    assert!(html_page.contains("We sent a new confirmation link to your email address."));
+
+    let tokens = sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM email_verification_tokens WHERE user_id = $1",
+        app.test_user.user_id,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(tokens, Some(1));
+    app.get_confirm(&old_token).await;
+    assert!(!is_verified(&app).await);
+}
@@ -0,0 +1,60 @@
+use crate::helpers::spawn_app;
+use crate::executions::EXECUTIONS_CSV;
+
+#[tokio::test]
+async fn equity_requires_login() {
+    let app = spawn_app().await;
+
+    let response = app.get_equity(&[]).await;
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn equity_returns_the_curve_per_trade_and_per_day() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+
+    let response = app.get_equity(&[]).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let body: serde_json::Value = response.json().await.expect("Failed to parse the equity curve");
+    let per_trade = body["per_trade"].as_array().unwrap();
+    assert_eq!(per_trade.len(), 1);
+    assert!((per_trade[0]["equity"].as_f64().unwrap() - 108.32).abs() < 1e-9);
+    assert_eq!(per_trade[0]["drawdown"].as_f64(), Some(0.0));
+
+    let per_day = body["per_day"].as_array().unwrap();
+    assert_eq!(per_day.len(), 1);
+    assert_eq!(per_day[0]["trade_count"].as_u64(), Some(1));
+}
+
+#[tokio::test]
+async fn equity_is_filtered() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+
+    for params in [
+        [("from", "2030-01-01"), ("account", "")],
+        [("from", ""), ("account", "Sim999")],
+    ] {
+        let response = app.get_equity(&params).await;
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        let body: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(body["per_trade"].as_array().unwrap().len(), 0);
+    }
+
+    let response = app.get_equity(&[("account", "Sim101"), ("instrument", "ES 09-24")]).await;
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["per_trade"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn equity_rejects_invalid_dates() {
+    let app = spawn_app().await;
+    app.login().await;
+
+    let response = app.get_equity(&[("from", "07/01/2024")]).await;
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,127 @@
+use crate::accounts::{account_form, EVALUATION_CSV};
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use crate::executions::EXECUTIONS_CSV;
+use uuid::Uuid;
+
+/// A -$501.68 ES trade in the evaluation account the next day
+const LOSING_EVALUATION_CSV: &str = "\
+Instrument,Action,Quantity,Price,Time,ID,E/X,Position,Order ID,Name,Commission,Rate,Account,Connection,
+ES 09-24,Sell,1,5561,7/24/2024 9:31:00 AM,a3,Entry,1 S,ao3,Entry,$0.84,1,APEX-12345-01,Rithmic,
+ES 09-24,Buy,1,5571,7/24/2024 9:40:00 AM,a4,Exit,-,ao4,Stop,$0.84,1,APEX-12345-01,Rithmic,
+";
+
+fn rules_form(profit_target: &str, daily_loss_limit: &str) -> serde_json::Value {
+    serde_json::json!({
+        "profit_target": profit_target,
+        "max_drawdown": "2000",
+        "drawdown_type": "end_of_day",
+        "daily_loss_limit": daily_loss_limit,
+        "min_trading_days": "",
+        "consistency_percent": "",
+    })
+}
+
+/// Imports a +$48.32 trade into a $50,000 evaluation account
+async fn evaluation_account(app: &TestApp) -> Uuid {
+    app.post_import_executions(EVALUATION_CSV, "+00:00").await;
+    let account_id = app.account_id("APEX-12345-01").await;
+    app.post_account(account_id, &account_form("Apex 50K", "evaluation", "50000")).await;
+    account_id
+}
+
+async fn post_rules(app: &TestApp, account_id: Uuid, body: &serde_json::Value) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/accounts/{}/evaluation", &app.address, account_id))
+        .form(body)
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn get_evaluation(app: &TestApp, account_id: Uuid) -> reqwest::Response {
+    app.get_path(&format!("/accounts/{}/evaluation", account_id)).await
+}
+
+#[tokio::test]
+async fn only_evaluation_accounts_have_rules() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    let account_id = app.account_id("Sim101").await;
+
+    let response = get_evaluation(&app, account_id).await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    let response = post_rules(&app, account_id, &rules_form("3000", "")).await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn evaluation_shows_progress_towards_the_rules() {
+    let app = spawn_app().await;
+    app.login().await;
+    let account_id = evaluation_account(&app).await;
+
+    let html_page = get_evaluation(&app, account_id).await.text().await.unwrap();
+    assert!(html_page.contains("Set this account's rules below"));
+
+    let response = post_rules(&app, account_id, &rules_form("$3,000", "1000")).await;
+    assert_is_redirect_to(&response, &format!("/accounts/{}/evaluation", account_id));
+
+    let html_page = get_evaluation(&app, account_id).await.text().await.unwrap();
+    assert!(html_page.contains("Evaluation rules saved."));
+    assert!(html_page.contains("with a balance of +$50048.32"));
+    assert!(html_page.contains("<td>Profit Target</td>"));
+    // 3,000 less the 48.32 made so far
+    assert!(html_page.contains("<td>+$2951.68</td>"));
+    // The drawdown trails the close of the day the trade was in
+    assert!(html_page.contains("<td>Balance above +$48048.32</td>"));
+    assert!(html_page.contains(r#"<option value="end_of_day" selected>"#));
+    assert!(html_page.contains(r#"value="3000""#));
+}
+
+#[tokio::test]
+async fn breaches_are_recorded_with_the_trade_that_caused_them() {
+    let app = spawn_app().await;
+    app.login().await;
+    let account_id = evaluation_account(&app).await;
+    post_rules(&app, account_id, &rules_form("3000", "500")).await;
+
+    app.post_import_executions(LOSING_EVALUATION_CSV, "+00:00").await;
+
+    let losing_trade_id = *app.trade_ids().await.last().unwrap();
+    let breach = sqlx::query!(
+        "SELECT rule, trade_id, value, breach_limit FROM rule_breaches WHERE account_id = $1",
+        account_id,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .expect("Failed to fetch the breach.");
+    assert_eq!(breach.rule, "daily_loss_limit");
+    assert_eq!(breach.trade_id, Some(losing_trade_id));
+    assert_eq!(breach.breach_limit, -500.0);
+
+    let html_page = get_evaluation(&app, account_id).await.text().await.unwrap();
+    assert!(html_page.contains("Failed"));
+    assert!(html_page.contains(&format!("/trades/{}", losing_trade_id)));
+
+    // Loosening the rule removes the breach
+    post_rules(&app, account_id, &rules_form("3000", "1000")).await;
+    let breaches = sqlx::query_scalar!("SELECT COUNT(*) FROM rule_breaches WHERE account_id = $1", account_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(breaches, Some(0));
+}
+
+#[tokio::test]
+async fn invalid_rules_are_rejected() {
+    let app = spawn_app().await;
+    app.login().await;
+    let account_id = evaluation_account(&app).await;
+
+    let response = post_rules(&app, account_id, &rules_form("-5", "")).await;
+    assert_is_redirect_to(&response, &format!("/accounts/{}/evaluation", account_id));
+
+    let html_page = get_evaluation(&app, account_id).await.text().await.unwrap();
+    assert!(html_page.contains("Profit target must be a dollar amount greater than zero."));
+}
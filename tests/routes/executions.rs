@@ -0,0 +1,126 @@
+use crate::helpers::spawn_app;
+
+pub const EXECUTIONS_CSV: &str = "\
+Instrument,Action,Quantity,Price,Time,ID,E/X,Position,Order ID,Name,Commission,Rate,Account,Connection,
+ES 09-24,Buy,1,5565.25,7/22/2024 9:31:05 AM,e1,Entry,1 L,o1,Entry,$2.09,1,Sim101,Playback Connection,
+ES 09-24,Sell,1,5567.5,7/22/2024 9:45:10 AM,e2,Exit,-,o2,Profit target,$2.09,1,Sim101,Playback Connection,
+";
+
+#[tokio::test]
+async fn import_requires_login() {
+    let app = spawn_app().await;
+
+    let response = app.get_import_executions().await;
+    assert!(response.status().is_redirection());
+    let location = response.headers().get("Location").unwrap().to_str().unwrap();
+    assert!(location.starts_with("/login"));
+}
+
+#[tokio::test]
+async fn get_import_executions() {
+    let app = spawn_app().await;
+    app.login().await;
+
+    let response = app.get_import_executions().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let html_page = response.text().await.expect("Failed to read the response body");
+    assert!(html_page.contains(r#"<input name="file" id="file" type="file""#));
+}
+
+#[tokio::test]
+async fn import_stores_executions_for_the_user() {
+    let app = spawn_app().await;
+    app.login().await;
+
+    let response = app.post_import_executions(EXECUTIONS_CSV, "-05:00").await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let html_page = response.text().await.expect("Failed to read the response body");
+    assert!(html_page.contains("New executions: 2"));
+
+    let saved = sqlx::query!(
+        "SELECT execution_id, user_id, price, executed_at FROM executions ORDER BY executed_at"
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .expect("Failed to fetch saved executions.");
+    assert_eq!(saved.len(), 2);
+    assert_eq!(saved[0].execution_id, "e1");
+    assert_eq!(saved[0].user_id, app.test_user.user_id);
+    assert_eq!(saved[0].executed_at, time::macros::datetime!(2024-07-22 14:31:05 UTC));
+}
+
+#[tokio::test]
+async fn reimporting_an_overlapping_file_skips_known_executions() {
+    let app = spawn_app().await;
+    app.login().await;
+
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    let overlapping = format!(
+        "{}ES 09-24,Sell,1,5570,7/22/2024 10:01:00 AM,e3,Entry,1 S,o3,Entry,$2.09,1,Sim101,Playback Connection,\n",
+        EXECUTIONS_CSV
+    );
+    let response = app.post_import_executions(&overlapping, "+00:00").await;
+    let html_page = response.text().await.expect("Failed to read the response body");
+    assert!(html_page.contains("New executions: 1"));
+    assert!(html_page.contains("Already imported: 2"));
+
+    let count = sqlx::query_scalar!("SELECT COUNT(*) FROM executions")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to count executions.");
+    assert_eq!(count, Some(3));
+}
+
+#[tokio::test]
+async fn invalid_rows_are_reported() {
+    let app = spawn_app().await;
+    app.login().await;
+
+    let csv = format!(
+        "{}ES 09-24,Buy,abc,5570,7/22/2024 10:01:00 AM,e3,Entry,1 L,o3,Entry,$2.09,1,Sim101,Playback Connection,\n",
+        EXECUTIONS_CSV
+    );
+    let response = app.post_import_executions(&csv, "+00:00").await;
+    let html_page = response.text().await.expect("Failed to read the response body");
+    assert!(html_page.contains("New executions: 2"));
+    assert!(html_page.contains("Line 4: abc is not a valid quantity."));
+}
+
+#[tokio::test]
+async fn import_rebuilds_round_trip_trades() {
+    let app = spawn_app().await;
+    app.login().await;
+
+    let response = app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    let html_page = response.text().await.expect("Failed to read the response body");
+    assert!(html_page.contains("Your trades are being rebuilt"));
+
+    let trade = sqlx::query!("SELECT id, direction, quantity, entry_price, exit_price, gross_pnl, gross_ticks FROM trades")
+        .fetch_one(&app.db_pool)
+        .await
+        .expect("Failed to fetch trade.");
+    assert_eq!(trade.direction, "long");
+    assert_eq!(trade.quantity, 1);
+    assert_eq!(trade.entry_price, 5565.25);
+    assert_eq!(trade.exit_price, 5567.5);
+    // 2.25 points on ES at $50 a point
+    assert_eq!(trade.gross_pnl, Some(112.5));
+    assert_eq!(trade.gross_ticks, Some(9.0));
+
+    // A later import that adds a new trade keeps the existing trade's id
+    let more = format!(
+        "{}ES 09-24,Sell,1,5570,7/22/2024 10:01:00 AM,e3,Entry,1 S,o3,Entry,$2.09,1,Sim101,Playback Connection,\n\
+        ES 09-24,Buy,1,5569,7/22/2024 10:05:00 AM,e4,Exit,-,o4,Stop loss,$2.09,1,Sim101,Playback Connection,\n",
+        EXECUTIONS_CSV
+    );
+    app.post_import_executions(&more, "+00:00").await;
+
+    let trades = sqlx::query!("SELECT id, direction FROM trades ORDER BY exit_time")
+        .fetch_all(&app.db_pool)
+        .await
+        .expect("Failed to fetch trades.");
+    assert_eq!(trades.len(), 2);
+    assert_eq!(trades[0].id, trade.id);
+    assert_eq!(trades[1].direction, "short");
+}
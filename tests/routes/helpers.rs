@@ -1,8 +1,13 @@
 use sqlx::{PgConnection, Executor, Connection};
-use tradesalsa::configuration::{get_configuration, DatabaseSettings};
+use tradesalsa::configuration::{get_configuration, DatabaseSettings, EmailBranding, SessionStoreKind, Settings, StatsCacheBackend};
 use tradesalsa::telemetry::{get_subscriber, init_subscriber};
-use tradesalsa::startup::Application;
+use tradesalsa::startup::{AppState, Application};
+use tradesalsa::email_outbox;
+use tradesalsa::jobs;
+use tradesalsa::emailer::{Email, InMemoryEmailClient};
 use sqlx::PgPool;
+use std::sync::Arc;
+use secrecy::Secret;
 use once_cell::sync::Lazy;
 use uuid::Uuid;
 use fake::faker::internet::en::SafeEmail;
@@ -37,13 +42,14 @@ impl TestUser {
         }
     }
 
-    /// This function will store the built test user into the db pool passed in
+    /// This function will store the built test user into the db pool passed in.
+    /// Test users have already confirmed their email address.
     async fn store(&self, pool: &PgPool) {
         let email = self.email.clone();
         let password_hash = password_auth::generate_hash(self.password.clone());
         sqlx::query!(
-            "INSERT INTO users (id, email, password_hash)
-            VALUES ($1, $2, $3)",
+            "INSERT INTO users (id, email, password_hash, email_verified_at)
+            VALUES ($1, $2, $3, NOW())",
             self.user_id,
             email,
             password_hash,
@@ -61,6 +67,9 @@ pub struct TestApp {
     pub db_pool: PgPool,
     pub api_client: reqwest::Client,
     pub test_user: TestUser,
+    pub email_client: Arc<InMemoryEmailClient>,
+    /// What the app's handlers and background tasks use
+    pub state: AppState,
     pub _db_settings: DatabaseSettings,
 }
 
@@ -83,7 +92,7 @@ impl TestApp {
         Body: serde::Serialize
     {
         self.api_client
-            .post(&format!("{}/register", &self.address))
+            .post(format!("{}/register", &self.address))
             .form(&body)
             .send()
             .await
@@ -92,7 +101,7 @@ impl TestApp {
 
     pub async fn get_register(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/register", &self.address))
+            .get(format!("{}/register", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -100,7 +109,7 @@ impl TestApp {
 
     pub async fn get_homepage_html(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/", &self.address))
+            .get(format!("{}/", &self.address))
             .send()
             .await
             .expect("Failed to get homepage")
@@ -108,7 +117,7 @@ impl TestApp {
 
     pub async fn get_health_check(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/health", &self.address))
+            .get(format!("{}/health", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -129,9 +138,348 @@ impl TestApp {
             .expect("Failed to execute request.")
     }
 
+    /// Logs in as the test user so the client's cookie store holds a session
+    pub async fn login(&self) {
+        let body = serde_json::json!({
+            "email": self.test_user.email,
+            "password": self.test_user.password,
+        });
+        let response = self.post_login(&body).await;
+        assert_is_redirect_to(&response, "/");
+    }
+
+    /// Replaces the test user with a new one and logs in as them, with a fresh cookie store
+    pub async fn login_as_new_user(&mut self) {
+        self.test_user = TestUser::generate();
+        self.test_user.store(&self.db_pool).await;
+        self.api_client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .cookie_store(true)
+            .build()
+            .unwrap();
+        self.login().await;
+    }
+
+    /// Stores another user, without logging in as them
+    pub async fn store_user(&self) -> TestUser {
+        let user = TestUser::generate();
+        user.store(&self.db_pool).await;
+        user
+    }
+
+    /// A separate client with its own cookie store, logged in as `user`
+    pub async fn client_logged_in_as(&self, user: &TestUser) -> reqwest::Client {
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .cookie_store(true)
+            .build()
+            .unwrap();
+        let response = client
+            .post(format!("{}/login", &self.address))
+            .form(&[("email", &user.email), ("password", &user.password)])
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        assert_is_redirect_to(&response, "/");
+        client
+    }
+
+    /// Makes the test user as if they never confirmed their email address
+    pub async fn unverify_email(&self) {
+        sqlx::query!("UPDATE users SET email_verified_at = NULL WHERE id = $1", self.test_user.user_id)
+            .execute(&self.db_pool)
+            .await
+            .expect("Failed to unverify the test user.");
+    }
+
+    /// Runs every due job. The app's own workers may be running some of them,
+    /// so this waits for them.
+    pub async fn run_jobs(&self) {
+        for _ in 0..50 {
+            while jobs::run_next(&self.state).await.expect("Failed to run a job.") {}
+            let pending = sqlx::query_scalar!(
+                "SELECT COUNT(*) FROM jobs WHERE failed_at IS NULL AND run_at <= NOW()"
+            )
+            .fetch_one(&self.db_pool)
+            .await
+            .unwrap();
+            if pending == Some(0) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+    }
+
+    /// Runs due jobs, delivers everything in the outbox and returns every
+    /// email sent so far. The app's own worker may be sending some of them,
+    /// so this waits for it.
+    pub async fn sent_emails(&self) -> Vec<Email> {
+        self.run_jobs().await;
+        for _ in 0..50 {
+            email_outbox::deliver_pending(&self.db_pool, self.email_client.as_ref())
+                .await
+                .expect("Failed to deliver emails.");
+            let pending = sqlx::query_scalar!(
+                "SELECT COUNT(*) FROM email_outbox
+                WHERE sent_at IS NULL AND failed_at IS NULL AND next_attempt_at <= NOW()"
+            )
+            .fetch_one(&self.db_pool)
+            .await
+            .unwrap();
+            if pending == Some(0) {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        self.email_client.sent()
+    }
+
+    pub async fn get_confirm(&self, token: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/confirm", &self.address))
+            .query(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_forgot_password(&self, email: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/forgot-password", &self.address))
+            .form(&[("email", email)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_reset_password(&self, token: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/reset-password", &self.address))
+            .query(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_reset_password(&self, token: &str, password: &str, confirmation: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/reset-password", &self.address))
+            .form(&[("token", token), ("password", password), ("password_confirmation", confirmation)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_settings(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/settings", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_settings<Body>(&self, path: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize
+    {
+        self.api_client
+            .post(format!("{}/settings{}", &self.address, path))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_import_executions(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/executions/import", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Imports `csv`, then runs the trade rebuild it queues
+    pub async fn post_import_executions(&self, csv: &str, utc_offset: &str) -> reqwest::Response {
+        let file = reqwest::multipart::Part::text(csv.to_string())
+            .file_name("executions.csv")
+            .mime_str("text/csv")
+            .unwrap();
+        let form = reqwest::multipart::Form::new()
+            .text("utc_offset", utc_offset.to_string())
+            .part("file", file);
+        let response = self
+            .api_client
+            .post(format!("{}/executions/import", &self.address))
+            .multipart(form)
+            .send()
+            .await
+            .expect("Failed to execute request.");
+        self.run_jobs().await;
+        response
+    }
+
+    /// Gives the test user a role from the `roles` table
+    pub async fn grant_role(&self, role: &str) {
+        sqlx::query!(
+            "INSERT INTO user_roles (user_id, role_id) SELECT $1, id FROM roles WHERE name = $2",
+            self.test_user.user_id,
+            role,
+        )
+        .execute(&self.db_pool)
+        .await
+        .expect("Failed to grant role.");
+    }
+
+    pub async fn get_contract_specs(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/contract-specs", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_equity(&self, query_params: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/equity", &self.address))
+            .query(query_params)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_calendar(&self, query_params: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/calendar", &self.address))
+            .query(query_params)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_trades(&self, query_params: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/trades", &self.address))
+            .query(query_params)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// The test user's trade ids in the order they were closed
+    pub async fn trade_ids(&self) -> Vec<Uuid> {
+        sqlx::query_scalar!(
+            "SELECT id FROM trades WHERE user_id = $1 ORDER BY exit_time",
+            self.test_user.user_id,
+        )
+        .fetch_all(&self.db_pool)
+        .await
+        .expect("Failed to fetch trade ids.")
+    }
+
+    pub async fn get_trade(&self, trade_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/trades/{}", &self.address, trade_id))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_journal<Body>(&self, trade_id: Uuid, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize
+    {
+        self.api_client
+            .post(format!("{}/trades/{}/journal", &self.address, trade_id))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_screenshot(&self, trade_id: Uuid, bytes: &[u8], file_name: &str) -> reqwest::Response {
+        let file = reqwest::multipart::Part::bytes(bytes.to_vec())
+            .file_name(file_name.to_string());
+        let form = reqwest::multipart::Form::new().part("screenshot", file);
+        self.api_client
+            .post(format!("{}/trades/{}/screenshots", &self.address, trade_id))
+            .multipart(form)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_path(&self, path: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}{}", &self.address, path))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_path(&self, path: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}{}", &self.address, path))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_reports(&self, query_params: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/reports", &self.address))
+            .query(query_params)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_accounts(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/accounts", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_account<Body>(&self, account_id: Uuid, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize
+    {
+        self.api_client
+            .post(format!("{}/accounts/{}", &self.address, account_id))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// The id of the test user's account with the NinjaTrader name `name`
+    pub async fn account_id(&self, name: &str) -> Uuid {
+        sqlx::query_scalar!(
+            "SELECT id FROM accounts WHERE user_id = $1 AND name = $2",
+            self.test_user.user_id,
+            name,
+        )
+        .fetch_one(&self.db_pool)
+        .await
+        .expect("Failed to fetch the account id.")
+    }
+
+    pub async fn post_contract_spec<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize
+    {
+        self.api_client
+            .post(format!("{}/contract-specs", &self.address))
+            .form(&body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
     pub async fn get_protected(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/protected", &self.address))
+            .get(format!("{}/protected", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -139,6 +487,14 @@ impl TestApp {
 }
 
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+/// Like `spawn_app`, with `configure` changing the test configuration first.
+pub async fn spawn_app_with<F>(configure: F) -> TestApp
+where
+    F: FnOnce(&mut Settings),
+{
     /*
      * The first time 'initialize is invoked the code in 'TRACING' is executed.
      * All other invocations will instead skip execution (so init_subscriber() is only called once)
@@ -150,37 +506,72 @@ pub async fn spawn_app() -> TestApp {
         c.database.database_name = Uuid::new_v4().to_string();
         // Use a random OS port
         c.application.port = 0;
+        // Keep uploaded files out of the working tree
+        c.application.upload_directory = std::env::temp_dir()
+            .join("tradesalsa-test-uploads")
+            .to_string_lossy()
+            .to_string();
+        // Emails look the same whatever the local branding is
+        c.email.branding = EmailBranding::default();
+        c.email.previews = true;
+        // Sessions are kept in Postgres like in production, unless a Redis
+        // test instance is given, e.g. one started with scripts/init_redis.sh.
+        // Cached statistics go to Redis then too, and to memory otherwise.
+        match std::env::var("TEST_REDIS_URI") {
+            Ok(redis_uri) => {
+                c.redis_uri = Secret::new(redis_uri);
+                c.session_store = SessionStoreKind::Redis;
+                c.stats_cache.backend = StatsCacheBackend::Redis;
+            }
+            Err(_) => {
+                c.session_store = SessionStoreKind::Postgres;
+                c.stats_cache.backend = StatsCacheBackend::Memory;
+            }
+        }
+        configure(&mut c);
         c
     };
 
     /* Session */
     let db_pool = configure_database(&configuration.database).await;
 
-    let application = Application::build(configuration.clone())
+    let email_client = Arc::new(InMemoryEmailClient::default());
+    let application = Application::build_with_email_client(configuration.clone(), email_client.clone())
         .await
         .expect("Failed to build application");
 
     let application_port = application.port();
     let address = format!("http://127.0.0.1:{}", application_port);
+    let state = application.state();
 
-    let _ = tokio::spawn(application.run_until_stopped());
+    drop(tokio::spawn(application.run_until_stopped()));
     let client = reqwest::Client::builder()
         .redirect(reqwest::redirect::Policy::none())
         .cookie_store(true)
         .build()
         .unwrap();
-    let mut test_app = TestApp {
+    let test_app = TestApp {
         address,
         db_pool,
         _port: application_port,
         test_user: TestUser::generate(),
         api_client: client,
+        email_client,
+        state,
         _db_settings: configuration.database
     };
-    test_app.test_user.store(&mut test_app.db_pool).await;
+    test_app.test_user.store(&test_app.db_pool).await;
     test_app
 }
 
+/// A migrated database of its own, without an app running against it
+pub async fn spawn_database() -> PgPool {
+    Lazy::force(&TRACING);
+    let mut configuration = get_configuration().expect("Failed to read configuration");
+    configuration.database.database_name = Uuid::new_v4().to_string();
+    configure_database(&configuration.database).await
+}
+
 async fn configure_database(config: &DatabaseSettings) -> PgPool {
     /* Create database to use for testing */
     let mut connection = PgConnection::connect_with(&config.without_db())
@@ -1,4 +1,5 @@
 use crate::helpers::spawn_app;
+use crate::executions::EXECUTIONS_CSV;
 
 #[tokio::test]
 async fn homepage() {
@@ -10,3 +11,36 @@ async fn homepage() {
     let html_page = response.text().await.expect("Failed to read the response body");
     assert!(html_page.contains("HOMEPAGE BABY!"));
 }
+
+#[tokio::test]
+async fn homepage_shows_the_dashboard_to_logged_in_users() {
+    let app = spawn_app().await;
+    app.login().await;
+
+    let response = app.get_homepage_html().await;
+    let html_page = response.text().await.expect("Failed to read the response body");
+    assert!(html_page.contains("No trades yet."));
+
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+
+    let response = app.get_homepage_html().await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let html_page = response.text().await.expect("Failed to read the response body");
+    // 2.25 ES points less $4.18 in commission
+    assert!(html_page.contains("<tr><th>Net PnL</th><td>+$108.32</td></tr>"));
+    assert!(html_page.contains("<tr><th>Win Rate</th><td>+100.00%</td></tr>"));
+    assert!(html_page.contains("No losses"));
+}
+
+#[tokio::test]
+async fn dashboard_draws_the_equity_curve() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+
+    let response = app.get_homepage_html().await;
+    let html_page = response.text().await.expect("Failed to read the response body");
+    assert!(html_page.contains("<svg"));
+    assert!(html_page.contains("<polyline"));
+    assert!(html_page.contains(r#"<option value="Sim101">Sim101</option>"#));
+}
@@ -0,0 +1,162 @@
+use crate::helpers::{spawn_app, spawn_database, TestApp};
+use std::time::Duration;
+use tokio::sync::watch;
+use tradesalsa::digests::QueueDueDigests;
+use tradesalsa::jobs;
+use tradesalsa::password_reset::SendResetLink;
+use tradesalsa::startup::AppState;
+
+/// The app's state on a database of its own, so the app's workers don't run
+/// the jobs these tests queue.
+async fn isolated_state(app: &TestApp) -> AppState {
+    AppState {
+        db: spawn_database().await,
+        ..app.state.clone()
+    }
+}
+
+async fn queue_raw_job(state: &AppState, kind: &str, payload: serde_json::Value, max_attempts: i32) -> uuid::Uuid {
+    let id = uuid::Uuid::new_v4();
+    sqlx::query!(
+        "INSERT INTO jobs (id, kind, payload, max_attempts) VALUES ($1, $2, $3, $4)",
+        id,
+        kind,
+        payload,
+        max_attempts,
+    )
+    .execute(&state.db)
+    .await
+    .unwrap();
+    id
+}
+
+#[tokio::test]
+async fn finished_jobs_are_removed() {
+    let app = spawn_app().await;
+    let state = isolated_state(&app).await;
+    jobs::enqueue(&state.db, &SendResetLink { email: "nobody@example.com".to_string() })
+        .await
+        .unwrap();
+
+    assert!(jobs::run_next(&state).await.unwrap());
+    assert!(!jobs::run_next(&state).await.unwrap());
+    let count = sqlx::query_scalar!("SELECT COUNT(*) FROM jobs").fetch_one(&state.db).await.unwrap();
+    assert_eq!(count, Some(0));
+}
+
+#[tokio::test]
+async fn failing_jobs_are_retried_then_kept_as_failed() {
+    let app = spawn_app().await;
+    let state = isolated_state(&app).await;
+    // Not a valid payload for the job
+    let id = queue_raw_job(&state, "send_reset_link", serde_json::json!({}), 2).await;
+
+    assert!(jobs::run_next(&state).await.unwrap());
+    let job = sqlx::query!(
+        "SELECT attempts, last_error, run_at > NOW() AS later, failed_at, locked_until FROM jobs WHERE id = $1",
+        id
+    )
+    .fetch_one(&state.db)
+    .await
+    .unwrap();
+    assert_eq!(job.attempts, 1);
+    assert!(job.last_error.unwrap().contains("missing field `email`"));
+    assert_eq!(job.later, Some(true));
+    assert!(job.failed_at.is_none());
+    assert!(job.locked_until.is_none());
+    // Not due yet
+    assert!(!jobs::run_next(&state).await.unwrap());
+
+    sqlx::query!("UPDATE jobs SET run_at = NOW() WHERE id = $1", id).execute(&state.db).await.unwrap();
+    assert!(jobs::run_next(&state).await.unwrap());
+    let job = sqlx::query!("SELECT attempts, failed_at FROM jobs WHERE id = $1", id)
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+    assert_eq!(job.attempts, 2);
+    assert!(job.failed_at.is_some());
+
+    sqlx::query!("UPDATE jobs SET run_at = NOW() WHERE id = $1", id).execute(&state.db).await.unwrap();
+    assert!(!jobs::run_next(&state).await.unwrap());
+}
+
+#[tokio::test]
+async fn unknown_job_kinds_are_retried() {
+    let app = spawn_app().await;
+    let state = isolated_state(&app).await;
+    let id = queue_raw_job(&state, "from_a_newer_release", serde_json::json!({}), 5).await;
+
+    assert!(jobs::run_next(&state).await.unwrap());
+    let job = sqlx::query!("SELECT attempts, last_error, failed_at FROM jobs WHERE id = $1", id)
+        .fetch_one(&state.db)
+        .await
+        .unwrap();
+    assert_eq!(job.attempts, 1);
+    assert_eq!(job.last_error.as_deref(), Some("Unknown job kind from_a_newer_release"));
+    assert!(job.failed_at.is_none());
+}
+
+#[tokio::test]
+async fn a_leased_job_is_skipped_until_the_lease_runs_out() {
+    let app = spawn_app().await;
+    let state = isolated_state(&app).await;
+    let id = queue_raw_job(&state, "send_reset_link", serde_json::json!({ "email": "nobody@example.com" }), 5).await;
+
+    sqlx::query!("UPDATE jobs SET locked_until = NOW() + INTERVAL '5 minutes' WHERE id = $1", id)
+        .execute(&state.db)
+        .await
+        .unwrap();
+    assert!(!jobs::run_next(&state).await.unwrap());
+
+    // The worker running it stopped, so it's picked up once the lease runs out
+    sqlx::query!("UPDATE jobs SET locked_until = NOW() - INTERVAL '1 second' WHERE id = $1", id)
+        .execute(&state.db)
+        .await
+        .unwrap();
+    assert!(jobs::run_next(&state).await.unwrap());
+    let count = sqlx::query_scalar!("SELECT COUNT(*) FROM jobs").fetch_one(&state.db).await.unwrap();
+    assert_eq!(count, Some(0));
+}
+
+#[tokio::test]
+async fn workers_stop_when_told_to() {
+    let app = spawn_app().await;
+    let state = isolated_state(&app).await;
+    let (stop, stopped) = watch::channel(false);
+    let workers = tokio::spawn(jobs::run_workers(state.clone(), stopped));
+
+    jobs::enqueue(&state.db, &SendResetLink { email: app.test_user.email.clone() })
+        .await
+        .unwrap();
+    for _ in 0..50 {
+        let count = sqlx::query_scalar!("SELECT COUNT(*) FROM jobs").fetch_one(&state.db).await.unwrap();
+        if count == Some(0) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    stop.send(true).unwrap();
+    tokio::time::timeout(Duration::from_secs(5), workers)
+        .await
+        .expect("The workers didn't stop")
+        .unwrap();
+    let count = sqlx::query_scalar!("SELECT COUNT(*) FROM jobs").fetch_one(&state.db).await.unwrap();
+    assert_eq!(count, Some(0));
+}
+
+#[tokio::test]
+async fn scheduled_jobs_are_queued_once_at_a_time() {
+    let app = spawn_app().await;
+    let state = isolated_state(&app).await;
+    let count = || sqlx::query_scalar!("SELECT COUNT(*) FROM jobs WHERE kind = 'queue_due_digests'").fetch_one(&state.db);
+
+    jobs::enqueue_unless_queued(&state.db, &QueueDueDigests).await.unwrap();
+    jobs::enqueue_unless_queued(&state.db, &QueueDueDigests).await.unwrap();
+    assert_eq!(count().await.unwrap(), Some(1));
+
+    assert!(jobs::run_next(&state).await.unwrap());
+    assert_eq!(count().await.unwrap(), Some(0));
+    jobs::enqueue_unless_queued(&state.db, &QueueDueDigests).await.unwrap();
+    assert_eq!(count().await.unwrap(), Some(1));
+}
@@ -0,0 +1,167 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+use crate::calendar::EVENING_SESSION_CSV;
+use crate::executions::EXECUTIONS_CSV;
+
+const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+#[tokio::test]
+async fn journal_is_saved_and_rendered() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    let trade_id = app.trade_ids().await[0];
+
+    let body = serde_json::json!({
+        "notes": "Waited for the **retest**\n\n<script>alert(1)</script>",
+        "rating": "4",
+        "risk": "50",
+        "tags": "ORB, patience",
+    });
+    let response = app.post_journal(trade_id, &body).await;
+    assert_is_redirect_to(&response, &format!("/trades/{}", trade_id));
+
+    let html_page = app.get_trade(trade_id).await.text().await.unwrap();
+    assert!(html_page.contains("Journal saved."));
+    assert!(html_page.contains("Waited for the <strong>retest</strong>"));
+    assert!(!html_page.contains("<script>alert(1)</script>"));
+    assert!(html_page.contains("Rating: 4 / 5"));
+    assert!(html_page.contains("<tr><th>R Multiple</th><td>+2.17R</td></tr>"));
+    assert!(html_page.contains(r#"value="ORB, patience""#));
+}
+
+#[tokio::test]
+async fn invalid_journal_is_rejected() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    let trade_id = app.trade_ids().await[0];
+
+    let body = serde_json::json!({ "notes": "", "rating": "9", "risk": "", "tags": "" });
+    let response = app.post_journal(trade_id, &body).await;
+    assert_is_redirect_to(&response, &format!("/trades/{}", trade_id));
+
+    let html_page = app.get_trade(trade_id).await.text().await.unwrap();
+    assert!(html_page.contains("Rating must be a whole number from 1 to 5."));
+}
+
+#[tokio::test]
+async fn journal_survives_later_imports() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    let trade_id = app.trade_ids().await[0];
+    let body = serde_json::json!({ "notes": "Keep this", "rating": "", "risk": "", "tags": "ORB" });
+    app.post_journal(trade_id, &body).await;
+
+    app.post_import_executions(EVENING_SESSION_CSV, "+00:00").await;
+
+    assert_eq!(app.trade_ids().await[0], trade_id);
+    let html_page = app.get_trade(trade_id).await.text().await.unwrap();
+    assert!(html_page.contains("Keep this"));
+}
+
+#[tokio::test]
+async fn tags_filter_every_view() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    app.post_import_executions(EVENING_SESSION_CSV, "+00:00").await;
+    let trade_ids = app.trade_ids().await;
+    let body = serde_json::json!({ "notes": "", "rating": "", "risk": "", "tags": "FOMO" });
+    app.post_journal(trade_ids[1], &body).await;
+
+    let html_page = app.get_trades(&[("tag", "fomo")]).await.text().await.unwrap();
+    assert!(html_page.contains("-$104.18"));
+    assert!(!html_page.contains("+$108.32"));
+
+    let body: serde_json::Value = app.get_equity(&[("tag", "FOMO")]).await.json().await.unwrap();
+    assert_eq!(body["per_trade"].as_array().unwrap().len(), 1);
+
+    let html_page = app.get_calendar(&[("month", "2024-07"), ("tag", "FOMO")]).await.text().await.unwrap();
+    assert!(html_page.contains("-$104.18"));
+    assert!(!html_page.contains("+$108.32"));
+
+    let response = app.get_path("/?tag=FOMO").await;
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<tr><th>Net PnL</th><td>-$104.18</td></tr>"));
+    assert!(html_page.contains(r#"<option value="FOMO" selected>FOMO</option>"#));
+}
+
+#[tokio::test]
+async fn screenshots_are_served_only_to_their_owner() {
+    let mut app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    let trade_id = app.trade_ids().await[0];
+
+    let response = app.post_screenshot(trade_id, PNG, "chart.png").await;
+    assert_is_redirect_to(&response, &format!("/trades/{}", trade_id));
+    let html_page = app.get_trade(trade_id).await.text().await.unwrap();
+    let start = html_page.find("/screenshots/").expect("The screenshot should be shown");
+    let path = &html_page[start..html_page[start..].find('"').unwrap() + start];
+    assert!(path.ends_with(".png"));
+
+    let response = app.get_path(path).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(response.bytes().await.unwrap().as_ref(), PNG);
+
+    app.login_as_new_user().await;
+    let response = app.get_path(path).await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    let response = app.get_trade(trade_id).await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn screenshots_must_be_images() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    let trade_id = app.trade_ids().await[0];
+
+    app.post_screenshot(trade_id, b"<svg onload=alert(1)></svg>", "chart.png").await;
+
+    let html_page = app.get_trade(trade_id).await.text().await.unwrap();
+    assert!(html_page.contains("Screenshots must be PNG, JPEG, GIF or WebP images."));
+    assert!(!html_page.contains("/screenshots/"));
+}
+
+#[tokio::test]
+async fn screenshots_can_be_deleted() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    let trade_id = app.trade_ids().await[0];
+    app.post_screenshot(trade_id, PNG, "chart.png").await;
+
+    let screenshot_id: uuid::Uuid = sqlx::query_scalar!("SELECT id FROM trade_screenshots WHERE trade_id = $1", trade_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let response = app.post_path(&format!("/trades/{}/screenshots/{}/delete", trade_id, screenshot_id)).await;
+    assert_is_redirect_to(&response, &format!("/trades/{}", trade_id));
+
+    let html_page = app.get_trade(trade_id).await.text().await.unwrap();
+    assert!(html_page.contains("Screenshot deleted."));
+    assert!(!html_page.contains("/screenshots/"));
+}
+
+#[tokio::test]
+async fn screenshots_are_only_deleted_through_their_trade() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    let trade_id = app.trade_ids().await[0];
+    app.post_screenshot(trade_id, PNG, "chart.png").await;
+    let screenshot_id: uuid::Uuid = sqlx::query_scalar!("SELECT id FROM trade_screenshots WHERE trade_id = $1", trade_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+
+    let other_trade_id = uuid::Uuid::new_v4();
+    let response = app.post_path(&format!("/trades/{}/screenshots/{}/delete", other_trade_id, screenshot_id)).await;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let html_page = app.get_trade(trade_id).await.text().await.unwrap();
+    assert!(html_page.contains("/screenshots/"));
+}
@@ -0,0 +1,153 @@
+use crate::helpers::{assert_is_redirect_to, fake_email, spawn_app, spawn_app_with, TestApp};
+use tokio::task::JoinSet;
+
+async fn post_login(app: &TestApp, email: &str, password: &str) -> reqwest::Response {
+    let body = serde_json::json!({ "email": email, "password": password });
+    app.post_login(&body).await
+}
+
+async fn throttle(app: &TestApp, scope: &str, key: &str) -> Option<(i32, bool)> {
+    sqlx::query!(
+        "SELECT failures, locked_until > NOW() AS locked FROM login_throttles WHERE scope = $1 AND key = $2",
+        scope,
+        key,
+    )
+    .fetch_optional(&app.db_pool)
+    .await
+    .unwrap()
+    .map(|row| (row.failures, row.locked.unwrap_or(false)))
+}
+
+async fn set_failures(app: &TestApp, scope: &str, key: &str, failures: i32) {
+    sqlx::query!(
+        "INSERT INTO login_throttles (scope, key, failures, last_failure_at) VALUES ($1, $2, $3, NOW())",
+        scope,
+        key,
+        failures,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+}
+
+async fn login_page(app: &TestApp) -> String {
+    app.get_login(None).await.text().await.unwrap()
+}
+
+#[tokio::test]
+async fn failed_logins_are_counted_per_account_and_ip() {
+    let app = spawn_app().await;
+    let email = app.test_user.email.to_uppercase();
+
+    let response = post_login(&app, &email, "wrong-password").await;
+    assert_is_redirect_to(&response, "/login");
+    assert!(login_page(&app).await.contains("Invalid Credentials"));
+
+    assert_eq!(throttle(&app, "account", &app.test_user.email.to_lowercase()).await, Some((1, false)));
+    assert_eq!(throttle(&app, "ip", "127.0.0.1").await, Some((1, false)));
+}
+
+#[tokio::test]
+async fn repeated_failures_make_the_next_attempt_wait() {
+    let app = spawn_app().await;
+    set_failures(&app, "account", &app.test_user.email.to_lowercase(), 6).await;
+
+    // Even the right password isn't checked until the wait is over
+    let response = post_login(&app, &app.test_user.email, &app.test_user.password).await;
+    assert_is_redirect_to(&response, "/login");
+    assert!(login_page(&app).await.contains("Too many failed login attempts. Try again in 8 seconds."));
+}
+
+#[tokio::test]
+async fn enough_failures_lock_the_account() {
+    let app = spawn_app().await;
+    let key = app.test_user.email.to_lowercase();
+    set_failures(&app, "account", &key, 9).await;
+    sqlx::query!("UPDATE login_throttles SET last_failure_at = NOW() - INTERVAL '10 minutes'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    post_login(&app, &app.test_user.email, "wrong-password").await;
+    assert_eq!(throttle(&app, "account", &key).await, Some((0, true)));
+
+    post_login(&app, &app.test_user.email, &app.test_user.password).await;
+    assert!(login_page(&app).await.contains("Too many failed login attempts. Try again in 15 minutes."));
+}
+
+#[tokio::test]
+async fn unknown_emails_are_throttled_like_real_ones() {
+    let app = spawn_app().await;
+    let email = fake_email();
+    set_failures(&app, "account", &email.to_lowercase(), 6).await;
+
+    post_login(&app, &email, "wrong-password").await;
+    assert!(login_page(&app).await.contains("Too many failed login attempts. Try again in 8 seconds."));
+}
+
+#[tokio::test]
+async fn too_many_failures_from_one_ip_lock_out_every_account() {
+    let app = spawn_app().await;
+    set_failures(&app, "ip", "127.0.0.1", 49).await;
+    sqlx::query!("UPDATE login_throttles SET last_failure_at = NOW() - INTERVAL '10 minutes'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    post_login(&app, &fake_email(), "wrong-password").await;
+    assert_eq!(throttle(&app, "ip", "127.0.0.1").await, Some((0, true)));
+
+    post_login(&app, &app.test_user.email, &app.test_user.password).await;
+    assert!(login_page(&app).await.contains("Too many failed login attempts."));
+}
+
+#[tokio::test]
+async fn logging_in_forgets_the_accounts_failures() {
+    let app = spawn_app().await;
+    post_login(&app, &app.test_user.email, "wrong-password").await;
+    post_login(&app, &app.test_user.email, "wrong-password").await;
+
+    app.login().await;
+    assert_eq!(throttle(&app, "account", &app.test_user.email.to_lowercase()).await, None);
+    assert_eq!(throttle(&app, "ip", "127.0.0.1").await, Some((2, false)));
+}
+
+#[tokio::test]
+async fn parallel_attempts_are_throttled_like_sequential_ones() {
+    let app = spawn_app().await;
+    let key = app.test_user.email.to_lowercase();
+
+    let mut attempts = JoinSet::new();
+    for _ in 0..10 {
+        let request = app
+            .api_client
+            .post(format!("{}/login", &app.address))
+            .form(&serde_json::json!({ "email": &app.test_user.email, "password": "wrong-password" }));
+        attempts.spawn(request.send());
+    }
+    while let Some(attempt) = attempts.join_next().await {
+        attempt.unwrap().unwrap();
+    }
+
+    // The three free attempts and the one after them, with the rest told to wait
+    let (failures, locked) = throttle(&app, "account", &key).await.unwrap();
+    assert!(failures <= 4, "{} attempts were checked", failures);
+    assert!(!locked);
+}
+
+#[tokio::test]
+async fn clients_behind_a_trusted_proxy_are_throttled_by_their_own_ip() {
+    let app = spawn_app_with(|c| c.application.trusted_proxies = vec!["127.0.0.1".parse().unwrap()]).await;
+
+    let body = serde_json::json!({ "email": &app.test_user.email, "password": "wrong-password" });
+    app.api_client
+        .post(format!("{}/login", &app.address))
+        .header("X-Forwarded-For", "198.51.100.1, 203.0.113.7")
+        .form(&body)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(throttle(&app, "ip", "203.0.113.7").await, Some((1, false)));
+    assert_eq!(throttle(&app, "ip", "127.0.0.1").await, None);
+}
@@ -3,3 +3,27 @@ mod health_check;
 mod homepage;
 mod auth;
 mod protected;
+mod executions;
+mod contract_specs;
+mod equity;
+mod calendar;
+mod trades;
+mod journal;
+mod reports;
+mod accounts;
+mod evaluations;
+mod email_verification;
+mod password_reset;
+mod settings;
+mod authorization;
+mod admin;
+mod login_throttle;
+mod two_factor;
+mod api_tokens;
+mod api_v1;
+mod email_outbox;
+mod email_previews;
+mod digests;
+mod jobs;
+mod stats_cache;
+mod session_store;
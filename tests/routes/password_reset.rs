@@ -0,0 +1,146 @@
+use crate::helpers::{assert_is_redirect_to, fake_email, spawn_app, TestApp};
+use tradesalsa::password_reset;
+
+const NEW_PASSWORD: &str = "N3w-trading-pass!";
+
+async fn create_token(app: &TestApp) -> String {
+    password_reset::create_token(&mut app.db_pool.acquire().await.unwrap(), app.test_user.user_id)
+        .await
+        .expect("Failed to create a token.")
+}
+
+async fn unused_tokens(app: &TestApp) -> i64 {
+    sqlx::query_scalar!(
+        "SELECT COUNT(*) FROM password_reset_tokens WHERE user_id = $1 AND used_at IS NULL",
+        app.test_user.user_id,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .unwrap()
+}
+
+async fn can_login_with(app: &TestApp, password: &str) -> bool {
+    let body = serde_json::json!({ "email": app.test_user.email, "password": password });
+    let response = app.post_login(&body).await;
+    response.headers().get("Location").unwrap() == "/"
+}
+
+#[tokio::test]
+async fn forgot_password_responds_the_same_for_unknown_emails() {
+    let app = spawn_app().await;
+
+    let unknown = app.post_forgot_password(&fake_email()).await;
+    assert_is_redirect_to(&unknown, "/forgot-password");
+    let unknown_page = app.get_path("/forgot-password").await.text().await.unwrap();
+
+    let known = app.post_forgot_password(&app.test_user.email).await;
+    assert_is_redirect_to(&known, "/forgot-password");
+    let known_page = app.get_path("/forgot-password").await.text().await.unwrap();
+
+    assert_eq!(unknown_page, known_page);
+    assert!(known_page.contains("If that email address has an account, we sent it a link to reset the password."));
+
+    // The links are created by background jobs
+    app.run_jobs().await;
+    assert_eq!(unused_tokens(&app).await, 1);
+    let sent = app.sent_emails().await;
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].to, app.test_user.email);
+}
+
+#[tokio::test]
+async fn reset_links_change_the_password_once() {
+    let app = spawn_app().await;
+    let token = create_token(&app).await;
+
+    let html_page = app.get_reset_password(&token).await.text().await.unwrap();
+    assert!(html_page.contains(&format!(r#"name="token" value="{}""#, token)));
+
+    let response = app.post_reset_password(&token, NEW_PASSWORD, NEW_PASSWORD).await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login(None).await.text().await.unwrap();
+    assert!(html_page.contains("Your password was reset. Log in with your new password."));
+
+    assert!(!can_login_with(&app, &app.test_user.password).await);
+    assert!(can_login_with(&app, NEW_PASSWORD).await);
+    let actions = sqlx::query_scalar!("SELECT action FROM audit_events WHERE user_id = $1", app.test_user.user_id)
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(actions, ["password_reset"]);
+
+    // The token is used up
+    let response = app.post_reset_password(&token, "An0ther-pass!word", "An0ther-pass!word").await;
+    assert_is_redirect_to(&response, "/forgot-password");
+    assert!(can_login_with(&app, NEW_PASSWORD).await);
+    let response = app.get_reset_password(&token).await;
+    assert_is_redirect_to(&response, "/forgot-password");
+}
+
+#[tokio::test]
+async fn resetting_the_password_logs_out_existing_sessions() {
+    let app = spawn_app().await;
+    app.login().await;
+    assert_eq!(app.get_accounts().await.status(), reqwest::StatusCode::OK);
+
+    let token = create_token(&app).await;
+    app.post_reset_password(&token, NEW_PASSWORD, NEW_PASSWORD).await;
+
+    let response = app.get_accounts().await;
+    assert!(response.status().is_redirection());
+    let location = response.headers().get("Location").unwrap().to_str().unwrap();
+    assert!(location.starts_with("/login"));
+}
+
+#[tokio::test]
+async fn expired_and_unknown_tokens_are_rejected() {
+    let app = spawn_app().await;
+    let token = create_token(&app).await;
+    sqlx::query!(
+        "UPDATE password_reset_tokens SET expires_at = NOW() - INTERVAL '1 minute' WHERE user_id = $1",
+        app.test_user.user_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    for token in [token.as_str(), "not-a-token", ""] {
+        let response = app.get_reset_password(token).await;
+        assert_is_redirect_to(&response, "/forgot-password");
+        let response = app.post_reset_password(token, NEW_PASSWORD, NEW_PASSWORD).await;
+        assert_is_redirect_to(&response, "/forgot-password");
+    }
+    let html_page = app.get_path("/forgot-password").await.text().await.unwrap();
+    assert!(html_page.contains("This password reset link is invalid or has expired."));
+    assert!(can_login_with(&app, &app.test_user.password).await);
+}
+
+#[tokio::test]
+async fn weak_or_mismatched_passwords_keep_the_token() {
+    let app = spawn_app().await;
+    let token = create_token(&app).await;
+    let reset_url = format!("/reset-password?token={}", token);
+
+    let response = app.post_reset_password(&token, NEW_PASSWORD, "Something-else1!").await;
+    assert_is_redirect_to(&response, &reset_url);
+    let html_page = app.get_reset_password(&token).await.text().await.unwrap();
+    assert!(html_page.contains("The passwords don&#x27;t match."));
+
+    let response = app.post_reset_password(&token, "short", "short").await;
+    assert_is_redirect_to(&response, &reset_url);
+
+    assert_eq!(unused_tokens(&app).await, 1);
+    assert!(can_login_with(&app, &app.test_user.password).await);
+}
+
+#[tokio::test]
+async fn requesting_a_new_link_replaces_the_old_one() {
+    let app = spawn_app().await;
+    let old_token = create_token(&app).await;
+    create_token(&app).await;
+
+    assert_eq!(unused_tokens(&app).await, 1);
+    let response = app.get_reset_password(&old_token).await;
+    assert_is_redirect_to(&response, "/forgot-password");
+}
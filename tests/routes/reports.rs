@@ -0,0 +1,82 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+use crate::calendar::EVENING_SESSION_CSV;
+use crate::executions::EXECUTIONS_CSV;
+
+/// A +$108.32 ES trade tagged ORB with $50 risk, and a -$104.18 ES trade tagged FOMO
+async fn journaled_trades(app: &TestApp) {
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    app.post_import_executions(EVENING_SESSION_CSV, "+00:00").await;
+    let trade_ids = app.trade_ids().await;
+    let body = serde_json::json!({ "notes": "", "rating": "", "risk": "50", "tags": "ORB" });
+    app.post_journal(trade_ids[0], &body).await;
+    let body = serde_json::json!({ "notes": "", "rating": "", "risk": "", "tags": "FOMO" });
+    app.post_journal(trade_ids[1], &body).await;
+}
+
+#[tokio::test]
+async fn reports_require_login() {
+    let app = spawn_app().await;
+
+    let response = app.get_reports(&[]).await;
+    assert!(response.status().is_redirection());
+    let location = response.headers().get("Location").unwrap().to_str().unwrap();
+    assert!(location.starts_with("/login"));
+}
+
+#[tokio::test]
+async fn reports_break_down_performance_by_group() {
+    let app = spawn_app().await;
+    app.login().await;
+    journaled_trades(&app).await;
+
+    let response = app.get_reports(&[]).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let html_page = response.text().await.unwrap();
+
+    for title in ["Tag", "Instrument", "Day of Week", "Hour of Day", "Duration"] {
+        assert!(html_page.contains(&format!("<h3>{}</h3>", title)));
+    }
+    assert!(html_page.contains("<td>ORB</td>"));
+    assert!(html_page.contains("<td>FOMO</td>"));
+    assert!(html_page.contains("<td>ES 09-24</td>"));
+    // 108.32 over 50 risked
+    assert!(html_page.contains("+2.17R"));
+    // The evening trade closes in Tuesday's session
+    assert!(html_page.contains("<td>Monday</td>"));
+    assert!(html_page.contains("<td>Tuesday</td>"));
+}
+
+#[tokio::test]
+async fn reports_are_sortable() {
+    let app = spawn_app().await;
+    app.login().await;
+    journaled_trades(&app).await;
+
+    let html_page = app.get_reports(&[("sort", "net_pnl"), ("order", "desc")]).await.text().await.unwrap();
+    assert!(html_page.find("<td>ORB</td>").unwrap() < html_page.find("<td>FOMO</td>").unwrap());
+    // The active column links to the other direction
+    assert!(html_page.contains("/reports?sort=net_pnl&order=asc"));
+
+    let html_page = app.get_reports(&[("sort", "net_pnl"), ("order", "asc")]).await.text().await.unwrap();
+    assert!(html_page.find("<td>FOMO</td>").unwrap() < html_page.find("<td>ORB</td>").unwrap());
+}
+
+#[tokio::test]
+async fn sort_links_keep_the_filters() {
+    let app = spawn_app().await;
+    app.login().await;
+    journaled_trades(&app).await;
+
+    let html_page = app.get_reports(&[("account", "Sim101"), ("tag", "ORB")]).await.text().await.unwrap();
+    assert!(html_page.contains("/reports?account=Sim101&tag=ORB&sort=win_rate&order=desc"));
+    assert!(!html_page.contains("<td>FOMO</td>"));
+}
+
+#[tokio::test]
+async fn unknown_sort_columns_are_rejected() {
+    let app = spawn_app().await;
+    app.login().await;
+
+    let response = app.get_reports(&[("sort", "password_hash")]).await;
+    assert_is_redirect_to(&response, "/reports");
+}
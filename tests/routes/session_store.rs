@@ -0,0 +1,25 @@
+use crate::helpers::spawn_app_with;
+use axum_login::tower_sessions::ExpiredDeletion;
+use tradesalsa::configuration::SessionStoreKind;
+use tradesalsa::session_store::AppSessionStore;
+
+#[tokio::test]
+async fn postgres_sessions_are_kept_until_they_expire() {
+    let app = spawn_app_with(|c| c.session_store = SessionStoreKind::Postgres).await;
+    app.login().await;
+    let count = || sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM tower_sessions.session").fetch_one(&app.db_pool);
+    assert_eq!(count().await.unwrap(), 1);
+    assert_eq!(app.get_settings().await.status(), reqwest::StatusCode::OK);
+
+    sqlx::query("UPDATE tower_sessions.session SET expiry_date = NOW() - INTERVAL '1 minute'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let store = AppSessionStore::new(SessionStoreKind::Postgres, &app.db_pool, None).await.unwrap();
+    store.delete_expired().await.unwrap();
+    assert_eq!(count().await.unwrap(), 0);
+
+    let response = app.get_settings().await;
+    assert_eq!(response.status(), reqwest::StatusCode::TEMPORARY_REDIRECT);
+    assert!(response.headers()["Location"].to_str().unwrap().starts_with("/login"));
+}
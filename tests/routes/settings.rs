@@ -0,0 +1,157 @@
+use crate::helpers::{assert_is_redirect_to, fake_email, spawn_app, TestApp};
+
+const NEW_PASSWORD: &str = "N3w-trading-pass!";
+
+fn password_form(current_password: &str, password: &str, confirmation: &str) -> serde_json::Value {
+    serde_json::json!({
+        "current_password": current_password,
+        "password": password,
+        "password_confirmation": confirmation,
+    })
+}
+
+/// Logs the test user in from another browser
+async fn other_session(app: &TestApp) -> reqwest::Client {
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap();
+    client
+        .post(format!("{}/login", &app.address))
+        .form(&[("email", &app.test_user.email), ("password", &app.test_user.password)])
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    client
+}
+
+async fn is_logged_in(app: &TestApp, client: &reqwest::Client) -> bool {
+    let response = client
+        .get(format!("{}/settings", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.");
+    response.status() == reqwest::StatusCode::OK
+}
+
+async fn audit_actions(app: &TestApp) -> Vec<String> {
+    sqlx::query_scalar!(
+        "SELECT action FROM audit_events WHERE user_id = $1 ORDER BY created_at",
+        app.test_user.user_id,
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn settings_require_login() {
+    let app = spawn_app().await;
+
+    let response = app.get_settings().await;
+    assert!(response.status().is_redirection());
+    let location = response.headers().get("Location").unwrap().to_str().unwrap();
+    assert!(location.starts_with("/login"));
+}
+
+#[tokio::test]
+async fn changing_the_password_logs_out_only_other_sessions() {
+    let app = spawn_app().await;
+    app.login().await;
+    let other = other_session(&app).await;
+    assert!(is_logged_in(&app, &other).await);
+
+    let form = password_form(&app.test_user.password, NEW_PASSWORD, NEW_PASSWORD);
+    let response = app.post_settings("/password", &form).await;
+    assert_is_redirect_to(&response, "/settings");
+
+    let html_page = app.get_settings().await.text().await.unwrap();
+    assert!(html_page.contains("Your password was changed. Your other sessions were logged out."));
+    assert!(html_page.contains("<td>Password changed</td>"));
+    assert!(!is_logged_in(&app, &other).await);
+    assert_eq!(audit_actions(&app).await, vec!["password_changed"]);
+
+    let body = serde_json::json!({ "email": app.test_user.email, "password": NEW_PASSWORD });
+    let response = app.post_login(&body).await;
+    assert_is_redirect_to(&response, "/");
+}
+
+#[tokio::test]
+async fn password_changes_need_the_current_password() {
+    let app = spawn_app().await;
+    app.login().await;
+
+    for (form, error) in [
+        (password_form("not-my-password", NEW_PASSWORD, NEW_PASSWORD), "Your current password is incorrect."),
+        (password_form(&app.test_user.password, NEW_PASSWORD, "Something-else1!"), "The passwords don&#x27;t match."),
+        (password_form(&app.test_user.password, "short", "short"), "Password is not a valid format."),
+    ] {
+        let response = app.post_settings("/password", &form).await;
+        assert_is_redirect_to(&response, "/settings");
+        let html_page = app.get_settings().await.text().await.unwrap();
+        assert!(html_page.contains(error));
+    }
+
+    assert!(audit_actions(&app).await.is_empty());
+    app.login().await;
+}
+
+#[tokio::test]
+async fn changing_the_email_needs_confirming_again() {
+    let app = spawn_app().await;
+    app.login().await;
+    let new_email = fake_email();
+
+    let form = serde_json::json!({ "email": new_email, "current_password": app.test_user.password });
+    let response = app.post_settings("/email", &form).await;
+    assert_is_redirect_to(&response, "/settings");
+
+    let user = sqlx::query!(
+        "SELECT email, email_verified_at FROM users WHERE id = $1",
+        app.test_user.user_id,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(user.email, new_email);
+    assert!(user.email_verified_at.is_none());
+    let token_email = sqlx::query_scalar!(
+        "SELECT email FROM email_verification_tokens WHERE user_id = $1 AND used_at IS NULL",
+        app.test_user.user_id,
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(token_email, new_email);
+
+    // The session survives, and the change is in the log
+    let html_page = app.get_settings().await.text().await.unwrap();
+    assert!(html_page.contains("We sent a confirmation link to the new address."));
+    assert!(html_page.contains(&format!("<td>{} to {}</td>", app.test_user.email, new_email)));
+    assert_eq!(audit_actions(&app).await, vec!["email_changed"]);
+}
+
+#[tokio::test]
+async fn email_changes_are_rejected_for_taken_or_invalid_addresses() {
+    let mut app = spawn_app().await;
+    app.login().await;
+    let taken = app.test_user.email.clone();
+    app.login_as_new_user().await;
+
+    let other_email = fake_email();
+    for (email, password, error) in [
+        (taken.as_str(), app.test_user.password.as_str(), "That email address is already in use."),
+        ("not-an-email", app.test_user.password.as_str(), "not-an-email is not a valid subscriber email."),
+        (app.test_user.email.as_str(), app.test_user.password.as_str(), "That is already your email address."),
+        (other_email.as_str(), "not-my-password", "Your current password is incorrect."),
+    ] {
+        let form = serde_json::json!({ "email": email, "current_password": password });
+        let response = app.post_settings("/email", &form).await;
+        assert_is_redirect_to(&response, "/settings");
+        let html_page = app.get_settings().await.text().await.unwrap();
+        assert!(html_page.contains(error), "{}", error);
+    }
+
+    assert!(audit_actions(&app).await.is_empty());
+}
@@ -0,0 +1,39 @@
+use crate::helpers::{spawn_app, TestApp};
+use crate::executions::EXECUTIONS_CSV;
+
+async fn equity_trade_count(app: &TestApp, params: &[(&str, &str)]) -> usize {
+    let response = app.get_equity(params).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let body: serde_json::Value = response.json().await.unwrap();
+    body["per_trade"].as_array().unwrap().len()
+}
+
+#[tokio::test]
+async fn equity_is_cached_until_trades_are_imported() {
+    let app = spawn_app().await;
+    app.login().await;
+    assert_eq!(equity_trade_count(&app, &[]).await, 0);
+
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    assert_eq!(equity_trade_count(&app, &[]).await, 1);
+
+    // Changed behind the app's back, so the cached curve is still served
+    sqlx::query!("DELETE FROM trades WHERE user_id = $1", app.test_user.user_id)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(equity_trade_count(&app, &[]).await, 1);
+}
+
+#[tokio::test]
+async fn saving_a_journal_entry_clears_cached_statistics() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    assert_eq!(equity_trade_count(&app, &[("tag", "ORB")]).await, 0);
+
+    let trade_id = app.trade_ids().await[0];
+    let body = serde_json::json!({ "notes": "", "rating": "", "risk": "", "tags": "ORB" });
+    app.post_journal(trade_id, &body).await;
+    assert_eq!(equity_trade_count(&app, &[("tag", "ORB")]).await, 1);
+}
@@ -0,0 +1,41 @@
+use crate::helpers::spawn_app;
+use crate::calendar::EVENING_SESSION_CSV;
+use crate::executions::EXECUTIONS_CSV;
+
+#[tokio::test]
+async fn trades_require_login() {
+    let app = spawn_app().await;
+
+    let response = app.get_trades(&[]).await;
+    assert!(response.status().is_redirection());
+    let location = response.headers().get("Location").unwrap().to_str().unwrap();
+    assert!(location.starts_with("/login"));
+}
+
+#[tokio::test]
+async fn trades_are_filtered_by_trading_day() {
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_import_executions(EXECUTIONS_CSV, "+00:00").await;
+    app.post_import_executions(EVENING_SESSION_CSV, "+00:00").await;
+
+    let html_page = app.get_trades(&[]).await.text().await.unwrap();
+    assert!(html_page.contains("+$108.32"));
+    assert!(html_page.contains("-$104.18"));
+
+    let response = app.get_trades(&[("from", "2024-07-23"), ("to", "2024-07-23")]).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<td>2024-07-23</td>"));
+    assert!(html_page.contains("-$104.18"));
+    assert!(!html_page.contains("+$108.32"));
+}
+
+#[tokio::test]
+async fn invalid_filters_are_reported() {
+    let app = spawn_app().await;
+    app.login().await;
+
+    let html_page = app.get_trades(&[("from", "07/01/2024")]).await.text().await.unwrap();
+    assert!(html_page.contains("is not a valid date. Use the format YYYY-MM-DD."));
+}
@@ -0,0 +1,349 @@
+use totp_rs::{Algorithm, Secret, TOTP};
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+/// The code the test user's authenticator would show `steps_ahead` steps from now
+async fn code(app: &TestApp, steps_ahead: u64) -> String {
+    let secret = sqlx::query_scalar!("SELECT secret FROM two_factor WHERE user_id = $1", app.test_user.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let secret = Secret::Encoded(secret).to_bytes().unwrap();
+    let totp = TOTP::new(Algorithm::SHA1, 6, 1, 30, secret, None, "test".to_string()).unwrap();
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    totp.generate(now + steps_ahead * 30)
+}
+
+/// Logs in, enrolls the test user and returns their recovery codes
+async fn enroll(app: &TestApp) -> Vec<String> {
+    app.login().await;
+    app.get_path("/settings/two-factor").await;
+    let code = code(app, 0).await;
+    let response = app.post_settings("/two-factor/enable", &[("code", code)]).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+
+    let html = response.text().await.unwrap();
+    html.split("<code>")
+        .skip(1)
+        .map(|part| part.split("</code>").next().unwrap().to_string())
+        .collect()
+}
+
+fn new_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap()
+}
+
+/// Enters the test user's password on a new client
+async fn start_login(app: &TestApp) -> reqwest::Client {
+    let client = new_client();
+    let response = client
+        .post(format!("{}/login", &app.address))
+        .form(&[("email", &app.test_user.email), ("password", &app.test_user.password)])
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/login/two-factor");
+    client
+}
+
+async fn post_code(app: &TestApp, client: &reqwest::Client, code: &str) -> reqwest::Response {
+    client
+        .post(format!("{}/login/two-factor", &app.address))
+        .form(&[("code", code)])
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn get(app: &TestApp, client: &reqwest::Client, path: &str) -> reqwest::Response {
+    client.get(format!("{}{}", &app.address, path)).send().await.unwrap()
+}
+
+async fn audit_actions(app: &TestApp) -> Vec<String> {
+    sqlx::query_scalar!(
+        "SELECT action FROM audit_events WHERE user_id = $1 ORDER BY created_at",
+        app.test_user.user_id,
+    )
+    .fetch_all(&app.db_pool)
+    .await
+    .unwrap()
+}
+
+async fn require_two_factor_for(app: &TestApp, role: &str) {
+    sqlx::query!("UPDATE roles SET requires_two_factor = TRUE WHERE name = $1", role)
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn enrollment_shows_a_qr_code_until_the_first_code_is_verified() {
+    let app = spawn_app().await;
+    app.login().await;
+
+    let html = app.get_path("/settings/two-factor").await.text().await.unwrap();
+    assert!(html.contains("<svg"));
+    assert!(!html.contains("<?xml"));
+    // Escaped, like everything Tera inserts
+    assert!(html.contains("otpauth:&#x2F;&#x2F;totp&#x2F;TradeSalsa:"));
+    // Reloading keeps the secret that was scanned
+    let secret = sqlx::query_scalar!("SELECT secret FROM two_factor WHERE user_id = $1", app.test_user.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    let html = app.get_path("/settings/two-factor").await.text().await.unwrap();
+    assert!(html.contains(&secret));
+
+    let response = app.post_settings("/two-factor/enable", &[("code", "000000")]).await;
+    assert_is_redirect_to(&response, "/settings/two-factor");
+    let html = app.get_path("/settings/two-factor").await.text().await.unwrap();
+    assert!(html.contains("That code is incorrect or has already been used."));
+
+    let code = code(&app, 0).await;
+    let html = app.post_settings("/two-factor/enable", &[("code", code)]).await.text().await.unwrap();
+    assert!(html.contains("This is the only time they're shown."));
+    assert_eq!(html.matches("<li><code>").count(), 10);
+
+    let html = app.get_settings().await.text().await.unwrap();
+    assert!(html.contains("On."));
+    assert_eq!(audit_actions(&app).await, vec!["two_factor_enabled"]);
+    let hashes = sqlx::query_scalar!("SELECT code_hash FROM two_factor_recovery_codes WHERE user_id = $1", app.test_user.user_id)
+        .fetch_all(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(hashes.len(), 10);
+    assert!(hashes.iter().all(|hash| hash.len() == 64));
+}
+
+#[tokio::test]
+async fn logging_in_asks_for_a_code_after_the_password() {
+    let app = spawn_app().await;
+    enroll(&app).await;
+
+    let client = start_login(&app).await;
+    // The password alone doesn't log the session in
+    let response = get(&app, &client, "/settings").await;
+    assert!(response.status().is_redirection());
+    let html = get(&app, &client, "/login/two-factor").await.text().await.unwrap();
+    assert!(html.contains("Enter the 6 digit code"));
+
+    let response = post_code(&app, &client, "000000").await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+    let html = get(&app, &client, "/login/two-factor").await.text().await.unwrap();
+    assert!(html.contains("That code is incorrect or has already been used."));
+
+    // The code used to enroll can't be used again
+    let used = code(&app, 0).await;
+    let response = post_code(&app, &client, &used).await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+
+    let next = code(&app, 1).await;
+    let response = post_code(&app, &client, &next).await;
+    assert_is_redirect_to(&response, "/");
+    assert_eq!(get(&app, &client, "/settings").await.status(), reqwest::StatusCode::OK);
+}
+
+#[tokio::test]
+async fn wrong_codes_are_throttled_like_passwords() {
+    let app = spawn_app().await;
+    enroll(&app).await;
+    let client = start_login(&app).await;
+
+    for _ in 0..4 {
+        post_code(&app, &client, "000000").await;
+    }
+    let next = code(&app, 1).await;
+    let response = post_code(&app, &client, &next).await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+    let html = get(&app, &client, "/login/two-factor").await.text().await.unwrap();
+    assert!(html.contains("Too many failed login attempts."));
+}
+
+#[tokio::test]
+async fn the_code_step_needs_a_password_first() {
+    let app = spawn_app().await;
+
+    let response = app.get_path("/login/two-factor").await;
+    assert_is_redirect_to(&response, "/login");
+    let response = post_code(&app, &app.api_client, "123456").await;
+    assert_is_redirect_to(&response, "/login");
+    let html = app.get_login(None).await.text().await.unwrap();
+    assert!(html.contains("Your login expired. Enter your password again."));
+}
+
+#[tokio::test]
+async fn recovery_codes_log_in_once() {
+    let app = spawn_app().await;
+    let recovery_codes = enroll(&app).await;
+    assert_eq!(recovery_codes.len(), 10);
+
+    let client = start_login(&app).await;
+    let typed = recovery_codes[0].to_uppercase();
+    let response = post_code(&app, &client, &typed).await;
+    assert_is_redirect_to(&response, "/");
+
+    let client = start_login(&app).await;
+    let response = post_code(&app, &client, &recovery_codes[0]).await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+
+    let html = app.get_path("/settings/two-factor").await.text().await.unwrap();
+    assert!(html.contains("You have 9 unused recovery codes."));
+    assert_eq!(audit_actions(&app).await, vec!["two_factor_enabled", "recovery_code_used"]);
+}
+
+#[tokio::test]
+async fn new_recovery_codes_replace_the_old_ones() {
+    let app = spawn_app().await;
+    let old_codes = enroll(&app).await;
+
+    let response = app.post_settings("/two-factor/recovery-codes", &[("current_password", "wrong")]).await;
+    assert_is_redirect_to(&response, "/settings/two-factor");
+
+    let password = app.test_user.password.clone();
+    let html = app.post_settings("/two-factor/recovery-codes", &[("current_password", password)])
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert_eq!(html.matches("<li><code>").count(), 10);
+
+    let client = start_login(&app).await;
+    let response = post_code(&app, &client, &old_codes[0]).await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[tokio::test]
+async fn turning_two_factor_off_needs_the_password() {
+    let app = spawn_app().await;
+    enroll(&app).await;
+
+    let response = app.post_settings("/two-factor/disable", &[("current_password", "wrong")]).await;
+    assert_is_redirect_to(&response, "/settings/two-factor");
+    let html = app.get_path("/settings/two-factor").await.text().await.unwrap();
+    assert!(html.contains("Your current password is incorrect."));
+
+    let password = app.test_user.password.clone();
+    let response = app.post_settings("/two-factor/disable", &[("current_password", password)]).await;
+    assert_is_redirect_to(&response, "/settings/two-factor");
+    let html = app.get_path("/settings/two-factor").await.text().await.unwrap();
+    assert!(html.contains("Two factor authentication is off."));
+
+    // Logging in takes just the password again
+    new_client()
+        .post(format!("{}/login", &app.address))
+        .form(&[("email", &app.test_user.email), ("password", &app.test_user.password)])
+        .send()
+        .await
+        .map(|response| assert_is_redirect_to(&response, "/"))
+        .unwrap();
+    let remaining = sqlx::query_scalar!("SELECT COUNT(*) FROM two_factor_recovery_codes WHERE user_id = $1", app.test_user.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(remaining, Some(0));
+}
+
+#[tokio::test]
+async fn roles_can_require_two_factor() {
+    let app = spawn_app().await;
+    app.grant_role("basic").await;
+    app.login().await;
+    require_two_factor_for(&app, "basic").await;
+
+    let response = app.get_path("/trades").await;
+    assert_is_redirect_to(&response, "/settings/two-factor");
+    let html = app.get_path("/settings/two-factor").await.text().await.unwrap();
+    assert!(html.contains("Your role requires two factor authentication. Set it up to continue."));
+
+    let code = code(&app, 0).await;
+    app.post_settings("/two-factor/enable", &[("code", code)]).await;
+    assert_eq!(app.get_path("/trades").await.status(), reqwest::StatusCode::OK);
+
+    let password = app.test_user.password.clone();
+    let response = app.post_settings("/two-factor/disable", &[("current_password", password)]).await;
+    assert_is_redirect_to(&response, "/settings/two-factor");
+    let html = app.get_path("/settings/two-factor").await.text().await.unwrap();
+    assert!(html.contains("Your role requires two factor authentication, so it can&#x27;t be turned off."));
+}
+
+#[tokio::test]
+async fn admins_choose_which_roles_require_two_factor() {
+    let app = spawn_app().await;
+    app.grant_role("admin").await;
+    app.login().await;
+
+    let html = app.get_path("/admin/roles").await.text().await.unwrap();
+    assert!(html.contains("Optional"));
+
+    let response = app.api_client
+        .post(format!("{}/admin/roles/basic/two-factor", &app.address))
+        .form(&[("required", "true")])
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/admin/roles");
+    let required = sqlx::query_scalar!("SELECT requires_two_factor FROM roles WHERE name = 'basic'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert!(required);
+    assert_eq!(audit_actions(&app).await, vec!["two_factor_requirement_changed"]);
+
+    let response = app.api_client
+        .post(format!("{}/admin/roles/nope/two-factor", &app.address))
+        .form(&[("required", "true")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn only_admins_manage_two_factor_requirements() {
+    let app = spawn_app().await;
+    app.login().await;
+
+    assert_eq!(app.get_path("/admin/roles").await.status(), reqwest::StatusCode::FORBIDDEN);
+    let response = app.api_client
+        .post(format!("{}/admin/roles/basic/two-factor", &app.address))
+        .form(&[("required", "true")])
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn admins_can_reset_a_users_two_factor() {
+    let app = spawn_app().await;
+    enroll(&app).await;
+    let admin = app.store_user().await;
+    sqlx::query!(
+        "INSERT INTO user_roles (user_id, role_id) SELECT $1, id FROM roles WHERE name = 'admin'",
+        admin.user_id,
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    let admin_client = app.client_logged_in_as(&admin).await;
+
+    let response = admin_client
+        .post(format!("{}/admin/users/{}/two-factor/reset", &app.address, app.test_user.user_id))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, &format!("/admin/users/{}", app.test_user.user_id));
+
+    let enrolled = sqlx::query_scalar!("SELECT COUNT(*) FROM two_factor WHERE user_id = $1", app.test_user.user_id)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(enrolled, Some(0));
+    assert_eq!(audit_actions(&app).await, vec!["two_factor_enabled", "two_factor_reset"]);
+}